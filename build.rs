@@ -8,19 +8,26 @@ static CONFIGS: &[(&str, usize)] = &[
     // Generated by gen_config.py. DO NOT EDIT.
     ("IFACE_MAX_ADDR_COUNT", 2),
     ("IFACE_MAX_MULTICAST_GROUP_COUNT", 4),
+    ("IFACE_MAX_MULTICAST_SOURCE_COUNT", 4),
     ("IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT", 4),
     ("IFACE_NEIGHBOR_CACHE_COUNT", 4),
+    ("IFACE_NEIGHBOR_PENDING_COUNT", 1),
+    ("IFACE_NEIGHBOR_PENDING_BUFFER_SIZE", 1500),
+    ("IFACE_PATH_MTU_CACHE_COUNT", 4),
     ("IFACE_MAX_ROUTE_COUNT", 2),
     ("FRAGMENTATION_BUFFER_SIZE", 1500),
     ("ASSEMBLER_MAX_SEGMENT_COUNT", 4),
     ("REASSEMBLY_BUFFER_SIZE", 1500),
     ("REASSEMBLY_BUFFER_COUNT", 1),
-    ("IPV6_HBH_MAX_OPTIONS", 1),
+    ("IPV6_HBH_MAX_OPTIONS", 2),
     ("DNS_MAX_RESULT_COUNT", 1),
     ("DNS_MAX_SERVER_COUNT", 1),
     ("DNS_MAX_NAME_SIZE", 255),
     ("RPL_RELATIONS_BUFFER_COUNT", 16),
     ("RPL_PARENTS_BUFFER_COUNT", 8),
+    ("IFACE_MAX_IPSEC_SA_COUNT", 4),
+    ("IFACE_MAX_IPSEC_POLICY_COUNT", 4),
+    ("IPSEC_BUFFER_SIZE", 1500),
     // END AUTOGENERATED CONFIG FEATURES
 ];
 
@@ -7,7 +7,7 @@ os.chdir(dname)
 features = []
 
 
-def feature(name, default, min, max, pow2=None):
+def feature(name, default, min, max, pow2=None, doc=None):
     vals = set()
     val = min
     while val <= max:
@@ -23,31 +23,48 @@ def feature(name, default, min, max, pow2=None):
             "name": name,
             "default": default,
             "vals": sorted(list(vals)),
+            "doc": doc,
         }
     )
 
 
 feature("iface_max_addr_count", default=2, min=1, max=8)
 feature("iface_max_multicast_group_count", default=4, min=1, max=1024, pow2=8)
+feature("iface_max_multicast_source_count", default=4, min=1, max=32, pow2=4)
 feature("iface_max_sixlowpan_address_context_count", default=4, min=1, max=1024, pow2=8)
 feature("iface_neighbor_cache_count", default=4, min=1, max=1024, pow2=8)
+feature("iface_neighbor_pending_count", default=1, min=1, max=32, pow2=4)
+feature(
+    "iface_neighbor_pending_buffer_size",
+    default=1500,
+    min=256,
+    max=65536,
+    pow2=True,
+    doc="Each of the iface-neighbor-pending-count queued packets takes this many octets of RAM in the Interface.",
+)
+feature("iface_path_mtu_cache_count", default=4, min=1, max=1024, pow2=8)
 feature("iface_max_route_count", default=2, min=1, max=1024, pow2=8)
 feature("fragmentation_buffer_size", default=1500, min=256, max=65536, pow2=True)
 feature("assembler_max_segment_count", default=4, min=1, max=32, pow2=4)
 feature("reassembly_buffer_size", default=1500, min=256, max=65536, pow2=True)
 feature("reassembly_buffer_count", default=1, min=1, max=32, pow2=4)
-feature("ipv6_hbh_max_options", default=1, min=1, max=32, pow2=4)
+feature("ipv6_hbh_max_options", default=2, min=1, max=32, pow2=4)
 feature("dns_max_result_count", default=1, min=1, max=32, pow2=4)
 feature("dns_max_server_count", default=1, min=1, max=32, pow2=4)
 feature("dns_max_name_size", default=255, min=64, max=255, pow2=True)
 feature("rpl_relations_buffer_count", default=16, min=1, max=128, pow2=True)
 feature("rpl_parents_buffer_count", default=8, min=2, max=32, pow2=True)
+feature("iface_max_ipsec_sa_count", default=4, min=1, max=32, pow2=4)
+feature("iface_max_ipsec_policy_count", default=4, min=1, max=32, pow2=4)
+feature("ipsec_buffer_size", default=1500, min=256, max=65536, pow2=True)
 
 # ========= Update Cargo.toml
 
 things = ""
 for f in features:
     name = f["name"].replace("_", "-")
+    if f["doc"]:
+        things += f"# {f['doc']}\n"
     for val in f["vals"]:
         things += f"{name}-{val} = []"
         if val == f["default"]:
@@ -4,6 +4,8 @@ use core::fmt;
 
 use managed::{ManagedMap, ManagedSlice};
 
+#[cfg(feature = "_proto-ipsec")]
+use crate::config::IPSEC_BUFFER_SIZE;
 use crate::config::{FRAGMENTATION_BUFFER_SIZE, REASSEMBLY_BUFFER_COUNT, REASSEMBLY_BUFFER_SIZE};
 use crate::storage::Assembler;
 use crate::time::{Duration, Instant};
@@ -248,6 +250,8 @@ pub(crate) const MAX_DECOMPRESSED_LEN: usize = 1500;
 pub(crate) enum FragKey {
     #[cfg(feature = "proto-ipv4-fragmentation")]
     Ipv4(Ipv4FragKey),
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    Ipv6(Ipv6FragKey),
     #[cfg(feature = "proto-sixlowpan-fragmentation")]
     Sixlowpan(SixlowpanFragKey),
 }
@@ -261,6 +265,10 @@ pub(crate) struct FragmentsBuffer {
 
     #[cfg(feature = "_proto-fragmentation")]
     pub reassembly_timeout: Duration,
+
+    /// Buffer for decrypted IPsec payloads.
+    #[cfg(feature = "_proto-ipsec")]
+    pub ipsec_buf: [u8; IPSEC_BUFFER_SIZE],
 }
 
 #[cfg(not(feature = "_proto-fragmentation"))]
@@ -284,6 +292,8 @@ pub(crate) struct Fragmenter {
 
     #[cfg(feature = "proto-ipv4-fragmentation")]
     pub ipv4: Ipv4Fragmenter,
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    pub ipv6: Ipv6Fragmenter,
     #[cfg(feature = "proto-sixlowpan-fragmentation")]
     pub sixlowpan: SixlowpanFragmenter,
 }
@@ -301,6 +311,24 @@ pub(crate) struct Ipv4Fragmenter {
     pub ident: u16,
 }
 
+#[cfg(feature = "proto-ipv6-fragmentation")]
+pub(crate) struct Ipv6Fragmenter {
+    /// The length of the IPv6 header and the extension headers that are repeated in every
+    /// fragment.
+    pub unfragmentable_len: usize,
+    /// The offset of the Next Header field that is replaced with the Fragment header type.
+    pub next_header_offset: usize,
+    /// The type of the first header of the fragmented part, set in the Fragment header.
+    pub next_header: IpProtocol,
+    /// The destination hardware address.
+    #[cfg(feature = "medium-ethernet")]
+    pub dst_hardware_addr: EthernetAddress,
+    /// The identification of the fragmented packet.
+    pub ident: u32,
+    /// The path MTU towards the destination, which bounds the size of the fragments.
+    pub mtu: usize,
+}
+
 #[cfg(feature = "proto-sixlowpan-fragmentation")]
 pub(crate) struct SixlowpanFragmenter {
     /// The datagram size that is used for the fragmentation headers.
@@ -341,6 +369,17 @@ impl Fragmenter {
                 ident: 0,
             },
 
+            #[cfg(feature = "proto-ipv6-fragmentation")]
+            ipv6: Ipv6Fragmenter {
+                unfragmentable_len: 0,
+                next_header_offset: 0,
+                next_header: IpProtocol::Unknown(0),
+                #[cfg(feature = "medium-ethernet")]
+                dst_hardware_addr: EthernetAddress::default(),
+                ident: 0,
+                mtu: 0,
+            },
+
             #[cfg(feature = "proto-sixlowpan-fragmentation")]
             sixlowpan: SixlowpanFragmenter {
                 datagram_size: 0,
@@ -385,6 +424,18 @@ impl Fragmenter {
             }
         }
 
+        #[cfg(feature = "proto-ipv6-fragmentation")]
+        {
+            self.ipv6.unfragmentable_len = 0;
+            self.ipv6.next_header_offset = 0;
+            self.ipv6.next_header = IpProtocol::Unknown(0);
+            self.ipv6.mtu = 0;
+            #[cfg(feature = "medium-ethernet")]
+            {
+                self.ipv6.dst_hardware_addr = EthernetAddress::default();
+            }
+        }
+
         #[cfg(feature = "proto-sixlowpan-fragmentation")]
         {
             self.sixlowpan.datagram_size = 0;
@@ -0,0 +1,468 @@
+use super::*;
+
+use heapless::Deque;
+
+/// Maximum delay before the first probe, see RFC 5227 § 1.1.
+const PROBE_WAIT: Duration = Duration::from_secs(1);
+/// Number of probes sent before an address is used, see RFC 5227 § 1.1.
+const PROBE_NUM: u8 = 3;
+/// Minimum delay between two probes, see RFC 5227 § 1.1.
+const PROBE_MIN: Duration = Duration::from_secs(1);
+/// Maximum delay between two probes, see RFC 5227 § 1.1.
+const PROBE_MAX: Duration = Duration::from_secs(2);
+/// Delay between the last probe and the first announcement, see RFC 5227 § 1.1.
+const ANNOUNCE_WAIT: Duration = Duration::from_secs(2);
+/// Number of announcements sent once an address is used, see RFC 5227 § 1.1.
+const ANNOUNCE_NUM: u8 = 2;
+/// Delay between two announcements, see RFC 5227 § 1.1.
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+/// Number of conflicts after which the link-local addresses are picked at a limited rate, see
+/// RFC 5227 § 1.1.
+const MAX_CONFLICTS: u8 = 10;
+/// Delay between two picked link-local addresses after too many conflicts, see RFC 5227 § 1.1.
+const RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(60);
+/// Minimum interval between two defenses of an address, see RFC 5227 § 1.1.
+const DEFEND_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What the interface does when another host uses one of its IPv4 addresses, see
+/// RFC 5227 § 2.4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AcdPolicy {
+    /// Stop using the address.
+    Abandon,
+    /// Announce the address again, and stop using it on another conflict within ten seconds.
+    DefendOnce,
+    /// Keep using the address, and announce it again at most every ten seconds.
+    Defend,
+}
+
+/// The state of an IPv4 address of the interface, see RFC 5227 § 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Ipv4AddressState {
+    /// The interface probes whether another host uses the address, which is not used yet.
+    Probing,
+    /// The address is used, and the interface announces it to the other hosts.
+    Announcing,
+    /// The address is used.
+    Assigned,
+    /// Another host uses the address, which is not used.
+    Conflict,
+}
+
+/// An event of the Address Conflict Detection of an IPv4 address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AcdEvent {
+    /// No other host answered the probes, the address is now used.
+    Assigned(Ipv4Address),
+    /// Another host used the address, which was announced again.
+    Defended(Ipv4Address),
+    /// Another host uses the address, which is not used anymore.
+    Conflict(Ipv4Address),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AcdAddress {
+    addr: Ipv4Address,
+    state: Ipv4AddressState,
+    /// The number of probes or announcements left to send.
+    count: u8,
+    /// When the next probe or announcement is due.
+    next_at: Instant,
+    /// When the address was last defended.
+    defended_at: Option<Instant>,
+    /// Whether the address was picked by the link-local autoconfiguration.
+    link_local: bool,
+}
+
+/// The state of the link-local address autoconfiguration, see RFC 3927.
+#[derive(Debug)]
+struct LinkLocal {
+    /// The number of conflicts of the picked addresses.
+    conflicts: u8,
+    /// When the next address is picked, if the interface has none.
+    next_at: Instant,
+}
+
+#[derive(Debug)]
+pub(crate) struct Acd {
+    policy: Option<AcdPolicy>,
+    link_local: Option<LinkLocal>,
+    addresses: Vec<AcdAddress, IFACE_MAX_ADDR_COUNT>,
+    events: Deque<AcdEvent, IFACE_MAX_ADDR_COUNT>,
+}
+
+impl Acd {
+    pub(crate) fn new(policy: Option<AcdPolicy>, link_local: bool, now: Instant) -> Self {
+        Self {
+            policy,
+            link_local: link_local.then_some(LinkLocal {
+                conflicts: 0,
+                next_at: now,
+            }),
+            addresses: Vec::new(),
+            events: Deque::new(),
+        }
+    }
+
+    fn push_event(&mut self, event: AcdEvent) {
+        if self.events.is_full() {
+            self.events.pop_front();
+        }
+        // NOTE(unwrap): there is room for the event.
+        self.events.push_back(event).unwrap();
+    }
+}
+
+/// Return a random delay between `min` and `max`.
+fn random_delay(rand: &mut Rand, min: Duration, max: Duration) -> Duration {
+    let range = (max - min).total_millis() + 1;
+    min + Duration::from_millis(rand.rand_u32() as u64 % range)
+}
+
+/// Return a link-local address in 169.254.1.0 - 169.254.254.255 from a seed, see
+/// RFC 3927 § 2.1.
+fn link_local_address(seed: u32) -> Ipv4Address {
+    let host = 0x0100 + seed % (0xff00 - 0x0100);
+    Ipv4Address::new(169, 254, (host >> 8) as u8, host as u8)
+}
+
+impl Interface {
+    /// Return the state of an IPv4 address of the interface, or `None` if the interface does not
+    /// have the address.
+    pub fn ipv4_addr_state(&self, addr: &Ipv4Address) -> Option<Ipv4AddressState> {
+        self.inner.ipv4_addr_state(addr)
+    }
+
+    /// Return the oldest event of Address Conflict Detection not returned yet.
+    ///
+    /// Only the last [`IFACE_MAX_ADDR_COUNT`](crate::config::IFACE_MAX_ADDR_COUNT) events
+    /// are kept.
+    pub fn poll_acd_event(&mut self) -> Option<AcdEvent> {
+        self.inner.acd.events.pop_front()
+    }
+
+    /// Pick a link-local address if needed, and send the ARP probes and announcements that are
+    /// due, see RFC 5227 § 2.1 and § 2.3.
+    pub(super) fn acd_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        self.inner.ipv4_link_local_pick();
+
+        let mut did_something = false;
+        for index in 0..self.inner.acd.addresses.len() {
+            let entry = self.inner.acd.addresses[index];
+            if now < entry.next_at {
+                continue;
+            }
+
+            let announce = match entry.state {
+                Ipv4AddressState::Probing if entry.count == 0 => {
+                    net_debug!("acd: address {} is not in use", entry.addr);
+                    let acd = &mut self.inner.acd;
+                    acd.addresses[index].state = Ipv4AddressState::Announcing;
+                    acd.addresses[index].count = ANNOUNCE_NUM;
+                    acd.push_event(AcdEvent::Assigned(entry.addr));
+                    did_something = true;
+                    true
+                }
+                Ipv4AddressState::Probing => false,
+                Ipv4AddressState::Announcing => true,
+                _ => continue,
+            };
+
+            let Some(tx_token) = device.transmit(now) else {
+                break;
+            };
+
+            let probe_delay = random_delay(&mut self.inner.rand, PROBE_MIN, PROBE_MAX);
+            let entry = &mut self.inner.acd.addresses[index];
+            entry.count -= 1;
+            entry.next_at = match (announce, entry.count) {
+                (true, _) => now + ANNOUNCE_INTERVAL,
+                (false, 0) => now + ANNOUNCE_WAIT,
+                (false, _) => now + probe_delay,
+            };
+            if announce && entry.count == 0 {
+                entry.state = Ipv4AddressState::Assigned;
+            }
+
+            // Probes come from the unspecified address, announcements from the address itself.
+            let arp_repr = ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Request,
+                source_hardware_addr: self.inner.hardware_addr.ethernet_or_panic(),
+                source_protocol_addr: if announce {
+                    entry.addr
+                } else {
+                    Ipv4Address::UNSPECIFIED
+                },
+                target_hardware_addr: EthernetAddress([0; 6]),
+                target_protocol_addr: entry.addr,
+            };
+
+            if let Err(e) =
+                self.inner
+                    .dispatch_ethernet(tx_token, arp_repr.buffer_len(), |mut frame| {
+                        frame.set_dst_addr(EthernetAddress::BROADCAST);
+                        frame.set_ethertype(EthernetProtocol::Arp);
+
+                        arp_repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()))
+                    })
+            {
+                net_debug!("Failed to dispatch ARP probe: {:?}", e);
+            }
+
+            did_something = true;
+        }
+
+        did_something
+    }
+}
+
+impl InterfaceInner {
+    /// Return the time at which the next probe or announcement is due, or a link-local address
+    /// is picked.
+    pub(super) fn acd_poll_at(&self) -> Option<Instant> {
+        let link_local_at = self.acd.link_local.as_ref().and_then(|link_local| {
+            (!self.acd.addresses.iter().any(|entry| entry.link_local)).then_some(link_local.next_at)
+        });
+
+        self.acd
+            .addresses
+            .iter()
+            .filter(|entry| {
+                matches!(
+                    entry.state,
+                    Ipv4AddressState::Probing | Ipv4AddressState::Announcing
+                )
+            })
+            .map(|entry| entry.next_at)
+            .chain(link_local_at)
+            .min()
+    }
+
+    /// Start the detection on the IPv4 addresses added to the interface, and forget the removed
+    /// ones.
+    pub(super) fn acd_update_addrs(&mut self) {
+        let now = self.now;
+        let ip_addrs = &self.ip_addrs;
+        let acd = &mut self.acd;
+
+        acd.addresses.retain(|entry| {
+            ip_addrs
+                .iter()
+                .any(|cidr| cidr.address() == IpAddress::Ipv4(entry.addr))
+        });
+
+        for addr in ip_addrs.iter().filter_map(|cidr| match cidr {
+            IpCidr::Ipv4(cidr) => Some(cidr.address()),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }) {
+            if acd.addresses.iter().any(|entry| entry.addr == addr) {
+                continue;
+            }
+
+            let state = if acd.policy.is_none() || addr.is_loopback() || addr.is_unspecified() {
+                Ipv4AddressState::Assigned
+            } else {
+                Ipv4AddressState::Probing
+            };
+
+            // NOTE(unwrap): there are no more tracked addresses than addresses.
+            acd.addresses
+                .push(AcdAddress {
+                    addr,
+                    state,
+                    count: PROBE_NUM,
+                    next_at: now + random_delay(&mut self.rand, Duration::ZERO, PROBE_WAIT),
+                    defended_at: None,
+                    link_local: false,
+                })
+                .unwrap();
+        }
+    }
+
+    /// Pick a link-local address when the autoconfiguration is enabled and the interface has
+    /// none, see RFC 3927 § 2.1.
+    fn ipv4_link_local_pick(&mut self) {
+        let now = self.now;
+        let Some(link_local) = &self.acd.link_local else {
+            return;
+        };
+        if now < link_local.next_at || self.acd.addresses.iter().any(|entry| entry.link_local) {
+            return;
+        }
+
+        // The first address is derived from the hardware address, so that the interface picks
+        // the same address each time.
+        let mut addr = if link_local.conflicts == 0 {
+            let mac = self.hardware_addr.ethernet_or_panic();
+            let b = mac.as_bytes();
+            link_local_address(u32::from_be_bytes([0, b[3], b[4], b[5]]) ^ b[2] as u32)
+        } else {
+            link_local_address(self.rand.rand_u32())
+        };
+        if self
+            .ip_addrs
+            .iter()
+            .any(|cidr| cidr.address() == addr.into())
+        {
+            addr = link_local_address(self.rand.rand_u32());
+        }
+        if self
+            .ip_addrs
+            .iter()
+            .any(|cidr| cidr.address() == addr.into())
+        {
+            return;
+        }
+        if self.ip_addrs.push(IpCidr::new(addr.into(), 16)).is_err() {
+            net_debug!("acd: no space for the link-local address");
+            return;
+        }
+        net_debug!("acd: probing link-local address {}", addr);
+
+        // NOTE(unwrap): there are no more tracked addresses than addresses.
+        self.acd
+            .addresses
+            .push(AcdAddress {
+                addr,
+                state: Ipv4AddressState::Probing,
+                count: PROBE_NUM,
+                next_at: now + random_delay(&mut self.rand, Duration::ZERO, PROBE_WAIT),
+                defended_at: None,
+                link_local: true,
+            })
+            .unwrap();
+    }
+
+    /// Return the state of an IPv4 address of the interface.
+    pub(super) fn ipv4_addr_state(&self, addr: &Ipv4Address) -> Option<Ipv4AddressState> {
+        if !self
+            .ip_addrs
+            .iter()
+            .any(|cidr| cidr.address() == IpAddress::Ipv4(*addr))
+        {
+            return None;
+        }
+
+        Some(
+            self.acd
+                .addresses
+                .iter()
+                .find(|entry| entry.addr == *addr)
+                .map_or(Ipv4AddressState::Assigned, |entry| entry.state),
+        )
+    }
+
+    /// Return whether an IPv4 address is used by the interface: it is not probed, and does not
+    /// conflict with another host.
+    pub(super) fn acd_is_assigned(&self, addr: &Ipv4Address) -> bool {
+        !matches!(
+            self.ipv4_addr_state(addr),
+            Some(Ipv4AddressState::Probing | Ipv4AddressState::Conflict)
+        )
+    }
+
+    /// Detect the conflicts of the addresses of the interface with the sender of an ARP packet,
+    /// see RFC 5227 § 2.1.1 and § 2.4.
+    pub(super) fn acd_process_arp(
+        &mut self,
+        source_hardware_addr: EthernetAddress,
+        source_protocol_addr: Ipv4Address,
+        target_protocol_addr: Ipv4Address,
+    ) {
+        if HardwareAddress::Ethernet(source_hardware_addr) == self.hardware_addr {
+            return;
+        }
+
+        // The sender uses the address, or probes it while the interface probes it too.
+        let probe = source_protocol_addr.is_unspecified();
+        let Some(index) = self.acd.addresses.iter().position(|entry| {
+            entry.addr == source_protocol_addr
+                || (probe
+                    && entry.state == Ipv4AddressState::Probing
+                    && entry.addr == target_protocol_addr)
+        }) else {
+            return;
+        };
+
+        let now = self.now;
+        let entry = self.acd.addresses[index];
+        // Link-local addresses are defended once, see RFC 3927 § 2.5.
+        let policy = if entry.link_local {
+            Some(AcdPolicy::DefendOnce)
+        } else {
+            self.acd.policy
+        };
+        let recently_defended = matches!(entry.defended_at, Some(t) if now < t + DEFEND_INTERVAL);
+
+        match policy {
+            None => (),
+            _ if entry.state == Ipv4AddressState::Conflict => (),
+            _ if entry.state == Ipv4AddressState::Probing => self.acd_conflict(index),
+            Some(AcdPolicy::Abandon) => self.acd_conflict(index),
+            Some(AcdPolicy::DefendOnce) if recently_defended => self.acd_conflict(index),
+            Some(AcdPolicy::Defend) if recently_defended => (),
+            Some(_) => {
+                let addr = entry.addr;
+                net_debug!("acd: defending address {}", addr);
+                let entry = &mut self.acd.addresses[index];
+                entry.defended_at = Some(now);
+                entry.state = Ipv4AddressState::Announcing;
+                entry.count = entry.count.max(1);
+                entry.next_at = now;
+                self.acd.push_event(AcdEvent::Defended(addr));
+            }
+        }
+    }
+
+    /// Stop using an address which another host uses. A conflicting link-local address is
+    /// replaced with another one, see RFC 3927 § 2.2.1.
+    fn acd_conflict(&mut self, index: usize) {
+        let now = self.now;
+        let entry = self.acd.addresses[index];
+        net_debug!("acd: address {} is used by another host", entry.addr);
+        self.acd.push_event(AcdEvent::Conflict(entry.addr));
+
+        if !entry.link_local {
+            self.acd.addresses[index].state = Ipv4AddressState::Conflict;
+            return;
+        }
+
+        self.acd.addresses.swap_remove(index);
+        self.ip_addrs
+            .retain(|cidr| cidr.address() != IpAddress::Ipv4(entry.addr));
+        if let Some(link_local) = &mut self.acd.link_local {
+            link_local.conflicts = link_local.conflicts.saturating_add(1);
+            link_local.next_at = if link_local.conflicts >= MAX_CONFLICTS {
+                now + RATE_LIMIT_INTERVAL
+            } else {
+                now
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_local_addresses() {
+        assert_eq!(link_local_address(0), Ipv4Address::new(169, 254, 1, 0));
+        assert_eq!(
+            link_local_address(0xff00 - 0x0100 - 1),
+            Ipv4Address::new(169, 254, 254, 255)
+        );
+        assert_eq!(
+            link_local_address(0xff00 - 0x0100),
+            Ipv4Address::new(169, 254, 1, 0)
+        );
+    }
+}
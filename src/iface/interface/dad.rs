@@ -0,0 +1,285 @@
+use super::*;
+
+use heapless::Deque;
+
+/// Time between the Neighbor Solicitations of Duplicate Address Detection, and after the last
+/// one, see RFC 4861 § 10.
+const RETRANS_TIMER: Duration = Duration::from_secs(1);
+
+/// The state of an IPv6 address of the interface, see RFC 4862 § 2 and RFC 4429 § 2.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Ipv6AddressState {
+    /// Duplicate Address Detection is running on the address, which is not used yet.
+    Tentative,
+    /// Duplicate Address Detection is running on the address, which is already used, but
+    /// avoided as a source address.
+    Optimistic,
+    /// The address is unique on the link, and is used without restriction.
+    Preferred,
+    /// The preferred lifetime of the autoconfigured address ended. The address is still used,
+    /// but avoided as a source address.
+    Deprecated,
+    /// Another node of the link uses the address, which is not used.
+    Duplicate,
+}
+
+/// An event of the Duplicate Address Detection of an IPv6 address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DadEvent {
+    /// No other node of the link uses the address, which is now preferred.
+    Assigned(Ipv6Address),
+    /// Another node of the link uses the address.
+    Duplicate(Ipv6Address),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DadAddress {
+    addr: Ipv6Address,
+    state: Ipv6AddressState,
+    /// The number of Neighbor Solicitations left to send.
+    probes: u8,
+    /// When the next Neighbor Solicitation is due, or when the detection completes if none is
+    /// left.
+    next_at: Instant,
+}
+
+#[derive(Debug)]
+pub(crate) struct Dad {
+    transmits: u8,
+    optimistic: bool,
+    addresses: Vec<DadAddress, IFACE_MAX_ADDR_COUNT>,
+    events: Deque<DadEvent, IFACE_MAX_ADDR_COUNT>,
+}
+
+impl Dad {
+    pub(crate) fn new(transmits: u8, optimistic: bool) -> Self {
+        Self {
+            transmits,
+            optimistic,
+            addresses: Vec::new(),
+            events: Deque::new(),
+        }
+    }
+
+    fn push_event(&mut self, event: DadEvent) {
+        if self.events.is_full() {
+            self.events.pop_front();
+        }
+        // NOTE(unwrap): there is room for the event.
+        self.events.push_back(event).unwrap();
+    }
+}
+
+impl Interface {
+    /// Return the state of an IPv6 address of the interface, or `None` if the interface does not
+    /// have the address.
+    pub fn ipv6_addr_state(&self, addr: &Ipv6Address) -> Option<Ipv6AddressState> {
+        self.inner.ipv6_addr_state(addr)
+    }
+
+    /// Return the oldest event of Duplicate Address Detection not returned yet.
+    ///
+    /// Only the last [`IFACE_MAX_ADDR_COUNT`](crate::config::IFACE_MAX_ADDR_COUNT) events
+    /// are kept.
+    pub fn poll_dad_event(&mut self) -> Option<DadEvent> {
+        self.inner.dad.events.pop_front()
+    }
+
+    /// Send the Neighbor Solicitations of Duplicate Address Detection that are due, and assign
+    /// the addresses whose detection completed, see RFC 4862 § 5.4.
+    pub(super) fn dad_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        let mut did_something = false;
+
+        for index in 0..self.inner.dad.addresses.len() {
+            let entry = self.inner.dad.addresses[index];
+            if !matches!(
+                entry.state,
+                Ipv6AddressState::Tentative | Ipv6AddressState::Optimistic
+            ) || now < entry.next_at
+            {
+                continue;
+            }
+
+            if entry.probes == 0 {
+                net_debug!("dad: address {} is unique", entry.addr);
+                let dad = &mut self.inner.dad;
+                dad.addresses[index].state = Ipv6AddressState::Preferred;
+                dad.push_event(DadEvent::Assigned(entry.addr));
+                did_something = true;
+                continue;
+            }
+
+            let Some(tx_token) = device.transmit(now) else {
+                break;
+            };
+            let entry = &mut self.inner.dad.addresses[index];
+            entry.probes -= 1;
+            entry.next_at = now + RETRANS_TIMER;
+
+            // The solicitation comes from the unspecified address, without a Source Link-Layer
+            // Address option, see RFC 4862 § 5.4.2.
+            let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+                target_addr: entry.addr,
+                lladdr: None,
+            });
+            let ip_repr = Ipv6Repr {
+                src_addr: Ipv6Address::UNSPECIFIED,
+                dst_addr: entry.addr.solicited_node(),
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_repr.buffer_len(),
+                hop_limit: 0xff,
+            };
+
+            // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+            self.inner
+                .dispatch_ip(
+                    tx_token,
+                    PacketMeta::default(),
+                    Packet::new_ipv6(ip_repr, IpPayload::Icmpv6(icmp_repr)),
+                    &mut self.fragmenter,
+                )
+                .unwrap();
+
+            did_something = true;
+        }
+
+        did_something
+    }
+}
+
+impl InterfaceInner {
+    /// Return the time at which the next Neighbor Solicitation is due or the next detection
+    /// completes.
+    pub(super) fn dad_poll_at(&self) -> Option<Instant> {
+        self.dad
+            .addresses
+            .iter()
+            .filter(|entry| {
+                matches!(
+                    entry.state,
+                    Ipv6AddressState::Tentative | Ipv6AddressState::Optimistic
+                )
+            })
+            .map(|entry| entry.next_at)
+            .min()
+    }
+
+    /// Start the detection on the IPv6 addresses added to the interface, and forget the removed
+    /// ones. The new addresses are optimistic if `optimistic` is set and optimistic DAD is
+    /// enabled.
+    pub(super) fn dad_update_addrs(&mut self, optimistic: bool) {
+        let now = self.now;
+        let ip_addrs = &self.ip_addrs;
+        let dad = &mut self.dad;
+
+        dad.addresses.retain(|entry| {
+            ip_addrs
+                .iter()
+                .any(|cidr| cidr.address() == IpAddress::Ipv6(entry.addr))
+        });
+
+        for addr in ip_addrs.iter().filter_map(|cidr| match cidr {
+            IpCidr::Ipv6(cidr) => Some(cidr.address()),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }) {
+            if dad.addresses.iter().any(|entry| entry.addr == addr) {
+                continue;
+            }
+
+            let state = if dad.transmits == 0 || addr.is_loopback() || addr.is_unspecified() {
+                Ipv6AddressState::Preferred
+            } else if optimistic && dad.optimistic {
+                Ipv6AddressState::Optimistic
+            } else {
+                Ipv6AddressState::Tentative
+            };
+
+            // NOTE(unwrap): there are no more tracked addresses than addresses.
+            dad.addresses
+                .push(DadAddress {
+                    addr,
+                    state,
+                    probes: dad.transmits,
+                    next_at: now,
+                })
+                .unwrap();
+        }
+    }
+
+    /// Return the state of an IPv6 address of the interface.
+    pub(super) fn ipv6_addr_state(&self, addr: &Ipv6Address) -> Option<Ipv6AddressState> {
+        if !self
+            .ip_addrs
+            .iter()
+            .any(|cidr| cidr.address() == IpAddress::Ipv6(*addr))
+        {
+            return None;
+        }
+
+        let state = self
+            .dad
+            .addresses
+            .iter()
+            .find(|entry| entry.addr == *addr)
+            .map_or(Ipv6AddressState::Preferred, |entry| entry.state);
+
+        #[cfg(feature = "proto-slaac")]
+        if state == Ipv6AddressState::Preferred && self.slaac_is_deprecated(addr) {
+            return Some(Ipv6AddressState::Deprecated);
+        }
+
+        Some(state)
+    }
+
+    /// Return whether an IPv6 address is assigned to the interface: the detection did not fail,
+    /// and the address is not tentative.
+    pub(super) fn dad_is_assigned(&self, addr: &Ipv6Address) -> bool {
+        !matches!(
+            self.ipv6_addr_state(addr),
+            Some(Ipv6AddressState::Tentative | Ipv6AddressState::Duplicate)
+        )
+    }
+
+    /// Return whether an IPv6 address is avoided as a source address, see RFC 6724 § 5 and
+    /// RFC 4429 § 3.3.
+    pub(super) fn dad_is_deprecated(&self, addr: &Ipv6Address) -> bool {
+        matches!(
+            self.ipv6_addr_state(addr),
+            Some(Ipv6AddressState::Optimistic | Ipv6AddressState::Deprecated)
+        )
+    }
+
+    /// Return whether the detection is running on an IPv6 address.
+    pub(super) fn dad_is_probing(&self, addr: &Ipv6Address) -> bool {
+        matches!(
+            self.ipv6_addr_state(addr),
+            Some(Ipv6AddressState::Tentative | Ipv6AddressState::Optimistic)
+        )
+    }
+
+    /// Stop using an address which another node of the link uses, see RFC 4862 § 5.4.5.
+    pub(super) fn dad_duplicate(&mut self, addr: Ipv6Address) {
+        let Some(entry) = self
+            .dad
+            .addresses
+            .iter_mut()
+            .find(|entry| entry.addr == addr)
+        else {
+            return;
+        };
+
+        net_debug!("dad: address {} is a duplicate", addr);
+        entry.state = Ipv6AddressState::Duplicate;
+        self.dad.push_event(DadEvent::Duplicate(addr));
+
+        #[cfg(feature = "proto-slaac")]
+        self.slaac_dad_failed(addr);
+    }
+}
@@ -0,0 +1,208 @@
+use super::*;
+
+/// The reason why a packet cannot be forwarded, reported to its source with an ICMP error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) enum ForwardError {
+    /// No interface has a route to the destination.
+    NoRoute,
+    /// The source address is not valid outside of the link the packet was received on.
+    BeyondScope,
+    /// The packet is larger than the MTU of the outgoing interface.
+    PacketTooBig(usize),
+}
+
+impl Interface {
+    /// Enable or disable the forwarding of the packets that are not addressed to the interface.
+    pub(crate) fn set_forwarding(&mut self, forwarding: bool) {
+        self.inner.forwarding = forwarding;
+    }
+
+    /// Return `true` when the interface can dispatch a forwarded packet, which is not the case
+    /// while the fragments of a previous packet are being sent.
+    pub(crate) fn can_forward(&self) -> bool {
+        #[cfg(feature = "_proto-fragmentation")]
+        return self.fragmenter.is_empty();
+        #[cfg(not(feature = "_proto-fragmentation"))]
+        return true;
+    }
+
+    /// Dispatch a packet that was forwarded to this interface. `datagram` holds the IP packet,
+    /// as emitted by [`InterfaceInner::emit_forwarded`].
+    ///
+    /// A packet addressed to the interface is processed as if it was received, and the packet
+    /// sent in response is given to `route`.
+    pub(crate) fn dispatch_forwarded<Tx, R>(
+        &mut self,
+        tx_token: Tx,
+        sockets: &mut SocketSet<'_>,
+        datagram: &[u8],
+        mut route: R,
+    ) where
+        Tx: TxToken,
+        R: for<'p> FnMut(&InterfaceInner, Packet<'p>) -> Option<Packet<'p>>,
+    {
+        let Some(packet) = InterfaceInner::parse_forwarded(datagram) else {
+            return;
+        };
+
+        let packet = if self.inner.has_ip_addr(packet.ip_repr().dst_addr()) {
+            self.inner
+                .process_ip(
+                    sockets,
+                    PacketMeta::default(),
+                    datagram,
+                    &mut self.fragments,
+                )
+                .and_then(|packet| route(&self.inner, packet))
+        } else {
+            Some(packet)
+        };
+
+        if let Some(packet) = packet {
+            if let Err(err) = self.inner.dispatch_ip(
+                tx_token,
+                PacketMeta::default(),
+                packet,
+                &mut self.fragmenter,
+            ) {
+                net_debug!("Failed to forward packet: {:?}", err);
+            }
+        }
+    }
+}
+
+impl InterfaceInner {
+    /// Return the prefix length of the most specific route to an address through this
+    /// interface, or `None` when the interface has no route to it.
+    pub(crate) fn route_prefix_len(&self, addr: &IpAddress) -> Option<u8> {
+        // The routes of a RPL DODAG are host routes, except the default route via the preferred
+        // parent.
+        #[cfg(feature = "proto-rpl")]
+        #[allow(irrefutable_let_patterns)] // if only ipv6 is enabled
+        if let IpAddress::Ipv6(dst_addr) = addr {
+            if let Some(rpl) = &self.rpl {
+                match rpl.next_hop(dst_addr) {
+                    Some(next_hop) if Some(next_hop) != rpl.parent() => return Some(128),
+                    Some(_) => return Some(0),
+                    None => (),
+                }
+            }
+        }
+
+        let on_link = self
+            .ip_addrs
+            .iter()
+            .filter(|cidr| cidr.contains_addr(addr))
+            .map(|cidr| cidr.prefix_len())
+            .max();
+        let routed = self
+            .routes
+            .lookup_route(addr, self.now)
+            .map(|route| route.cidr.prefix_len());
+
+        on_link.max(routed)
+    }
+
+    /// Return the size of the largest IP packet that can be forwarded on this interface.
+    pub(crate) fn forwarding_mtu(&self) -> usize {
+        // 6LoWPAN fragments the IPv6 packets to fit in the frames.
+        #[cfg(feature = "proto-sixlowpan-fragmentation")]
+        if self.caps.medium == Medium::Ieee802154 {
+            return IPV6_MIN_MTU;
+        }
+
+        self.caps.ip_mtu()
+    }
+
+    /// Emit a packet forwarded to this interface in `buffer`, which is `ip_repr().buffer_len()`
+    /// bytes long.
+    pub(crate) fn emit_forwarded(&self, packet: &Packet, buffer: &mut [u8]) {
+        let ip_repr = packet.ip_repr();
+        ip_repr.emit(&mut *buffer, &self.caps.checksum);
+        #[cfg(feature = "proto-ipv4")]
+        if let Packet::Ipv4(packet) = packet {
+            let mut ipv4_packet = Ipv4Packet::new_unchecked(&mut *buffer);
+            ipv4_packet.set_dont_frag(packet.dont_frag());
+            if self.caps.checksum.ipv4.tx() {
+                ipv4_packet.fill_checksum();
+            }
+        }
+        packet.emit_payload(&ip_repr, &mut buffer[ip_repr.header_len()..], &self.caps);
+    }
+
+    /// Parse a packet emitted by [`emit_forwarded`](Self::emit_forwarded).
+    fn parse_forwarded(datagram: &[u8]) -> Option<Packet<'_>> {
+        match IpVersion::of_packet(datagram) {
+            #[cfg(feature = "proto-ipv4")]
+            Ok(IpVersion::Ipv4) => {
+                let packet = check!(Ipv4Packet::new_checked(datagram));
+                let repr = check!(Ipv4Repr::parse(&packet, &ChecksumCapabilities::ignored()));
+                Some(Packet::new_ipv4_forwarded(
+                    repr,
+                    packet.payload(),
+                    packet.dont_frag(),
+                ))
+            }
+            #[cfg(feature = "proto-ipv6")]
+            Ok(IpVersion::Ipv6) => {
+                let packet = check!(Ipv6Packet::new_checked(datagram));
+                let repr = check!(Ipv6Repr::parse(&packet));
+                Some(Packet::new_ipv6(repr, IpPayload::Raw(packet.payload())))
+            }
+            _ => None,
+        }
+    }
+
+    /// Build the ICMP error sent to the source of a packet that cannot be forwarded.
+    pub(crate) fn forward_error<'p>(
+        &self,
+        packet: Packet<'p>,
+        error: ForwardError,
+    ) -> Option<Packet<'p>> {
+        let IpPayload::Raw(payload) = *packet.payload() else {
+            return None;
+        };
+
+        match packet.ip_repr() {
+            #[cfg(feature = "proto-ipv4")]
+            IpRepr::Ipv4(ipv4_repr) => {
+                self.icmpv4_forward_error(ipv4_repr, payload, |header, data| match error {
+                    // IPv4 has no address scopes.
+                    ForwardError::NoRoute | ForwardError::BeyondScope => {
+                        Icmpv4Repr::DstUnreachable {
+                            reason: Icmpv4DstUnreachable::NetUnreachable,
+                            header,
+                            data,
+                        }
+                    }
+                    ForwardError::PacketTooBig(_) => Icmpv4Repr::DstUnreachable {
+                        reason: Icmpv4DstUnreachable::FragRequired,
+                        header,
+                        data,
+                    },
+                })
+            }
+            #[cfg(feature = "proto-ipv6")]
+            IpRepr::Ipv6(ipv6_repr) => {
+                self.icmpv6_forward_error(ipv6_repr, payload, |header, data| match error {
+                    ForwardError::NoRoute => Icmpv6Repr::DstUnreachable {
+                        reason: Icmpv6DstUnreachable::NoRoute,
+                        header,
+                        data,
+                    },
+                    ForwardError::BeyondScope => Icmpv6Repr::DstUnreachable {
+                        reason: Icmpv6DstUnreachable::BeyondScope,
+                        header,
+                        data,
+                    },
+                    ForwardError::PacketTooBig(mtu) => Icmpv6Repr::PktTooBig {
+                        mtu: mtu as u32,
+                        header,
+                        data,
+                    },
+                })
+            }
+        }
+    }
+}
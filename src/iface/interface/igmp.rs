@@ -1,124 +1,268 @@
 use super::*;
+use crate::config::IFACE_MAX_MULTICAST_SOURCE_COUNT;
 
-/// Error type for `join_multicast_group`, `leave_multicast_group`.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub enum MulticastError {
-    /// The hardware device transmit buffer is full. Try again later.
-    Exhausted,
-    /// The table of joined multicast groups is already full.
-    GroupTableFull,
-    /// IPv6 multicast is not yet supported.
-    Ipv6NotSupported,
-}
+/// The number of times a state change report is sent, see [RFC 3376 § 8.1].
+///
+/// [RFC 3376 § 8.1]: https://tools.ietf.org/html/rfc3376#section-8.1
+const IGMP_ROBUSTNESS: u8 = 2;
 
-impl core::fmt::Display for MulticastError {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        match self {
-            MulticastError::Exhausted => write!(f, "Exhausted"),
-            MulticastError::GroupTableFull => write!(f, "GroupTableFull"),
-            MulticastError::Ipv6NotSupported => write!(f, "Ipv6NotSupported"),
-        }
+/// The maximum delay between the retransmissions of a state change report, see
+/// [RFC 3376 § 8.11].
+///
+/// [RFC 3376 § 8.11]: https://tools.ietf.org/html/rfc3376#section-8.11
+const IGMP_UNSOLICITED_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the interface speaks an older version of IGMP after hearing a query of that
+/// version, with the default robustness, query interval and query response interval, see
+/// [RFC 3376 § 8.12].
+///
+/// [RFC 3376 § 8.12]: https://tools.ietf.org/html/rfc3376#section-8.12
+const IGMP_OLDER_VERSION_QUERIER_PRESENT_TIMEOUT: Duration = Duration::from_secs(2 * 125 + 10);
+
+/// The length of a group record without its sources.
+const IGMP_RECORD_HEADER_LEN: usize = 8;
+
+/// The length of the largest group record we emit.
+const IGMP_RECORD_MAX_LEN: usize = IGMP_RECORD_HEADER_LEN + 4 * IFACE_MAX_MULTICAST_SOURCE_COUNT;
+
+/// The length of the records of a report that fits in the minimum IPv4 MTU, after the IPv4
+/// header and the report header.
+const IGMP_REPORT_MAX_LEN: usize = IPV4_MIN_MTU - IPV4_HEADER_LEN - 8;
+
+/// Emit a group record in `buffer`, and return its length, or `None` if it does not fit.
+fn emit_record(
+    buffer: &mut [u8],
+    record_type: IgmpRecordType,
+    group: Ipv4Address,
+    sources: &[Ipv4Address],
+) -> Option<usize> {
+    let len = IGMP_RECORD_HEADER_LEN + 4 * sources.len();
+    let mut record = IgmpGroupRecord::new_unchecked(buffer.get_mut(..len)?);
+    record.set_record_type(record_type);
+    record.set_aux_data_len(0);
+    record.set_num_srcs(sources.len() as u16);
+    record.set_mcast_addr(group);
+    for (index, source) in sources.iter().enumerate() {
+        record.set_source(index, *source);
     }
+
+    Some(len)
 }
 
-#[cfg(feature = "std")]
-impl std::error::Error for MulticastError {}
+/// Return the type of the record reporting the current state of a group in a given filter mode.
+fn current_state_record(filter_mode: MulticastFilterMode) -> IgmpRecordType {
+    match filter_mode {
+        MulticastFilterMode::Include => IgmpRecordType::ModeIsInclude,
+        MulticastFilterMode::Exclude => IgmpRecordType::ModeIsExclude,
+    }
+}
 
 impl Interface {
-    /// Add an address to a list of subscribed multicast IP addresses.
+    /// Set the source filter of an IPv4 multicast group, sending a report when it changed.
+    /// An INCLUDE filter without sources leaves the group.
     ///
-    /// Returns `Ok(announce_sent)` if the address was added successfully, where `announce_sent`
-    /// indicates whether an initial immediate announcement has been sent.
-    pub fn join_multicast_group<D, T: Into<IpAddress>>(
+    /// While an IGMPv1 or IGMPv2 querier is present, only joining and leaving the group is
+    /// reported, and the source filter is only applied to the received packets.
+    pub(super) fn join_ipv4_multicast_group<D>(
         &mut self,
         device: &mut D,
-        addr: T,
-        timestamp: Instant,
+        addr: Ipv4Address,
+        filter_mode: MulticastFilterMode,
+        sources: MulticastSources<Ipv4Address>,
     ) -> Result<bool, MulticastError>
     where
         D: Device + ?Sized,
     {
-        self.inner.now = timestamp;
-
-        match addr.into() {
-            IpAddress::Ipv4(addr) => {
-                let is_not_new = self
-                    .inner
-                    .ipv4_multicast_groups
-                    .insert(addr, ())
-                    .map_err(|_| MulticastError::GroupTableFull)?
-                    .is_some();
-                if is_not_new {
-                    Ok(false)
-                } else if let Some(pkt) = self.inner.igmp_report_packet(IgmpVersion::Version2, addr)
-                {
-                    // Send initial membership report
-                    let tx_token = device
-                        .transmit(timestamp)
-                        .ok_or(MulticastError::Exhausted)?;
+        let older_version = self.inner.igmp_older_version();
+        let groups = &mut self.inner.ipv4_multicast_groups;
+        let was_member = matches!(groups.get(&addr), Some(group) if group.is_member());
+        let changed = match groups.get_mut(&addr) {
+            Some(group) => group.set_filter(filter_mode, sources),
+            None if filter_mode == MulticastFilterMode::Include && sources.is_empty() => {
+                return Ok(false)
+            }
+            None => {
+                let mut group = MulticastGroup::new();
+                group.set_filter(filter_mode, sources);
+                groups
+                    .insert(addr, group)
+                    .map_err(|_| MulticastError::GroupTableFull)?;
+                true
+            }
+        };
+        let is_member = groups[&addr].is_member();
 
-                    // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
-                    self.inner
-                        .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
-                        .unwrap();
+        // The all-systems group is never reported, see RFC 3376 § 5.
+        if addr == Ipv4Address::MULTICAST_ALL_SYSTEMS || !changed {
+            if !is_member {
+                groups.remove(&addr);
+            }
+            return Ok(false);
+        }
 
-                    Ok(true)
-                } else {
-                    Ok(false)
+        let pkt = match older_version {
+            None => {
+                groups[&addr].state_change = Some((IGMP_ROBUSTNESS, self.inner.now));
+                self.igmp_send_state_change(device, addr)?;
+                return Ok(true);
+            }
+            // Older versions only report joining and leaving the group, see RFC 3376 § 7.2.1.
+            Some(version) if is_member && !was_member => {
+                self.inner.igmp_report_packet(version, addr)
+            }
+            Some(version) if !is_member => {
+                groups.remove(&addr);
+                match version {
+                    IgmpVersion::Version1 => None,
+                    IgmpVersion::Version2 => self.inner.igmp_leave_packet(addr),
                 }
             }
-            // Multicast is not yet implemented for other address families
-            #[allow(unreachable_patterns)]
-            _ => Err(MulticastError::Ipv6NotSupported),
+            Some(_) => None,
+        };
+
+        if let Some(pkt) = pkt {
+            let tx_token = device
+                .transmit(self.inner.now)
+                .ok_or(MulticastError::Exhausted)?;
+
+            // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+            self.inner
+                .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                .unwrap();
+
+            Ok(true)
+        } else {
+            Ok(false)
         }
     }
 
-    /// Remove an address from the subscribed multicast IP addresses.
-    ///
-    /// Returns `Ok(leave_sent)` if the address was removed successfully, where `leave_sent`
-    /// indicates whether an immediate leave packet has been sent.
-    pub fn leave_multicast_group<D, T: Into<IpAddress>>(
+    /// Send the IGMPv3 state change report of a group, and schedule its retransmission.
+    fn igmp_send_state_change<D>(
         &mut self,
         device: &mut D,
-        addr: T,
-        timestamp: Instant,
-    ) -> Result<bool, MulticastError>
+        addr: Ipv4Address,
+    ) -> Result<(), MulticastError>
+    where
+        D: Device + ?Sized,
+    {
+        let Some(group) = self.inner.ipv4_multicast_groups.get(&addr) else {
+            return Ok(());
+        };
+
+        let record_type = match group.filter_mode {
+            MulticastFilterMode::Include => IgmpRecordType::ChangeToInclude,
+            MulticastFilterMode::Exclude => IgmpRecordType::ChangeToExclude,
+        };
+        let mut data = [0u8; IGMP_RECORD_MAX_LEN];
+        // NOTE(unwrap): the buffer fits a record with all the sources of a group.
+        let len = emit_record(&mut data, record_type, addr, &group.sources).unwrap();
+
+        let tx_token = device
+            .transmit(self.inner.now)
+            .ok_or(MulticastError::Exhausted)?;
+        let pkt = self.inner.igmp_v3_report_packet(1, &data[..len]);
+        // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+        self.inner
+            .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+            .unwrap();
+
+        let next_report = self.inner.now
+            + self
+                .inner
+                .multicast_random_delay(IGMP_UNSOLICITED_REPORT_INTERVAL);
+        let group = &mut self.inner.ipv4_multicast_groups[&addr];
+        match group.state_change {
+            Some((count, _)) if count > 1 => group.state_change = Some((count - 1, next_report)),
+            _ if group.is_member() => group.state_change = None,
+            _ => {
+                self.inner.ipv4_multicast_groups.remove(&addr);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send the IGMPv3 reports that are due: the response to a general query, the responses
+    /// to the queries about a group, and the retransmissions of the state change reports.
+    fn igmpv3_egress<D>(&mut self, device: &mut D) -> bool
     where
         D: Device + ?Sized,
     {
-        self.inner.now = timestamp;
-
-        match addr.into() {
-            IpAddress::Ipv4(addr) => {
-                let was_not_present = self.inner.ipv4_multicast_groups.remove(&addr).is_none();
-                if was_not_present {
-                    Ok(false)
-                } else if let Some(pkt) = self.inner.igmp_leave_packet(addr) {
-                    // Send group leave packet
-                    let tx_token = device
-                        .transmit(timestamp)
-                        .ok_or(MulticastError::Exhausted)?;
+        let now = self.inner.now;
+
+        if let IgmpReportState::ToGeneralQueryV3 {
+            timeout,
+            next_index,
+        } = self.inner.igmp_report_state
+        {
+            if now >= timeout {
+                let mut data = [0u8; IGMP_REPORT_MAX_LEN];
+                let (records, len, next_index) =
+                    self.inner.igmp_general_report(next_index, &mut data);
 
+                if records > 0 {
+                    let Some(tx_token) = device.transmit(now) else {
+                        return false;
+                    };
+                    let pkt = self.inner.igmp_v3_report_packet(records, &data[..len]);
                     // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
                     self.inner
                         .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
                         .unwrap();
-
-                    Ok(true)
-                } else {
-                    Ok(false)
                 }
+
+                self.inner.igmp_report_state = match next_index {
+                    Some(next_index) => IgmpReportState::ToGeneralQueryV3 {
+                        timeout,
+                        next_index,
+                    },
+                    None => IgmpReportState::Inactive,
+                };
+                return true;
             }
-            // Multicast is not yet implemented for other address families
-            #[allow(unreachable_patterns)]
-            _ => Err(MulticastError::Ipv6NotSupported),
         }
-    }
 
-    /// Check whether the interface listens to given destination multicast IP address.
-    pub fn has_multicast_group<T: Into<IpAddress>>(&self, addr: T) -> bool {
-        self.inner.has_multicast_group(addr)
+        let due = self
+            .inner
+            .ipv4_multicast_groups
+            .iter()
+            .find_map(
+                |(addr, group)| match (&group.query_response, group.state_change) {
+                    (Some((timeout, _)), _) if now >= *timeout => Some((*addr, true)),
+                    (_, Some((_, timeout))) if now >= timeout => Some((*addr, false)),
+                    _ => None,
+                },
+            );
+
+        match due {
+            Some((addr, true)) => {
+                let group = &mut self.inner.ipv4_multicast_groups[&addr];
+                let mut data = [0u8; IGMP_RECORD_MAX_LEN];
+                let len = group
+                    .query_response
+                    .as_ref()
+                    .and_then(|(_, queried)| group.current_state(queried))
+                    .and_then(|(filter_mode, sources)| {
+                        emit_record(&mut data, current_state_record(filter_mode), addr, &sources)
+                    });
+
+                if let Some(len) = len {
+                    let Some(tx_token) = device.transmit(now) else {
+                        return false;
+                    };
+                    let pkt = self.inner.igmp_v3_report_packet(1, &data[..len]);
+                    // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+                    self.inner
+                        .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                        .unwrap();
+                }
+
+                self.inner.ipv4_multicast_groups[&addr].query_response = None;
+                true
+            }
+            Some((addr, false)) => self.igmp_send_state_change(device, addr).is_ok(),
+            None => false,
+        }
     }
 
     /// Depending on `igmp_report_state` and the therein contained
@@ -159,7 +303,7 @@ impl Interface {
                     .ipv4_multicast_groups
                     .iter()
                     .nth(next_index)
-                    .map(|(addr, ())| *addr);
+                    .map(|(addr, _)| *addr);
 
                 match addr {
                     Some(addr) => {
@@ -196,17 +340,109 @@ impl Interface {
                     }
                 }
             }
-            _ => false,
+            _ => self.igmpv3_egress(device),
         }
     }
 }
 
 impl InterfaceInner {
-    /// Host duties of the **IGMPv2** protocol.
+    /// Return the version of the oldest querier heard on the link recently, which the
+    /// interface then speaks, see [RFC 3376 § 7.2.1]. `None` means that IGMPv3 is spoken.
+    ///
+    /// [RFC 3376 § 7.2.1]: https://tools.ietf.org/html/rfc3376#section-7.2.1
+    pub(super) fn igmp_older_version(&self) -> Option<IgmpVersion> {
+        let present = |timeout: Option<Instant>| matches!(timeout, Some(t) if self.now < t);
+        if present(self.igmpv1_querier_present) {
+            Some(IgmpVersion::Version1)
+        } else if present(self.igmpv2_querier_present) {
+            Some(IgmpVersion::Version2)
+        } else {
+            None
+        }
+    }
+
+    /// Record that a querier of an older version is present, and cancel the pending IGMPv3
+    /// reports when switching to that version.
+    fn igmp_older_querier_present(&mut self, version: IgmpVersion) {
+        let timeout = Some(self.now + IGMP_OLDER_VERSION_QUERIER_PRESENT_TIMEOUT);
+        match version {
+            IgmpVersion::Version1 => self.igmpv1_querier_present = timeout,
+            IgmpVersion::Version2 => self.igmpv2_querier_present = timeout,
+        }
+
+        if let IgmpReportState::ToGeneralQueryV3 { .. } = self.igmp_report_state {
+            self.igmp_report_state = IgmpReportState::Inactive;
+        }
+        for group in self.ipv4_multicast_groups.values_mut() {
+            group.state_change = None;
+            group.query_response = None;
+        }
+        // The groups that were being left are forgotten, older versions only report leaving
+        // a group once.
+        while let Some(addr) = self
+            .ipv4_multicast_groups
+            .iter()
+            .find(|(_, group)| !group.is_member())
+            .map(|(addr, _)| *addr)
+        {
+            self.ipv4_multicast_groups.remove(&addr);
+        }
+    }
+
+    /// Return the time at which the next IGMP report is due.
+    pub(super) fn igmp_poll_at(&self) -> Option<Instant> {
+        let general = match self.igmp_report_state {
+            IgmpReportState::Inactive => None,
+            IgmpReportState::ToGeneralQuery { timeout, .. }
+            | IgmpReportState::ToSpecificQuery { timeout, .. }
+            | IgmpReportState::ToGeneralQueryV3 { timeout, .. } => Some(timeout),
+        };
+
+        self.ipv4_multicast_groups
+            .values()
+            .flat_map(|group| {
+                let query = group.query_response.as_ref().map(|(timeout, _)| *timeout);
+                let state_change = group.state_change.map(|(_, timeout)| timeout);
+                query.into_iter().chain(state_change)
+            })
+            .chain(general)
+            .min()
+    }
+
+    /// Emit in `buffer` the group records of the response to a general query, starting at the
+    /// `next_index`-th group. Return the number of records, their length, and the index of the
+    /// first group left for the next report.
+    fn igmp_general_report(
+        &self,
+        next_index: usize,
+        buffer: &mut [u8],
+    ) -> (u16, usize, Option<usize>) {
+        let groups = self.ipv4_multicast_groups.iter().filter(|(addr, group)| {
+            group.is_member() && **addr != Ipv4Address::MULTICAST_ALL_SYSTEMS
+        });
+
+        let mut records = 0;
+        let mut len = 0;
+        for (index, (addr, group)) in groups.enumerate().skip(next_index) {
+            let record_type = current_state_record(group.filter_mode);
+            match emit_record(&mut buffer[len..], record_type, *addr, &group.sources) {
+                Some(record_len) => {
+                    records += 1;
+                    len += record_len;
+                }
+                None => return (records, len, Some(index)),
+            }
+        }
+
+        (records, len, None)
+    }
+
+    /// Host duties of the **IGMPv1**, **IGMPv2** and **IGMPv3** protocols.
     ///
     /// Sets up `igmp_report_state` for responding to IGMP general/specific membership queries.
     /// Membership must not be reported immediately in order to avoid flooding the network
-    /// after a query is broadcasted by a router; this is not currently done.
+    /// after a query is broadcasted by a router. IGMPv3 queries are answered after a random
+    /// delay, while the IGMPv1/v2 reports are spread evenly across the maximum response time.
     pub(super) fn process_igmp<'frame>(
         &mut self,
         ipv4_repr: Ipv4Repr,
@@ -215,55 +451,117 @@ impl InterfaceInner {
         let igmp_packet = check!(IgmpPacket::new_checked(ip_payload));
         let igmp_repr = check!(IgmpRepr::parse(&igmp_packet));
 
-        // FIXME: report membership after a delay
         match igmp_repr {
             IgmpRepr::MembershipQuery {
                 group_addr,
                 version,
                 max_resp_time,
             } => {
-                // General query
-                if group_addr.is_unspecified()
-                    && ipv4_repr.dst_addr == Ipv4Address::MULTICAST_ALL_SYSTEMS
-                {
-                    // Are we member in any groups?
-                    if self.ipv4_multicast_groups.iter().next().is_some() {
-                        let interval = match version {
-                            IgmpVersion::Version1 => Duration::from_millis(100),
-                            IgmpVersion::Version2 => {
-                                // No dependence on a random generator
-                                // (see [#24](https://github.com/m-labs/smoltcp/issues/24))
-                                // but at least spread reports evenly across max_resp_time.
-                                let intervals = self.ipv4_multicast_groups.len() as u32 + 1;
-                                max_resp_time / intervals
-                            }
-                        };
-                        self.igmp_report_state = IgmpReportState::ToGeneralQuery {
-                            version,
-                            timeout: self.now + interval,
-                            interval,
-                            next_index: 0,
-                        };
-                    }
-                } else {
-                    // Group-specific query
-                    if self.has_multicast_group(group_addr) && ipv4_repr.dst_addr == group_addr {
-                        // Don't respond immediately
-                        let timeout = max_resp_time / 4;
-                        self.igmp_report_state = IgmpReportState::ToSpecificQuery {
-                            version,
-                            timeout: self.now + timeout,
-                            group: group_addr,
-                        };
-                    }
-                }
+                self.igmp_older_querier_present(version);
+                // A IGMPv2 query is answered with IGMPv1 reports while a IGMPv1 querier is
+                // present.
+                let version = self.igmp_older_version().unwrap_or(version);
+                self.process_igmp_older_query(ipv4_repr, group_addr, version, max_resp_time);
             }
+            IgmpRepr::MembershipQueryV3 {
+                max_resp_time,
+                group_addr,
+                num_srcs,
+                data,
+                ..
+            } => match self.igmp_older_version() {
+                Some(version) => {
+                    self.process_igmp_older_query(ipv4_repr, group_addr, version, max_resp_time)
+                }
+                None => {
+                    let sources = data
+                        .chunks_exact(4)
+                        .take(num_srcs as usize)
+                        .map(Ipv4Address::from_bytes);
+                    self.process_igmpv3_query(group_addr, max_resp_time, sources);
+                }
+            },
             // Ignore membership reports
-            IgmpRepr::MembershipReport { .. } => (),
+            IgmpRepr::MembershipReport { .. } | IgmpRepr::MembershipReportV3 { .. } => (),
             // Ignore hosts leaving groups
             IgmpRepr::LeaveGroup { .. } => (),
         }
 
         None
     }
+
+    /// Schedule the IGMPv1/v2 reports answering a query.
+    fn process_igmp_older_query(
+        &mut self,
+        ipv4_repr: Ipv4Repr,
+        group_addr: Ipv4Address,
+        version: IgmpVersion,
+        max_resp_time: Duration,
+    ) {
+        // General query
+        if group_addr.is_unspecified() && ipv4_repr.dst_addr == Ipv4Address::MULTICAST_ALL_SYSTEMS {
+            // Are we member in any groups?
+            if self.ipv4_multicast_groups.iter().next().is_some() {
+                let interval = match version {
+                    IgmpVersion::Version1 => Duration::from_millis(100),
+                    IgmpVersion::Version2 => {
+                        // No dependence on a random generator
+                        // (see [#24](https://github.com/m-labs/smoltcp/issues/24))
+                        // but at least spread reports evenly across max_resp_time.
+                        let intervals = self.ipv4_multicast_groups.len() as u32 + 1;
+                        max_resp_time / intervals
+                    }
+                };
+                self.igmp_report_state = IgmpReportState::ToGeneralQuery {
+                    version,
+                    timeout: self.now + interval,
+                    interval,
+                    next_index: 0,
+                };
+            }
+        } else {
+            // Group-specific query
+            if self.has_multicast_group(group_addr) && ipv4_repr.dst_addr == group_addr {
+                // Don't respond immediately
+                let timeout = max_resp_time / 4;
+                self.igmp_report_state = IgmpReportState::ToSpecificQuery {
+                    version,
+                    timeout: self.now + timeout,
+                    group: group_addr,
+                };
+            }
+        }
+    }
+
+    /// Schedule the IGMPv3 report answering a query, see [RFC 3376 § 5.2].
+    ///
+    /// [RFC 3376 § 5.2]: https://tools.ietf.org/html/rfc3376#section-5.2
+    fn process_igmpv3_query(
+        &mut self,
+        group_addr: Ipv4Address,
+        max_resp_time: Duration,
+        sources: impl Iterator<Item = Ipv4Address>,
+    ) {
+        let timeout = self.now + self.multicast_random_delay(max_resp_time);
+
+        let general_pending = matches!(
+            self.igmp_report_state,
+            IgmpReportState::ToGeneralQueryV3 { timeout: pending, .. } if pending <= timeout
+        );
+
+        if group_addr.is_unspecified() {
+            if !general_pending {
+                self.igmp_report_state = IgmpReportState::ToGeneralQueryV3 {
+                    timeout,
+                    next_index: 0,
+                };
+            }
+        } else if !general_pending {
+            if let Some(group) = self.ipv4_multicast_groups.get_mut(&group_addr) {
+                if group.is_member() {
+                    group.schedule_query_response(timeout, sources);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,776 @@
+use super::*;
+
+use crate::iface::ipsec::{icv_eq, Direction, Protocol, SecurityAssociation, MAX_ICV_LEN};
+use crate::iface::ipsec::{PolicyAction, SecurityAssociations, SecurityPolicies, Selector};
+
+impl Interface {
+    /// Get the IPsec security association database.
+    pub fn ipsec_associations(&self) -> &SecurityAssociations {
+        &self.inner.ipsec_sad
+    }
+
+    /// Get a mutable reference to the IPsec security association database.
+    pub fn ipsec_associations_mut(&mut self) -> &mut SecurityAssociations {
+        &mut self.inner.ipsec_sad
+    }
+
+    /// Get the IPsec security policy database.
+    pub fn ipsec_policies(&self) -> &SecurityPolicies {
+        &self.inner.ipsec_spd
+    }
+
+    /// Get a mutable reference to the IPsec security policy database.
+    pub fn ipsec_policies_mut(&mut self) -> &mut SecurityPolicies {
+        &mut self.inner.ipsec_spd
+    }
+}
+
+/// The result of applying the security policy database to an outgoing packet.
+pub(super) enum IpSecEgress {
+    /// Send the packet as is.
+    Bypass,
+    /// Drop the packet.
+    Discard,
+    /// Protect the packet with AH or ESP.
+    Protect(Encapsulation),
+}
+
+/// The IPsec header wrapped around the payload of an outgoing packet.
+pub(super) enum Encapsulation {
+    #[cfg(feature = "proto-ipsec-ah")]
+    Ah(AhEncapsulation),
+    #[cfg(feature = "proto-ipsec-esp")]
+    Esp(EspEncapsulation),
+}
+
+impl Encapsulation {
+    /// Return the IP protocol number of the IPsec header.
+    pub(super) fn protocol(&self) -> IpProtocol {
+        match self {
+            #[cfg(feature = "proto-ipsec-ah")]
+            Self::Ah(_) => IpProtocol::IpSecAh,
+            #[cfg(feature = "proto-ipsec-esp")]
+            Self::Esp(_) => IpProtocol::IpSecEsp,
+        }
+    }
+
+    /// Return the length of the IPsec packet, which replaces the original IP payload.
+    pub(super) fn buffer_len(&self) -> usize {
+        match self {
+            #[cfg(feature = "proto-ipsec-ah")]
+            Self::Ah(ah) => ah.buffer_len(),
+            #[cfg(feature = "proto-ipsec-esp")]
+            Self::Esp(esp) => esp.buffer_len(),
+        }
+    }
+
+    /// Update the next header and payload length of `ip_repr` for the encapsulated packet.
+    pub(super) fn encapsulate(&self, ip_repr: &mut IpRepr) {
+        encapsulate(ip_repr, self.protocol(), self.buffer_len());
+    }
+
+    /// Return the SPI of the outbound security association protecting the packet.
+    fn spi(&self) -> u32 {
+        match self {
+            #[cfg(feature = "proto-ipsec-ah")]
+            Self::Ah(ah) => ah.spi,
+            #[cfg(feature = "proto-ipsec-esp")]
+            Self::Esp(esp) => esp.spi,
+        }
+    }
+
+    /// Set the sequence number of the packet.
+    fn set_sequence_number(&mut self, sequence_number: u64) {
+        match self {
+            #[cfg(feature = "proto-ipsec-ah")]
+            Self::Ah(ah) => ah.sequence_number = sequence_number,
+            #[cfg(feature = "proto-ipsec-esp")]
+            Self::Esp(esp) => esp.sequence_number = sequence_number,
+        }
+    }
+
+    /// Set the identification of an IPv4 packet that is fragmented after encapsulation.
+    #[cfg(feature = "proto-ipv4-fragmentation")]
+    pub(super) fn set_ipv4_ident(&mut self, ident: u16) {
+        match self {
+            #[cfg(feature = "proto-ipsec-ah")]
+            Self::Ah(ah) => ah.ipv4_ident = ident,
+            #[cfg(feature = "proto-ipsec-esp")]
+            Self::Esp(_) => (),
+        }
+    }
+
+    /// Emit the IPsec packet into `buffer`, using `emit_payload` to emit the inner payload.
+    pub(super) fn emit(
+        &self,
+        sad: &SecurityAssociations,
+        buffer: &mut [u8],
+        emit_payload: impl FnOnce(&mut [u8]),
+    ) {
+        // NOTE(unwrap): the security association was found when the packet was encapsulated,
+        // and it cannot be removed while the packet is dispatched.
+        let sa = sad.get(self.spi(), Direction::Outbound).unwrap();
+        match self {
+            #[cfg(feature = "proto-ipsec-ah")]
+            Self::Ah(ah) => ah.emit(sa, buffer, emit_payload),
+            #[cfg(feature = "proto-ipsec-esp")]
+            Self::Esp(esp) => esp.emit(sa, buffer, emit_payload),
+        }
+    }
+}
+
+fn encapsulate(ip_repr: &mut IpRepr, protocol: IpProtocol, payload_len: usize) {
+    match ip_repr {
+        #[cfg(feature = "proto-ipv4")]
+        IpRepr::Ipv4(repr) => repr.next_header = protocol,
+        #[cfg(feature = "proto-ipv6")]
+        IpRepr::Ipv6(repr) => repr.next_header = protocol,
+    }
+    ip_repr.set_payload_len(payload_len);
+}
+
+/// Everything that is needed to insert an AH into an outgoing packet.
+#[cfg(feature = "proto-ipsec-ah")]
+pub(super) struct AhEncapsulation {
+    spi: u32,
+    sequence_number: u64,
+    inner_protocol: IpProtocol,
+    inner_len: usize,
+    icv_len: usize,
+    /// The header of the encapsulated packet, which is covered by the ICV.
+    ip_repr: IpRepr,
+    #[cfg(feature = "proto-ipv4")]
+    ipv4_ident: u16,
+}
+
+#[cfg(feature = "proto-ipsec-ah")]
+impl AhEncapsulation {
+    /// Return `None` if the security association has no integrity algorithm.
+    fn new(sa: &SecurityAssociation, ip_repr: &IpRepr) -> Option<Self> {
+        let Some((alg, _)) = sa.integrity() else {
+            net_debug!("ah: no integrity algorithm configured");
+            return None;
+        };
+
+        let mut ah = Self {
+            spi: sa.spi(),
+            sequence_number: 0,
+            inner_protocol: ip_repr.next_header(),
+            inner_len: ip_repr.payload_len(),
+            icv_len: alg.icv_len(),
+            ip_repr: ip_repr.clone(),
+            #[cfg(feature = "proto-ipv4")]
+            ipv4_ident: 0,
+        };
+        let buffer_len = ah.buffer_len();
+        encapsulate(&mut ah.ip_repr, IpProtocol::IpSecAh, buffer_len);
+        Some(ah)
+    }
+
+    /// Length of the AH, including the ICV and its padding.
+    ///
+    /// RFC 4302 § 2.2: the header must be a multiple of 32 bits for IPv4, and a multiple of
+    /// 64 bits for IPv6.
+    fn header_len(&self) -> usize {
+        let align = match self.ip_repr.version() {
+            #[cfg(feature = "proto-ipv4")]
+            IpVersion::Ipv4 => 4,
+            #[cfg(feature = "proto-ipv6")]
+            IpVersion::Ipv6 => 8,
+        };
+        (AH_FIXED_LEN + self.icv_len + align - 1) / align * align
+    }
+
+    /// Return the length of the AH and the inner payload.
+    fn buffer_len(&self) -> usize {
+        self.header_len() + self.inner_len
+    }
+
+    /// Emit the AH and the inner payload into `buffer`.
+    fn emit(
+        &self,
+        sa: &SecurityAssociation,
+        buffer: &mut [u8],
+        emit_payload: impl FnOnce(&mut [u8]),
+    ) {
+        let header_len = self.header_len();
+        let icv_len = self.icv_len;
+        let (header, payload) = buffer[..self.buffer_len()].split_at_mut(header_len);
+
+        emit_payload(payload);
+
+        let mut ah_packet = IpSecAuthHeaderPacket::new_unchecked(&mut header[..]);
+        ah_packet.set_next_header(self.inner_protocol);
+        ah_packet.set_payload_len((header_len / 4 - 2) as u8);
+        ah_packet.clear_reserved();
+        ah_packet.set_security_parameters_index(self.spi);
+        ah_packet.set_sequence_number(self.sequence_number as u32);
+        ah_packet.integrity_check_value_mut().fill(0);
+
+        let mut ip_header = [0u8; MAX_IP_HEADER_LEN];
+        let ip_header = &mut ip_header[..self.ip_repr.header_len()];
+        self.ip_repr
+            .emit(&mut ip_header[..], &ChecksumCapabilities::ignored());
+        #[cfg(feature = "proto-ipv4")]
+        #[allow(irrefutable_let_patterns)] // if only ipv4 is enabled
+        if let IpRepr::Ipv4(_) = self.ip_repr {
+            Ipv4Packet::new_unchecked(&mut ip_header[..]).set_ident(self.ipv4_ident);
+        }
+        clear_mutable_fields(ip_header);
+
+        if let Some((alg, key)) = sa.integrity() {
+            let mut seq_high = [0u8; 4];
+            let seq_high = implicit_seq_high(sa, self.sequence_number, &mut seq_high);
+            let mut icv = [0u8; MAX_ICV_LEN];
+            alg.compute(
+                key,
+                &[&ip_header[..], &header[..], &payload[..], seq_high],
+                &mut icv[..icv_len],
+            );
+            header[AH_FIXED_LEN..][..icv_len].copy_from_slice(&icv[..icv_len]);
+        }
+    }
+}
+
+/// Return the high-order 32 bits of an extended sequence number, which are covered by the
+/// ICV but not transmitted. Returns an empty slice if ESN is disabled.
+fn implicit_seq_high<'a>(sa: &SecurityAssociation, seq: u64, buffer: &'a mut [u8; 4]) -> &'a [u8] {
+    if !sa.extended_sequence_numbers() {
+        return &[];
+    }
+    *buffer = ((seq >> 32) as u32).to_be_bytes();
+    &buffer[..]
+}
+
+/// Length of the AH fields preceding the ICV.
+#[cfg(feature = "proto-ipsec-ah")]
+const AH_FIXED_LEN: usize = 12;
+
+/// Length of the longest IP header covered by the AH ICV. IPv4 options are not supported.
+#[cfg(feature = "proto-ipsec-ah")]
+const MAX_IP_HEADER_LEN: usize = 40;
+
+/// Zero the IP header fields that may change in transit, see RFC 4302 § 3.3.3.1.
+///
+/// The IPv4 header checksum is zeroed as well.
+#[cfg(feature = "proto-ipsec-ah")]
+fn clear_mutable_fields(ip_header: &mut [u8]) {
+    match IpVersion::of_packet(ip_header) {
+        #[cfg(feature = "proto-ipv4")]
+        Ok(IpVersion::Ipv4) => {
+            let mut packet = Ipv4Packet::new_unchecked(ip_header);
+            packet.set_dscp(0);
+            packet.set_ecn(0);
+            packet.clear_flags();
+            packet.set_frag_offset(0);
+            packet.set_hop_limit(0);
+            packet.set_checksum(0);
+        }
+        #[cfg(feature = "proto-ipv6")]
+        Ok(IpVersion::Ipv6) => {
+            let mut packet = Ipv6Packet::new_unchecked(ip_header);
+            packet.set_traffic_class(0);
+            packet.set_flow_label(0);
+            packet.set_hop_limit(0);
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Everything that is needed to wrap the payload of an outgoing packet in ESP.
+#[cfg(feature = "proto-ipsec-esp")]
+pub(super) struct EspEncapsulation {
+    spi: u32,
+    sequence_number: u64,
+    inner_protocol: IpProtocol,
+    inner_len: usize,
+    iv_len: usize,
+    block_size: usize,
+    icv_len: usize,
+}
+
+#[cfg(feature = "proto-ipsec-esp")]
+impl EspEncapsulation {
+    /// Return `None` if the packet cannot be encrypted with the security association.
+    fn new(sa: &SecurityAssociation, ip_repr: &IpRepr) -> Option<Self> {
+        let (iv_len, block_size) = match sa.encryption() {
+            Some((alg, key)) => {
+                if !alg.block_size().is_power_of_two() {
+                    net_debug!("esp: block size {} is not a power of two", alg.block_size());
+                    return None;
+                }
+                if let Err(e) = alg.check_key(key) {
+                    net_debug!("esp: key rejected by the encryption algorithm: {:?}", e);
+                    return None;
+                }
+                (alg.iv_len(), alg.block_size())
+            }
+            None => (0, 1),
+        };
+
+        Some(Self {
+            spi: sa.spi(),
+            sequence_number: 0,
+            inner_protocol: ip_repr.next_header(),
+            inner_len: ip_repr.payload_len(),
+            iv_len,
+            block_size,
+            icv_len: sa.integrity().map_or(0, |(alg, _)| alg.icv_len()),
+        })
+    }
+
+    /// Length of the encrypted part: the inner payload, the padding, the pad length and the
+    /// next header fields.
+    fn encrypted_len(&self) -> usize {
+        let align = self.block_size.max(4);
+        (self.inner_len + 2 + align - 1) / align * align
+    }
+
+    /// Return the length of the ESP packet, which replaces the original IP payload.
+    fn buffer_len(&self) -> usize {
+        IPSEC_ESP_HEADER_LEN + self.iv_len + self.encrypted_len() + self.icv_len
+    }
+
+    /// Emit the ESP packet into `buffer`, using `emit_payload` to emit the inner payload.
+    fn emit(
+        &self,
+        sa: &SecurityAssociation,
+        buffer: &mut [u8],
+        emit_payload: impl FnOnce(&mut [u8]),
+    ) {
+        let iv_len = self.iv_len;
+        let encrypted_len = self.encrypted_len();
+        let buffer = &mut buffer[..self.buffer_len()];
+
+        IpSecEspRepr {
+            security_parameters_index: self.spi,
+            sequence_number: self.sequence_number as u32,
+        }
+        .emit(&mut IpSecEspPacket::new_unchecked(&mut buffer[..]));
+
+        let (authenticated, icv) =
+            buffer.split_at_mut(IPSEC_ESP_HEADER_LEN + iv_len + encrypted_len);
+        let (iv, data) = authenticated[IPSEC_ESP_HEADER_LEN..].split_at_mut(iv_len);
+
+        emit_payload(&mut data[..self.inner_len]);
+
+        // RFC 4303 § 2.4: the padding bytes are initialized with 1, 2, 3, ...
+        let pad_len = encrypted_len - self.inner_len - 2;
+        for (i, b) in data[self.inner_len..][..pad_len].iter_mut().enumerate() {
+            *b = i as u8 + 1;
+        }
+        data[encrypted_len - 2] = pad_len as u8;
+        data[encrypted_len - 1] = self.inner_protocol.into();
+
+        if let Some((alg, key)) = sa.encryption() {
+            alg.encrypt(key, iv, data);
+        }
+
+        if let Some((alg, key)) = sa.integrity() {
+            let mut seq_high = [0u8; 4];
+            let seq_high = implicit_seq_high(sa, self.sequence_number, &mut seq_high);
+            alg.compute(key, &[authenticated, seq_high], icv);
+        }
+    }
+}
+
+/// Extract the source and destination ports of a TCP or UDP payload.
+fn transport_ports(protocol: IpProtocol, payload: &[u8]) -> (Option<u16>, Option<u16>) {
+    match protocol {
+        IpProtocol::Udp => match UdpPacket::new_checked(payload) {
+            Ok(udp) => (Some(udp.src_port()), Some(udp.dst_port())),
+            Err(_) => (None, None),
+        },
+        IpProtocol::Tcp => match TcpPacket::new_checked(payload) {
+            Ok(tcp) => (Some(tcp.src_port()), Some(tcp.dst_port())),
+            Err(_) => (None, None),
+        },
+        _ => (None, None),
+    }
+}
+
+impl InterfaceInner {
+    /// Apply IPsec processing to a received packet.
+    ///
+    /// The AH is verified against `ip_header`, the raw IP header of the packet. ESP packets
+    /// are verified and decrypted into `buffer`. The security policy database is then applied
+    /// to the (inner) packet. Returns the protocol and payload that should be processed
+    /// further, or `None` if the packet must be dropped.
+    pub(super) fn process_ipsec<'a>(
+        &mut self,
+        ip_repr: &IpRepr,
+        #[allow(unused_variables)] ip_header: &[u8],
+        next_header: IpProtocol,
+        ip_payload: &'a [u8],
+        #[allow(unused_variables)] buffer: Option<&'a mut [u8]>,
+    ) -> Option<(IpProtocol, &'a [u8])> {
+        // The SPI of the security association which verified the packet.
+        #[allow(unused_mut)]
+        let mut protected_by = None;
+
+        #[cfg(feature = "proto-ipsec-ah")]
+        let (next_header, ip_payload) = match next_header {
+            IpProtocol::IpSecAh => {
+                let (spi, next_header, ip_payload) =
+                    self.process_ah(ip_repr.src_addr(), ip_header, ip_payload)?;
+                protected_by = Some(spi);
+                (next_header, ip_payload)
+            }
+            _ => (next_header, ip_payload),
+        };
+
+        #[cfg(feature = "proto-ipsec-esp")]
+        let (next_header, ip_payload) = match next_header {
+            IpProtocol::IpSecEsp => {
+                let Some(buffer) = buffer else {
+                    net_debug!("esp: not supported on this medium");
+                    return None;
+                };
+                let (spi, next_header, ip_payload) =
+                    self.process_esp(ip_repr.src_addr(), ip_payload, buffer)?;
+                protected_by = Some(spi);
+                (next_header, ip_payload)
+            }
+            _ => (next_header, ip_payload),
+        };
+
+        let (src_port, dst_port) = transport_ports(next_header, ip_payload);
+        let selector = Selector {
+            remote_addr: ip_repr.src_addr(),
+            protocol: next_header,
+            local_port: dst_port,
+            remote_port: src_port,
+        };
+
+        match self.ipsec_spd.lookup(&selector) {
+            PolicyAction::Discard => {
+                net_debug!("ipsec: packet discarded by policy");
+                None
+            }
+            PolicyAction::Protect { .. } if protected_by.is_none() => {
+                net_debug!("ipsec: unprotected packet matches a protect policy, dropping");
+                None
+            }
+            PolicyAction::Protect { spi } if protected_by != Some(spi) => {
+                net_debug!(
+                    "ipsec: packet is not protected by the security association with SPI {:#010x}, dropping",
+                    spi
+                );
+                None
+            }
+            _ => Some((next_header, ip_payload)),
+        }
+    }
+
+    /// Look up the inbound security association for a received AH or ESP packet.
+    fn inbound_sa(
+        &mut self,
+        protocol: Protocol,
+        spi: u32,
+        src_addr: IpAddress,
+    ) -> Option<&mut SecurityAssociation> {
+        match self.ipsec_sad.get_mut(spi, Direction::Inbound) {
+            Some(sa) if sa.protocol() == protocol && sa.remote_addr() == src_addr => Some(sa),
+            _ => {
+                net_debug!("ipsec: no security association for SPI {:#010x}", spi);
+                None
+            }
+        }
+    }
+
+    /// Verify the ICV of an AH packet, see RFC 4302 § 3.4. Returns the SPI of the security
+    /// association, and the protocol and payload following the AH.
+    ///
+    /// Only transport mode is supported. For IPv6, the AH must directly follow the fixed
+    /// header, and for IPv4, the header must not contain options.
+    #[cfg(feature = "proto-ipsec-ah")]
+    fn process_ah<'a>(
+        &mut self,
+        src_addr: IpAddress,
+        ip_header: &[u8],
+        ip_payload: &'a [u8],
+    ) -> Option<(u32, IpProtocol, &'a [u8])> {
+        let ah_packet = check!(IpSecAuthHeaderPacket::new_checked(ip_payload));
+        let ah_repr = check!(IpSecAuthHeaderRepr::parse(&ah_packet));
+
+        match IpVersion::of_packet(ip_header) {
+            #[cfg(feature = "proto-ipv4")]
+            Ok(IpVersion::Ipv4) if ip_header.len() != IPV4_HEADER_LEN => {
+                net_debug!("ah: IPv4 options are not supported");
+                return None;
+            }
+            #[cfg(feature = "proto-ipv6")]
+            Ok(IpVersion::Ipv6)
+                if Ipv6Packet::new_unchecked(ip_header).next_header() != IpProtocol::IpSecAh =>
+            {
+                net_debug!("ah: extension headers before the AH are not supported");
+                return None;
+            }
+            _ => (),
+        }
+
+        let sa = self.inbound_sa(Protocol::Ah, ah_repr.security_parameters_index, src_addr)?;
+        let seq = match sa.replay_window_mut().check(ah_repr.sequence_number) {
+            Ok(seq) => seq,
+            Err(e) => {
+                net_debug!("ah: {}", e);
+                return None;
+            }
+        };
+
+        let Some((alg, key)) = sa.integrity() else {
+            net_debug!("ah: no integrity algorithm configured");
+            return None;
+        };
+
+        let icv_len = alg.icv_len();
+        let icv = ah_repr.integrity_check_value;
+        // The ICV field may be followed by up to 7 octets of padding.
+        if icv_len > MAX_ICV_LEN || icv.len() < icv_len || icv.len() > icv_len + 7 {
+            net_debug!("ah: invalid ICV length");
+            return None;
+        }
+
+        let mut header = [0u8; MAX_IP_HEADER_LEN];
+        let header = &mut header[..ip_header.len()];
+        header.copy_from_slice(ip_header);
+        clear_mutable_fields(header);
+
+        // The payload may have been reassembled, so the length field is recomputed.
+        match IpVersion::of_packet(header) {
+            #[cfg(feature = "proto-ipv4")]
+            Ok(IpVersion::Ipv4) => Ipv4Packet::new_unchecked(&mut header[..])
+                .set_total_len((ip_header.len() + ip_payload.len()) as u16),
+            #[cfg(feature = "proto-ipv6")]
+            Ok(IpVersion::Ipv6) => {
+                Ipv6Packet::new_unchecked(&mut header[..]).set_payload_len(ip_payload.len() as u16)
+            }
+            _ => unreachable!(),
+        }
+
+        let ah_len = ah_packet.header_len();
+        let zeros = [0u8; MAX_ICV_LEN + 7];
+        let mut seq_high = [0u8; 4];
+        let seq_high = implicit_seq_high(sa, seq, &mut seq_high);
+        let mut expected = [0u8; MAX_ICV_LEN];
+        alg.compute(
+            key,
+            &[
+                &header[..],
+                &ip_payload[..AH_FIXED_LEN],
+                &zeros[..icv.len()],
+                &ip_payload[ah_len..],
+                seq_high,
+            ],
+            &mut expected[..icv_len],
+        );
+        if !icv_eq(&expected[..icv_len], &icv[..icv_len]) {
+            net_debug!("ah: integrity check failed");
+            return None;
+        }
+
+        sa.replay_window_mut().accept(seq);
+
+        Some((
+            ah_repr.security_parameters_index,
+            ah_repr.next_header,
+            ah_packet.payload(),
+        ))
+    }
+
+    /// Verify and decrypt an ESP packet, see RFC 4303 § 3.4. Returns the SPI of the security
+    /// association, and the protocol and payload of the decrypted packet.
+    ///
+    /// Only transport mode is supported.
+    #[cfg(feature = "proto-ipsec-esp")]
+    fn process_esp<'a>(
+        &mut self,
+        src_addr: IpAddress,
+        ip_payload: &'a [u8],
+        buffer: &'a mut [u8],
+    ) -> Option<(u32, IpProtocol, &'a [u8])> {
+        let esp_packet = check!(IpSecEspPacket::new_checked(ip_payload));
+        let esp_repr = check!(IpSecEspRepr::parse(&esp_packet));
+
+        let sa = self.inbound_sa(Protocol::Esp, esp_repr.security_parameters_index, src_addr)?;
+
+        let iv_len = sa.encryption().map_or(0, |(alg, _)| alg.iv_len());
+        let block_size = sa.encryption().map_or(1, |(alg, _)| alg.block_size());
+        let icv_len = sa.integrity().map_or(0, |(alg, _)| alg.icv_len());
+
+        if ip_payload.len() < IPSEC_ESP_HEADER_LEN + iv_len + 2 + icv_len {
+            net_debug!("esp: packet too short");
+            return None;
+        }
+
+        let (authenticated, icv) = ip_payload.split_at(ip_payload.len() - icv_len);
+
+        // RFC 4303 § 3.4.3: the anti-replay service requires integrity protection.
+        let anti_replay = sa.integrity().is_some();
+        let seq = if anti_replay {
+            match sa.replay_window_mut().check(esp_repr.sequence_number) {
+                Ok(seq) => seq,
+                Err(e) => {
+                    net_debug!("esp: {}", e);
+                    return None;
+                }
+            }
+        } else {
+            sa.replay_window().sequence_number(esp_repr.sequence_number)
+        };
+
+        // Verify the ICV before doing anything else with the packet.
+        if let Some((alg, key)) = sa.integrity() {
+            if icv_len > MAX_ICV_LEN {
+                net_debug!("esp: ICV length {} not supported", icv_len);
+                return None;
+            }
+
+            let mut seq_high = [0u8; 4];
+            let seq_high = implicit_seq_high(sa, seq, &mut seq_high);
+            let mut expected = [0u8; MAX_ICV_LEN];
+            alg.compute(key, &[authenticated, seq_high], &mut expected[..icv_len]);
+            if !icv_eq(&expected[..icv_len], icv) {
+                net_debug!("esp: integrity check failed");
+                return None;
+            }
+        }
+
+        if anti_replay {
+            sa.replay_window_mut().accept(seq);
+        }
+
+        let iv = &authenticated[IPSEC_ESP_HEADER_LEN..][..iv_len];
+        let data = &authenticated[IPSEC_ESP_HEADER_LEN + iv_len..];
+
+        if data.len() % block_size.max(4) != 0 {
+            net_debug!("esp: payload is not aligned");
+            return None;
+        }
+
+        if data.len() > buffer.len() {
+            net_debug!("esp: packet too large for the decryption buffer");
+            return None;
+        }
+
+        let buffer = &mut buffer[..data.len()];
+        buffer.copy_from_slice(data);
+
+        if let Some((alg, key)) = sa.encryption() {
+            if let Err(e) = alg.decrypt(key, iv, buffer) {
+                net_debug!("esp: decryption failed: {:?}", e);
+                return None;
+            }
+        }
+
+        let buffer: &'a [u8] = buffer;
+        let next_header = IpProtocol::from(buffer[buffer.len() - 1]);
+        let pad_len = buffer[buffer.len() - 2] as usize;
+
+        if pad_len + 2 > buffer.len() {
+            net_debug!("esp: invalid pad length");
+            return None;
+        }
+
+        let inner_len = buffer.len() - 2 - pad_len;
+        let padding = &buffer[inner_len..][..pad_len];
+        if padding.iter().enumerate().any(|(i, b)| *b != i as u8 + 1) {
+            net_debug!("esp: invalid padding");
+            return None;
+        }
+
+        match next_header {
+            IpProtocol::Ipv6NoNxt => {
+                net_trace!("esp: dropping dummy packet");
+                None
+            }
+            IpProtocol::Unknown(4) | IpProtocol::Unknown(41) => {
+                net_debug!("esp: tunnel mode is not supported");
+                None
+            }
+            _ => Some((
+                esp_repr.security_parameters_index,
+                next_header,
+                &buffer[..inner_len],
+            )),
+        }
+    }
+
+    /// Apply the security policy database to an outgoing packet.
+    pub(super) fn ipsec_egress(&mut self, packet: &Packet) -> IpSecEgress {
+        let ip_repr = packet.ip_repr();
+
+        let (protocol, ports) = match packet.payload() {
+            #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
+            IpPayload::Udp(udp_repr, _) => (
+                IpProtocol::Udp,
+                (Some(udp_repr.src_port), Some(udp_repr.dst_port)),
+            ),
+            #[cfg(feature = "socket-dhcpv4")]
+            IpPayload::Dhcpv4(udp_repr, _) => (
+                IpProtocol::Udp,
+                (Some(udp_repr.src_port), Some(udp_repr.dst_port)),
+            ),
+            #[cfg(feature = "socket-tcp")]
+            IpPayload::Tcp(tcp_repr) => (
+                IpProtocol::Tcp,
+                (Some(tcp_repr.src_port), Some(tcp_repr.dst_port)),
+            ),
+            _ => (ip_repr.next_header(), (None, None)),
+        };
+
+        let selector = Selector {
+            remote_addr: ip_repr.dst_addr(),
+            protocol,
+            local_port: ports.0,
+            remote_port: ports.1,
+        };
+
+        match self.ipsec_spd.lookup(&selector) {
+            PolicyAction::Bypass => IpSecEgress::Bypass,
+            PolicyAction::Discard => {
+                net_debug!("ipsec: packet discarded by policy");
+                IpSecEgress::Discard
+            }
+            PolicyAction::Protect { spi } => {
+                let Some(sa) = self.ipsec_sad.get_mut(spi, Direction::Outbound) else {
+                    net_debug!(
+                        "ipsec: no outbound security association for SPI {:#010x}",
+                        spi
+                    );
+                    return IpSecEgress::Discard;
+                };
+
+                // The packet is checked before a sequence number is spent on it.
+                let encapsulation = match sa.protocol() {
+                    #[cfg(feature = "proto-ipsec-ah")]
+                    Protocol::Ah => AhEncapsulation::new(sa, &ip_repr).map(Encapsulation::Ah),
+                    #[cfg(feature = "proto-ipsec-esp")]
+                    Protocol::Esp => EspEncapsulation::new(sa, &ip_repr).map(Encapsulation::Esp),
+                    #[allow(unreachable_patterns)]
+                    protocol => {
+                        net_debug!("ipsec: {:?} support is not enabled", protocol);
+                        None
+                    }
+                };
+                let Some(mut encapsulation) = encapsulation else {
+                    return IpSecEgress::Discard;
+                };
+
+                // The IPv4 total length and the IPv6 payload length are 16 bit fields.
+                if ip_repr.header_len() + encapsulation.buffer_len() > u16::MAX as usize {
+                    net_debug!("ipsec: encapsulated packet is too large");
+                    return IpSecEgress::Discard;
+                }
+
+                let Some(sequence_number) = sa.next_sequence_number() else {
+                    net_debug!("ipsec: sequence number exhausted for SPI {:#010x}", spi);
+                    return IpSecEgress::Discard;
+                };
+                encapsulation.set_sequence_number(sequence_number);
+
+                IpSecEgress::Protect(encapsulation)
+            }
+        }
+    }
+}
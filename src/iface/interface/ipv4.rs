@@ -2,6 +2,13 @@ use super::*;
 
 use log::{debug, info};
 
+/// The smallest MTU of an IPv4 path, see RFC 791.
+const MIN_PATH_MTU: usize = 68;
+
+/// Common MTU values, used to estimate the path MTU when a router doesn't report it, see
+/// RFC 1191 § 7.
+const PATH_MTU_PLATEAUS: [usize; 10] = [32000, 17914, 8166, 4352, 2002, 1492, 1006, 508, 296, 68];
+
 impl Interface {
     /// Process fragments that still need to be sent for IPv4 packets.
     ///
@@ -18,7 +25,9 @@ impl Interface {
             self.fragmenter.reset();
         }
 
-        if self.fragmenter.is_empty() {
+        if self.fragmenter.is_empty()
+            || IpVersion::of_packet(&self.fragmenter.buffer) != Ok(IpVersion::Ipv4)
+        {
             return false;
         }
 
@@ -53,6 +62,11 @@ impl InterfaceInner {
         for cidr in self.ip_addrs.iter() {
             #[allow(irrefutable_let_patterns)] // if only ipv4 is enabled
             if let IpCidr::Ipv4(cidr) = cidr {
+                // Probed and conflicting addresses are not used, see RFC 5227 § 2.1.
+                #[cfg(feature = "proto-ipv4-acd")]
+                if !self.acd_is_assigned(&cidr.address()) {
+                    continue;
+                }
                 debug!("lhw debug in get_source_address_ipv4 {}, {}",_dst_addr, cidr.address());
                 return Some(cidr.address());
             }
@@ -98,7 +112,8 @@ impl InterfaceInner {
         ipv4_packet: &Ipv4Packet<&'a [u8]>,
         frag: &'a mut FragmentsBuffer,
     ) -> Option<Packet<'a>> {
-        let ipv4_repr = check!(Ipv4Repr::parse(ipv4_packet, &self.caps.checksum));
+        #[allow(unused_mut)]
+        let mut ipv4_repr = check!(Ipv4Repr::parse(ipv4_packet, &self.caps.checksum));
         if !self.is_unicast_v4(ipv4_repr.src_addr) && !ipv4_repr.src_addr.is_unspecified() {
             // Discard packets with non-unicast source addresses but allow unspecified
             net_debug!("non-unicast or unspecified source address");
@@ -146,7 +161,8 @@ impl InterfaceInner {
         #[cfg(not(feature = "proto-ipv4-fragmentation"))]
         let ip_payload = ipv4_packet.payload();
 
-        let ip_repr = IpRepr::Ipv4(ipv4_repr);
+        #[allow(unused_mut)]
+        let mut ip_repr = IpRepr::Ipv4(ipv4_repr);
 
         #[cfg(feature = "socket-raw")]
         let handled_by_raw_socket = self.raw_socket_filter(sockets, &ip_repr, ip_payload);
@@ -183,6 +199,19 @@ impl InterfaceInner {
             }
         }
 
+        // Drop the packets of the sources filtered out of a joined multicast group. The IGMP
+        // queries about the group are still processed.
+        #[cfg(feature = "proto-igmp")]
+        if ipv4_repr.next_header != IpProtocol::Igmp
+            && matches!(
+                self.ipv4_multicast_groups.get(&ipv4_repr.dst_addr),
+                Some(group) if group.is_member() && !group.accepts(&ipv4_repr.src_addr)
+            )
+        {
+            net_trace!("multicast source filtered out");
+            return None;
+        }
+
         if !self.has_ip_addr(ipv4_repr.dst_addr)
             && !self.has_multicast_group(ipv4_repr.dst_addr)
             && !self.is_broadcast_v4(ipv4_repr.dst_addr)
@@ -196,10 +225,35 @@ impl InterfaceInner {
                     .lookup(&IpAddress::Ipv4(ipv4_repr.dst_addr), self.now)
                     .map_or(true, |router_addr| !self.has_ip_addr(router_addr))
             {
+                #[cfg(feature = "iface-forwarding")]
+                if self.forwarding {
+                    return self.forward_ipv4(ipv4_repr, ipv4_packet.dont_frag(), ip_payload);
+                }
+
                 return None;
             }
         }
 
+        #[cfg(feature = "_proto-ipsec")]
+        let ip_payload = match self.process_ipsec(
+            &ip_repr,
+            &ipv4_packet.as_ref()[..ipv4_packet.header_len() as usize],
+            ipv4_repr.next_header,
+            ip_payload,
+            Some(&mut frag.ipsec_buf[..]),
+        ) {
+            Some((next_header, ip_payload)) => {
+                if next_header != ipv4_repr.next_header {
+                    // The payload was unwrapped, process it as if it was received without IPsec.
+                    ipv4_repr.next_header = next_header;
+                    ipv4_repr.payload_len = ip_payload.len();
+                    ip_repr = IpRepr::Ipv4(ipv4_repr);
+                }
+                ip_payload
+            }
+            None => return None,
+        };
+
         match ipv4_repr.next_header {
             IpProtocol::Icmp => self.process_icmpv4(sockets, ipv4_repr, ip_payload),
 
@@ -248,6 +302,23 @@ impl InterfaceInner {
                 target_protocol_addr,
                 ..
             } => {
+                #[cfg(feature = "proto-ipv4-acd")]
+                self.acd_process_arp(
+                    source_hardware_addr,
+                    source_protocol_addr,
+                    target_protocol_addr,
+                );
+
+                // Update the cached hardware address of the sender, even from packets which
+                // are not for us, so that gratuitous ARP packets are taken into account.
+                if source_protocol_addr.is_unicast() && source_hardware_addr.is_unicast() {
+                    self.neighbor_cache.update(
+                        source_protocol_addr.into(),
+                        source_hardware_addr.into(),
+                        timestamp,
+                    );
+                }
+
                 // Only process ARP packets for us.
                 if !self.has_ip_addr(target_protocol_addr) && !self.any_ip {
                     info!("lhw debug in process_arp not has ip addr tar: {}",target_protocol_addr);
@@ -263,13 +334,20 @@ impl InterfaceInner {
                     return None;
                 }
 
+                // Probes come from the unspecified address. They are answered, so that the
+                // sender detects the conflict, but fill no cache entry, see RFC 5227 § 2.1.1.
+                let probe =
+                    operation == ArpOperation::Request && source_protocol_addr.is_unspecified();
+
                 // Discard packets with non-unicast source addresses.
-                if !source_protocol_addr.is_unicast() || !source_hardware_addr.is_unicast() {
+                if !(source_protocol_addr.is_unicast() || probe)
+                    || !source_hardware_addr.is_unicast()
+                {
                     net_debug!("arp: non-unicast source address");
                     return None;
                 }
 
-                if !self.in_same_network(&IpAddress::Ipv4(source_protocol_addr)) {
+                if !probe && !self.in_same_network(&IpAddress::Ipv4(source_protocol_addr)) {
                     net_debug!("arp: source IP address not in same network as us");
                     return None;
                 }
@@ -278,12 +356,23 @@ impl InterfaceInner {
                 // Fill the ARP cache from any ARP packet aimed at us (both request or response).
                 // We fill from requests too because if someone is requesting our address they
                 // are probably going to talk to us, so we avoid having to request their address
-                // when we later reply to them.
-                self.neighbor_cache.fill(
-                    source_protocol_addr.into(),
-                    source_hardware_addr.into(),
-                    timestamp,
-                );
+                // when we later reply to them. A reply confirms that the sender is reachable,
+                // like a solicited Neighbor Advertisement.
+                match operation {
+                    _ if probe => (),
+                    ArpOperation::Reply => self.neighbor_cache.advert(
+                        source_protocol_addr.into(),
+                        Some(source_hardware_addr.into()),
+                        true,
+                        true,
+                        timestamp,
+                    ),
+                    _ => self.neighbor_cache.fill_stale(
+                        source_protocol_addr.into(),
+                        source_hardware_addr.into(),
+                        timestamp,
+                    ),
+                }
 
                 if operation == ArpOperation::Request {
                     let src_hardware_addr = self.hardware_addr.ethernet_or_panic();
@@ -344,6 +433,15 @@ impl InterfaceInner {
             // Ignore any echo replies.
             Icmpv4Repr::EchoReply { .. } => None,
 
+            Icmpv4Repr::DstUnreachable {
+                reason: Icmpv4DstUnreachable::FragRequired,
+                header,
+                ..
+            } => {
+                self.process_frag_required(&icmp_packet, header);
+                None
+            }
+
             // Don't report an error if a packet with unknown type
             // has been handled by an ICMP socket
             #[cfg(feature = "socket-icmp")]
@@ -354,6 +452,111 @@ impl InterfaceInner {
         }
     }
 
+    /// Lower the path MTU towards the destination of a packet that was too big, after an
+    /// ICMPv4 "fragmentation required" error, see RFC 1191 § 6.
+    fn process_frag_required(&mut self, icmp_packet: &Icmpv4Packet<&[u8]>, header: Ipv4Repr) {
+        // The packet that was too big must have been sent by us.
+        if !self.has_ip_addr(header.src_addr) {
+            return;
+        }
+
+        let mtu = match icmp_packet.next_hop_mtu() {
+            // Old routers don't report the MTU of the next hop, so the next plateau below the
+            // size of the packet is used, see RFC 1191 § 7.
+            0 => {
+                let total_len = Ipv4Packet::new_unchecked(icmp_packet.data()).total_len() as usize;
+                PATH_MTU_PLATEAUS
+                    .iter()
+                    .copied()
+                    .find(|&plateau| plateau < total_len)
+                    .unwrap_or(MIN_PATH_MTU)
+            }
+            mtu => mtu as usize,
+        };
+
+        self.path_mtu_reduced(header.dst_addr.into(), mtu.max(MIN_PATH_MTU));
+    }
+
+    /// Forward a packet that is not for this interface. The router polling the interface sends
+    /// it on the interface with a route to its destination.
+    #[cfg(feature = "iface-forwarding")]
+    fn forward_ipv4<'frame>(
+        &self,
+        ipv4_repr: Ipv4Repr,
+        dont_frag: bool,
+        ip_payload: &'frame [u8],
+    ) -> Option<Packet<'frame>> {
+        if !ipv4_repr.dst_addr.is_unicast() || ipv4_repr.src_addr.is_unspecified() {
+            net_trace!("packet IP address not for this interface");
+            return None;
+        }
+
+        if ipv4_repr.hop_limit <= 1 {
+            return self.icmpv4_forward_error(ipv4_repr, ip_payload, |header, data| {
+                Icmpv4Repr::TimeExceeded {
+                    reason: Icmpv4TimeExceeded::TtlExpired,
+                    header,
+                    data,
+                }
+            });
+        }
+
+        net_trace!("forwarding packet to {}", ipv4_repr.dst_addr);
+        Some(Packet::new_ipv4_forwarded(
+            Ipv4Repr {
+                hop_limit: ipv4_repr.hop_limit - 1,
+                // The payload may have been reassembled from fragments.
+                payload_len: ip_payload.len(),
+                ..ipv4_repr
+            },
+            ip_payload,
+            dont_frag,
+        ))
+    }
+
+    /// Build an ICMPv4 error for a packet that could not be forwarded. The error is sent from one
+    /// of our addresses, with as much of the original payload as we can.
+    #[cfg(any(feature = "iface-forwarding", feature = "medium-ethernet"))]
+    pub(super) fn icmpv4_forward_error<'frame>(
+        &self,
+        ipv4_repr: Ipv4Repr,
+        ip_payload: &'frame [u8],
+        icmp_repr: impl FnOnce(Ipv4Repr, &'frame [u8]) -> Icmpv4Repr<'frame>,
+    ) -> Option<Packet<'frame>> {
+        // No ICMP error is sent about an ICMP error (RFC 1812 § 4.3.2.7).
+        if ipv4_repr.next_header == IpProtocol::Icmp
+            && matches!(
+                ip_payload
+                    .first()
+                    .map(|&msg_type| Icmpv4Message::from(msg_type)),
+                Some(
+                    Icmpv4Message::DstUnreachable
+                        | Icmpv4Message::Redirect
+                        | Icmpv4Message::TimeExceeded
+                        | Icmpv4Message::ParamProblem
+                )
+            )
+        {
+            return None;
+        }
+
+        let src_addr = self.get_source_address_ipv4(&ipv4_repr.src_addr)?;
+        let payload_len =
+            icmp_reply_payload_len(ip_payload.len(), IPV4_MIN_MTU, ipv4_repr.buffer_len());
+        let icmp_repr = icmp_repr(ipv4_repr, &ip_payload[..payload_len]);
+
+        Some(Packet::new_ipv4(
+            Ipv4Repr {
+                src_addr,
+                dst_addr: ipv4_repr.src_addr,
+                next_header: IpProtocol::Icmp,
+                payload_len: icmp_repr.buffer_len(),
+                hop_limit: 64,
+            },
+            IpPayload::Icmpv4(icmp_repr),
+        ))
+    }
+
     pub(super) fn icmpv4_reply<'frame, 'icmp: 'frame>(
         &self,
         ipv4_repr: Ipv4Repr,
@@ -507,4 +710,25 @@ impl InterfaceInner {
             )
         })
     }
+
+    /// Build an IGMPv3 report with `records` group records, sent to all the IGMPv3 routers of
+    /// the link. The source is unspecified before an address is assigned, see
+    /// [RFC 3376 § 4.2.13](https://tools.ietf.org/html/rfc3376#section-4.2.13).
+    #[cfg(feature = "proto-igmp")]
+    pub(super) fn igmp_v3_report_packet<'p>(&self, records: u16, data: &'p [u8]) -> Packet<'p> {
+        let igmp_repr = IgmpRepr::MembershipReportV3 {
+            nr_group_records: records,
+            data,
+        };
+        Packet::new_ipv4(
+            Ipv4Repr {
+                src_addr: self.ipv4_addr().unwrap_or(Ipv4Address::UNSPECIFIED),
+                dst_addr: Ipv4Address::MULTICAST_ALL_IGMPV3_ROUTERS,
+                next_header: IpProtocol::Igmp,
+                payload_len: igmp_repr.buffer_len(),
+                hop_limit: 1,
+            },
+            IpPayload::Igmp(igmp_repr),
+        )
+    }
 }
@@ -1,24 +1,69 @@
 use super::*;
 
-/// Enum used for the process_hopbyhop function. In some cases, when discarding a packet, an ICMP
-/// parameter problem message needs to be transmitted to the source of the address. In other cases,
-/// the processing of the IP packet can continue.
+/// Enum used for the functions processing extension headers. In some cases, when discarding a
+/// packet, an ICMP parameter problem message needs to be transmitted to the source of the address.
+/// In other cases, the processing of the IP packet can continue, or the packet is forwarded.
 #[allow(clippy::large_enum_variant)]
-enum HopByHopResponse<'frame> {
+enum ExtHeaderResponse<'frame> {
     /// Continue processing the IPv6 packet.
     Continue((IpProtocol, &'frame [u8])),
     /// Discard the packet and maybe send back an ICMPv6 packet.
     Discard(Option<Packet<'frame>>),
+    /// Forward the packet to the next hop.
+    #[cfg(feature = "proto-rpl")]
+    Forward(Packet<'frame>),
 }
 
 // We implement `Default` such that we can use the check! macro.
-impl Default for HopByHopResponse<'_> {
+impl Default for ExtHeaderResponse<'_> {
     fn default() -> Self {
         Self::Discard(None)
     }
 }
 
+impl Interface {
+    /// Process fragments that still need to be sent for IPv6 packets.
+    ///
+    /// This function returns a boolean value indicating whether any packets were
+    /// processed or emitted, and thus, whether the readiness of any socket might
+    /// have changed.
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    pub(super) fn ipv6_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        // Reset the buffer when we transmitted everything.
+        if self.fragmenter.finished() {
+            self.fragmenter.reset();
+        }
+
+        if self.fragmenter.is_empty()
+            || IpVersion::of_packet(&self.fragmenter.buffer) != Ok(IpVersion::Ipv6)
+        {
+            return false;
+        }
+
+        let pkt = &self.fragmenter;
+        if pkt.packet_len > pkt.sent_bytes {
+            if let Some(tx_token) = device.transmit(self.inner.now) {
+                self.inner
+                    .dispatch_ipv6_frag(tx_token, &mut self.fragmenter);
+                return true;
+            }
+        }
+        false
+    }
+}
+
 impl InterfaceInner {
+    /// Get the next IPv6 fragment identification.
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    pub(super) fn next_ipv6_frag_ident(&mut self) -> u32 {
+        let ident = self.ipv6_frag_ident;
+        self.ipv6_frag_ident = self.ipv6_frag_ident.wrapping_add(1);
+        ident
+    }
+
     /// Return the IPv6 address that is a candidate source address for the given destination
     /// address, based on RFC 6724.
     ///
@@ -72,13 +117,19 @@ impl InterfaceInner {
             bits as usize
         }
 
+        // Tentative and duplicate addresses are not candidates, see RFC 4862 § 5.4.
+        #[cfg(feature = "proto-ipv6-dad")]
+        let is_assigned = |a: &&Ipv6Cidr| self.dad_is_assigned(&a.address());
+        #[cfg(not(feature = "proto-ipv6-dad"))]
+        let is_assigned = |_: &&Ipv6Cidr| true;
+
         // If the destination address is a loopback address, or when there are no IPv6 addresses in
         // the interface, then the loopback address is the only candidate source address.
         if dst_addr.is_loopback()
             || self
                 .ip_addrs
                 .iter()
-                .filter(|a| matches!(a, IpCidr::Ipv6(_)))
+                .filter(|a| matches!(a, IpCidr::Ipv6(a) if is_assigned(&a)))
                 .count()
                 == 0
         {
@@ -91,7 +142,7 @@ impl InterfaceInner {
             .find_map(|a| match a {
                 #[cfg(feature = "proto-ipv4")]
                 IpCidr::Ipv4(_) => None,
-                IpCidr::Ipv6(a) => Some(a),
+                IpCidr::Ipv6(a) => Some(a).filter(is_assigned),
             })
             .unwrap(); // NOTE: we check above that there is at least one IPv6 address.
 
@@ -101,7 +152,7 @@ impl InterfaceInner {
             #[cfg(feature = "proto-ipv6")]
             IpCidr::Ipv6(a) => Some(a),
         }) {
-            if !is_candidate_source_address(dst_addr, &addr.address()) {
+            if !is_candidate_source_address(dst_addr, &addr.address()) || !is_assigned(&addr) {
                 continue;
             }
 
@@ -124,7 +175,21 @@ impl InterfaceInner {
                 candidate = addr;
             }
 
-            // Rule 3: avoid deprecated addresses (TODO)
+            // Rule 3: avoid deprecated addresses, and optimistic ones (RFC 4429 § 3.3). The rule
+            // only applies when the previous ones did not decide.
+            #[cfg(feature = "proto-ipv6-dad")]
+            if candidate.address() != *dst_addr
+                && candidate.address().multicast_scope() == addr.address().multicast_scope()
+            {
+                let candidate_deprecated = self.dad_is_deprecated(&candidate.address());
+                if candidate_deprecated != self.dad_is_deprecated(&addr.address()) {
+                    if candidate_deprecated {
+                        candidate = addr;
+                    }
+                    continue;
+                }
+            }
+
             // Rule 4: prefer home addresses (TODO)
             // Rule 5: prefer outgoing interfaces (TODO)
             // Rule 5.5: prefer addresses in a prefix advertises by the next-hop (TODO).
@@ -171,37 +236,129 @@ impl InterfaceInner {
         sockets: &mut SocketSet,
         meta: PacketMeta,
         ipv6_packet: &Ipv6Packet<&'frame [u8]>,
+        #[allow(unused_variables)] frag: Option<&'frame mut FragmentsBuffer>,
     ) -> Option<Packet<'frame>> {
-        let ipv6_repr = check!(Ipv6Repr::parse(ipv6_packet));
+        #[allow(unused_mut)]
+        let mut ipv6_repr = check!(Ipv6Repr::parse(ipv6_packet));
+
+        // The reassembly buffers and the IPsec buffer are borrowed separately.
+        #[cfg(feature = "proto-ipv6-fragmentation")]
+        let mut reassembly = None;
+        #[cfg(feature = "_proto-ipsec")]
+        let mut ipsec_buf = None;
+        #[cfg(any(feature = "proto-ipv6-fragmentation", feature = "_proto-ipsec"))]
+        if let Some(frag) = frag {
+            #[cfg(feature = "proto-ipv6-fragmentation")]
+            {
+                reassembly = Some((&mut frag.assembler, frag.reassembly_timeout));
+            }
+            #[cfg(feature = "_proto-ipsec")]
+            {
+                ipsec_buf = Some(&mut frag.ipsec_buf[..]);
+            }
+        }
 
-        if !ipv6_repr.src_addr.is_unicast() {
-            // Discard packets with non-unicast source addresses.
+        // Discard packets with non-unicast source addresses, except the Neighbor Solicitations
+        // of Duplicate Address Detection, see RFC 4862 § 5.4.2.
+        let dad_solicit = ipv6_repr.src_addr.is_unspecified()
+            && ipv6_repr.next_header == IpProtocol::Icmpv6
+            && ipv6_packet.payload().first() == Some(&Icmpv6Message::NeighborSolicit.into());
+        if !ipv6_repr.src_addr.is_unicast() && !dad_solicit {
             net_debug!("non-unicast source address");
             return None;
         }
 
         let (next_header, ip_payload) = if ipv6_repr.next_header == IpProtocol::HopByHop {
             match self.process_hopbyhop(ipv6_repr, ipv6_packet.payload()) {
-                HopByHopResponse::Discard(e) => return e,
-                HopByHopResponse::Continue(next) => next,
+                ExtHeaderResponse::Discard(e) => return e,
+                ExtHeaderResponse::Continue(next) => next,
+                #[cfg(feature = "proto-rpl")]
+                ExtHeaderResponse::Forward(packet) => return Some(packet),
             }
         } else {
             (ipv6_repr.next_header, ipv6_packet.payload())
         };
 
+        // Drop the packets of the sources filtered out of a joined multicast group. The MLD
+        // queries about the group are still processed.
+        #[cfg(feature = "proto-mld")]
+        if matches!(
+            self.ipv6_multicast_groups.get(&ipv6_repr.dst_addr),
+            Some(group) if group.is_member() && !group.accepts(&ipv6_repr.src_addr)
+        ) && !(next_header == IpProtocol::Icmpv6
+            && ip_payload.first() == Some(&Icmpv6Message::MldQuery.into()))
+        {
+            net_trace!("multicast source filtered out");
+            return None;
+        }
+
         if !self.has_ip_addr(ipv6_repr.dst_addr)
             && !self.has_multicast_group(ipv6_repr.dst_addr)
             && !ipv6_repr.dst_addr.is_loopback()
         {
+            // Nodes of a RPL DODAG route the packets of their neighbors.
+            #[cfg(feature = "proto-rpl")]
+            if self.rpl.is_some() {
+                return self.forward_ipv6(ipv6_repr, ipv6_packet.payload());
+            }
+
+            #[cfg(feature = "iface-forwarding")]
+            if self.forwarding {
+                return self.forward_ipv6(ipv6_repr, ipv6_packet.payload());
+            }
+
             net_trace!("packet IP address not for this interface");
             return None;
         }
 
+        #[cfg(feature = "proto-ipv6-routing")]
+        let (next_header, ip_payload) = if next_header == IpProtocol::Ipv6Route {
+            match self.process_routing(ipv6_repr, ipv6_packet.payload(), ip_payload) {
+                ExtHeaderResponse::Discard(e) => return e,
+                ExtHeaderResponse::Continue(next) => next,
+                #[cfg(feature = "proto-rpl")]
+                ExtHeaderResponse::Forward(packet) => return Some(packet),
+            }
+        } else {
+            (next_header, ip_payload)
+        };
+
+        #[cfg(feature = "proto-ipv6-fragmentation")]
+        let (next_header, ip_payload) = if next_header == IpProtocol::Ipv6Frag {
+            match self.process_fragment(ipv6_repr, ip_payload, reassembly) {
+                ExtHeaderResponse::Discard(e) => return e,
+                ExtHeaderResponse::Continue(next) => next,
+                #[cfg(feature = "proto-rpl")]
+                ExtHeaderResponse::Forward(packet) => return Some(packet),
+            }
+        } else {
+            (next_header, ip_payload)
+        };
+
         #[cfg(feature = "socket-raw")]
         let handled_by_raw_socket = self.raw_socket_filter(sockets, &ipv6_repr.into(), ip_payload);
         #[cfg(not(feature = "socket-raw"))]
         let handled_by_raw_socket = false;
 
+        #[cfg(feature = "_proto-ipsec")]
+        let (next_header, ip_payload) = match self.process_ipsec(
+            &ipv6_repr.into(),
+            &ipv6_packet.as_ref()[..IPV6_HEADER_LEN],
+            next_header,
+            ip_payload,
+            ipsec_buf,
+        ) {
+            Some((inner_header, inner_payload)) => {
+                if inner_header != next_header {
+                    // The payload was unwrapped, process it as if it was received without IPsec.
+                    ipv6_repr.next_header = inner_header;
+                    ipv6_repr.payload_len = inner_payload.len();
+                }
+                (inner_header, inner_payload)
+            }
+            None => return None,
+        };
+
         self.process_nxt_hdr(
             sockets,
             meta,
@@ -216,7 +373,7 @@ impl InterfaceInner {
         &mut self,
         ipv6_repr: Ipv6Repr,
         ip_payload: &'frame [u8],
-    ) -> HopByHopResponse<'frame> {
+    ) -> ExtHeaderResponse<'frame> {
         let param_problem = || {
             let payload_len =
                 icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
@@ -239,6 +396,8 @@ impl InterfaceInner {
         for opt_repr in &hbh_repr.options {
             match opt_repr {
                 Ipv6OptionRepr::Pad1 | Ipv6OptionRepr::PadN(_) => (),
+                // MLD messages are processed like other ICMPv6 messages.
+                Ipv6OptionRepr::RouterAlert(_) => (),
                 #[cfg(feature = "proto-rpl")]
                 Ipv6OptionRepr::Rpl(_) => {}
 
@@ -246,15 +405,15 @@ impl InterfaceInner {
                     match Ipv6OptionFailureType::from(*type_) {
                         Ipv6OptionFailureType::Skip => (),
                         Ipv6OptionFailureType::Discard => {
-                            return HopByHopResponse::Discard(None);
+                            return ExtHeaderResponse::Discard(None);
                         }
                         Ipv6OptionFailureType::DiscardSendAll => {
-                            return HopByHopResponse::Discard(param_problem());
+                            return ExtHeaderResponse::Discard(param_problem());
                         }
                         Ipv6OptionFailureType::DiscardSendUnicast
                             if !ipv6_repr.dst_addr.is_multicast() =>
                         {
-                            return HopByHopResponse::Discard(param_problem());
+                            return ExtHeaderResponse::Discard(param_problem());
                         }
                         _ => unreachable!(),
                     }
@@ -262,12 +421,374 @@ impl InterfaceInner {
             }
         }
 
-        HopByHopResponse::Continue((
+        ExtHeaderResponse::Continue((
             ext_repr.next_header,
             &ip_payload[ext_repr.header_len() + ext_repr.data.len()..],
         ))
     }
 
+    /// Process a Fragment header, and reassemble the fragmented packet, see [RFC 8200 § 4.5].
+    /// The packet is processed once its last fragment is received.
+    ///
+    /// [RFC 8200 § 4.5]: https://www.rfc-editor.org/rfc/rfc8200#section-4.5
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    fn process_fragment<'frame>(
+        &mut self,
+        ipv6_repr: Ipv6Repr,
+        ip_payload: &'frame [u8],
+        reassembly: Option<(&'frame mut PacketAssemblerSet<FragKey>, Duration)>,
+    ) -> ExtHeaderResponse<'frame> {
+        let ext_hdr = check!(Ipv6ExtHeader::new_checked(ip_payload));
+        let ext_repr = check!(Ipv6ExtHeaderRepr::parse(&ext_hdr));
+        let frag_hdr = check!(Ipv6FragmentHeader::new_checked(ext_repr.data));
+        let frag_repr = check!(Ipv6FragmentRepr::parse(&frag_hdr));
+        let payload = &ip_payload[ext_repr.header_len() + ext_repr.data.len()..];
+
+        // An atomic fragment is processed right away, see RFC 6946 § 4.
+        if frag_repr.frag_offset == 0 && !frag_repr.more_frags {
+            return ExtHeaderResponse::Continue((ext_repr.next_header, payload));
+        }
+
+        // There is no reassembly buffer for packets received over 6LoWPAN.
+        let Some((assembler, reassembly_timeout)) = reassembly else {
+            net_debug!("no buffer to reassemble the fragmented packet");
+            return ExtHeaderResponse::Discard(None);
+        };
+
+        // All the fragments except the last one carry a multiple of 8 octets.
+        if frag_repr.more_frags && payload.len() % 8 != 0 {
+            let payload_len =
+                icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
+            return ExtHeaderResponse::Discard(self.icmpv6_reply(
+                ipv6_repr,
+                Icmpv6Repr::ParamProblem {
+                    reason: Icmpv6ParamProblem::ErroneousHdrField,
+                    // The offset of the Payload Length field.
+                    pointer: 4,
+                    header: ipv6_repr,
+                    data: &ip_payload[..payload_len],
+                },
+            ));
+        }
+
+        let key = FragKey::Ipv6(frag_repr.get_key(&ipv6_repr));
+        let f = match assembler.get(&key, self.now + reassembly_timeout) {
+            Ok(f) => f,
+            Err(_) => {
+                net_debug!("No available packet assembler for fragmented packet");
+                return ExtHeaderResponse::Discard(None);
+            }
+        };
+
+        // The reassembled data starts with the type of the first header of the fragmented part,
+        // given by the first fragment, see RFC 8200 § 4.5.
+        let offset = 1 + frag_repr.frag_offset as usize * 8;
+        if frag_repr.frag_offset == 0 {
+            check!(f.add(&[ext_repr.next_header.into()], 0));
+        }
+        if !frag_repr.more_frags {
+            // This is the last fragment, so we know the total size
+            check!(f.set_total_size(offset + payload.len()));
+        }
+
+        if let Err(e) = f.add(payload, offset) {
+            net_debug!("fragmentation error: {:?}", e);
+            return ExtHeaderResponse::Discard(None);
+        }
+
+        match f.assemble() {
+            Some(data) => ExtHeaderResponse::Continue((data[0].into(), &data[1..])),
+            None => ExtHeaderResponse::Discard(None),
+        }
+    }
+
+    /// Send the next fragment of the IPv6 packet held by `frag`. The IPv6 header and the
+    /// extension headers preceding the Fragment header are repeated in every fragment.
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    pub(super) fn dispatch_ipv6_frag<Tx: TxToken>(&mut self, tx_token: Tx, frag: &mut Fragmenter) {
+        let unfragmentable_len = frag.ipv6.unfragmentable_len;
+        let frag_header_len = 2 + Ipv6FragmentRepr::buffer_len(&Ipv6FragmentRepr {
+            frag_offset: 0,
+            more_frags: false,
+            ident: 0,
+        });
+
+        // All the fragments except the last one carry a multiple of 8 octets.
+        let max_payload_len = (frag.ipv6.mtu - unfragmentable_len - frag_header_len) & !7;
+        let remaining_len = frag.packet_len - frag.sent_bytes;
+        let payload_len = remaining_len.min(max_payload_len);
+        let frag_repr = Ipv6FragmentRepr {
+            frag_offset: ((frag.sent_bytes - unfragmentable_len) / 8) as u16,
+            more_frags: payload_len < remaining_len,
+            ident: frag.ipv6.ident,
+        };
+
+        let ip_len = unfragmentable_len + frag_header_len + payload_len;
+        #[allow(unused_mut)]
+        let mut tx_len = ip_len;
+        #[cfg(feature = "medium-ethernet")]
+        if matches!(self.caps.medium, Medium::Ethernet) {
+            tx_len += EthernetFrame::<&[u8]>::header_len();
+        }
+
+        #[allow(unused_mut)]
+        tx_token.consume(tx_len, |mut tx_buffer| {
+            #[cfg(feature = "medium-ethernet")]
+            if matches!(self.caps.medium, Medium::Ethernet) {
+                let mut frame = EthernetFrame::new_unchecked(&mut tx_buffer[..]);
+                frame.set_src_addr(self.hardware_addr.ethernet_or_panic());
+                frame.set_dst_addr(frag.ipv6.dst_hardware_addr);
+                frame.set_ethertype(EthernetProtocol::Ipv6);
+                tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
+            }
+
+            // Copy the headers preceding the Fragment header, and update them.
+            tx_buffer[..unfragmentable_len].copy_from_slice(&frag.buffer[..unfragmentable_len]);
+            tx_buffer[frag.ipv6.next_header_offset] = IpProtocol::Ipv6Frag.into();
+            Ipv6Packet::new_unchecked(&mut tx_buffer[..])
+                .set_payload_len((ip_len - IPV6_HEADER_LEN) as u16);
+
+            let frag_header = &mut tx_buffer[unfragmentable_len..][..frag_header_len];
+            Ipv6ExtHeaderRepr {
+                next_header: frag.ipv6.next_header,
+                length: 0,
+                data: &[],
+            }
+            .emit(&mut Ipv6ExtHeader::new_unchecked(&mut frag_header[..]));
+            frag_repr.emit(&mut Ipv6FragmentHeader::new_unchecked(
+                &mut frag_header[2..],
+            ));
+
+            tx_buffer[unfragmentable_len + frag_header_len..][..payload_len]
+                .copy_from_slice(&frag.buffer[frag.sent_bytes..][..payload_len]);
+        });
+
+        frag.sent_bytes += payload_len;
+    }
+
+    /// Process a Routing header. Packets with a RPL Source Route Header are forwarded to the next
+    /// address of the route, see [RFC 6554 § 4.2]. `ext_headers` holds the extension headers of
+    /// the packet, starting with the headers preceding the Routing header.
+    ///
+    /// [RFC 6554 § 4.2]: https://datatracker.ietf.org/doc/html/rfc6554#section-4.2
+    #[cfg(feature = "proto-ipv6-routing")]
+    fn process_routing<'frame>(
+        &mut self,
+        ipv6_repr: Ipv6Repr,
+        #[allow(unused_variables)] ext_headers: &'frame [u8],
+        ip_payload: &'frame [u8],
+    ) -> ExtHeaderResponse<'frame> {
+        let ext_hdr = check!(Ipv6ExtHeader::new_checked(ip_payload));
+        let ext_repr = check!(Ipv6ExtHeaderRepr::parse(&ext_hdr));
+        let routing_hdr = check!(Ipv6RoutingHeader::new_checked(ext_repr.data));
+        let next = (
+            ext_repr.next_header,
+            &ip_payload[ext_repr.header_len() + ext_repr.data.len()..],
+        );
+
+        // A Routing header with no segments left is ignored.
+        if routing_hdr.segments_left() == 0 {
+            return ExtHeaderResponse::Continue(next);
+        }
+
+        let param_problem = |pointer: usize| {
+            let payload_len =
+                icmp_reply_payload_len(ext_headers.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
+            let offset = ext_headers.len() - ip_payload.len();
+            self.icmpv6_reply(
+                ipv6_repr,
+                Icmpv6Repr::ParamProblem {
+                    reason: Icmpv6ParamProblem::ErroneousHdrField,
+                    pointer: (ipv6_repr.buffer_len() + offset + pointer) as u32,
+                    header: ipv6_repr,
+                    data: &ext_headers[0..payload_len],
+                },
+            )
+        };
+
+        // The offsets of the Routing Type and the Segments Left fields.
+        const ROUTING_TYPE: usize = 2;
+        #[cfg(feature = "proto-rpl")]
+        const SEGMENTS_LEFT: usize = 3;
+
+        match Ipv6RoutingRepr::parse(&routing_hdr) {
+            #[cfg(feature = "proto-rpl")]
+            Ok(
+                routing_repr @ Ipv6RoutingRepr::Rpl {
+                    segments_left,
+                    cmpr_i,
+                    cmpr_e,
+                    pad,
+                    addresses,
+                },
+            ) if self.rpl.is_some() => {
+                let Some(n) = routing_repr.rpl_address_count() else {
+                    return ExtHeaderResponse::Discard(param_problem(SEGMENTS_LEFT));
+                };
+                if segments_left as usize > n {
+                    return ExtHeaderResponse::Discard(param_problem(SEGMENTS_LEFT));
+                }
+
+                let segments_left = segments_left - 1;
+                let i = n - segments_left as usize;
+                let Some(next_hop) = routing_repr.rpl_address(i, &ipv6_repr.dst_addr) else {
+                    return ExtHeaderResponse::Discard(None);
+                };
+                if next_hop.is_multicast() {
+                    net_debug!("rpl: source route to multicast address {}", next_hop);
+                    return ExtHeaderResponse::Discard(None);
+                }
+
+                // Our address further down the route means that the route has a loop.
+                if (i + 1..=n)
+                    .filter_map(|j| routing_repr.rpl_address(j, &ipv6_repr.dst_addr))
+                    .any(|address| self.has_ip_addr(address))
+                {
+                    net_debug!("rpl: loop in source route");
+                    return ExtHeaderResponse::Discard(param_problem(SEGMENTS_LEFT));
+                }
+
+                if ipv6_repr.hop_limit <= 1 {
+                    return ExtHeaderResponse::Discard(self.icmpv6_forward_error(
+                        ipv6_repr,
+                        ext_headers,
+                        |header, data| Icmpv6Repr::TimeExceeded {
+                            reason: Icmpv6TimeExceeded::HopLimitExceeded,
+                            header,
+                            data,
+                        },
+                    ));
+                }
+
+                // The Hop-by-Hop header preceding the Routing header is forwarded as well.
+                let hop_by_hop = match &ext_headers[..ext_headers.len() - ip_payload.len()] {
+                    [] => None,
+                    hbh => {
+                        let ext_hdr = check!(Ipv6ExtHeader::new_checked(hbh));
+                        let ext_repr = check!(Ipv6ExtHeaderRepr::parse(&ext_hdr));
+                        let hbh_hdr = check!(Ipv6HopByHopHeader::new_checked(ext_repr.data));
+                        Some(check!(Ipv6HopByHopRepr::parse(&hbh_hdr)))
+                    }
+                };
+
+                // The addresses are forwarded unchanged: the address of the next hop is not swapped
+                // with the destination address, so the route is not recorded in the header.
+                let addresses = &ext_repr.data[ext_repr.data.len() - addresses.len()..];
+                let payload = next.1;
+
+                ExtHeaderResponse::Forward(Packet::Ipv6(PacketV6 {
+                    header: Ipv6Repr {
+                        dst_addr: next_hop,
+                        next_header: next.0,
+                        payload_len: payload.len(),
+                        hop_limit: ipv6_repr.hop_limit - 1,
+                        ..ipv6_repr
+                    },
+                    hop_by_hop,
+                    #[cfg(feature = "proto-ipv6-fragmentation")]
+                    fragment: None,
+                    routing: Some(Ipv6RoutingRepr::Rpl {
+                        segments_left,
+                        cmpr_i,
+                        cmpr_e,
+                        pad,
+                        addresses,
+                    }),
+                    payload: IpPayload::Raw(payload),
+                }))
+            }
+            // Routing types we do not process cannot be skipped when segments are left.
+            _ => ExtHeaderResponse::Discard(param_problem(ROUTING_TYPE)),
+        }
+    }
+
+    /// Forward a packet that is not for this interface to the next hop towards its destination.
+    /// The extension headers are forwarded unchanged.
+    #[cfg(any(feature = "proto-rpl", feature = "iface-forwarding"))]
+    fn forward_ipv6<'frame>(
+        &self,
+        ipv6_repr: Ipv6Repr,
+        ip_payload: &'frame [u8],
+    ) -> Option<Packet<'frame>> {
+        let dst_addr = ipv6_repr.dst_addr;
+        if !dst_addr.is_unicast() || dst_addr.is_link_local() {
+            net_trace!("packet IP address not for this interface");
+            return None;
+        }
+
+        if ipv6_repr.hop_limit <= 1 {
+            return self.icmpv6_forward_error(ipv6_repr, ip_payload, |header, data| {
+                Icmpv6Repr::TimeExceeded {
+                    reason: Icmpv6TimeExceeded::HopLimitExceeded,
+                    header,
+                    data,
+                }
+            });
+        }
+
+        // A router looks up the routes of all its interfaces.
+        #[cfg(feature = "iface-forwarding")]
+        let routed_by_router = self.forwarding;
+        #[cfg(not(feature = "iface-forwarding"))]
+        let routed_by_router = false;
+
+        if !routed_by_router && self.route(&dst_addr.into(), self.now).is_none() {
+            net_debug!("no route to forward packet to {}", dst_addr);
+            return self.icmpv6_forward_error(ipv6_repr, ip_payload, |header, data| {
+                Icmpv6Repr::DstUnreachable {
+                    reason: Icmpv6DstUnreachable::NoRoute,
+                    header,
+                    data,
+                }
+            });
+        }
+
+        net_trace!("forwarding packet to {}", dst_addr);
+        Some(Packet::new_ipv6(
+            Ipv6Repr {
+                hop_limit: ipv6_repr.hop_limit - 1,
+                ..ipv6_repr
+            },
+            IpPayload::Raw(ip_payload),
+        ))
+    }
+
+    /// Build an ICMPv6 error for a packet that could not be forwarded. The error is sent from one
+    /// of our addresses, with as much of the original payload as we can.
+    #[cfg(any(
+        feature = "proto-rpl",
+        feature = "iface-forwarding",
+        feature = "medium-ethernet"
+    ))]
+    pub(super) fn icmpv6_forward_error<'frame>(
+        &self,
+        ipv6_repr: Ipv6Repr,
+        ip_payload: &'frame [u8],
+        icmp_repr: impl FnOnce(Ipv6Repr, &'frame [u8]) -> Icmpv6Repr<'frame>,
+    ) -> Option<Packet<'frame>> {
+        // No ICMPv6 error is sent about an ICMPv6 error (RFC 4443 § 2.4).
+        if ipv6_repr.next_header == IpProtocol::Icmpv6
+            && ip_payload
+                .first()
+                .map_or(false, |&msg_type| Icmpv6Message::from(msg_type).is_error())
+        {
+            return None;
+        }
+
+        let payload_len =
+            icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, ipv6_repr.buffer_len());
+        let icmp_repr = icmp_repr(ipv6_repr, &ip_payload[..payload_len]);
+
+        let src_addr = self.get_source_address_ipv6(&ipv6_repr.src_addr);
+        self.icmpv6_reply(
+            Ipv6Repr {
+                dst_addr: src_addr,
+                ..ipv6_repr
+            },
+            icmp_repr,
+        )
+    }
+
     /// Given the next header value forward the payload onto the correct process
     /// function.
     fn process_nxt_hdr<'frame>(
@@ -362,6 +883,28 @@ impl InterfaceInner {
             // Ignore any echo replies.
             Icmpv6Repr::EchoReply { .. } => None,
 
+            // Lower the path MTU towards the destination of the packet that was too big, but
+            // never below the IPv6 minimum MTU, see RFC 8201 § 4.
+            Icmpv6Repr::PktTooBig { mtu, header, .. } => {
+                // The packet that was too big must have been sent by us.
+                if self.has_ip_addr(header.src_addr) {
+                    let mtu = usize::try_from(mtu).unwrap_or(usize::MAX).max(IPV6_MIN_MTU);
+                    self.path_mtu_reduced(header.dst_addr.into(), mtu);
+                }
+                None
+            }
+
+            // Router Advertisements configure the interface on all the media.
+            #[cfg(feature = "proto-slaac")]
+            Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+                router_lifetime,
+                lladdr,
+                prefix_info,
+                ..
+            }) if ip_repr.hop_limit == 0xff => {
+                self.process_router_advert(ip_repr, router_lifetime, lladdr, prefix_info)
+            }
+
             // Forward any NDISC packets to the ndisc packet handler
             #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
             Icmpv6Repr::Ndisc(repr) if ip_repr.hop_limit == 0xff => match self.caps.medium {
@@ -373,6 +916,12 @@ impl InterfaceInner {
                 Medium::Ip => None,
             },
 
+            #[cfg(feature = "proto-rpl")]
+            Icmpv6Repr::Rpl(repr) => self.process_rpl(ip_repr, repr),
+
+            #[cfg(feature = "proto-mld")]
+            Icmpv6Repr::Mld(repr) => self.process_mld(ip_repr, repr),
+
             // Don't report an error if a packet with unknown type
             // has been handled by an ICMP socket
             #[cfg(feature = "socket-icmp")]
@@ -395,18 +944,28 @@ impl InterfaceInner {
                 target_addr,
                 flags,
             } => {
+                // Another node uses the address being tested, see RFC 4862 § 5.4.4.
+                #[cfg(feature = "proto-ipv6-dad")]
+                if self.dad_is_probing(&target_addr) {
+                    self.dad_duplicate(target_addr);
+                    return None;
+                }
+
                 let ip_addr = ip_repr.src_addr.into();
-                if let Some(lladdr) = lladdr {
-                    let lladdr = check!(lladdr.parse(self.caps.medium));
-                    if !lladdr.is_unicast() || !target_addr.is_unicast() {
-                        return None;
-                    }
-                    if flags.contains(NdiscNeighborFlags::OVERRIDE)
-                        || !self.neighbor_cache.lookup(&ip_addr, self.now).found()
-                    {
-                        self.neighbor_cache.fill(ip_addr, lladdr, self.now)
-                    }
+                let lladdr = match lladdr {
+                    Some(lladdr) => Some(check!(lladdr.parse(self.caps.medium))),
+                    None => None,
+                };
+                if !lladdr.map_or(true, |lladdr| lladdr.is_unicast()) || !target_addr.is_unicast() {
+                    return None;
                 }
+                self.neighbor_cache.advert(
+                    ip_addr,
+                    lladdr,
+                    flags.contains(NdiscNeighborFlags::SOLICITED),
+                    flags.contains(NdiscNeighborFlags::OVERRIDE),
+                    self.now,
+                );
                 None
             }
             NdiscRepr::NeighborSolicit {
@@ -414,25 +973,42 @@ impl InterfaceInner {
                 lladdr,
                 ..
             } => {
+                // Another node is testing the address we are testing, see RFC 4862 § 5.4.3.
+                #[cfg(feature = "proto-ipv6-dad")]
+                if ip_repr.src_addr.is_unspecified() && self.dad_is_probing(&target_addr) {
+                    self.dad_duplicate(target_addr);
+                    return None;
+                }
+
                 if let Some(lladdr) = lladdr {
                     let lladdr = check!(lladdr.parse(self.caps.medium));
                     if !lladdr.is_unicast() || !target_addr.is_unicast() {
                         return None;
                     }
                     self.neighbor_cache
-                        .fill(ip_repr.src_addr.into(), lladdr, self.now);
+                        .fill_stale(ip_repr.src_addr.into(), lladdr, self.now);
                 }
 
                 if self.has_solicited_node(ip_repr.dst_addr) && self.has_ip_addr(target_addr) {
+                    // A node running Duplicate Address Detection can not receive unicast
+                    // packets, the advertisement is multicast, see RFC 4861 § 7.2.4.
+                    let (flags, dst_addr) = if ip_repr.src_addr.is_unspecified() {
+                        (
+                            NdiscNeighborFlags::empty(),
+                            Ipv6Address::LINK_LOCAL_ALL_NODES,
+                        )
+                    } else {
+                        (NdiscNeighborFlags::SOLICITED, ip_repr.src_addr)
+                    };
                     let advert = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
-                        flags: NdiscNeighborFlags::SOLICITED,
+                        flags,
                         target_addr,
                         #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
                         lladdr: Some(self.hardware_addr.into()),
                     });
                     let ip_repr = Ipv6Repr {
                         src_addr: target_addr,
-                        dst_addr: ip_repr.src_addr,
+                        dst_addr,
                         next_header: IpProtocol::Icmpv6,
                         hop_limit: 0xff,
                         payload_len: advert.buffer_len(),
@@ -0,0 +1,429 @@
+use super::*;
+use crate::config::{IFACE_MAX_MULTICAST_SOURCE_COUNT, IPV6_HBH_MAX_OPTIONS};
+
+// The MLD reports carry a Router Alert option, padded to 8 octets with a PadN option.
+const _: () = assert!(
+    IPV6_HBH_MAX_OPTIONS >= 2,
+    "MLD needs IPV6_HBH_MAX_OPTIONS to be at least 2"
+);
+
+/// The number of times a state change report is sent, see [RFC 3810 § 9.1].
+///
+/// [RFC 3810 § 9.1]: https://tools.ietf.org/html/rfc3810#section-9.1
+const MLD_ROBUSTNESS: u8 = 2;
+
+/// The maximum delay between the retransmissions of a state change report, see
+/// [RFC 3810 § 9.11].
+///
+/// [RFC 3810 § 9.11]: https://tools.ietf.org/html/rfc3810#section-9.11
+const MLD_UNSOLICITED_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The length of a multicast address record without its sources.
+const MLD_RECORD_HEADER_LEN: usize = 20;
+
+/// The length of the largest multicast address record we emit.
+const MLD_RECORD_MAX_LEN: usize = MLD_RECORD_HEADER_LEN + 16 * IFACE_MAX_MULTICAST_SOURCE_COUNT;
+
+/// The length of the records of a report that fits in the minimum IPv6 MTU, after the IPv6
+/// header, the Hop-by-Hop header and the report header.
+const MLD_REPORT_MAX_LEN: usize = IPV6_MIN_MTU - 40 - 8 - 8;
+
+/// Emit a multicast address record in `buffer`, and return its length, or `None` if it does not
+/// fit.
+fn emit_record(
+    buffer: &mut [u8],
+    record_type: MldRecordType,
+    group: Ipv6Address,
+    sources: &[Ipv6Address],
+) -> Option<usize> {
+    let len = MLD_RECORD_HEADER_LEN + 16 * sources.len();
+    let mut record = MldAddressRecord::new_unchecked(buffer.get_mut(..len)?);
+    record.set_record_type(record_type);
+    record.set_aux_data_len(0);
+    record.set_num_srcs(sources.len() as u16);
+    record.set_mcast_addr(group);
+    for (field, source) in record.payload_mut().chunks_exact_mut(16).zip(sources) {
+        field.copy_from_slice(source.as_bytes());
+    }
+
+    Some(len)
+}
+
+/// Return the type of the record reporting the current state of a group in a given filter mode.
+fn current_state_record(filter_mode: MulticastFilterMode) -> MldRecordType {
+    match filter_mode {
+        MulticastFilterMode::Include => MldRecordType::ModeIsInclude,
+        MulticastFilterMode::Exclude => MldRecordType::ModeIsExclude,
+    }
+}
+
+impl Interface {
+    /// Set the source filter of an IPv6 multicast group, sending a state change report when it
+    /// changed. An INCLUDE filter without sources leaves the group.
+    pub(super) fn join_ipv6_multicast_group<D>(
+        &mut self,
+        device: &mut D,
+        addr: Ipv6Address,
+        filter_mode: MulticastFilterMode,
+        sources: MulticastSources<Ipv6Address>,
+    ) -> Result<bool, MulticastError>
+    where
+        D: Device + ?Sized,
+    {
+        let groups = &mut self.inner.ipv6_multicast_groups;
+        let changed = match groups.get_mut(&addr) {
+            Some(group) => group.set_filter(filter_mode, sources),
+            None if filter_mode == MulticastFilterMode::Include && sources.is_empty() => {
+                return Ok(false)
+            }
+            None => {
+                let mut group = MulticastGroup::new();
+                group.set_filter(filter_mode, sources);
+                groups
+                    .insert(addr, group)
+                    .map_err(|_| MulticastError::GroupTableFull)?;
+                true
+            }
+        };
+
+        if !InterfaceInner::mld_reports(&addr) {
+            if !groups[&addr].is_member() {
+                groups.remove(&addr);
+            }
+            return Ok(false);
+        }
+
+        if !changed {
+            return Ok(false);
+        }
+
+        groups[&addr].state_change = Some((MLD_ROBUSTNESS, self.inner.now));
+        self.mld_send_state_change(device, addr)?;
+        Ok(true)
+    }
+
+    /// Send the state change report of a group, and schedule its retransmission.
+    fn mld_send_state_change<D>(
+        &mut self,
+        device: &mut D,
+        addr: Ipv6Address,
+    ) -> Result<(), MulticastError>
+    where
+        D: Device + ?Sized,
+    {
+        let Some(group) = self.inner.ipv6_multicast_groups.get(&addr) else {
+            return Ok(());
+        };
+
+        let record_type = match group.filter_mode {
+            MulticastFilterMode::Include => MldRecordType::ChangeToInclude,
+            MulticastFilterMode::Exclude => MldRecordType::ChangeToExclude,
+        };
+        let mut data = [0u8; MLD_RECORD_MAX_LEN];
+        // NOTE(unwrap): the buffer fits a record with all the sources of a group.
+        let len = emit_record(&mut data, record_type, addr, &group.sources).unwrap();
+
+        let tx_token = device
+            .transmit(self.inner.now)
+            .ok_or(MulticastError::Exhausted)?;
+        let pkt = self.inner.mld_report_packet(1, &data[..len]);
+        // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+        self.inner
+            .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+            .unwrap();
+
+        let next_report = self.inner.now
+            + self
+                .inner
+                .multicast_random_delay(MLD_UNSOLICITED_REPORT_INTERVAL);
+        let group = &mut self.inner.ipv6_multicast_groups[&addr];
+        match group.state_change {
+            Some((count, _)) if count > 1 => group.state_change = Some((count - 1, next_report)),
+            _ if group.is_member() => group.state_change = None,
+            _ => {
+                self.inner.ipv6_multicast_groups.remove(&addr);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send the MLD reports that are due: the response to a general query, the responses to
+    /// the queries about a group, and the retransmissions of the state change reports.
+    pub(crate) fn mld_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+
+        if let MldReportState::ToGeneralQuery {
+            timeout,
+            next_index,
+        } = self.inner.mld_report_state
+        {
+            if now >= timeout {
+                let mut data = [0u8; MLD_REPORT_MAX_LEN];
+                let (records, len, next_index) =
+                    self.inner.mld_general_report(next_index, &mut data);
+
+                if records > 0 {
+                    let Some(tx_token) = device.transmit(now) else {
+                        return false;
+                    };
+                    let pkt = self.inner.mld_report_packet(records, &data[..len]);
+                    // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+                    self.inner
+                        .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                        .unwrap();
+                }
+
+                self.inner.mld_report_state = match next_index {
+                    Some(next_index) => MldReportState::ToGeneralQuery {
+                        timeout,
+                        next_index,
+                    },
+                    None => MldReportState::Inactive,
+                };
+                return true;
+            }
+        }
+
+        let due = self
+            .inner
+            .ipv6_multicast_groups
+            .iter()
+            .find_map(
+                |(addr, group)| match (&group.query_response, group.state_change) {
+                    (Some((timeout, _)), _) if now >= *timeout => Some((*addr, true)),
+                    (_, Some((_, timeout))) if now >= timeout => Some((*addr, false)),
+                    _ => None,
+                },
+            );
+
+        match due {
+            Some((addr, true)) => {
+                let group = &mut self.inner.ipv6_multicast_groups[&addr];
+                let mut data = [0u8; MLD_RECORD_MAX_LEN];
+                let len = group
+                    .query_response
+                    .as_ref()
+                    .and_then(|(_, queried)| group.current_state(queried))
+                    .and_then(|(filter_mode, sources)| {
+                        emit_record(&mut data, current_state_record(filter_mode), addr, &sources)
+                    });
+
+                if let Some(len) = len {
+                    let Some(tx_token) = device.transmit(now) else {
+                        return false;
+                    };
+                    let pkt = self.inner.mld_report_packet(1, &data[..len]);
+                    // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+                    self.inner
+                        .dispatch_ip(tx_token, PacketMeta::default(), pkt, &mut self.fragmenter)
+                        .unwrap();
+                }
+
+                self.inner.ipv6_multicast_groups[&addr].query_response = None;
+                true
+            }
+            Some((addr, false)) => self.mld_send_state_change(device, addr).is_ok(),
+            None => false,
+        }
+    }
+}
+
+impl InterfaceInner {
+    /// Return `true` for the groups whose listeners are reported, which excludes the
+    /// all-nodes group and the interface-local groups, see [RFC 3810 § 6].
+    ///
+    /// [RFC 3810 § 6]: https://tools.ietf.org/html/rfc3810#section-6
+    fn mld_reports(addr: &Ipv6Address) -> bool {
+        *addr != Ipv6Address::LINK_LOCAL_ALL_NODES
+            && addr.multicast_scope() != Ipv6MulticastScope::InterfaceLocal
+    }
+
+    /// Return the time at which the next MLD report is due.
+    pub(super) fn mld_poll_at(&self) -> Option<Instant> {
+        let general = match self.mld_report_state {
+            MldReportState::ToGeneralQuery { timeout, .. } => Some(timeout),
+            MldReportState::Inactive => None,
+        };
+
+        self.ipv6_multicast_groups
+            .values()
+            .flat_map(|group| {
+                let query = group.query_response.as_ref().map(|(timeout, _)| *timeout);
+                let state_change = group.state_change.map(|(_, timeout)| timeout);
+                query.into_iter().chain(state_change)
+            })
+            .chain(general)
+            .min()
+    }
+
+    /// Build an MLDv2 report with `records` multicast address records, sent to all the MLDv2
+    /// routers of the link.
+    fn mld_report_packet<'p>(&self, records: u16, data: &'p [u8]) -> Packet<'p> {
+        // The source is link-local, or unspecified before one is assigned, see
+        // [RFC 3810 § 5.2.13](https://tools.ietf.org/html/rfc3810#section-5.2.13).
+        let src_addr = self
+            .ip_addrs
+            .iter()
+            .find_map(|cidr| match cidr {
+                IpCidr::Ipv6(cidr) if cidr.address().is_link_local() => Some(cidr.address()),
+                _ => None,
+            })
+            .unwrap_or(Ipv6Address::UNSPECIFIED);
+
+        let icmp_repr = Icmpv6Repr::Mld(MldRepr::Report {
+            nr_mcast_addr_rcrds: records,
+            data,
+        });
+
+        let mut options = heapless::Vec::new();
+        // NOTE(unwrap): there is room for two options, see the assertion above.
+        options
+            .push(Ipv6OptionRepr::RouterAlert(
+                Ipv6OptionRouterAlert::MulticastListenerDiscovery,
+            ))
+            .unwrap();
+        options.push(Ipv6OptionRepr::PadN(0)).unwrap();
+
+        Packet::Ipv6(PacketV6 {
+            header: Ipv6Repr {
+                src_addr,
+                dst_addr: Ipv6Address::LINK_LOCAL_ALL_MLDV2_ROUTERS,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_repr.buffer_len(),
+                hop_limit: 1,
+            },
+            hop_by_hop: Some(Ipv6HopByHopRepr { options }),
+            #[cfg(feature = "proto-ipv6-fragmentation")]
+            fragment: None,
+            #[cfg(feature = "proto-ipv6-routing")]
+            routing: None,
+            payload: IpPayload::Icmpv6(icmp_repr),
+        })
+    }
+
+    /// Emit in `buffer` the records of the response to a general query, starting at the
+    /// `next_index`-th group. Return the number of records, their length, and the index of the
+    /// first group left for the next report.
+    ///
+    /// The reported groups are the joined groups, and the solicited-node groups of the unicast
+    /// addresses of the interface, which it listens to without joining them.
+    fn mld_general_report(
+        &self,
+        next_index: usize,
+        buffer: &mut [u8],
+    ) -> (u16, usize, Option<usize>) {
+        let mut solicited_nodes: Vec<Ipv6Address, IFACE_MAX_ADDR_COUNT> = Vec::new();
+        for cidr in &self.ip_addrs {
+            #[allow(irrefutable_let_patterns)] // if only ipv6 is enabled
+            if let IpCidr::Ipv6(cidr) = cidr {
+                let addr = cidr.address();
+                if addr.is_unicast() && addr != Ipv6Address::LOOPBACK {
+                    let group = addr.solicited_node();
+                    let joined = matches!(
+                        self.ipv6_multicast_groups.get(&group),
+                        Some(group) if group.is_member()
+                    );
+                    if !joined && !solicited_nodes.contains(&group) {
+                        // NOTE(unwrap): there are at most as many groups as addresses.
+                        solicited_nodes.push(group).unwrap();
+                    }
+                }
+            }
+        }
+
+        let joined = self
+            .ipv6_multicast_groups
+            .iter()
+            .filter(|(addr, group)| group.is_member() && Self::mld_reports(addr))
+            .map(|(addr, group)| (*addr, group.filter_mode, &group.sources[..]));
+        let solicited = solicited_nodes
+            .iter()
+            .map(|addr| (*addr, MulticastFilterMode::Exclude, &[][..]));
+
+        let mut records = 0;
+        let mut len = 0;
+        for (index, (addr, filter_mode, sources)) in
+            joined.chain(solicited).enumerate().skip(next_index)
+        {
+            let record_type = current_state_record(filter_mode);
+            match emit_record(&mut buffer[len..], record_type, addr, sources) {
+                Some(record_len) => {
+                    records += 1;
+                    len += record_len;
+                }
+                None => return (records, len, Some(index)),
+            }
+        }
+
+        (records, len, None)
+    }
+
+    /// Host duties of the **MLDv2** protocol, see [RFC 3810 § 6.2].
+    ///
+    /// The queries are answered after a random delay, and the reports of other listeners are
+    /// ignored. Queries from MLDv1 routers are not answered.
+    ///
+    /// [RFC 3810 § 6.2]: https://tools.ietf.org/html/rfc3810#section-6.2
+    pub(super) fn process_mld<'frame>(
+        &mut self,
+        ip_repr: Ipv6Repr,
+        repr: MldRepr<'frame>,
+    ) -> Option<Packet<'frame>> {
+        let MldRepr::Query {
+            max_resp_code,
+            mcast_addr,
+            num_srcs,
+            data,
+            ..
+        } = repr
+        else {
+            return None;
+        };
+
+        // Queries are only sent by the routers of the link.
+        if !ip_repr.src_addr.is_link_local() {
+            net_debug!("mld: ignoring query from {}", ip_repr.src_addr);
+            return None;
+        }
+
+        // See RFC 3810 § 5.1.3.
+        let max_resp_delay = if max_resp_code < 0x8000 {
+            u64::from(max_resp_code)
+        } else {
+            let exp = (max_resp_code >> 12) & 0x7;
+            let mant = max_resp_code & 0xfff;
+            u64::from(mant | 0x1000) << (exp + 3)
+        };
+        let timeout = self.now + self.multicast_random_delay(Duration::from_millis(max_resp_delay));
+
+        let general_pending = matches!(
+            self.mld_report_state,
+            MldReportState::ToGeneralQuery { timeout: pending, .. } if pending <= timeout
+        );
+
+        if mcast_addr.is_unspecified() {
+            if !general_pending {
+                self.mld_report_state = MldReportState::ToGeneralQuery {
+                    timeout,
+                    next_index: 0,
+                };
+            }
+        } else if !general_pending {
+            if let Some(group) = self.ipv6_multicast_groups.get_mut(&mcast_addr) {
+                if group.is_member() {
+                    let sources = data
+                        .chunks_exact(16)
+                        .take(num_srcs as usize)
+                        .map(Ipv6Address::from_bytes);
+                    group.schedule_query_response(timeout, sources);
+                }
+            }
+        }
+
+        None
+    }
+}
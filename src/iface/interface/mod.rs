@@ -17,15 +17,56 @@ mod ipv6;
 #[cfg(feature = "proto-sixlowpan")]
 mod sixlowpan;
 
+#[cfg(feature = "proto-ipv4-acd")]
+mod acd;
+#[cfg(feature = "proto-ipv6-dad")]
+mod dad;
+#[cfg(feature = "iface-forwarding")]
+mod forwarding;
 #[cfg(feature = "proto-igmp")]
 mod igmp;
+#[cfg(feature = "_proto-ipsec")]
+mod ipsec;
+#[cfg(feature = "proto-mld")]
+mod mld;
+#[cfg(any(feature = "proto-igmp", feature = "proto-mld"))]
+mod multicast;
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+mod nud;
+#[cfg(feature = "proto-rpl")]
+mod rpl;
+#[cfg(feature = "proto-slaac")]
+mod slaac;
 #[cfg(feature = "socket-tcp")]
 mod tcp;
 #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
 mod udp;
 
-#[cfg(feature = "proto-igmp")]
-pub use igmp::MulticastError;
+#[cfg(any(feature = "proto-igmp", feature = "proto-mld"))]
+pub use multicast::{MulticastError, MulticastFilterMode};
+#[cfg(any(feature = "proto-igmp", feature = "proto-mld"))]
+use multicast::{MulticastGroup, MulticastSources};
+
+#[cfg(feature = "proto-ipv4-acd")]
+use acd::Acd;
+#[cfg(feature = "proto-ipv4-acd")]
+pub use acd::{AcdEvent, AcdPolicy, Ipv4AddressState};
+
+#[cfg(feature = "proto-ipv6-dad")]
+use dad::Dad;
+#[cfg(feature = "proto-ipv6-dad")]
+pub use dad::{DadEvent, Ipv6AddressState};
+
+#[cfg(feature = "proto-slaac")]
+use slaac::Slaac;
+#[cfg(feature = "proto-slaac")]
+pub use slaac::{SlaacAddress, SlaacConfig};
+
+#[cfg(feature = "iface-forwarding")]
+pub(crate) use forwarding::ForwardError;
+
+#[cfg(feature = "socket-tcp")]
+use tcp::SynCookies;
 
 use super::packet::*;
 
@@ -34,12 +75,21 @@ use heapless::{LinearMap, Vec};
 
 #[cfg(feature = "_proto-fragmentation")]
 use super::fragmentation::FragKey;
-#[cfg(any(feature = "proto-ipv4", feature = "proto-sixlowpan"))]
+#[cfg(any(
+    feature = "proto-ipv4",
+    feature = "proto-sixlowpan",
+    feature = "proto-ipv6-fragmentation"
+))]
 use super::fragmentation::PacketAssemblerSet;
 use super::fragmentation::{Fragmenter, FragmentsBuffer};
+#[cfg(feature = "_proto-ipsec")]
+use super::ipsec::{SecurityAssociations, SecurityPolicies};
+#[cfg(feature = "proto-rpl")]
+use super::rpl::{Config as RplConfig, Rpl};
 
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
 use super::neighbor::{Answer as NeighborAnswer, Cache as NeighborCache};
+use super::path_mtu::Cache as PathMtuCache;
 use super::socket_set::SocketSet;
 use crate::config::{
     IFACE_MAX_ADDR_COUNT, IFACE_MAX_MULTICAST_GROUP_COUNT,
@@ -98,6 +148,7 @@ pub struct InterfaceInner {
 
     #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
     neighbor_cache: NeighborCache,
+    path_mtu_cache: PathMtuCache,
     hardware_addr: HardwareAddress,
     #[cfg(feature = "medium-ieee802154")]
     sequence_no: u8,
@@ -105,6 +156,8 @@ pub struct InterfaceInner {
     pan_id: Option<Ieee802154Pan>,
     #[cfg(feature = "proto-ipv4-fragmentation")]
     ipv4_id: u16,
+    #[cfg(feature = "proto-ipv6-fragmentation")]
+    ipv6_frag_ident: u32,
     #[cfg(feature = "proto-sixlowpan")]
     sixlowpan_address_context:
         Vec<SixlowpanAddressContext, IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT>,
@@ -115,10 +168,39 @@ pub struct InterfaceInner {
     any_ip: bool,
     routes: Routes,
     #[cfg(feature = "proto-igmp")]
-    ipv4_multicast_groups: LinearMap<Ipv4Address, (), IFACE_MAX_MULTICAST_GROUP_COUNT>,
+    ipv4_multicast_groups:
+        LinearMap<Ipv4Address, MulticastGroup<Ipv4Address>, IFACE_MAX_MULTICAST_GROUP_COUNT>,
     /// When to report for (all or) the next multicast group membership via IGMP
     #[cfg(feature = "proto-igmp")]
     igmp_report_state: IgmpReportState,
+    /// Until when IGMPv1 and IGMPv2 queriers are known to be present on the link
+    #[cfg(feature = "proto-igmp")]
+    igmpv1_querier_present: Option<Instant>,
+    #[cfg(feature = "proto-igmp")]
+    igmpv2_querier_present: Option<Instant>,
+    #[cfg(feature = "proto-mld")]
+    ipv6_multicast_groups:
+        LinearMap<Ipv6Address, MulticastGroup<Ipv6Address>, IFACE_MAX_MULTICAST_GROUP_COUNT>,
+    /// When to report the multicast listener state in response to a general MLD query
+    #[cfg(feature = "proto-mld")]
+    mld_report_state: MldReportState,
+    #[cfg(feature = "_proto-ipsec")]
+    ipsec_sad: SecurityAssociations,
+    #[cfg(feature = "_proto-ipsec")]
+    ipsec_spd: SecurityPolicies,
+    #[cfg(feature = "proto-rpl")]
+    rpl: Option<Rpl>,
+    #[cfg(feature = "proto-slaac")]
+    slaac: Option<Slaac>,
+    #[cfg(feature = "proto-ipv6-dad")]
+    dad: Dad,
+    #[cfg(feature = "proto-ipv4-acd")]
+    acd: Acd,
+    /// Whether the packets that are not addressed to the interface are forwarded by a router.
+    #[cfg(feature = "iface-forwarding")]
+    forwarding: bool,
+    #[cfg(feature = "socket-tcp")]
+    syn_cookies: Option<SynCookies>,
 }
 
 /// Configuration structure used for creating a network interface.
@@ -143,6 +225,56 @@ pub struct Config {
     /// **NOTE**: we use the same PAN ID for destination and source.
     #[cfg(feature = "medium-ieee802154")]
     pub pan_id: Option<Ieee802154Pan>,
+
+    /// Run the RPL routing protocol with the given configuration.
+    ///
+    /// When set to `None`, RPL is disabled.
+    #[cfg(feature = "proto-rpl")]
+    pub rpl_config: Option<RplConfig>,
+
+    /// Configure IPv6 addresses and default routes from Router Advertisements (SLAAC) with
+    /// the given configuration.
+    ///
+    /// When set to `None`, SLAAC is disabled.
+    #[cfg(feature = "proto-slaac")]
+    pub slaac_config: Option<SlaacConfig>,
+
+    /// The number of Neighbor Solicitations sent by the Duplicate Address Detection of an IPv6
+    /// address, before the address is used.
+    ///
+    /// When set to 0, the addresses are used immediately. Defaults to 0, so the detection only
+    /// runs when it is configured.
+    #[cfg(feature = "proto-ipv6-dad")]
+    pub dad_transmits: u8,
+
+    /// Use the autoconfigured IPv6 addresses while their Duplicate Address Detection runs
+    /// (Optimistic DAD, RFC 4429).
+    ///
+    /// Defaults to `false`.
+    #[cfg(feature = "proto-ipv6-dad")]
+    pub optimistic_dad: bool,
+
+    /// Probe the IPv4 addresses before using them, and detect their conflicts with other hosts
+    /// with the given policy (ACD, RFC 5227).
+    ///
+    /// When set to `None`, the addresses are used immediately and their conflicts are not
+    /// detected.
+    #[cfg(feature = "proto-ipv4-acd")]
+    pub acd_policy: Option<AcdPolicy>,
+
+    /// Configure a 169.254/16 link-local IPv4 address (RFC 3927).
+    ///
+    /// Defaults to `false`.
+    #[cfg(feature = "proto-ipv4-acd")]
+    pub ipv4_link_local: bool,
+
+    /// Answer the TCP SYNs to an endpoint whose sockets are all busy with SYN cookies, and
+    /// accept the connection once the cookie is acknowledged, instead of resetting it.
+    ///
+    /// This keeps a SYN flood from filling the listening sockets with half-open connections.
+    /// Defaults to `false`.
+    #[cfg(feature = "socket-tcp")]
+    pub tcp_syn_cookies: bool,
 }
 
 impl Config {
@@ -152,6 +284,20 @@ impl Config {
             hardware_addr,
             #[cfg(feature = "medium-ieee802154")]
             pan_id: None,
+            #[cfg(feature = "proto-rpl")]
+            rpl_config: None,
+            #[cfg(feature = "proto-slaac")]
+            slaac_config: None,
+            #[cfg(feature = "proto-ipv6-dad")]
+            dad_transmits: 0,
+            #[cfg(feature = "proto-ipv6-dad")]
+            optimistic_dad: false,
+            #[cfg(feature = "proto-ipv4-acd")]
+            acd_policy: None,
+            #[cfg(feature = "proto-ipv4-acd")]
+            ipv4_link_local: false,
+            #[cfg(feature = "socket-tcp")]
+            tcp_syn_cookies: false,
         }
     }
 }
@@ -207,6 +353,42 @@ impl Interface {
             }
         }
 
+        #[cfg(feature = "proto-ipv6-fragmentation")]
+        let ipv6_frag_ident = rand.rand_u32();
+
+        #[cfg(feature = "proto-rpl")]
+        let rpl = config
+            .rpl_config
+            .map(|config| Rpl::new(config, now, &mut rand));
+
+        #[cfg(feature = "proto-slaac")]
+        let slaac = config
+            .slaac_config
+            .map(|config| Slaac::new(config, now, &mut rand));
+
+        #[cfg(feature = "socket-tcp")]
+        let syn_cookies = config.tcp_syn_cookies.then(|| SynCookies::new(&mut rand));
+
+        // Neighbor Discovery does not run without link-layer addresses.
+        #[cfg(feature = "proto-ipv6-dad")]
+        let dad = Dad::new(
+            match caps.medium {
+                #[cfg(feature = "medium-ip")]
+                Medium::Ip => 0,
+                #[allow(unreachable_patterns)]
+                _ => config.dad_transmits,
+            },
+            config.optimistic_dad,
+        );
+
+        // ARP only runs on Ethernet.
+        #[cfg(feature = "proto-ipv4-acd")]
+        let acd = match caps.medium {
+            Medium::Ethernet => Acd::new(config.acd_policy, config.ipv4_link_local, now),
+            #[allow(unreachable_patterns)]
+            _ => Acd::new(None, false, now),
+        };
+
         Interface {
             fragments: FragmentsBuffer {
                 #[cfg(feature = "proto-sixlowpan")]
@@ -216,6 +398,9 @@ impl Interface {
                 assembler: PacketAssemblerSet::new(),
                 #[cfg(feature = "_proto-fragmentation")]
                 reassembly_timeout: Duration::from_secs(60),
+
+                #[cfg(feature = "_proto-ipsec")]
+                ipsec_buf: [0u8; crate::config::IPSEC_BUFFER_SIZE],
             },
             fragmenter: Fragmenter::new(),
             inner: InterfaceInner {
@@ -228,10 +413,23 @@ impl Interface {
                 routes: Routes::new(),
                 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
                 neighbor_cache: NeighborCache::new(),
+                path_mtu_cache: PathMtuCache::new(),
                 #[cfg(feature = "proto-igmp")]
                 ipv4_multicast_groups: LinearMap::new(),
                 #[cfg(feature = "proto-igmp")]
                 igmp_report_state: IgmpReportState::Inactive,
+                #[cfg(feature = "proto-igmp")]
+                igmpv1_querier_present: None,
+                #[cfg(feature = "proto-igmp")]
+                igmpv2_querier_present: None,
+                #[cfg(feature = "proto-mld")]
+                ipv6_multicast_groups: LinearMap::new(),
+                #[cfg(feature = "proto-mld")]
+                mld_report_state: MldReportState::Inactive,
+                #[cfg(feature = "_proto-ipsec")]
+                ipsec_sad: SecurityAssociations::new(),
+                #[cfg(feature = "_proto-ipsec")]
+                ipsec_spd: SecurityPolicies::new(),
                 #[cfg(feature = "medium-ieee802154")]
                 sequence_no,
                 #[cfg(feature = "medium-ieee802154")]
@@ -240,8 +438,22 @@ impl Interface {
                 tag,
                 #[cfg(feature = "proto-ipv4-fragmentation")]
                 ipv4_id,
+                #[cfg(feature = "proto-ipv6-fragmentation")]
+                ipv6_frag_ident,
                 #[cfg(feature = "proto-sixlowpan")]
                 sixlowpan_address_context: Vec::new(),
+                #[cfg(feature = "proto-rpl")]
+                rpl,
+                #[cfg(feature = "proto-slaac")]
+                slaac,
+                #[cfg(feature = "proto-ipv6-dad")]
+                dad,
+                #[cfg(feature = "proto-ipv4-acd")]
+                acd,
+                #[cfg(feature = "iface-forwarding")]
+                forwarding: false,
+                #[cfg(feature = "socket-tcp")]
+                syn_cookies,
                 rand,
             },
         }
@@ -336,12 +548,21 @@ impl Interface {
 
     /// Update the IP addresses of the interface.
     ///
+    /// With the `proto-ipv6-dad` feature, the added IPv6 addresses are tentative until their
+    /// Duplicate Address Detection completes, see [`Interface::ipv6_addr_state`]. With the
+    /// `proto-ipv4-acd` feature and [`Config::acd_policy`] set, the added IPv4 addresses are
+    /// probed before they are used, see [`Interface::ipv4_addr_state`].
+    ///
     /// # Panics
     /// This function panics if any of the addresses are not unicast.
     pub fn update_ip_addrs<F: FnOnce(&mut Vec<IpCidr, IFACE_MAX_ADDR_COUNT>)>(&mut self, f: F) {
         f(&mut self.inner.ip_addrs);
         InterfaceInner::flush_neighbor_cache(&mut self.inner);
-        InterfaceInner::check_ip_addrs(&self.inner.ip_addrs)
+        InterfaceInner::check_ip_addrs(&self.inner.ip_addrs);
+        #[cfg(feature = "proto-ipv6-dad")]
+        self.inner.dad_update_addrs(false);
+        #[cfg(feature = "proto-ipv4-acd")]
+        self.inner.acd_update_addrs();
     }
 
     /// Check whether the interface has the given IP address assigned.
@@ -357,6 +578,55 @@ impl Interface {
         &mut self.inner.routes
     }
 
+    /// Return the path MTU towards `addr`, the size of the largest IP packet that can be sent
+    /// to it without being fragmented.
+    ///
+    /// This is the IP MTU of the interface, unless a router reported a smaller MTU with an ICMPv4
+    /// "fragmentation needed" or an ICMPv6 "packet too big" error in the last 10 minutes. UDP
+    /// datagrams should be kept below it.
+    pub fn path_mtu(&self, addr: &IpAddress) -> usize {
+        self.inner.path_mtu(addr)
+    }
+
+    /// Get the state of the RPL routing protocol, if it is enabled.
+    #[cfg(feature = "proto-rpl")]
+    pub fn rpl(&self) -> Option<&Rpl> {
+        self.inner.rpl.as_ref()
+    }
+
+    /// Report the outcome of a link-layer transmission to a RPL neighbor, identified by its
+    /// link-local address.
+    ///
+    /// `transmissions` is the number of attempts, including retransmissions, and `acknowledged`
+    /// tells whether a link-layer acknowledgment was received. These statistics estimate the ETX
+    /// of the links with the parents, which is the metric of the MRHOF objective function.
+    #[cfg(feature = "proto-rpl")]
+    pub fn rpl_link_transmission(
+        &mut self,
+        neighbor: Ipv6Address,
+        transmissions: u8,
+        acknowledged: bool,
+    ) {
+        let inner = &mut self.inner;
+        let Some(rpl) = &mut inner.rpl else {
+            return;
+        };
+        let mode = rpl.mode_of_operation;
+        let Some(dodag) = &mut rpl.dodag else {
+            return;
+        };
+        let Some(parent) = dodag.parent_set.find_mut(&neighbor) else {
+            return;
+        };
+
+        parent.link_stats_mut().update(transmissions, acknowledged);
+
+        // The metric of the link changed, which may change our Rank or preferred parent.
+        if !dodag.select_parent(mode, inner.now, &mut inner.rand) {
+            rpl.leave(inner.now);
+        }
+    }
+
     /// Enable or disable the AnyIP capability.
     ///
     /// AnyIP allowins packets to be received
@@ -411,6 +681,23 @@ impl Interface {
     ) -> bool
     where
         D: Device + ?Sized,
+    {
+        self.poll_routed(timestamp, device, sockets, |_, packet| Some(packet))
+    }
+
+    /// Poll the interface like [`poll`](Self::poll), but give the packets sent in response to
+    /// received packets to `route`. Only the packet returned by `route` is sent on the
+    /// interface.
+    pub(crate) fn poll_routed<D, R>(
+        &mut self,
+        timestamp: Instant,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+        mut route: R,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+        R: for<'p> FnMut(&InterfaceInner, Packet<'p>) -> Option<Packet<'p>>,
     {
         self.inner.now = timestamp;
 
@@ -428,12 +715,15 @@ impl Interface {
                 }
             }
             #[cfg(any(feature = "medium-ethernet", feature = "medium-ip"))]
-            _ =>
-            {
+            _ => {
                 #[cfg(feature = "proto-ipv4-fragmentation")]
                 if self.ipv4_egress(device) {
                     return true;
                 }
+                #[cfg(feature = "proto-ipv6-fragmentation")]
+                if self.ipv6_egress(device) {
+                    return true;
+                }
             }
         }
 
@@ -441,14 +731,44 @@ impl Interface {
 
         loop {
             let mut did_something = false;
-            did_something |= self.socket_ingress(device, sockets);
+            did_something |= self.socket_ingress(device, sockets, &mut route);
             did_something |= self.socket_egress(device, sockets);
 
+            #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+            {
+                did_something |= self.neighbor_egress(device, sockets);
+            }
+
             #[cfg(feature = "proto-igmp")]
             {
                 did_something |= self.igmp_egress(device);
             }
 
+            #[cfg(feature = "proto-mld")]
+            {
+                did_something |= self.mld_egress(device);
+            }
+
+            #[cfg(feature = "proto-rpl")]
+            {
+                did_something |= self.rpl_egress(device);
+            }
+
+            #[cfg(feature = "proto-slaac")]
+            {
+                did_something |= self.slaac_egress(device);
+            }
+
+            #[cfg(feature = "proto-ipv6-dad")]
+            {
+                did_something |= self.dad_egress(device);
+            }
+
+            #[cfg(feature = "proto-ipv4-acd")]
+            {
+                did_something |= self.acd_egress(device);
+            }
+
             if did_something {
                 readiness_may_have_changed = true;
             } else {
@@ -477,7 +797,7 @@ impl Interface {
 
         let inner = &mut self.inner;
 
-        sockets
+        let sockets_poll_at = sockets
             .items()
             .filter_map(move |item| {
                 let socket_poll_at = item.socket.poll_at(inner);
@@ -490,7 +810,62 @@ impl Interface {
                     PollAt::Now => Some(Instant::from_millis(0)),
                 }
             })
-            .min()
+            .min();
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        let sockets_poll_at = match self.inner.neighbor_poll_at() {
+            Some(neighbor_poll_at) => {
+                Some(sockets_poll_at.map_or(neighbor_poll_at, |at| at.min(neighbor_poll_at)))
+            }
+            None => sockets_poll_at,
+        };
+
+        #[cfg(feature = "proto-igmp")]
+        let sockets_poll_at = match self.inner.igmp_poll_at() {
+            Some(igmp_poll_at) => {
+                Some(sockets_poll_at.map_or(igmp_poll_at, |at| at.min(igmp_poll_at)))
+            }
+            None => sockets_poll_at,
+        };
+
+        #[cfg(feature = "proto-mld")]
+        let sockets_poll_at = match self.inner.mld_poll_at() {
+            Some(mld_poll_at) => {
+                Some(sockets_poll_at.map_or(mld_poll_at, |at| at.min(mld_poll_at)))
+            }
+            None => sockets_poll_at,
+        };
+
+        #[cfg(feature = "proto-slaac")]
+        let sockets_poll_at = match self.inner.slaac_poll_at() {
+            Some(slaac_poll_at) => {
+                Some(sockets_poll_at.map_or(slaac_poll_at, |at| at.min(slaac_poll_at)))
+            }
+            None => sockets_poll_at,
+        };
+
+        #[cfg(feature = "proto-ipv6-dad")]
+        let sockets_poll_at = match self.inner.dad_poll_at() {
+            Some(dad_poll_at) => {
+                Some(sockets_poll_at.map_or(dad_poll_at, |at| at.min(dad_poll_at)))
+            }
+            None => sockets_poll_at,
+        };
+
+        #[cfg(feature = "proto-ipv4-acd")]
+        let sockets_poll_at = match self.inner.acd_poll_at() {
+            Some(acd_poll_at) => {
+                Some(sockets_poll_at.map_or(acd_poll_at, |at| at.min(acd_poll_at)))
+            }
+            None => sockets_poll_at,
+        };
+
+        #[cfg(feature = "proto-rpl")]
+        if let Some(rpl_poll_at) = self.inner.rpl.as_ref().map(|rpl| rpl.poll_at()) {
+            return Some(sockets_poll_at.map_or(rpl_poll_at, |at| at.min(rpl_poll_at)));
+        }
+
+        sockets_poll_at
     }
 
     /// Return an _advisory wait time_ for calling [poll] the next time.
@@ -509,9 +884,15 @@ impl Interface {
         }
     }
 
-    fn socket_ingress<D>(&mut self, device: &mut D, sockets: &mut SocketSet<'_>) -> bool
+    fn socket_ingress<D, R>(
+        &mut self,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+        route: &mut R,
+    ) -> bool
     where
         D: Device + ?Sized,
+        R: for<'p> FnMut(&InterfaceInner, Packet<'p>) -> Option<Packet<'p>>,
     {
         let mut processed_any = false;
         debug!("lhw debug in socket_ingress before receive");
@@ -526,12 +907,19 @@ impl Interface {
                 match self.inner.caps.medium {
                     #[cfg(feature = "medium-ethernet")]
                     Medium::Ethernet => {
-                        if let Some(packet) = self.inner.process_ethernet(
+                        let packet = self.inner.process_ethernet(
                             sockets,
                             rx_meta,
                             frame,
                             &mut self.fragments,
-                        ) {
+                        );
+                        let packet = match packet {
+                            Some(EthernetPacket::Ip(packet)) => {
+                                route(&self.inner, packet).map(EthernetPacket::Ip)
+                            }
+                            packet => packet,
+                        };
+                        if let Some(packet) = packet {
                             if let Err(err) =
                                 self.inner.dispatch(tx_token, packet, &mut self.fragmenter)
                             {
@@ -541,9 +929,10 @@ impl Interface {
                     }
                     #[cfg(feature = "medium-ip")]
                     Medium::Ip => {
-                        if let Some(packet) =
-                            self.inner
-                                .process_ip(sockets, rx_meta, frame, &mut self.fragments)
+                        if let Some(packet) = self
+                            .inner
+                            .process_ip(sockets, rx_meta, frame, &mut self.fragments)
+                            .and_then(|packet| route(&self.inner, packet))
                         {
                             if let Err(err) = self.inner.dispatch_ip(
                                 tx_token,
@@ -557,12 +946,11 @@ impl Interface {
                     }
                     #[cfg(feature = "medium-ieee802154")]
                     Medium::Ieee802154 => {
-                        if let Some(packet) = self.inner.process_ieee802154(
-                            sockets,
-                            rx_meta,
-                            frame,
-                            &mut self.fragments,
-                        ) {
+                        if let Some(packet) = self
+                            .inner
+                            .process_ieee802154(sockets, rx_meta, frame, &mut self.fragments)
+                            .and_then(|packet| route(&self.inner, packet))
+                        {
                             if let Err(err) = self.inner.dispatch_ip(
                                 tx_token,
                                 PacketMeta::default(),
@@ -722,6 +1110,27 @@ impl InterfaceInner {
         self.caps.ip_mtu()
     }
 
+    /// Return the path MTU towards `addr`, which is never larger than the IP MTU.
+    pub(crate) fn path_mtu(&self, addr: &IpAddress) -> usize {
+        let mtu = self.ip_mtu();
+        match self.path_mtu_cache.lookup(addr, self.now) {
+            Some(path_mtu) => path_mtu.min(mtu),
+            None => mtu,
+        }
+    }
+
+    /// Lower the path MTU towards `addr`, after a router reported that a packet was too big.
+    /// The path MTU is never raised this way, see RFC 1191 § 6.3 and RFC 8201 § 4.
+    #[allow(unused)] // unused depending on which protocols are enabled
+    pub(crate) fn path_mtu_reduced(&mut self, addr: IpAddress, mtu: usize) {
+        if mtu >= self.path_mtu(&addr) {
+            return;
+        }
+
+        net_debug!("path MTU towards {} lowered to {}", addr, mtu);
+        self.path_mtu_cache.fill(addr, mtu, self.now);
+    }
+
     #[allow(unused)] // unused depending on which sockets are enabled, and in tests
     pub(crate) fn rand(&mut self) -> &mut Rand {
         &mut self.rand
@@ -761,32 +1170,60 @@ impl InterfaceInner {
     /// Check whether the interface has the given IP address assigned.
     fn has_ip_addr<T: Into<IpAddress>>(&self, addr: T) -> bool {
         let addr = addr.into();
+
+        // Tentative and duplicate addresses are not assigned, see RFC 4862 § 5.4.
+        #[cfg(feature = "proto-ipv6-dad")]
+        #[allow(irrefutable_let_patterns)]
+        if let IpAddress::Ipv6(addr) = addr {
+            if !self.dad_is_assigned(&addr) {
+                return false;
+            }
+        }
+
+        // Probed and conflicting addresses are not used, see RFC 5227 § 2.1.
+        #[cfg(feature = "proto-ipv4-acd")]
+        #[allow(irrefutable_let_patterns)]
+        if let IpAddress::Ipv4(addr) = addr {
+            if !self.acd_is_assigned(&addr) {
+                return false;
+            }
+        }
+
         self.ip_addrs.iter().any(|probe| probe.address() == addr)
     }
 
     /// Check whether the interface listens to given destination multicast IP address.
     ///
     /// If built without feature `proto-igmp` this function will
-    /// always return `false` when using IPv4.
+    /// always return `false` when using IPv4, and without feature
+    /// `proto-mld` it only accepts the groups that NDISC and RPL use.
     fn has_multicast_group<T: Into<IpAddress>>(&self, addr: T) -> bool {
         match addr.into() {
             #[cfg(feature = "proto-igmp")]
             IpAddress::Ipv4(key) => {
                 key == Ipv4Address::MULTICAST_ALL_SYSTEMS
-                    || self.ipv4_multicast_groups.get(&key).is_some()
+                    || matches!(self.ipv4_multicast_groups.get(&key), Some(group) if group.is_member())
             }
             #[cfg(feature = "proto-ipv6")]
             IpAddress::Ipv6(Ipv6Address::LINK_LOCAL_ALL_NODES) => true,
             #[cfg(feature = "proto-rpl")]
             IpAddress::Ipv6(Ipv6Address::LINK_LOCAL_ALL_RPL_NODES) => true,
             #[cfg(feature = "proto-ipv6")]
-            IpAddress::Ipv6(addr) => self.has_solicited_node(addr),
+            IpAddress::Ipv6(addr) => {
+                #[cfg(feature = "proto-mld")]
+                if matches!(self.ipv6_multicast_groups.get(&addr), Some(group) if group.is_member())
+                {
+                    return true;
+                }
+
+                self.has_solicited_node(addr)
+            }
             #[allow(unreachable_patterns)]
             _ => false,
         }
     }
 
-    #[cfg(feature = "medium-ip")]
+    #[cfg(any(feature = "medium-ip", feature = "iface-forwarding"))]
     fn process_ip<'frame>(
         &mut self,
         sockets: &mut SocketSet,
@@ -804,7 +1241,7 @@ impl InterfaceInner {
             #[cfg(feature = "proto-ipv6")]
             Ok(IpVersion::Ipv6) => {
                 let ipv6_packet = check!(Ipv6Packet::new_checked(ip_payload));
-                self.process_ipv6(sockets, meta, &ipv6_packet)
+                self.process_ipv6(sockets, meta, &ipv6_packet, Some(frag))
             }
             // Drop all other traffic.
             _ => None,
@@ -883,6 +1320,16 @@ impl InterfaceInner {
     }
 
     fn route(&self, addr: &IpAddress, timestamp: Instant) -> Option<IpAddress> {
+        // Route via the RPL DODAG. Its nodes share a prefix, but are not all in range of each
+        // other.
+        #[cfg(feature = "proto-rpl")]
+        #[allow(irrefutable_let_patterns)] // if only ipv6 is enabled
+        if let IpAddress::Ipv6(dst_addr) = addr {
+            if let Some(next_hop) = self.rpl.as_ref().and_then(|rpl| rpl.next_hop(dst_addr)) {
+                return Some(next_hop.into());
+            }
+        }
+
         // Send directly.
         // note: no need to use `self.is_broadcast()` to check for subnet-local broadcast addrs
         //       here because `in_same_network` will already return true.
@@ -974,10 +1421,31 @@ impl InterfaceInner {
             .route(dst_addr, self.now)
             .ok_or(DispatchError::NoRoute)?;
 
+        self.lookup_neighbor(tx_token, src_addr, &dst_addr, fragmenter)
+    }
+
+    /// Look up the hardware address of a neighbor, and send a neighbor solicitation when it is
+    /// not in the neighbor cache.
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    fn lookup_neighbor<Tx>(
+        &mut self,
+        tx_token: Tx,
+        src_addr: &IpAddress,
+        dst_addr: &IpAddress,
+        fragmenter: &mut Fragmenter,
+    ) -> Result<(HardwareAddress, Tx), DispatchError>
+    where
+        Tx: TxToken,
+    {
+        let dst_addr = *dst_addr;
+
         debug!("lhw debug in loopup hardware addr debugout");
         self.neighbor_cache.debug_out();
         match self.neighbor_cache.lookup(&dst_addr, self.now) {
-            NeighborAnswer::Found(hardware_addr) => return Ok((hardware_addr, tx_token)),
+            NeighborAnswer::Found(hardware_addr) => {
+                self.neighbor_cache.used(&dst_addr, self.now);
+                return Ok((hardware_addr, tx_token));
+            }
             NeighborAnswer::RateLimited => return Err(DispatchError::NeighborPending),
             _ => (), // XXX
         }
@@ -1021,9 +1489,16 @@ impl InterfaceInner {
                     dst_addr
                 );
 
+                // Solicitations from an optimistic address do not carry the link-layer address,
+                // which would override the one of the legitimate owner, see RFC 4429 § 3.3.
+                #[cfg(feature = "proto-ipv6-dad")]
+                let lladdr = (!self.dad_is_probing(&src_addr)).then(|| self.hardware_addr.into());
+                #[cfg(not(feature = "proto-ipv6-dad"))]
+                let lladdr = Some(self.hardware_addr.into());
+
                 let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
                     target_addr: dst_addr,
-                    lladdr: Some(self.hardware_addr.into()),
+                    lladdr,
                 });
 
                 let packet = Packet::new_ipv6(
@@ -1050,7 +1525,7 @@ impl InterfaceInner {
         }
 
         // The request got dispatched, limit the rate on the cache.
-        self.neighbor_cache.limit_rate(self.now);
+        self.neighbor_cache.limit_rate(&dst_addr, self.now);
         Err(DispatchError::NeighborPending)
     }
 
@@ -1059,6 +1534,47 @@ impl InterfaceInner {
         self.neighbor_cache.flush()
     }
 
+    /// Confirm that the next hop towards `addr` is reachable, because an upper-layer protocol
+    /// made forward progress with `addr`, see RFC 4861 § 7.3.1.
+    #[cfg(feature = "socket-tcp")]
+    pub(crate) fn confirm_reachable(&mut self, addr: &IpAddress) {
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        if let Some(next_hop) = self.route(addr, self.now) {
+            self.neighbor_cache.confirm(&next_hop, self.now);
+        }
+        #[cfg(not(any(feature = "medium-ethernet", feature = "medium-ieee802154")))]
+        let _ = addr;
+    }
+
+    /// Look up the hardware address of the next hop of a packet. The destination of a packet
+    /// with a RPL Source Route Header is the next hop of the route, which is a neighbor.
+    #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+    fn lookup_next_hop_hardware_addr<Tx>(
+        &mut self,
+        tx_token: Tx,
+        #[allow(unused_variables)] packet: &Packet,
+        ip_repr: &IpRepr,
+        fragmenter: &mut Fragmenter,
+    ) -> Result<(HardwareAddress, Tx), DispatchError>
+    where
+        Tx: TxToken,
+    {
+        let src_addr = ip_repr.src_addr();
+        let dst_addr = ip_repr.dst_addr();
+
+        #[cfg(feature = "proto-rpl")]
+        if let Packet::Ipv6(PacketV6 {
+            routing: Some(_), ..
+        }) = packet
+        {
+            if dst_addr.is_unicast() {
+                return self.lookup_neighbor(tx_token, &src_addr, &dst_addr, fragmenter);
+            }
+        }
+
+        self.lookup_hardware_addr(tx_token, &src_addr, &dst_addr, fragmenter)
+    }
+
     fn dispatch_ip<Tx: TxToken>(
         &mut self,
         // NOTE(unused_mut): tx_token isn't always mutated, depending on
@@ -1068,19 +1584,55 @@ impl InterfaceInner {
         packet: Packet,
         frag: &mut Fragmenter,
     ) -> Result<(), DispatchError> {
+        // A non-storing RPL root routes the packets for its DODAG with a source route.
+        #[cfg(feature = "proto-rpl")]
+        let mut source_route = [0u8; rpl::SOURCE_ROUTE_BUFFER_LEN];
+        #[cfg(feature = "proto-rpl")]
+        let packet = self.rpl_source_route(packet, &mut source_route);
+
         let mut ip_repr = packet.ip_repr();
         assert!(!ip_repr.dst_addr().is_unspecified());
 
+        // Apply the IPsec security policies:
+
+        #[cfg(feature = "_proto-ipsec")]
+        #[allow(unused_mut)]
+        let mut ipsec = match self.ipsec_egress(&packet) {
+            ipsec::IpSecEgress::Bypass => None,
+            ipsec::IpSecEgress::Discard => return Ok(()),
+            ipsec::IpSecEgress::Protect(encapsulation) => {
+                // The extension headers would have to be covered by the protection.
+                #[cfg(any(feature = "proto-ipv6-hbh", feature = "proto-ipv6-routing"))]
+                if let Packet::Ipv6(packet) = &packet {
+                    if packet.ext_headers_len() > 0 {
+                        net_debug!(
+                            "ipsec: {} with extension headers is not supported, dropping",
+                            encapsulation.protocol()
+                        );
+                        return Ok(());
+                    }
+                }
+
+                encapsulation.encapsulate(&mut ip_repr);
+                Some(encapsulation)
+            }
+        };
+
         // Dispatch IEEE802.15.4:
 
         #[cfg(feature = "medium-ieee802154")]
         if matches!(self.caps.medium, Medium::Ieee802154) {
-            let (addr, tx_token) = self.lookup_hardware_addr(
-                tx_token,
-                &ip_repr.src_addr(),
-                &ip_repr.dst_addr(),
-                frag,
-            )?;
+            #[cfg(feature = "_proto-ipsec")]
+            if let Some(encapsulation) = &ipsec {
+                net_debug!(
+                    "ipsec: {} is not supported over 6LoWPAN, dropping",
+                    encapsulation.protocol()
+                );
+                return Ok(());
+            }
+
+            let (addr, tx_token) =
+                self.lookup_next_hop_hardware_addr(tx_token, &packet, &ip_repr, frag)?;
             let addr = addr.ieee802154_or_panic();
 
             self.dispatch_ieee802154(addr, tx_token, meta, packet, frag);
@@ -1093,6 +1645,16 @@ impl InterfaceInner {
 
         #[cfg(feature = "proto-ipv4-fragmentation")]
         let ipv4_id = self.next_ipv4_frag_ident();
+        #[cfg(feature = "proto-ipv6-fragmentation")]
+        let ipv6_frag_ident = self.next_ipv6_frag_ident();
+
+        // The identification of fragmented packets is covered by the AH ICV.
+        #[cfg(all(feature = "_proto-ipsec", feature = "proto-ipv4-fragmentation"))]
+        if let Some(encapsulation) = &mut ipsec {
+            if ip_repr.buffer_len() > self.caps.max_transmission_unit {
+                encapsulation.set_ipv4_ident(ipv4_id);
+            }
+        }
 
         // First we calculate the total length that we will have to emit.
         let mut total_len = ip_repr.buffer_len();
@@ -1105,19 +1667,59 @@ impl InterfaceInner {
 
         // If the medium is Ethernet, then we need to retrieve the destination hardware address.
         #[cfg(feature = "medium-ethernet")]
-        let (dst_hardware_addr, mut tx_token) = match self.caps.medium {
-            Medium::Ethernet => {
-                match self.lookup_hardware_addr(
-                    tx_token,
-                    &ip_repr.src_addr(),
-                    &ip_repr.dst_addr(),
-                    frag,
-                )? {
-                    (HardwareAddress::Ethernet(addr), tx_token) => (addr, tx_token),
-                    (_, _) => unreachable!(),
-                }
+        let lookup = match self.caps.medium {
+            Medium::Ethernet => self
+                .lookup_next_hop_hardware_addr(tx_token, &packet, &ip_repr, frag)
+                .map(|(addr, tx_token)| (addr.ethernet_or_panic(), tx_token)),
+            _ => Ok((EthernetAddress([0; 6]), tx_token)),
+        };
+
+        // While the address resolution is in progress, the packet is queued if possible.
+        #[cfg(feature = "medium-ethernet")]
+        let pending_hop = match lookup {
+            Err(DispatchError::NeighborPending) => self.neighbor_pending_hop(&packet, &ip_repr),
+            _ => None,
+        };
+
+        // Emit function for the IP header and payload.
+        let emit_ip = |repr: &IpRepr, mut tx_buffer: &mut [u8]| {
+            repr.emit(&mut tx_buffer, &caps.checksum);
+
+            let payload = &mut tx_buffer[repr.header_len()..];
+
+            #[cfg(feature = "_proto-ipsec")]
+            if let Some(encapsulation) = &ipsec {
+                encapsulation.emit(&self.ipsec_sad, payload, |payload| {
+                    packet.emit_payload(repr, payload, &caps)
+                });
+                return;
             }
-            _ => (EthernetAddress([0; 6]), tx_token),
+
+            packet.emit_payload(repr, payload, &caps)
+        };
+
+        #[cfg(feature = "medium-ethernet")]
+        let (dst_hardware_addr, mut tx_token) = match lookup {
+            Ok(lookup) => lookup,
+            Err(e) => match pending_hop {
+                Some(next_hop)
+                    if self.neighbor_cache.enqueue(
+                        &next_hop,
+                        meta,
+                        ip_repr.src_addr(),
+                        ip_repr.buffer_len(),
+                        |buffer| emit_ip(&ip_repr, buffer),
+                    ) =>
+                {
+                    net_debug!(
+                        "queued packet to {} until {} is resolved",
+                        ip_repr.dst_addr(),
+                        next_hop
+                    );
+                    return Ok(());
+                }
+                _ => return Err(e),
+            },
         };
 
         // Emit function for the Ethernet header.
@@ -1139,14 +1741,6 @@ impl InterfaceInner {
             Ok(())
         };
 
-        // Emit function for the IP header and payload.
-        let emit_ip = |repr: &IpRepr, mut tx_buffer: &mut [u8]| {
-            repr.emit(&mut tx_buffer, &self.caps.checksum);
-
-            let payload = &mut tx_buffer[repr.header_len()..];
-            packet.emit_payload(repr, payload, &caps)
-        };
-
         let total_ip_len = ip_repr.buffer_len();
 
         match &mut ip_repr {
@@ -1190,8 +1784,8 @@ impl InterfaceInner {
                         // Modify the IP header
                         repr.payload_len = first_frag_ip_len - repr.buffer_len();
 
-                        // Emit the IP header to the buffer.
-                        emit_ip(&ip_repr, &mut frag.buffer);
+                        // Emit the IP header and the whole payload to the buffer.
+                        emit_ip(&ip_repr, &mut frag.buffer[..total_ip_len]);
 
                         let mut ipv4_packet = Ipv4Packet::new_unchecked(&mut frag.buffer[..]);
                         frag.ipv4.ident = ipv4_id;
@@ -1244,18 +1838,86 @@ impl InterfaceInner {
                     })
                 }
             }
-            // We don't support IPv6 fragmentation yet.
             #[cfg(feature = "proto-ipv6")]
-            IpRepr::Ipv6(_) => tx_token.consume(total_len, |mut tx_buffer| {
-                #[cfg(feature = "medium-ethernet")]
-                if matches!(self.caps.medium, Medium::Ethernet) {
-                    emit_ethernet(&ip_repr, tx_buffer)?;
-                    tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
-                }
+            IpRepr::Ipv6(_) => {
+                // If we have an IPv6 packet, then we need to check if we need to fragment it.
+                // Routers don't fragment IPv6 packets, so the path MTU is used.
+                let path_mtu = self.path_mtu(&ip_repr.dst_addr());
+                if total_ip_len > path_mtu {
+                    #[cfg(feature = "proto-ipv6-fragmentation")]
+                    {
+                        net_debug!("start fragmentation");
 
-                emit_ip(&ip_repr, tx_buffer);
-                Ok(())
-            }),
+                        if frag.buffer.len() < total_ip_len {
+                            net_debug!(
+                                "Fragmentation buffer is too small, at least {} needed. Dropping",
+                                total_ip_len
+                            );
+                            return Ok(());
+                        }
+
+                        // Emit the whole packet to the buffer, the fragments are cut from it.
+                        emit_ip(&ip_repr, &mut frag.buffer[..total_ip_len]);
+
+                        // The Hop-by-Hop and Routing headers are repeated in every fragment,
+                        // followed by the Fragment header, see RFC 8200 § 4.5.
+                        #[cfg(any(feature = "proto-ipv6-hbh", feature = "proto-ipv6-routing"))]
+                        let unfragmentable_len = match &packet {
+                            Packet::Ipv6(packet) => IPV6_HEADER_LEN + packet.ext_headers_len(),
+                            #[allow(unreachable_patterns)]
+                            _ => unreachable!(),
+                        };
+                        #[cfg(not(any(
+                            feature = "proto-ipv6-hbh",
+                            feature = "proto-ipv6-routing"
+                        )))]
+                        let unfragmentable_len = IPV6_HEADER_LEN;
+
+                        let mut next_header_offset = 6;
+                        let mut offset = IPV6_HEADER_LEN;
+                        while offset < unfragmentable_len {
+                            next_header_offset = offset;
+                            offset += (frag.buffer[offset + 1] as usize + 1) * 8;
+                        }
+
+                        #[cfg(feature = "medium-ethernet")]
+                        {
+                            frag.ipv6.dst_hardware_addr = dst_hardware_addr;
+                        }
+                        frag.ipv6.unfragmentable_len = unfragmentable_len;
+                        frag.ipv6.next_header_offset = next_header_offset;
+                        frag.ipv6.next_header = frag.buffer[next_header_offset].into();
+                        frag.ipv6.ident = ipv6_frag_ident;
+                        frag.ipv6.mtu = path_mtu;
+                        frag.packet_len = total_ip_len;
+                        frag.sent_bytes = unfragmentable_len;
+
+                        // Transmit the first fragment.
+                        self.dispatch_ipv6_frag(tx_token, frag);
+                        Ok(())
+                    }
+
+                    #[cfg(not(feature = "proto-ipv6-fragmentation"))]
+                    {
+                        net_debug!("Enable the `proto-ipv6-fragmentation` feature for fragmentation support.");
+                        Ok(())
+                    }
+                } else {
+                    tx_token.set_meta(meta);
+
+                    // No fragmentation is required.
+                    tx_token.consume(total_len, |mut tx_buffer| {
+                        #[cfg(feature = "medium-ethernet")]
+                        if matches!(self.caps.medium, Medium::Ethernet) {
+                            emit_ethernet(&ip_repr, tx_buffer)?;
+                            tx_buffer = &mut tx_buffer[EthernetFrame::<&[u8]>::header_len()..];
+                        }
+
+                        emit_ip(&ip_repr, tx_buffer);
+                        Ok(())
+                    })
+                }
+            }
         }
     }
 }
@@ -0,0 +1,403 @@
+use super::*;
+use crate::config::IFACE_MAX_MULTICAST_SOURCE_COUNT;
+
+/// Error type for `join_multicast_group`, `leave_multicast_group`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MulticastError {
+    /// The hardware device transmit buffer is full. Try again later.
+    Exhausted,
+    /// The table of joined multicast groups is already full.
+    GroupTableFull,
+    /// The source filter has more sources than a group can hold.
+    SourceTableFull,
+    /// A source of the filter is not a unicast address of the family of the group.
+    InvalidSource,
+    /// IPv4 multicast is not supported without the `proto-igmp` feature.
+    Ipv4NotSupported,
+    /// IPv6 multicast is not supported without the `proto-mld` feature.
+    Ipv6NotSupported,
+}
+
+impl core::fmt::Display for MulticastError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            MulticastError::Exhausted => write!(f, "Exhausted"),
+            MulticastError::GroupTableFull => write!(f, "GroupTableFull"),
+            MulticastError::SourceTableFull => write!(f, "SourceTableFull"),
+            MulticastError::InvalidSource => write!(f, "InvalidSource"),
+            MulticastError::Ipv4NotSupported => write!(f, "Ipv4NotSupported"),
+            MulticastError::Ipv6NotSupported => write!(f, "Ipv6NotSupported"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MulticastError {}
+
+/// The filter mode of the source filter of a multicast group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MulticastFilterMode {
+    /// Only the packets sent by the sources of the filter are received.
+    Include,
+    /// The packets sent by all the sources except those of the filter are received.
+    Exclude,
+}
+
+/// The source list of a multicast group.
+pub(crate) type MulticastSources<A> = Vec<A, IFACE_MAX_MULTICAST_SOURCE_COUNT>;
+
+/// The state of the interface for a multicast group, see [RFC 3810 § 4.2] and
+/// [RFC 3376 § 3.2].
+///
+/// An INCLUDE filter with no sources means that the interface does not listen to the group,
+/// the group is only kept until the state change reports of the interface leaving it are sent.
+///
+/// [RFC 3810 § 4.2]: https://tools.ietf.org/html/rfc3810#section-4.2
+/// [RFC 3376 § 3.2]: https://tools.ietf.org/html/rfc3376#section-3.2
+#[derive(Debug)]
+pub(crate) struct MulticastGroup<A> {
+    pub(crate) filter_mode: MulticastFilterMode,
+    pub(crate) sources: MulticastSources<A>,
+    /// The number of state change reports left to send, and when to send the next one.
+    pub(crate) state_change: Option<(u8, Instant)>,
+    /// When to answer a query about the group, with the queried sources. No sources means that
+    /// the query is about the whole group.
+    pub(crate) query_response: Option<(Instant, MulticastSources<A>)>,
+}
+
+impl<A: Copy + PartialEq> MulticastGroup<A> {
+    /// Create the state of a group the interface does not listen to.
+    pub(crate) fn new() -> Self {
+        Self {
+            filter_mode: MulticastFilterMode::Include,
+            sources: Vec::new(),
+            state_change: None,
+            query_response: None,
+        }
+    }
+
+    /// Return `true` when the interface listens to the group.
+    pub(crate) fn is_member(&self) -> bool {
+        self.filter_mode == MulticastFilterMode::Exclude || !self.sources.is_empty()
+    }
+
+    /// Return `true` when the packets sent to the group by `source` are received.
+    pub(crate) fn accepts(&self, source: &A) -> bool {
+        match self.filter_mode {
+            MulticastFilterMode::Include => self.sources.contains(source),
+            MulticastFilterMode::Exclude => !self.sources.contains(source),
+        }
+    }
+
+    /// Set the source filter of the group, and return `true` when it changed.
+    pub(crate) fn set_filter(
+        &mut self,
+        filter_mode: MulticastFilterMode,
+        sources: MulticastSources<A>,
+    ) -> bool {
+        let changed = filter_mode != self.filter_mode
+            || sources.len() != self.sources.len()
+            || sources.iter().any(|source| !self.sources.contains(source));
+
+        self.filter_mode = filter_mode;
+        self.sources = sources;
+        changed
+    }
+
+    /// Schedule the response to a query about the group, or about some of its sources.
+    ///
+    /// A pending response is sent at the earliest of the two deadlines, for the union of the
+    /// queried sources. A query about the whole group, or about more sources than we can hold,
+    /// makes it a response about the whole group.
+    pub(crate) fn schedule_query_response(
+        &mut self,
+        timeout: Instant,
+        queried: impl Iterator<Item = A>,
+    ) {
+        let (timeout, mut sources) = match self.query_response.take() {
+            Some((pending_timeout, sources)) => (pending_timeout.min(timeout), Some(sources)),
+            None => (timeout, None),
+        };
+
+        let mut queried = queried.peekable();
+        let is_group_query = queried.peek().is_none();
+        match &mut sources {
+            Some(sources) if sources.is_empty() => (),
+            _ if is_group_query => sources = Some(Vec::new()),
+            _ => {
+                let list = sources.get_or_insert_with(Vec::new);
+                for source in queried {
+                    if !list.contains(&source) && list.push(source).is_err() {
+                        list.clear();
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.query_response = Some((timeout, sources.unwrap_or_default()));
+    }
+
+    /// Return the filter mode and the sources reported in answer to a query about `queried`
+    /// sources, or about the whole group when there are none. `None` means that no report is
+    /// sent, because the interface does not receive the packets of any queried source.
+    pub(crate) fn current_state(
+        &self,
+        queried: &[A],
+    ) -> Option<(MulticastFilterMode, MulticastSources<A>)> {
+        if !self.is_member() {
+            return None;
+        }
+
+        if queried.is_empty() {
+            return Some((self.filter_mode, self.sources.clone()));
+        }
+
+        let sources: MulticastSources<A> = queried
+            .iter()
+            .filter(|source| self.accepts(source))
+            .copied()
+            .collect();
+        (!sources.is_empty()).then_some((MulticastFilterMode::Include, sources))
+    }
+}
+
+impl InterfaceInner {
+    /// Return a random delay shorter than `max`, used to spread the reports of the listeners
+    /// of a link.
+    pub(super) fn multicast_random_delay(&mut self, max: Duration) -> Duration {
+        match max.total_millis() {
+            0 => Duration::ZERO,
+            max => Duration::from_millis(self.rand.rand_u32() as u64 % max),
+        }
+    }
+}
+
+/// Collect the sources of a filter, which must be unicast addresses that `convert` accepts.
+fn collect_sources<T, A>(
+    sources: &[T],
+    convert: impl Fn(IpAddress) -> Option<A>,
+) -> Result<MulticastSources<A>, MulticastError>
+where
+    T: Into<IpAddress> + Copy,
+    A: PartialEq,
+{
+    let mut collected = Vec::new();
+    for source in sources {
+        let source: IpAddress = (*source).into();
+        let source = convert(source)
+            .filter(|_| source.is_unicast())
+            .ok_or(MulticastError::InvalidSource)?;
+        if !collected.contains(&source) {
+            collected
+                .push(source)
+                .map_err(|_| MulticastError::SourceTableFull)?;
+        }
+    }
+
+    Ok(collected)
+}
+
+impl Interface {
+    /// Add an address to a list of subscribed multicast IP addresses.
+    ///
+    /// Returns `Ok(announce_sent)` if the address was added successfully, where `announce_sent`
+    /// indicates whether an initial immediate announcement has been sent.
+    pub fn join_multicast_group<D, T: Into<IpAddress>>(
+        &mut self,
+        device: &mut D,
+        addr: T,
+        timestamp: Instant,
+    ) -> Result<bool, MulticastError>
+    where
+        D: Device + ?Sized,
+    {
+        self.join_multicast_group_with_sources::<D, IpAddress>(
+            device,
+            addr.into(),
+            MulticastFilterMode::Exclude,
+            &[],
+            timestamp,
+        )
+    }
+
+    /// Subscribe to a multicast IP address, only receiving the packets of the sources allowed
+    /// by a source filter. Joining a group the interface already listens to changes its filter.
+    ///
+    /// An `Exclude` filter without sources receives the packets of all the sources, like
+    /// [`join_multicast_group`](Self::join_multicast_group), and an `Include` filter without
+    /// sources leaves the group.
+    ///
+    /// Returns `Ok(announce_sent)` if the filter was set successfully, where `announce_sent`
+    /// indicates whether an immediate report of the change has been sent.
+    pub fn join_multicast_group_with_sources<D, T>(
+        &mut self,
+        device: &mut D,
+        addr: T,
+        filter_mode: MulticastFilterMode,
+        sources: &[T],
+        timestamp: Instant,
+    ) -> Result<bool, MulticastError>
+    where
+        D: Device + ?Sized,
+        T: Into<IpAddress> + Copy,
+    {
+        self.inner.now = timestamp;
+
+        match addr.into() {
+            #[cfg(feature = "proto-igmp")]
+            IpAddress::Ipv4(addr) => {
+                let sources = collect_sources(sources, |source| match source {
+                    IpAddress::Ipv4(source) => Some(source),
+                    #[allow(unreachable_patterns)]
+                    _ => None,
+                })?;
+                self.join_ipv4_multicast_group(device, addr, filter_mode, sources)
+            }
+            #[cfg(feature = "proto-mld")]
+            IpAddress::Ipv6(addr) => {
+                let sources = collect_sources(sources, |source| match source {
+                    IpAddress::Ipv6(source) => Some(source),
+                    #[allow(unreachable_patterns)]
+                    _ => None,
+                })?;
+                self.join_ipv6_multicast_group(device, addr, filter_mode, sources)
+            }
+            #[cfg(all(feature = "proto-ipv4", not(feature = "proto-igmp")))]
+            IpAddress::Ipv4(_) => Err(MulticastError::Ipv4NotSupported),
+            #[cfg(all(feature = "proto-ipv6", not(feature = "proto-mld")))]
+            IpAddress::Ipv6(_) => Err(MulticastError::Ipv6NotSupported),
+        }
+    }
+
+    /// Remove an address from the subscribed multicast IP addresses.
+    ///
+    /// Returns `Ok(leave_sent)` if the address was removed successfully, where `leave_sent`
+    /// indicates whether an immediate leave packet has been sent.
+    pub fn leave_multicast_group<D, T: Into<IpAddress>>(
+        &mut self,
+        device: &mut D,
+        addr: T,
+        timestamp: Instant,
+    ) -> Result<bool, MulticastError>
+    where
+        D: Device + ?Sized,
+    {
+        self.inner.now = timestamp;
+
+        match addr.into() {
+            #[cfg(feature = "proto-igmp")]
+            IpAddress::Ipv4(addr) => self.join_ipv4_multicast_group(
+                device,
+                addr,
+                MulticastFilterMode::Include,
+                Vec::new(),
+            ),
+            #[cfg(feature = "proto-mld")]
+            IpAddress::Ipv6(addr) => self.join_ipv6_multicast_group(
+                device,
+                addr,
+                MulticastFilterMode::Include,
+                Vec::new(),
+            ),
+            #[cfg(all(feature = "proto-ipv4", not(feature = "proto-igmp")))]
+            IpAddress::Ipv4(_) => Err(MulticastError::Ipv4NotSupported),
+            #[cfg(all(feature = "proto-ipv6", not(feature = "proto-mld")))]
+            IpAddress::Ipv6(_) => Err(MulticastError::Ipv6NotSupported),
+        }
+    }
+
+    /// Check whether the interface listens to given destination multicast IP address.
+    pub fn has_multicast_group<T: Into<IpAddress>>(&self, addr: T) -> bool {
+        self.inner.has_multicast_group(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(list: &[u8]) -> MulticastSources<u8> {
+        list.iter().copied().collect()
+    }
+
+    #[test]
+    fn filter() {
+        let mut group = MulticastGroup::new();
+        assert!(!group.is_member());
+        assert!(!group.accepts(&1));
+
+        assert!(group.set_filter(MulticastFilterMode::Include, sources(&[1, 2])));
+        assert!(group.is_member());
+        assert!(group.accepts(&1));
+        assert!(!group.accepts(&3));
+        assert!(!group.set_filter(MulticastFilterMode::Include, sources(&[2, 1])));
+
+        assert!(group.set_filter(MulticastFilterMode::Exclude, sources(&[1])));
+        assert!(!group.accepts(&1));
+        assert!(group.accepts(&3));
+
+        assert!(group.set_filter(MulticastFilterMode::Include, sources(&[])));
+        assert!(!group.is_member());
+    }
+
+    #[test]
+    fn current_state() {
+        let mut group = MulticastGroup::new();
+        assert_eq!(group.current_state(&[]), None);
+
+        group.set_filter(MulticastFilterMode::Include, sources(&[1, 2]));
+        assert_eq!(
+            group.current_state(&[]),
+            Some((MulticastFilterMode::Include, sources(&[1, 2])))
+        );
+        assert_eq!(
+            group.current_state(&[2, 3]),
+            Some((MulticastFilterMode::Include, sources(&[2])))
+        );
+        assert_eq!(group.current_state(&[3]), None);
+
+        group.set_filter(MulticastFilterMode::Exclude, sources(&[1]));
+        assert_eq!(
+            group.current_state(&[]),
+            Some((MulticastFilterMode::Exclude, sources(&[1])))
+        );
+        assert_eq!(
+            group.current_state(&[1, 3]),
+            Some((MulticastFilterMode::Include, sources(&[3])))
+        );
+    }
+
+    #[test]
+    fn query_response() {
+        let mut group = MulticastGroup::<u8>::new();
+        group.schedule_query_response(Instant::from_millis(100), [1, 2].into_iter());
+        group.schedule_query_response(Instant::from_millis(50), [2, 3].into_iter());
+        assert_eq!(
+            group.query_response,
+            Some((Instant::from_millis(50), sources(&[1, 2, 3])))
+        );
+
+        // A query about the whole group supersedes queries about sources.
+        group.schedule_query_response(Instant::from_millis(200), [].into_iter());
+        assert_eq!(
+            group.query_response,
+            Some((Instant::from_millis(50), sources(&[])))
+        );
+        group.schedule_query_response(Instant::from_millis(10), [4].into_iter());
+        assert_eq!(
+            group.query_response,
+            Some((Instant::from_millis(10), sources(&[])))
+        );
+
+        // Too many queried sources make it a response about the whole group.
+        group.query_response = None;
+        group.schedule_query_response(Instant::from_millis(10), 0..=255);
+        assert_eq!(
+            group.query_response,
+            Some((Instant::from_millis(10), sources(&[])))
+        );
+    }
+}
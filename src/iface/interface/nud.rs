@@ -0,0 +1,337 @@
+use super::*;
+
+#[cfg(feature = "medium-ethernet")]
+use crate::iface::neighbor::PendingPacket;
+use crate::iface::neighbor::{Neighbor, NeighborCacheFull};
+
+impl Interface {
+    /// Return the neighbors of the neighbor cache, with their state at the last poll.
+    pub fn neighbors(&self) -> impl Iterator<Item = (IpAddress, Neighbor)> + '_ {
+        self.inner.neighbor_cache.iter(self.inner.now)
+    }
+
+    /// Add a static neighbor, replacing any entry for `protocol_addr`.
+    ///
+    /// A static neighbor never expires and is never evicted, and its hardware address is not
+    /// updated by ARP or NDISC. It is only removed with [`remove_neighbor`](Self::remove_neighbor).
+    pub fn add_static_neighbor(
+        &mut self,
+        protocol_addr: IpAddress,
+        hardware_addr: HardwareAddress,
+    ) -> Result<(), NeighborCacheFull> {
+        self.inner
+            .neighbor_cache
+            .fill_static(protocol_addr, hardware_addr)
+    }
+
+    /// Remove a neighbor, static or not, from the neighbor cache. The packets waiting for its
+    /// address resolution are dropped.
+    pub fn remove_neighbor(&mut self, protocol_addr: &IpAddress) -> Option<Neighbor> {
+        self.inner.neighbor_cache.remove(protocol_addr)
+    }
+
+    /// Remove all the neighbors from the neighbor cache, except the static ones.
+    pub fn flush_neighbors(&mut self) {
+        self.inner.neighbor_cache.flush()
+    }
+
+    /// Send the unicast probes of Neighbor Unreachability Detection that are due, and evict the
+    /// neighbors which did not answer them, see RFC 4861 § 7.3.3.
+    ///
+    /// The packets queued for a neighbor are sent when its address resolution completes, and
+    /// discarded with an ICMP error when it fails.
+    pub(super) fn neighbor_egress<D>(
+        &mut self,
+        device: &mut D,
+        #[allow(unused_variables)] sockets: &mut SocketSet,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        self.inner.neighbor_cache.expire(now);
+
+        let mut did_something = false;
+
+        #[cfg(feature = "medium-ethernet")]
+        while let Some((neighbor, queued_src_addr)) = self.inner.neighbor_cache.solicit_due(now) {
+            let Some(tx_token) = device.transmit(now) else {
+                return did_something;
+            };
+
+            // The request is sent from the source of the queued packet if it is one of our
+            // addresses, see RFC 4861 § 7.2.2.
+            let src_addr = if self.inner.has_ip_addr(queued_src_addr) {
+                Some(queued_src_addr)
+            } else {
+                self.inner.get_source_address(&neighbor)
+            };
+            let Some(src_addr) = src_addr else {
+                // The request cannot be sent, the attempt counts towards the resolution failure.
+                self.inner.neighbor_cache.limit_rate(&neighbor, now);
+                continue;
+            };
+
+            // A request which was sent limits the rate of the next one, so the neighbor is no
+            // longer due a request. Otherwise, it is retried at the next poll.
+            if let Err(e) =
+                self.inner
+                    .lookup_neighbor(tx_token, &src_addr, &neighbor, &mut self.fragmenter)
+            {
+                if self.inner.neighbor_cache.solicit_due(now) == Some((neighbor, queued_src_addr)) {
+                    net_debug!("Failed to dispatch request for {}: {:?}", neighbor, e);
+                    break;
+                }
+            }
+            did_something = true;
+        }
+
+        #[cfg(feature = "medium-ethernet")]
+        while let Some((neighbor, hardware_addr)) = self.inner.neighbor_cache.pending_due() {
+            let Some(tx_token) = device.transmit(now) else {
+                return did_something;
+            };
+            // NOTE(unwrap): the neighbor has queued packets.
+            let pending = self.inner.neighbor_cache.dequeue(&neighbor).unwrap();
+
+            match hardware_addr {
+                Some(hardware_addr) => {
+                    self.inner
+                        .dispatch_pending(tx_token, hardware_addr, &pending)
+                }
+                None => self.inner.discard_pending(
+                    tx_token,
+                    sockets,
+                    &neighbor,
+                    &pending,
+                    &mut self.fragmenter,
+                ),
+            }
+            did_something = true;
+        }
+
+        while let Some((neighbor, hardware_addr)) = self.inner.neighbor_cache.probe_due(now) {
+            let Some(tx_token) = device.transmit(now) else {
+                break;
+            };
+            self.inner.neighbor_cache.probe_sent(&neighbor, now);
+
+            net_debug!("probing neighbor {} at {}", neighbor, hardware_addr);
+            match neighbor {
+                #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+                IpAddress::Ipv4(neighbor) if self.inner.caps.medium == Medium::Ethernet => {
+                    let Some(src_addr) = self.inner.get_source_address_ipv4(&neighbor) else {
+                        continue;
+                    };
+
+                    // The probe is unicast, RFC 1122 § 2.3.2.1.
+                    let target_hardware_addr = hardware_addr.ethernet_or_panic();
+                    let arp_repr = ArpRepr::EthernetIpv4 {
+                        operation: ArpOperation::Request,
+                        source_hardware_addr: self.inner.hardware_addr.ethernet_or_panic(),
+                        source_protocol_addr: src_addr,
+                        target_hardware_addr,
+                        target_protocol_addr: neighbor,
+                    };
+
+                    if let Err(e) = self.inner.dispatch_ethernet(
+                        tx_token,
+                        arp_repr.buffer_len(),
+                        |mut frame| {
+                            frame.set_dst_addr(target_hardware_addr);
+                            frame.set_ethertype(EthernetProtocol::Arp);
+
+                            arp_repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()))
+                        },
+                    ) {
+                        net_debug!("Failed to dispatch ARP probe: {:?}", e);
+                    }
+                }
+                #[cfg(feature = "proto-ipv6")]
+                IpAddress::Ipv6(neighbor) => {
+                    let src_addr = self.inner.get_source_address_ipv6(&neighbor);
+
+                    #[cfg(feature = "proto-ipv6-dad")]
+                    let lladdr = (!self.inner.dad_is_probing(&src_addr))
+                        .then(|| self.inner.hardware_addr.into());
+                    #[cfg(not(feature = "proto-ipv6-dad"))]
+                    let lladdr = Some(self.inner.hardware_addr.into());
+
+                    // The probe is sent to the cached hardware address of the neighbor, which is
+                    // still found in the cache.
+                    let solicit = Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+                        target_addr: neighbor,
+                        lladdr,
+                    });
+                    let packet = Packet::new_ipv6(
+                        Ipv6Repr {
+                            src_addr,
+                            dst_addr: neighbor,
+                            next_header: IpProtocol::Icmpv6,
+                            payload_len: solicit.buffer_len(),
+                            hop_limit: 0xff,
+                        },
+                        IpPayload::Icmpv6(solicit),
+                    );
+
+                    if let Err(e) = self.inner.dispatch_ip(
+                        tx_token,
+                        PacketMeta::default(),
+                        packet,
+                        &mut self.fragmenter,
+                    ) {
+                        net_debug!("Failed to dispatch NDISC probe: {:?}", e);
+                    }
+                }
+                #[allow(unreachable_patterns)]
+                _ => continue,
+            }
+
+            did_something = true;
+        }
+
+        did_something
+    }
+}
+
+impl InterfaceInner {
+    /// Return the time at which the next unicast probe is due, a neighbor is evicted, or queued
+    /// packets are sent.
+    pub(super) fn neighbor_poll_at(&self) -> Option<Instant> {
+        self.neighbor_cache.poll_at()
+    }
+
+    /// Return the next hop a packet is queued for while its address is being resolved, see
+    /// RFC 1122 § 2.3.2.2 and RFC 4861 § 7.2.2, or `None` if the packet cannot be queued.
+    #[cfg(feature = "medium-ethernet")]
+    pub(super) fn neighbor_pending_hop(
+        &self,
+        #[allow(unused_variables)] packet: &Packet,
+        ip_repr: &IpRepr,
+    ) -> Option<IpAddress> {
+        // The fragments of a packet are not queued.
+        if ip_repr.buffer_len() > self.caps.ip_mtu() {
+            return None;
+        }
+
+        let dst_addr = ip_repr.dst_addr();
+        #[cfg(feature = "proto-rpl")]
+        let next_hop = match packet {
+            Packet::Ipv6(PacketV6 {
+                routing: Some(_), ..
+            }) if dst_addr.is_unicast() => Some(dst_addr),
+            _ => self.route(&dst_addr, self.now),
+        };
+        #[cfg(not(feature = "proto-rpl"))]
+        let next_hop = self.route(&dst_addr, self.now);
+
+        next_hop
+    }
+
+    /// Send a queued packet to its next hop, whose hardware address was resolved.
+    #[cfg(feature = "medium-ethernet")]
+    fn dispatch_pending<Tx>(
+        &mut self,
+        mut tx_token: Tx,
+        hardware_addr: HardwareAddress,
+        pending: &PendingPacket,
+    ) where
+        Tx: TxToken,
+    {
+        tx_token.set_meta(pending.meta);
+        let result = self.dispatch_ethernet(tx_token, pending.packet.len(), |mut frame| {
+            frame.set_dst_addr(hardware_addr.ethernet_or_panic());
+            match pending.src_addr {
+                #[cfg(feature = "proto-ipv4")]
+                IpAddress::Ipv4(_) => frame.set_ethertype(EthernetProtocol::Ipv4),
+                #[cfg(feature = "proto-ipv6")]
+                IpAddress::Ipv6(_) => frame.set_ethertype(EthernetProtocol::Ipv6),
+            }
+            frame.payload_mut().copy_from_slice(&pending.packet);
+        });
+
+        if let Err(e) = result {
+            net_debug!("Failed to dispatch queued packet: {:?}", e);
+        }
+    }
+
+    /// Discard a queued packet whose next hop could not be resolved, and report it to its source
+    /// with an ICMP Destination Unreachable error, see RFC 1812 § 4.3.3.1 and RFC 4861 § 7.2.2.
+    /// The errors about the packets of the interface are delivered to its sockets.
+    #[cfg(feature = "medium-ethernet")]
+    fn discard_pending<Tx>(
+        &mut self,
+        tx_token: Tx,
+        sockets: &mut SocketSet,
+        neighbor: &IpAddress,
+        pending: &PendingPacket,
+        fragmenter: &mut Fragmenter,
+    ) where
+        Tx: TxToken,
+    {
+        net_debug!("dropped queued packet, {} is unreachable", neighbor);
+
+        let error = match pending.src_addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(_) => {
+                let packet = Ipv4Packet::new_unchecked(&pending.packet[..]);
+                let Ok(ipv4_repr) = Ipv4Repr::parse(&packet, &self.caps.checksum) else {
+                    return;
+                };
+                self.icmpv4_forward_error(ipv4_repr, packet.payload(), |header, data| {
+                    Icmpv4Repr::DstUnreachable {
+                        reason: Icmpv4DstUnreachable::HostUnreachable,
+                        header,
+                        data,
+                    }
+                })
+            }
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(_) => {
+                let packet = Ipv6Packet::new_unchecked(&pending.packet[..]);
+                let Ok(ipv6_repr) = Ipv6Repr::parse(&packet) else {
+                    return;
+                };
+                self.icmpv6_forward_error(ipv6_repr, packet.payload(), |header, data| {
+                    Icmpv6Repr::DstUnreachable {
+                        reason: Icmpv6DstUnreachable::AddrUnreachable,
+                        header,
+                        data,
+                    }
+                })
+            }
+        };
+
+        let Some(error) = error else {
+            return;
+        };
+        if self.has_ip_addr(pending.src_addr) {
+            self.process_local_error(sockets, &error);
+        } else if let Err(e) = self.dispatch_ip(tx_token, PacketMeta::default(), error, fragmenter)
+        {
+            net_debug!("Failed to dispatch ICMP error: {:?}", e);
+        }
+    }
+
+    /// Process an ICMP error about a packet of the interface as if it was received, so that it
+    /// reaches the sockets.
+    #[cfg(feature = "medium-ethernet")]
+    fn process_local_error(&mut self, sockets: &mut SocketSet, error: &Packet) {
+        match error.ip_repr() {
+            #[cfg(feature = "proto-ipv4")]
+            IpRepr::Ipv4(ipv4_repr) => {
+                let mut buffer = [0; IPV4_MIN_MTU];
+                let payload = &mut buffer[..ipv4_repr.payload_len];
+                error.emit_payload(&ipv4_repr.into(), payload, &self.caps);
+                self.process_icmpv4(sockets, ipv4_repr, payload);
+            }
+            #[cfg(feature = "proto-ipv6")]
+            IpRepr::Ipv6(ipv6_repr) => {
+                let mut buffer = [0; IPV6_MIN_MTU];
+                let payload = &mut buffer[..ipv6_repr.payload_len];
+                error.emit_payload(&ipv6_repr.into(), payload, &self.caps);
+                self.process_icmpv6(sockets, ipv6_repr, payload);
+            }
+        }
+    }
+}
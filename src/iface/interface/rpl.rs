@@ -0,0 +1,780 @@
+use super::*;
+
+use crate::config::RPL_RELATIONS_BUFFER_COUNT;
+use crate::iface::rpl::*;
+
+/// Space for the DODAG Configuration option and the Prefix Information option of a DIO.
+const DIO_OPTIONS_LEN: usize = (2 + 14) + (2 + 30);
+/// Space for a RPL Target option per address of the interface and per route of the sub-DODAG,
+/// and a Transit Information option with a parent address.
+const DAO_OPTIONS_LEN: usize =
+    (IFACE_MAX_ADDR_COUNT + RPL_RELATIONS_BUFFER_COUNT) * (2 + 2 + 16) + (2 + 4 + 16);
+
+/// Space for the addresses of a RPL Source Route Header, with padding.
+pub(super) const SOURCE_ROUTE_BUFFER_LEN: usize = RPL_RELATIONS_BUFFER_COUNT * 16 + 8;
+
+/// DAO-ACK status for an accepted DAO, see RFC 6550 § 6.5.1.
+const DAO_ACK_STATUS_ACCEPTED: u8 = 0;
+/// DAO-ACK status for a DAO that could not be stored. Values from 128 are rejections.
+const DAO_ACK_STATUS_REJECTED: u8 = 128;
+
+impl Interface {
+    /// Send the RPL messages that are due: DIS messages while the node is not part of a DODAG,
+    /// DIO messages when the Trickle timer fires, and DAO messages to the preferred parent.
+    pub(super) fn rpl_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        let Some(rpl) = &mut self.inner.rpl else {
+            return false;
+        };
+
+        let mode_of_operation = rpl.mode_of_operation;
+        let Some(dodag) = &mut rpl.dodag else {
+            if now < rpl.dis_expiration {
+                return false;
+            }
+
+            rpl.dis_expiration = now + DIS_INTERVAL;
+            let dis = RplRepr::DodagInformationSolicitation { options: &[] };
+            return self.rpl_transmit(device, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES, dis);
+        };
+
+        dodag.relations.purge(now);
+
+        if let Some(dst_addr) = dodag.unicast_dio.take() {
+            return self.rpl_transmit_dio(device, dst_addr);
+        }
+
+        // Nodes that have no parent yet do not advertise the DODAG.
+        if dodag.dio_timer.poll(now, &mut self.inner.rand) && dodag.rank != Rank::INFINITE {
+            return self.rpl_transmit_dio(device, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES);
+        }
+
+        match dodag.dao_expiration {
+            Some(dao_expiration) if now >= dao_expiration => (),
+            _ => return false,
+        }
+
+        if dodag.parent.is_none()
+            || mode_of_operation == RplModeOfOperation::NoDownwardRoutesMaintained
+        {
+            dodag.dao_expiration = None;
+            return false;
+        }
+
+        dodag.dao_sequence.increment();
+        dodag.dao_sent(now);
+
+        let mut options = [0u8; DAO_OPTIONS_LEN];
+        let Some((dst_addr, dao)) = self.inner.rpl_dao(&mut options) else {
+            net_debug!("rpl: no address to advertise in a DAO");
+            return false;
+        };
+        self.rpl_transmit(device, dst_addr, dao)
+    }
+
+    fn rpl_transmit_dio<D>(&mut self, device: &mut D, dst_addr: Ipv6Address) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let mut options = [0u8; DIO_OPTIONS_LEN];
+        match self.inner.rpl_dio(&mut options) {
+            Some(dio) => self.rpl_transmit(device, dst_addr, dio),
+            None => false,
+        }
+    }
+
+    fn rpl_transmit<D>(&mut self, device: &mut D, dst_addr: Ipv6Address, repr: RplRepr) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        // Messages to the link use a link-local source address. A DAO in non-storing mode is
+        // sent to the root, from a global address.
+        let src_addr = self.inner.get_source_address_ipv6(&dst_addr);
+        let link_local = dst_addr.is_link_local() || dst_addr.is_multicast();
+        if src_addr.is_unspecified() || (link_local && !src_addr.is_link_local()) {
+            net_debug!("rpl: no source address to send {}", repr);
+            return false;
+        }
+
+        let Some(tx_token) = device.transmit(self.inner.now) else {
+            return false;
+        };
+
+        let icmp_repr = Icmpv6Repr::Rpl(repr);
+        let ip_repr = Ipv6Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+        };
+
+        if let Err(e) = self.inner.dispatch_ip(
+            tx_token,
+            PacketMeta::default(),
+            Packet::new_ipv6(ip_repr, IpPayload::Icmpv6(icmp_repr)),
+            &mut self.fragmenter,
+        ) {
+            net_debug!("rpl: failed to transmit to {}: {:?}", dst_addr, e);
+        }
+
+        true
+    }
+}
+
+impl InterfaceInner {
+    pub(super) fn process_rpl<'frame>(
+        &mut self,
+        ip_repr: Ipv6Repr,
+        repr: RplRepr<'frame>,
+    ) -> Option<Packet<'frame>> {
+        if self.rpl.is_none() {
+            net_trace!("rpl: not enabled, ignoring {}", repr);
+            return None;
+        }
+
+        match repr {
+            RplRepr::DodagInformationSolicitation { options } => {
+                self.process_rpl_dis(ip_repr, options)
+            }
+            RplRepr::DodagInformationObject { .. } => self.process_rpl_dio(ip_repr, repr),
+            RplRepr::DestinationAdvertisementObject { .. } => self.process_rpl_dao(ip_repr, repr),
+            RplRepr::DestinationAdvertisementObjectAck { .. } => {
+                self.process_rpl_dao_ack(ip_repr, repr)
+            }
+        }
+    }
+
+    fn process_rpl_dis<'frame>(
+        &mut self,
+        ip_repr: Ipv6Repr,
+        options: &[u8],
+    ) -> Option<Packet<'frame>> {
+        let dodag = self.rpl.as_mut()?.dodag.as_mut()?;
+
+        // Nodes that have no parent yet do not advertise the DODAG.
+        if dodag.rank == Rank::INFINITE {
+            return None;
+        }
+
+        for option in RplOptionsIterator::new(options) {
+            match option {
+                Ok(RplOptionRepr::SolicitedInformation {
+                    rpl_instance_id,
+                    version_predicate,
+                    instance_id_predicate,
+                    dodag_id_predicate,
+                    dodag_id,
+                    version_number,
+                }) => {
+                    if (instance_id_predicate && rpl_instance_id != dodag.instance_id)
+                        || (dodag_id_predicate && dodag_id != dodag.id)
+                        || (version_predicate
+                            && SequenceCounter::new(version_number) != dodag.version_number)
+                    {
+                        net_trace!("rpl: DIS does not solicit our DODAG");
+                        return None;
+                    }
+                }
+                Ok(_) => (),
+                Err(_) => {
+                    net_trace!("rpl: malformed DIS option");
+                    return None;
+                }
+            }
+        }
+
+        if ip_repr.dst_addr.is_multicast() {
+            dodag.dio_timer.hear_inconsistency(self.now, &mut self.rand);
+        } else {
+            dodag.unicast_dio = Some(ip_repr.src_addr);
+        }
+
+        None
+    }
+
+    fn process_rpl_dio<'frame>(
+        &mut self,
+        ip_repr: Ipv6Repr,
+        repr: RplRepr<'frame>,
+    ) -> Option<Packet<'frame>> {
+        let RplRepr::DodagInformationObject {
+            rpl_instance_id,
+            version_number,
+            rank,
+            grounded,
+            mode_of_operation,
+            dodag_preference,
+            dtsn,
+            dodag_id,
+            options,
+        } = repr
+        else {
+            unreachable!()
+        };
+
+        let now = self.now;
+        let rpl = self.rpl.as_mut()?;
+        let src_addr = ip_repr.src_addr;
+
+        if !src_addr.is_link_local() {
+            net_trace!("rpl: ignoring DIO from non link-local address {}", src_addr);
+            return None;
+        }
+
+        let mut configuration = None;
+        let mut global_address = None;
+        for option in RplOptionsIterator::new(options) {
+            match option {
+                Ok(RplOptionRepr::DodagConfiguration {
+                    dio_interval_doublings,
+                    dio_interval_min,
+                    dio_redundancy_constant,
+                    max_rank_increase,
+                    minimum_hop_rank_increase,
+                    objective_code_point,
+                    default_lifetime,
+                    lifetime_unit,
+                    ..
+                }) => {
+                    configuration = Some(DodagConfiguration {
+                        dio_interval_doublings,
+                        dio_interval_min,
+                        dio_redundancy_constant,
+                        max_rank_increase,
+                        min_hop_rank_increase: minimum_hop_rank_increase,
+                        objective_code_point,
+                        default_lifetime,
+                        lifetime_unit,
+                    })
+                }
+                // The sender advertises one of its addresses, used as DAO parent in non-storing
+                // mode.
+                Ok(RplOptionRepr::PrefixInformation {
+                    router_address: true,
+                    destination_prefix,
+                    ..
+                }) if destination_prefix.len() == 16 => {
+                    global_address = Some(Ipv6Address::from_bytes(destination_prefix));
+                }
+                Ok(_) => (),
+                Err(_) => {
+                    net_trace!("rpl: malformed DIO option");
+                    break;
+                }
+            }
+        }
+
+        let version_number = SequenceCounter::new(version_number);
+        let mode = rpl.mode_of_operation;
+
+        if rpl.is_root {
+            let dodag = rpl.dodag.as_mut()?;
+            if rpl_instance_id == dodag.instance_id && dodag_id == dodag.id {
+                if version_number == dodag.version_number && rank != Rank::INFINITE.raw_value() {
+                    dodag.dio_timer.hear_consistent();
+                } else {
+                    dodag.dio_timer.hear_inconsistency(now, &mut self.rand);
+                }
+            }
+            return None;
+        }
+
+        if mode_of_operation != mode {
+            net_trace!(
+                "rpl: ignoring DIO with mode of operation {:?}",
+                mode_of_operation
+            );
+            return None;
+        }
+
+        let mut consistent = true;
+        let dodag = match &mut rpl.dodag {
+            Some(dodag) if dodag.instance_id == rpl_instance_id && dodag.id == dodag_id => {
+                if version_number > dodag.version_number {
+                    // Global repair: the root started a new version of the DODAG, so the parent
+                    // set is built again.
+                    net_debug!("rpl: new DODAG version {}", version_number.value());
+                    dodag.version_number = version_number;
+                    dodag.parent_set.clear();
+                    dodag.parent = None;
+                    dodag.rank = Rank::INFINITE;
+                    consistent = false;
+                } else if version_number < dodag.version_number {
+                    dodag.dio_timer.hear_inconsistency(now, &mut self.rand);
+                    return None;
+                }
+                dodag
+            }
+            Some(_) => {
+                net_trace!("rpl: ignoring DIO of DODAG {}", dodag_id);
+                return None;
+            }
+            None => {
+                if rank == Rank::INFINITE.raw_value() {
+                    return None;
+                }
+
+                // The DODAG configuration is needed to interpret the Rank of the nodes.
+                let Some(configuration) = configuration.filter(|c| c.is_supported()) else {
+                    net_debug!(
+                        "rpl: not joining DODAG {}, unsupported configuration",
+                        dodag_id
+                    );
+                    return None;
+                };
+
+                rpl.join(
+                    rpl_instance_id,
+                    dodag_id,
+                    version_number,
+                    configuration,
+                    now,
+                    &mut self.rand,
+                )
+            }
+        };
+
+        let old_parent = dodag.parent;
+        let old_rank = dodag.rank;
+        let parent_rank = Rank::new(rank, dodag.configuration.min_hop_rank_increase);
+
+        if rank == Rank::INFINITE.raw_value() {
+            // The sender left the DODAG.
+            dodag.parent_set.remove(&src_addr);
+        } else if parent_rank < dodag.rank || dodag.parent == Some(src_addr) {
+            let mut parent = Parent::new(dodag_preference, parent_rank, version_number, dodag_id);
+            parent.set_global_address(global_address);
+            dodag.parent_set.add(src_addr, parent);
+        } else {
+            // Routing through a node with an equal or a greater Rank may cause a loop.
+            dodag.parent_set.remove(&src_addr);
+        }
+
+        if !dodag.select_parent(mode, now, &mut self.rand) {
+            rpl.leave(now);
+            return None;
+        }
+
+        if dodag.parent == Some(src_addr) {
+            let dtsn = SequenceCounter::new(dtsn);
+            if old_parent == dodag.parent
+                && dtsn > dodag.parent_dtsn
+                && mode != RplModeOfOperation::NoDownwardRoutesMaintained
+            {
+                // The parent asks for our routes.
+                dodag.schedule_dao(now, &mut self.rand);
+            }
+            dodag.parent_dtsn = dtsn;
+            dodag.grounded = grounded;
+            dodag.preference = dodag_preference;
+        }
+
+        if consistent && old_parent == dodag.parent && old_rank == dodag.rank {
+            dodag.dio_timer.hear_consistent();
+        }
+
+        None
+    }
+
+    fn process_rpl_dao<'frame>(
+        &mut self,
+        ip_repr: Ipv6Repr,
+        repr: RplRepr<'frame>,
+    ) -> Option<Packet<'frame>> {
+        let RplRepr::DestinationAdvertisementObject {
+            rpl_instance_id,
+            expect_ack,
+            sequence,
+            dodag_id,
+            options,
+        } = repr
+        else {
+            unreachable!()
+        };
+
+        let now = self.now;
+        let rpl = self.rpl.as_mut()?;
+        let mode = rpl.mode_of_operation;
+        let is_root = rpl.is_root;
+        let dodag = rpl.dodag.as_mut()?;
+
+        if rpl_instance_id != dodag.instance_id || dodag_id.map_or(false, |id| id != dodag.id) {
+            net_trace!("rpl: ignoring DAO for another DODAG");
+            return None;
+        }
+
+        let storing = is_storing(mode);
+        if !storing && (mode == RplModeOfOperation::NoDownwardRoutesMaintained || !is_root) {
+            net_trace!("rpl: ignoring DAO, routes are not stored by this node");
+            return None;
+        }
+
+        // In storing mode, the sender of the DAO is the next hop towards the targets. In
+        // non-storing mode, the root stores the DAO parent of the targets, to build source routes.
+        let mut routes_changed = false;
+
+        let mut status = DAO_ACK_STATUS_ACCEPTED;
+        let mut targets = heapless::Vec::<Ipv6Address, RPL_RELATIONS_BUFFER_COUNT>::new();
+
+        for option in RplOptionsIterator::new(options) {
+            match option {
+                Ok(RplOptionRepr::RplTarget { prefix, .. }) => {
+                    if targets.push(prefix).is_err() {
+                        status = DAO_ACK_STATUS_REJECTED;
+                    }
+                }
+                // A Transit Information option applies to the targets preceding it.
+                Ok(RplOptionRepr::TransitInformation {
+                    path_lifetime,
+                    parent_address,
+                    ..
+                }) => {
+                    let lifetime = Duration::from_secs(
+                        path_lifetime as u64 * dodag.configuration.lifetime_unit as u64,
+                    );
+
+                    let next_hop = if storing {
+                        ip_repr.src_addr
+                    } else if let Some(parent_address) = parent_address {
+                        parent_address
+                    } else {
+                        net_debug!("rpl: DAO without parent address in non-storing mode");
+                        status = DAO_ACK_STATUS_REJECTED;
+                        targets.clear();
+                        continue;
+                    };
+
+                    for target in &targets {
+                        let old_next_hop = dodag.relations.find_next_hop(*target);
+
+                        if path_lifetime == 0 {
+                            net_trace!("rpl: removing route to {}", target);
+                            dodag.relations.remove_relation(*target);
+                            routes_changed |= old_next_hop.is_some();
+                        } else if dodag
+                            .relations
+                            .add_relation(*target, next_hop, now + lifetime)
+                        {
+                            net_trace!("rpl: route to {} via {}", target, next_hop);
+                            routes_changed |= old_next_hop != Some(next_hop);
+                        } else {
+                            status = DAO_ACK_STATUS_REJECTED;
+                        }
+                    }
+
+                    targets.clear();
+                }
+                Ok(_) => (),
+                Err(_) => {
+                    net_trace!("rpl: malformed DAO option");
+                    return None;
+                }
+            }
+        }
+
+        // In storing mode, the routes of our sub-DODAG are advertised to our own parent.
+        if routes_changed && storing && !is_root {
+            dodag.schedule_dao(now, &mut self.rand);
+        }
+
+        if !expect_ack {
+            return None;
+        }
+
+        self.icmpv6_reply(
+            ip_repr,
+            Icmpv6Repr::Rpl(RplRepr::DestinationAdvertisementObjectAck {
+                rpl_instance_id,
+                sequence,
+                status,
+                dodag_id,
+            }),
+        )
+    }
+
+    fn process_rpl_dao_ack<'frame>(
+        &mut self,
+        ip_repr: Ipv6Repr,
+        repr: RplRepr<'frame>,
+    ) -> Option<Packet<'frame>> {
+        let RplRepr::DestinationAdvertisementObjectAck {
+            rpl_instance_id,
+            sequence,
+            status,
+            ..
+        } = repr
+        else {
+            unreachable!()
+        };
+
+        let now = self.now;
+        let rpl = self.rpl.as_mut()?;
+        let mode = rpl.mode_of_operation;
+        let dodag = rpl.dodag.as_mut()?;
+
+        // Only the last DAO that was sent is acknowledged.
+        if rpl_instance_id != dodag.instance_id
+            || dodag.dao_retransmissions == 0
+            || sequence != dodag.dao_sequence.value()
+        {
+            net_trace!("rpl: ignoring unexpected DAO-ACK");
+            return None;
+        }
+
+        dodag.schedule_dao_refresh(now);
+
+        if status >= DAO_ACK_STATUS_REJECTED {
+            net_debug!(
+                "rpl: DAO rejected by {} with status {}",
+                ip_repr.src_addr,
+                status
+            );
+
+            // In non-storing mode, the DAO-ACK comes from the root.
+            if !is_storing(mode) {
+                return None;
+            }
+
+            // The parent cannot store our routes, try another one.
+            dodag.parent_set.remove(&ip_repr.src_addr);
+            if !dodag.select_parent(mode, now, &mut self.rand) {
+                rpl.leave(now);
+            }
+        }
+
+        None
+    }
+
+    /// Build a DIO advertising our DODAG. The DODAG Configuration option is written in `options`,
+    /// followed in non-storing mode by a Prefix Information option with our global address.
+    fn rpl_dio<'o>(&self, options: &'o mut [u8]) -> Option<RplRepr<'o>> {
+        let rpl = self.rpl.as_ref()?;
+        let dodag = rpl.dodag.as_ref()?;
+        let configuration = dodag.configuration;
+
+        let option = RplOptionRepr::DodagConfiguration {
+            authentication_enabled: false,
+            path_control_size: 0,
+            dio_interval_doublings: configuration.dio_interval_doublings,
+            dio_interval_min: configuration.dio_interval_min,
+            dio_redundancy_constant: configuration.dio_redundancy_constant,
+            max_rank_increase: configuration.max_rank_increase,
+            minimum_hop_rank_increase: configuration.min_hop_rank_increase,
+            objective_code_point: configuration.objective_code_point,
+            default_lifetime: configuration.default_lifetime,
+            lifetime_unit: configuration.lifetime_unit,
+        };
+        let mut len = option.buffer_len();
+        option.emit(&mut RplOptionPacket::new_unchecked(&mut options[..len]));
+
+        // The root is known by the DODAG ID in the routes built from DAO parents.
+        let global_address = if rpl.is_root {
+            Some(dodag.id)
+        } else {
+            self.rpl_global_addresses().next()
+        };
+
+        if rpl.mode_of_operation == RplModeOfOperation::NonStoringMode {
+            if let Some(address) = global_address {
+                let option = RplOptionRepr::PrefixInformation {
+                    prefix_length: 128,
+                    on_link: false,
+                    autonomous_address_configuration: false,
+                    router_address: true,
+                    valid_lifetime: u32::MAX,
+                    preferred_lifetime: u32::MAX,
+                    destination_prefix: address.as_bytes(),
+                };
+                let option_len = option.buffer_len();
+                option.emit(&mut RplOptionPacket::new_unchecked(
+                    &mut options[len..][..option_len],
+                ));
+                len += option_len;
+            }
+        }
+        let options: &'o [u8] = options;
+
+        Some(RplRepr::DodagInformationObject {
+            rpl_instance_id: dodag.instance_id,
+            version_number: dodag.version_number.value(),
+            rank: dodag.rank.raw_value(),
+            grounded: dodag.grounded,
+            mode_of_operation: rpl.mode_of_operation,
+            dodag_preference: dodag.preference,
+            dtsn: dodag.dtsn.value(),
+            dodag_id: dodag.id,
+            options: &options[..len],
+        })
+    }
+
+    /// Insert a RPL Source Route Header in a packet that a non-storing root sends to a node of its
+    /// DODAG that is not a neighbor. The destination of the packet becomes the first hop of the
+    /// route, and the addresses of the header are written in `buffer`.
+    pub(super) fn rpl_source_route<'p>(
+        &self,
+        packet: Packet<'p>,
+        buffer: &'p mut [u8],
+    ) -> Packet<'p> {
+        #[allow(unreachable_patterns)] // if only ipv6 is enabled
+        let mut packet = match packet {
+            Packet::Ipv6(packet) if packet.routing.is_none() => packet,
+            packet => return packet,
+        };
+
+        let route = match &self.rpl {
+            Some(rpl)
+                if rpl.is_root && rpl.mode_of_operation == RplModeOfOperation::NonStoringMode =>
+            {
+                rpl.source_route(&packet.header.dst_addr)
+            }
+            _ => None,
+        };
+        let Some((first_hop, hops)) = route.as_deref().and_then(|route| route.split_first()) else {
+            return Packet::Ipv6(packet);
+        };
+        if hops.is_empty() {
+            return Packet::Ipv6(packet);
+        }
+
+        // Elide the prefix that the addresses share with the first hop, which is the destination
+        // address of the packet.
+        let cmpr = hops
+            .iter()
+            .map(|address| {
+                address
+                    .as_bytes()
+                    .iter()
+                    .zip(first_hop.as_bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            })
+            .min()
+            .unwrap_or(0)
+            .min(15);
+
+        let mut len = 0;
+        for address in hops {
+            let address = &address.as_bytes()[cmpr..];
+            buffer[len..][..address.len()].copy_from_slice(address);
+            len += address.len();
+        }
+
+        // The header is padded to a multiple of 8 octets.
+        let pad = (8 - len % 8) % 8;
+        buffer[len..][..pad].fill(0);
+        len += pad;
+
+        packet.header.dst_addr = *first_hop;
+        packet.routing = Some(Ipv6RoutingRepr::Rpl {
+            segments_left: hops.len() as u8,
+            cmpr_i: cmpr as u8,
+            cmpr_e: cmpr as u8,
+            pad: pad as u8,
+            addresses: &buffer[..len],
+        });
+        Packet::Ipv6(packet)
+    }
+
+    /// Return the global unicast addresses of the interface.
+    fn rpl_global_addresses(&self) -> impl Iterator<Item = Ipv6Address> + '_ {
+        self.ip_addrs
+            .iter()
+            .filter_map(|cidr| match cidr.address() {
+                #[allow(unreachable_patterns)] // if only ipv6 is enabled
+                IpAddress::Ipv6(address)
+                    if address.is_unicast()
+                        && !address.is_link_local()
+                        && !address.is_loopback() =>
+                {
+                    Some(address)
+                }
+                _ => None,
+            })
+    }
+
+    /// Build a DAO and return it together with its destination. The options are written in
+    /// `options`.
+    ///
+    /// In storing mode, the DAO is sent to the preferred parent and advertises the addresses of
+    /// the interface and the routes of our sub-DODAG. In non-storing mode, the DAO is sent to the
+    /// root and advertises the addresses of the interface, with the global address of the
+    /// preferred parent.
+    fn rpl_dao<'o>(&self, options: &'o mut [u8]) -> Option<(Ipv6Address, RplRepr<'o>)> {
+        let rpl = self.rpl.as_ref()?;
+        let dodag = rpl.dodag.as_ref()?;
+        let parent = dodag.parent?;
+        let storing = is_storing(rpl.mode_of_operation);
+
+        let (dst_addr, parent_address) = if storing {
+            (parent, None)
+        } else {
+            let Some(parent_address) = dodag
+                .parent_set
+                .find(&parent)
+                .and_then(|parent| parent.global_address())
+            else {
+                net_debug!("rpl: preferred parent {} has no global address", parent);
+                return None;
+            };
+            (dodag.id, Some(parent_address))
+        };
+
+        let addresses = self.rpl_global_addresses();
+        let routes = dodag.relations.destinations().filter(|_| storing).copied();
+
+        let mut len = 0;
+        for address in addresses.chain(routes) {
+            let option = RplOptionRepr::RplTarget {
+                prefix_length: 128,
+                prefix: address,
+            };
+            let option_len = option.buffer_len();
+            option.emit(&mut RplOptionPacket::new_unchecked(
+                &mut options[len..][..option_len],
+            ));
+            len += option_len;
+        }
+
+        if len == 0 {
+            return None;
+        }
+
+        let option = RplOptionRepr::TransitInformation {
+            external: false,
+            path_control: 0,
+            path_sequence: dodag.dao_path_sequence.value(),
+            path_lifetime: dodag.configuration.default_lifetime,
+            parent_address,
+        };
+        let option_len = option.buffer_len();
+        option.emit(&mut RplOptionPacket::new_unchecked(
+            &mut options[len..][..option_len],
+        ));
+        len += option_len;
+        let options: &'o [u8] = options;
+
+        Some((
+            dst_addr,
+            RplRepr::DestinationAdvertisementObject {
+                rpl_instance_id: dodag.instance_id,
+                expect_ack: true,
+                sequence: dodag.dao_sequence.value(),
+                dodag_id: Some(dodag.id),
+                options: &options[..len],
+            },
+        ))
+    }
+}
+
+/// Return `true` when the parents store the routes of their sub-DODAG.
+fn is_storing(mode_of_operation: RplModeOfOperation) -> bool {
+    matches!(
+        mode_of_operation,
+        RplModeOfOperation::StoringModeWithoutMulticast
+            | RplModeOfOperation::StoringModeWithMulticast
+    )
+}
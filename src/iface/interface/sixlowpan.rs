@@ -99,7 +99,12 @@ impl InterfaceInner {
             }
         };
 
-        self.process_ipv6(sockets, meta, &check!(Ipv6Packet::new_checked(payload)))
+        self.process_ipv6(
+            sockets,
+            meta,
+            &check!(Ipv6Packet::new_checked(payload)),
+            None,
+        )
     }
 
     #[cfg(feature = "proto-sixlowpan-fragmentation")]
@@ -249,26 +254,18 @@ impl InterfaceInner {
                     }
                 },
                 SixlowpanNextHeader::Uncompressed(proto) => {
-                    // We have a 6LoWPAN uncompressed header.
-                    match proto {
-                        IpProtocol::Tcp | IpProtocol::Udp | IpProtocol::Icmpv6 => {
-                            // There can be no protocol after this one, so we can just copy the
-                            // rest of the data buffer. There is also no length field in the UDP
-                            // header that we need to correct as this header was not changed by the
-                            // 6LoWPAN compressor.
-                            if data.len() > buffer.len() {
-                                return Err(Error);
-                            }
-                            buffer[..data.len()].copy_from_slice(data);
-                            payload_len += data.len();
-                            decompressed_len += data.len();
-                            break;
-                        }
-                        proto => {
-                            net_debug!("Unsupported uncompressed next header: {:?}", proto);
-                            return Err(Error);
-                        }
+                    // We have a 6LoWPAN uncompressed header. Everything after this header is
+                    // carried inline, so we can just copy the rest of the data buffer. There is
+                    // also no length field in the UDP header that we need to correct as this
+                    // header was not changed by the 6LoWPAN compressor.
+                    net_trace!("6LoWPAN: uncompressed next header {}", proto);
+                    if data.len() > buffer.len() {
+                        return Err(Error);
                     }
+                    buffer[..data.len()].copy_from_slice(data);
+                    payload_len += data.len();
+                    decompressed_len += data.len();
+                    break;
                 }
             }
         }
@@ -329,7 +326,12 @@ impl InterfaceInner {
                     return;
                 }
 
-                let payload_length = packet.header.payload_len;
+                #[allow(unused_mut)]
+                let mut payload_length = packet.header.payload_len;
+                #[cfg(any(feature = "proto-ipv6-hbh", feature = "proto-ipv6-routing"))]
+                {
+                    payload_length += packet.ext_headers_len();
+                }
 
                 Self::ipv6_to_sixlowpan(
                     &self.checksum_caps(),
@@ -420,7 +422,7 @@ impl InterfaceInner {
         ieee_repr: &Ieee802154Repr,
         mut buffer: &mut [u8],
     ) {
-        let last_header = packet.payload.as_sixlowpan_next_header();
+        let last_header = packet.sixlowpan_next_header();
         let next_header = last_header;
 
         #[cfg(feature = "proto-ipv6-hbh")]
@@ -456,7 +458,7 @@ impl InterfaceInner {
 
         // Emit the Hop-by-Hop header
         #[cfg(feature = "proto-ipv6-hbh")]
-        if let Some(hbh) = packet.hop_by_hop {
+        if let Some(hbh) = &packet.hop_by_hop {
             #[allow(unused)]
             let next_header = last_header;
 
@@ -491,7 +493,7 @@ impl InterfaceInner {
         if let Some(routing) = &packet.routing {
             let ext_hdr = SixlowpanExtHeaderRepr {
                 ext_header_id: SixlowpanExtHeaderId::RoutingHeader,
-                next_header,
+                next_header: last_header,
                 length: routing.buffer_len() as u8,
             };
             ext_hdr.emit(&mut SixlowpanExtHeaderPacket::new_unchecked(
@@ -505,11 +507,14 @@ impl InterfaceInner {
             buffer = &mut buffer[routing.buffer_len()..];
         }
 
+        // The checksums of the upper layer use the final destination of the packet.
+        let upper_layer_header = packet.upper_layer_header();
+
         match &mut packet.payload {
             IpPayload::Icmpv6(icmp_repr) => {
                 icmp_repr.emit(
-                    &packet.header.src_addr,
-                    &packet.header.dst_addr,
+                    &upper_layer_header.src_addr,
+                    &upper_layer_header.dst_addr,
                     &mut Icmpv6Packet::new_unchecked(&mut buffer[..icmp_repr.buffer_len()]),
                     checksum_caps,
                 );
@@ -521,8 +526,8 @@ impl InterfaceInner {
                     &mut SixlowpanUdpNhcPacket::new_unchecked(
                         &mut buffer[..udp_repr.header_len() + payload.len()],
                     ),
-                    &iphc_repr.src_addr,
-                    &iphc_repr.dst_addr,
+                    &upper_layer_header.src_addr,
+                    &upper_layer_header.dst_addr,
                     payload.len(),
                     |buf| buf.copy_from_slice(payload),
                     checksum_caps,
@@ -532,13 +537,19 @@ impl InterfaceInner {
             IpPayload::Tcp(tcp_repr) => {
                 tcp_repr.emit(
                     &mut TcpPacket::new_unchecked(&mut buffer[..tcp_repr.buffer_len()]),
-                    &packet.header.src_addr.into(),
-                    &packet.header.dst_addr.into(),
+                    &upper_layer_header.src_addr.into(),
+                    &upper_layer_header.dst_addr.into(),
                     checksum_caps,
                 );
             }
-            #[cfg(feature = "socket-raw")]
-            IpPayload::Raw(_raw) => todo!(),
+            #[cfg(any(
+                feature = "socket-raw",
+                feature = "proto-rpl",
+                feature = "iface-forwarding"
+            ))]
+            IpPayload::Raw(raw) => {
+                buffer[..raw.len()].copy_from_slice(raw);
+            }
 
             #[allow(unreachable_patterns)]
             _ => unreachable!(),
@@ -554,7 +565,7 @@ impl InterfaceInner {
         packet: &PacketV6,
         ieee_repr: &Ieee802154Repr,
     ) -> (usize, usize, usize) {
-        let last_header = packet.payload.as_sixlowpan_next_header();
+        let last_header = packet.sixlowpan_next_header();
         let next_header = last_header;
 
         #[cfg(feature = "proto-ipv6-hbh")]
@@ -610,7 +621,7 @@ impl InterfaceInner {
 
             total_size += ext_hdr.buffer_len() + options_size;
             compressed_hdr_size += ext_hdr.buffer_len() + options_size;
-            uncompressed_hdr_size += hbh.buffer_len() + options_size;
+            uncompressed_hdr_size += 2 + options_size;
         }
 
         // Add the routing header to the sizes.
@@ -618,12 +629,12 @@ impl InterfaceInner {
         if let Some(routing) = &packet.routing {
             let ext_hdr = SixlowpanExtHeaderRepr {
                 ext_header_id: SixlowpanExtHeaderId::RoutingHeader,
-                next_header,
+                next_header: last_header,
                 length: routing.buffer_len() as u8,
             };
             total_size += ext_hdr.buffer_len() + routing.buffer_len();
             compressed_hdr_size += ext_hdr.buffer_len() + routing.buffer_len();
-            uncompressed_hdr_size += routing.buffer_len();
+            uncompressed_hdr_size += 2 + routing.buffer_len();
         }
 
         match packet.payload {
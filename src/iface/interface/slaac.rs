@@ -0,0 +1,575 @@
+use super::*;
+
+use crate::iface::Route;
+use crate::sha256::Sha256;
+use crate::wire::{NdiscPrefixInfoFlags, NdiscPrefixInformation, RawHardwareAddress};
+
+/// Maximum delay before the first Router Solicitation, see RFC 4861 § 10.
+const MAX_RTR_SOLICITATION_DELAY: Duration = Duration::from_secs(1);
+/// Interval between the Router Solicitations, see RFC 4861 § 10.
+const RTR_SOLICITATION_INTERVAL: Duration = Duration::from_secs(4);
+/// Number of Router Solicitations sent when no router answers, see RFC 4861 § 10.
+const MAX_RTR_SOLICITATIONS: u8 = 3;
+/// Unauthenticated advertisements can not shorten the valid lifetime of an address below two
+/// hours, see RFC 4862 § 5.5.3.
+const MIN_VALID_LIFETIME: Duration = Duration::from_secs(2 * 60 * 60);
+/// A lifetime of all ones is infinite, see RFC 4861 § 4.6.2.
+const INFINITE_LIFETIME: Duration = Duration::from_secs(0xffff_ffff);
+/// Number of stable interface identifiers tried for a prefix after a duplicate address was
+/// detected, see RFC 7217 § 6.
+#[cfg(feature = "proto-ipv6-dad")]
+const IDGEN_RETRIES: u8 = 3;
+
+/// How the interface identifier of the autoconfigured IPv6 addresses is generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlaacConfig {
+    /// Modified EUI-64 identifiers derived from the hardware address, see
+    /// [RFC 4291 Appendix A].
+    ///
+    /// `Medium::Ip` has no hardware address, no address is configured with it.
+    ///
+    /// [RFC 4291 Appendix A]: https://tools.ietf.org/html/rfc4291#appendix-A
+    Eui64,
+    /// Stable, semantically opaque identifiers, see [RFC 7217]. The identifier is different for
+    /// each prefix, and can not be guessed without the secret key.
+    ///
+    /// [RFC 7217]: https://tools.ietf.org/html/rfc7217
+    StablePrivacy { secret_key: [u8; 16] },
+}
+
+/// An IPv6 address configured from a prefix advertised by a router.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SlaacAddress {
+    pub cidr: Ipv6Cidr,
+    /// The address is deprecated after this instant. `None` means "forever".
+    pub preferred_until: Option<Instant>,
+    /// The address is removed after this instant. `None` means "forever".
+    pub valid_until: Option<Instant>,
+    /// The number of duplicate addresses detected in the prefix.
+    #[cfg(feature = "proto-ipv6-dad")]
+    pub(crate) dad_counter: u8,
+}
+
+#[derive(Debug)]
+pub(crate) struct Slaac {
+    config: SlaacConfig,
+    /// The number of Router Solicitations left to send, and when the next one is due.
+    solicitation: Option<(u8, Instant)>,
+    addresses: Vec<SlaacAddress, IFACE_MAX_ADDR_COUNT>,
+}
+
+impl Slaac {
+    pub(crate) fn new(config: SlaacConfig, now: Instant, rand: &mut Rand) -> Self {
+        let delay = Duration::from_millis(
+            rand.rand_u32() as u64 % (MAX_RTR_SOLICITATION_DELAY.total_millis() + 1),
+        );
+
+        Self {
+            config,
+            solicitation: Some((MAX_RTR_SOLICITATIONS, now + delay)),
+            addresses: Vec::new(),
+        }
+    }
+}
+
+/// Return the instant at which a lifetime of an advertised prefix ends.
+fn lifetime_end(now: Instant, lifetime: Duration) -> Option<Instant> {
+    (lifetime != INFINITE_LIFETIME).then(|| now + lifetime)
+}
+
+/// Return the modified EUI-64 interface identifier of a hardware address.
+fn eui64_interface_id(hardware_addr: HardwareAddress) -> Option<[u8; 8]> {
+    match hardware_addr {
+        #[cfg(feature = "medium-ethernet")]
+        HardwareAddress::Ethernet(addr) => {
+            let b = addr.as_bytes();
+            Some([b[0] ^ 0x02, b[1], b[2], 0xff, 0xfe, b[3], b[4], b[5]])
+        }
+        #[cfg(feature = "medium-ieee802154")]
+        HardwareAddress::Ieee802154(Ieee802154Address::Short(b)) => {
+            Some([0, 0, 0, 0xff, 0xfe, 0, b[0], b[1]])
+        }
+        #[cfg(feature = "medium-ieee802154")]
+        HardwareAddress::Ieee802154(addr) => addr.as_eui_64(),
+        #[cfg(feature = "medium-ip")]
+        HardwareAddress::Ip => None,
+    }
+}
+
+/// Return the address with the interface identifier `id` in the 64-bit `prefix`.
+fn slaac_address(prefix: &Ipv6Address, id: &[u8; 8]) -> Ipv6Address {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&prefix.as_bytes()[..8]);
+    bytes[8..].copy_from_slice(id);
+    Ipv6Address::from_bytes(&bytes)
+}
+
+/// Return whether an interface identifier is reserved, see RFC 5453.
+fn is_reserved_interface_id(id: &[u8; 8]) -> bool {
+    *id == [0; 8] || (id[..7] == [0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff] && id[7] >= 0x80)
+}
+
+/// Return the stable interface identifier of an address in `prefix`, see RFC 7217 § 5.
+fn stable_interface_id(
+    secret_key: &[u8; 16],
+    prefix: &Ipv6Address,
+    hardware_addr: HardwareAddress,
+    mut dad_counter: u8,
+) -> [u8; 8] {
+    let net_iface: RawHardwareAddress = match hardware_addr {
+        #[cfg(feature = "medium-ip")]
+        HardwareAddress::Ip => RawHardwareAddress::from_bytes(&[]),
+        #[allow(unreachable_patterns)]
+        addr => addr.into(),
+    };
+
+    loop {
+        let mut hash = Sha256::new();
+        hash.update(&prefix.as_bytes()[..8]);
+        hash.update(net_iface.as_bytes());
+        hash.update(&[dad_counter]);
+        hash.update(secret_key);
+
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&hash.finalize()[..8]);
+        if !is_reserved_interface_id(&id) {
+            return id;
+        }
+        dad_counter = dad_counter.wrapping_add(1);
+    }
+}
+
+impl Interface {
+    /// Return the IPv6 addresses configured from the prefixes advertised by routers.
+    pub fn slaac_addresses(&self) -> &[SlaacAddress] {
+        match &self.inner.slaac {
+            Some(slaac) => &slaac.addresses,
+            None => &[],
+        }
+    }
+
+    /// Remove the expired autoconfigured addresses, and send the Router Solicitations that
+    /// are due.
+    pub(super) fn slaac_egress<D>(&mut self, device: &mut D) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let now = self.inner.now;
+        let expired = self.inner.slaac_expire();
+
+        let Some((count, at)) = self
+            .inner
+            .slaac
+            .as_ref()
+            .and_then(|slaac| slaac.solicitation)
+        else {
+            return expired;
+        };
+        if now < at {
+            return expired;
+        }
+
+        self.inner.slaac_link_local();
+
+        // The source address is the link-local address, or the unspecified address without
+        // a Source Link-Layer Address option when there is none, see RFC 4861 § 6.3.7.
+        let src_addr = self.inner.link_local_ipv6_address();
+        #[cfg(feature = "proto-ipv6-dad")]
+        let src_addr = src_addr.filter(|addr| self.inner.dad_is_assigned(addr));
+        let src_addr = src_addr.unwrap_or(Ipv6Address::UNSPECIFIED);
+
+        // Solicitations from an optimistic address do not carry the link-layer address either,
+        // see RFC 4429 § 3.3.
+        #[cfg(feature = "proto-ipv6-dad")]
+        let optimistic = self.inner.dad_is_probing(&src_addr);
+        #[cfg(not(feature = "proto-ipv6-dad"))]
+        let optimistic = false;
+
+        let lladdr = match self.inner.caps.medium {
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip => None,
+            #[allow(unreachable_patterns)]
+            _ if src_addr.is_unspecified() || optimistic => None,
+            _ => Some(self.inner.hardware_addr.into()),
+        };
+
+        let Some(tx_token) = device.transmit(now) else {
+            return expired;
+        };
+        if let Some(slaac) = &mut self.inner.slaac {
+            slaac.solicitation = (count > 1).then(|| (count - 1, now + RTR_SOLICITATION_INTERVAL));
+        }
+
+        let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit { lladdr });
+        let ip_repr = Ipv6Repr {
+            src_addr,
+            dst_addr: Ipv6Address::LINK_LOCAL_ALL_ROUTERS,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 0xff,
+        };
+
+        // NOTE(unwrap): packet destination is multicast, which is always routable and doesn't require neighbor discovery.
+        self.inner
+            .dispatch_ip(
+                tx_token,
+                PacketMeta::default(),
+                Packet::new_ipv6(ip_repr, IpPayload::Icmpv6(icmp_repr)),
+                &mut self.fragmenter,
+            )
+            .unwrap();
+
+        true
+    }
+}
+
+impl InterfaceInner {
+    /// Return the time at which the next Router Solicitation is due or the next autoconfigured
+    /// address expires.
+    pub(super) fn slaac_poll_at(&self) -> Option<Instant> {
+        let slaac = self.slaac.as_ref()?;
+        slaac
+            .addresses
+            .iter()
+            .filter_map(|addr| addr.valid_until)
+            .chain(slaac.solicitation.map(|(_, at)| at))
+            .min()
+    }
+
+    /// Return the first link-local IPv6 address of the interface.
+    fn link_local_ipv6_address(&self) -> Option<Ipv6Address> {
+        self.ip_addrs.iter().find_map(|cidr| match cidr {
+            IpCidr::Ipv6(cidr) if cidr.address().is_link_local() => Some(cidr.address()),
+            _ => None,
+        })
+    }
+
+    /// Return the interface identifier of the addresses autoconfigured in `prefix`.
+    fn slaac_interface_id(&self, config: SlaacConfig, prefix: &Ipv6Address) -> Option<[u8; 8]> {
+        match config {
+            SlaacConfig::Eui64 => eui64_interface_id(self.hardware_addr),
+            SlaacConfig::StablePrivacy { secret_key } => Some(stable_interface_id(
+                &secret_key,
+                prefix,
+                self.hardware_addr,
+                0,
+            )),
+        }
+    }
+
+    /// Configure a link-local address, when the interface has none, see RFC 4862 § 5.3.
+    fn slaac_link_local(&mut self) {
+        let Some(slaac) = &self.slaac else {
+            return;
+        };
+        if self.link_local_ipv6_address().is_some() {
+            return;
+        }
+
+        let prefix = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0);
+        let Some(id) = self.slaac_interface_id(slaac.config, &prefix) else {
+            return;
+        };
+
+        let cidr = IpCidr::Ipv6(Ipv6Cidr::new(slaac_address(&prefix, &id), 64));
+        if self.ip_addrs.push(cidr).is_err() {
+            net_debug!("slaac: no space for the link-local address");
+            return;
+        }
+
+        #[cfg(feature = "proto-ipv6-dad")]
+        self.dad_update_addrs(true);
+    }
+
+    /// Remove the autoconfigured addresses whose valid lifetime ended. Return whether any
+    /// address was removed.
+    fn slaac_expire(&mut self) -> bool {
+        let now = self.now;
+        let Some(slaac) = &mut self.slaac else {
+            return false;
+        };
+
+        let mut expired = false;
+        while let Some(index) = slaac
+            .addresses
+            .iter()
+            .position(|addr| matches!(addr.valid_until, Some(t) if now >= t))
+        {
+            let addr = slaac.addresses.swap_remove(index);
+            net_debug!("slaac: address {} expired", addr.cidr);
+            self.ip_addrs
+                .retain(|cidr| *cidr != IpCidr::Ipv6(addr.cidr));
+            expired = true;
+        }
+
+        #[cfg(feature = "proto-ipv6-dad")]
+        if expired {
+            self.dad_update_addrs(false);
+        }
+
+        expired
+    }
+
+    /// Process a Router Advertisement: install the default route through the router, and
+    /// configure an address in the advertised prefix, see RFC 4862 § 5.5.3.
+    pub(super) fn process_router_advert<'frame>(
+        &mut self,
+        ip_repr: Ipv6Repr,
+        router_lifetime: Duration,
+        lladdr: Option<RawHardwareAddress>,
+        prefix_info: Option<NdiscPrefixInformation>,
+    ) -> Option<Packet<'frame>> {
+        // Router Advertisements come from the link-local address of the router, see
+        // RFC 4861 § 6.1.2.
+        if self.slaac.is_none() || !ip_repr.src_addr.is_link_local() {
+            return None;
+        }
+        let router = ip_repr.src_addr;
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+        match (self.caps.medium, lladdr) {
+            #[cfg(feature = "medium-ip")]
+            (Medium::Ip, _) => (),
+            (medium, Some(lladdr)) => {
+                let lladdr = check!(lladdr.parse(medium));
+                if lladdr.is_unicast() {
+                    self.neighbor_cache
+                        .fill_stale(router.into(), lladdr, self.now);
+                }
+            }
+            (_, None) => (),
+        }
+        #[cfg(not(any(feature = "medium-ethernet", feature = "medium-ieee802154")))]
+        let _ = lladdr;
+
+        if let Some(slaac) = &mut self.slaac {
+            slaac.solicitation = None;
+        }
+
+        let now = self.now;
+        let default = IpCidr::Ipv6(Ipv6Cidr::new(Ipv6Address::UNSPECIFIED, 0));
+        self.routes.update(|routes| {
+            // The expired default routes are removed to make room for the new ones.
+            routes.retain(|route| {
+                route.cidr != default
+                    || (route.via_router != IpAddress::Ipv6(router)
+                        && !matches!(route.expires_at, Some(t) if now >= t))
+            });
+
+            if router_lifetime != Duration::ZERO {
+                let expires_at = Some(now + router_lifetime);
+                let route = Route {
+                    cidr: default,
+                    via_router: router.into(),
+                    preferred_until: expires_at,
+                    expires_at,
+                };
+                if routes.push(route).is_err() {
+                    net_debug!("slaac: no space for the route via {}", router);
+                }
+            }
+        });
+
+        if let Some(prefix_info) = prefix_info {
+            self.slaac_process_prefix(prefix_info);
+        }
+
+        None
+    }
+
+    /// Configure or update the address in an advertised prefix, see RFC 4862 § 5.5.3.
+    fn slaac_process_prefix(&mut self, prefix_info: NdiscPrefixInformation) {
+        if !prefix_info.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+            || prefix_info.prefix.is_link_local()
+            || prefix_info.preferred_lifetime > prefix_info.valid_lifetime
+        {
+            return;
+        }
+
+        let now = self.now;
+        let prefix = Ipv6Cidr::new(prefix_info.prefix, prefix_info.prefix_len);
+        let preferred_until = lifetime_end(now, prefix_info.preferred_lifetime);
+        let valid_until = lifetime_end(now, prefix_info.valid_lifetime);
+
+        let Some(slaac) = &mut self.slaac else {
+            return;
+        };
+
+        if let Some(addr) = slaac.addresses.iter_mut().find(|addr| {
+            addr.cidr.prefix_len() == prefix.prefix_len()
+                && prefix.contains_addr(&addr.cidr.address())
+        }) {
+            addr.preferred_until = preferred_until;
+
+            // `None` is later than any instant.
+            let later = |a: Option<Instant>, b: Option<Instant>| match (a, b) {
+                (None, _) => b.is_some(),
+                (Some(_), None) => false,
+                (Some(a), Some(b)) => a > b,
+            };
+            let two_hours = Some(now + MIN_VALID_LIFETIME);
+            if later(valid_until, two_hours) || later(valid_until, addr.valid_until) {
+                addr.valid_until = valid_until;
+            } else if later(addr.valid_until, two_hours) {
+                addr.valid_until = two_hours;
+            }
+            return;
+        }
+
+        // Interface identifiers are 64 bits long, see RFC 4291 § 2.5.1.
+        if prefix_info.valid_lifetime == Duration::ZERO || prefix.prefix_len() != 64 {
+            return;
+        }
+
+        let config = slaac.config;
+        let Some(id) = self.slaac_interface_id(config, &prefix.address()) else {
+            net_debug!("slaac: no interface identifier for {}", prefix);
+            return;
+        };
+        let cidr = Ipv6Cidr::new(slaac_address(&prefix.address(), &id), 64);
+
+        if self.ip_addrs.contains(&IpCidr::Ipv6(cidr)) {
+            return;
+        }
+        if self.ip_addrs.push(IpCidr::Ipv6(cidr)).is_err() {
+            net_debug!("slaac: no space for the address {}", cidr);
+            return;
+        }
+        net_debug!("slaac: configured address {}", cidr);
+
+        // NOTE(unwrap): there are no more autoconfigured addresses than addresses.
+        let slaac = self.slaac.as_mut().unwrap();
+        slaac
+            .addresses
+            .push(SlaacAddress {
+                cidr,
+                preferred_until,
+                valid_until,
+                #[cfg(feature = "proto-ipv6-dad")]
+                dad_counter: 0,
+            })
+            .unwrap();
+
+        #[cfg(feature = "proto-ipv6-dad")]
+        self.dad_update_addrs(true);
+    }
+
+    /// Return whether the preferred lifetime of an autoconfigured address ended.
+    #[cfg(feature = "proto-ipv6-dad")]
+    pub(super) fn slaac_is_deprecated(&self, addr: &Ipv6Address) -> bool {
+        let now = self.now;
+        self.slaac.as_ref().map_or(false, |slaac| {
+            slaac.addresses.iter().any(|slaac_addr| {
+                slaac_addr.cidr.address() == *addr
+                    && matches!(slaac_addr.preferred_until, Some(t) if now >= t)
+            })
+        })
+    }
+
+    /// Replace a duplicate autoconfigured address with another stable address in its prefix,
+    /// or remove it, see RFC 4862 § 5.4.5 and RFC 7217 § 6.
+    #[cfg(feature = "proto-ipv6-dad")]
+    pub(super) fn slaac_dad_failed(&mut self, addr: Ipv6Address) {
+        let Some(slaac) = &mut self.slaac else {
+            return;
+        };
+        let Some(index) = slaac
+            .addresses
+            .iter()
+            .position(|slaac_addr| slaac_addr.cidr.address() == addr)
+        else {
+            return;
+        };
+
+        let duplicate = slaac.addresses[index];
+        self.ip_addrs
+            .retain(|cidr| *cidr != IpCidr::Ipv6(duplicate.cidr));
+
+        match slaac.config {
+            SlaacConfig::StablePrivacy { secret_key } if duplicate.dad_counter < IDGEN_RETRIES => {
+                let dad_counter = duplicate.dad_counter + 1;
+                let prefix = duplicate.cidr.address();
+                let id = stable_interface_id(&secret_key, &prefix, self.hardware_addr, dad_counter);
+                let cidr = Ipv6Cidr::new(slaac_address(&prefix, &id), 64);
+                net_debug!("slaac: replacing duplicate address {} with {}", addr, cidr);
+
+                slaac.addresses[index] = SlaacAddress {
+                    cidr,
+                    dad_counter,
+                    ..duplicate
+                };
+                // NOTE(unwrap): the duplicate address was removed.
+                self.ip_addrs.push(IpCidr::Ipv6(cidr)).unwrap();
+                self.dad_update_addrs(true);
+            }
+            _ => {
+                net_debug!("slaac: removing duplicate address {}", addr);
+                slaac.addresses.swap_remove(index);
+                self.dad_update_addrs(false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "medium-ethernet")]
+    fn eui64_ethernet() {
+        let addr = HardwareAddress::Ethernet(EthernetAddress([0x00, 0x1b, 0x21, 0x3c, 0x4d, 0x5e]));
+        assert_eq!(
+            eui64_interface_id(addr),
+            Some([0x02, 0x1b, 0x21, 0xff, 0xfe, 0x3c, 0x4d, 0x5e])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "medium-ieee802154")]
+    fn eui64_ieee802154() {
+        let addr = HardwareAddress::Ieee802154(Ieee802154Address::Extended([
+            0x00, 0x12, 0x4b, 0x00, 0x01, 0x02, 0x03, 0x04,
+        ]));
+        assert_eq!(
+            eui64_interface_id(addr),
+            Some([0x02, 0x12, 0x4b, 0x00, 0x01, 0x02, 0x03, 0x04])
+        );
+
+        let addr = HardwareAddress::Ieee802154(Ieee802154Address::Short([0xab, 0xcd]));
+        assert_eq!(
+            eui64_interface_id(addr),
+            Some([0, 0, 0, 0xff, 0xfe, 0, 0xab, 0xcd])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "medium-ethernet")]
+    fn stable_interface_ids() {
+        let addr = HardwareAddress::Ethernet(EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]));
+        let prefix = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0);
+        let other_prefix = Ipv6Address::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 0);
+        let key = [0x2a; 16];
+
+        let id = stable_interface_id(&key, &prefix, addr, 0);
+        assert_eq!(id, stable_interface_id(&key, &prefix, addr, 0));
+        assert_ne!(id, stable_interface_id(&key, &other_prefix, addr, 0));
+        assert_ne!(id, stable_interface_id(&[0x2b; 16], &prefix, addr, 0));
+        assert_ne!(id, stable_interface_id(&key, &prefix, addr, 1));
+        assert_ne!(Some(id), eui64_interface_id(addr));
+    }
+
+    #[test]
+    fn reserved_interface_ids() {
+        assert!(is_reserved_interface_id(&[0; 8]));
+        assert!(is_reserved_interface_id(&[
+            0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x80
+        ]));
+        assert!(!is_reserved_interface_id(&[
+            0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f
+        ]));
+        assert!(!is_reserved_interface_id(&[0, 0, 0, 0, 0, 0, 0, 1]));
+    }
+}
@@ -1,6 +1,108 @@
 use super::*;
 
-use crate::socket::tcp::Socket;
+use crate::sha256::Sha256;
+use crate::socket::tcp::{Socket, State, SynCookie};
+
+/// Interval after which the counter of the SYN cookies is incremented. A cookie is valid for
+/// two periods.
+const SYN_COOKIE_PERIOD: u64 = 64;
+/// The maximum segment sizes which can be encoded in a SYN cookie. The largest one not above
+/// the MSS of the remote is used.
+const SYN_COOKIE_MSS: [u16; 4] = [536, 1220, 1440, 1460];
+/// Encoded window scale of a remote not supporting window scaling.
+const SYN_COOKIE_NO_WINDOW_SCALE: u32 = 0xf;
+
+/// The secret of the SYN cookies sent by the interface.
+///
+/// A SYN cookie is the initial sequence number of a SYN|ACK answering a SYN statelessly.
+/// Its 3 high bits are a counter incremented every [SYN_COOKIE_PERIOD] seconds, the next 7
+/// bits encode the options of the SYN, and the low 22 bits are a keyed hash of the connection,
+/// the counter and the options.
+#[derive(Debug)]
+pub(crate) struct SynCookies {
+    secret: [u8; 16],
+}
+
+impl SynCookies {
+    pub(crate) fn new(rand: &mut Rand) -> Self {
+        let mut secret = [0u8; 16];
+        for chunk in secret.chunks_mut(4) {
+            chunk.copy_from_slice(&rand.rand_u32().to_be_bytes());
+        }
+        Self { secret }
+    }
+
+    fn counter(now: Instant) -> u32 {
+        (now.secs() as u64 / SYN_COOKIE_PERIOD) as u32
+    }
+
+    fn hash(
+        &self,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+        remote_isn: TcpSeqNumber,
+        counter: u32,
+        options: u32,
+    ) -> u32 {
+        let mut hash = Sha256::new();
+        hash.update(ip_repr.src_addr().as_bytes());
+        hash.update(ip_repr.dst_addr().as_bytes());
+        hash.update(&repr.src_port.to_be_bytes());
+        hash.update(&repr.dst_port.to_be_bytes());
+        hash.update(&remote_isn.0.to_be_bytes());
+        hash.update(&counter.to_be_bytes());
+        hash.update(&options.to_be_bytes());
+        hash.update(&self.secret);
+
+        let digest = hash.finalize();
+        u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) & 0x3fffff
+    }
+
+    /// Return the SYN cookie answering the SYN `repr`.
+    pub(crate) fn encode(&self, now: Instant, ip_repr: &IpRepr, repr: &TcpRepr) -> TcpSeqNumber {
+        let mss = repr.max_seg_size.unwrap_or(SYN_COOKIE_MSS[0]);
+        let mss_index = SYN_COOKIE_MSS
+            .iter()
+            .rposition(|&cookie_mss| cookie_mss <= mss)
+            .unwrap_or(0) as u32;
+        let window_scale = match repr.window_scale {
+            Some(scale) => scale.min(14) as u32,
+            None => SYN_COOKIE_NO_WINDOW_SCALE,
+        };
+        let options = mss_index << 5 | (repr.sack_permitted as u32) << 4 | window_scale;
+
+        let counter = Self::counter(now);
+        let hash = self.hash(ip_repr, repr, repr.seq_number, counter, options);
+        TcpSeqNumber(((counter & 0x7) << 29 | options << 22 | hash) as i32)
+    }
+
+    /// Return the options encoded in the SYN cookie acknowledged by `repr`, if it is valid.
+    pub(crate) fn decode(
+        &self,
+        now: Instant,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+    ) -> Option<SynCookie> {
+        let cookie = (repr.ack_number? - 1).0 as u32;
+        let options = (cookie >> 22) & 0x7f;
+        let counter = Self::counter(now);
+        let counter = [counter, counter.wrapping_sub(1)]
+            .into_iter()
+            .find(|counter| counter & 0x7 == cookie >> 29)?;
+        if self.hash(ip_repr, repr, repr.seq_number - 1, counter, options) != cookie & 0x3fffff {
+            return None;
+        }
+
+        Some(SynCookie {
+            mss: SYN_COOKIE_MSS[(options >> 5) as usize & 0x3],
+            window_scale: match options & 0xf {
+                SYN_COOKIE_NO_WINDOW_SCALE => None,
+                scale => Some(scale as u8),
+            },
+            sack_permitted: options & 0x10 != 0,
+        })
+    }
+}
 
 impl InterfaceInner {
     pub(crate) fn process_tcp<'frame>(
@@ -35,11 +137,57 @@ impl InterfaceInner {
         {
             // Never reply to a TCP RST packet with another TCP RST packet. We also never want to
             // send a TCP RST packet with unspecified addresses.
-            None
-        } else {
-            // The packet wasn't handled by a socket, send a TCP RST packet.
-            let (ip, tcp) = tcp::Socket::rst_reply(&ip_repr, &tcp_repr);
-            Some(Packet::new(ip, IpPayload::Tcp(tcp)))
+            return None;
+        }
+
+        if let Some(reply) = self.process_syn_cookie(sockets, &ip_repr, &tcp_repr) {
+            return reply;
+        }
+
+        // The packet wasn't handled by a socket, send a TCP RST packet.
+        let (ip, tcp) = tcp::Socket::rst_reply(&ip_repr, &tcp_repr);
+        Some(Packet::new(ip, IpPayload::Tcp(tcp)))
+    }
+
+    /// Answer a SYN to an endpoint without a listening socket with a SYN cookie, or accept the
+    /// connection acknowledging a valid SYN cookie.
+    ///
+    /// The connection is only accepted by a socket still listening on the endpoint, so that a
+    /// half-open connection is never dropped. Returns `None` if the segment isn't handled.
+    fn process_syn_cookie<'frame>(
+        &mut self,
+        sockets: &mut SocketSet,
+        ip_repr: &IpRepr,
+        tcp_repr: &TcpRepr,
+    ) -> Option<Option<Packet<'frame>>> {
+        let syn_cookies = self.syn_cookies.as_ref()?;
+        match (tcp_repr.control, tcp_repr.ack_number) {
+            (TcpControl::Syn, None) => {
+                let cookie = syn_cookies.encode(self.now, ip_repr, tcp_repr);
+                let tcp_socket = sockets
+                    .items()
+                    .filter_map(|i| Socket::downcast(&i.socket))
+                    .find(|socket| socket.listens_on(ip_repr, tcp_repr))?;
+                net_debug!("TCP SYN to a busy endpoint, sending a SYN cookie");
+                let (ip, tcp) = tcp_socket.syn_cookie_reply(self, ip_repr, tcp_repr, cookie);
+                Some(Some(Packet::new(ip, IpPayload::Tcp(tcp))))
+            }
+            (TcpControl::None | TcpControl::Psh | TcpControl::Fin, Some(_)) => {
+                let cookie = syn_cookies.decode(self.now, ip_repr, tcp_repr)?;
+                let tcp_socket = sockets
+                    .items_mut()
+                    .filter_map(|i| Socket::downcast_mut(&mut i.socket))
+                    .find(|socket| {
+                        socket.state() == State::Listen && socket.listens_on(ip_repr, tcp_repr)
+                    })?;
+                net_debug!("TCP ACK with a valid SYN cookie, accepting the connection");
+                Some(
+                    tcp_socket
+                        .accept_syn_cookie(self, ip_repr, tcp_repr, cookie)
+                        .map(|(ip, tcp)| Packet::new(ip, IpPayload::Tcp(tcp))),
+                )
+            }
+            _ => None,
         }
     }
 }
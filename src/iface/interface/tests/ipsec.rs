@@ -0,0 +1,771 @@
+use super::*;
+
+use crate::iface::ipsec::*;
+
+const SPI: u32 = 0x0000_1234;
+#[cfg(feature = "proto-ipsec-esp")]
+const KEY: &[u8] = b"0123456789abcdef";
+
+/// A keyed checksum, good enough to detect modifications in tests.
+#[cfg(feature = "proto-ipsec-esp")]
+struct TestIntegrity;
+
+#[cfg(feature = "proto-ipsec-esp")]
+impl IntegrityAlgorithm for TestIntegrity {
+    fn icv_len(&self) -> usize {
+        4
+    }
+
+    fn compute(&self, key: &[u8], data: &[&[u8]], icv: &mut [u8]) {
+        let mut hash = 0x811c_9dc5u32;
+        for b in key.iter().chain(data.iter().flat_map(|d| d.iter())) {
+            hash = (hash ^ *b as u32).wrapping_mul(0x0100_0193);
+        }
+        icv.copy_from_slice(&hash.to_be_bytes());
+    }
+}
+
+/// XOR "encryption" with an 8 octet block size, to exercise the padding.
+#[cfg(feature = "proto-ipsec-esp")]
+struct TestEncryption;
+
+#[cfg(feature = "proto-ipsec-esp")]
+impl EncryptionAlgorithm for TestEncryption {
+    fn iv_len(&self) -> usize {
+        4
+    }
+
+    fn block_size(&self) -> usize {
+        8
+    }
+
+    fn check_key(&self, key: &[u8]) -> Result<(), CryptoError> {
+        match key.len() {
+            0 => Err(CryptoError),
+            _ => Ok(()),
+        }
+    }
+
+    fn encrypt(&self, key: &[u8], iv: &mut [u8], data: &mut [u8]) {
+        iv.copy_from_slice(&[0xaa; 4]);
+        self.decrypt(key, iv, data).unwrap();
+    }
+
+    fn decrypt(&self, key: &[u8], iv: &[u8], data: &mut [u8]) -> Result<(), CryptoError> {
+        if iv != [0xaa; 4] {
+            return Err(CryptoError);
+        }
+        for (i, b) in data.iter_mut().enumerate() {
+            *b ^= key[i % key.len()];
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "proto-ipsec-esp")]
+static TEST_INTEGRITY: TestIntegrity = TestIntegrity;
+#[cfg(feature = "proto-ipsec-esp")]
+static TEST_ENCRYPTION: TestEncryption = TestEncryption;
+#[cfg(feature = "proto-ipsec-ah")]
+static HMAC_SHA256: HmacSha256 = HmacSha256;
+
+fn add_sas(
+    iface: &mut Interface,
+    protocol: Protocol,
+    remote: IpAddress,
+    configure: impl Fn(&mut SecurityAssociation),
+) {
+    for direction in [Direction::Inbound, Direction::Outbound] {
+        let mut sa = SecurityAssociation::new(SPI, direction, protocol, remote);
+        configure(&mut sa);
+        iface.ipsec_associations_mut().add(sa).unwrap();
+    }
+}
+
+#[cfg(feature = "proto-ipsec-esp")]
+fn add_esp_sas(iface: &mut Interface, remote: IpAddress, encryption: bool) {
+    add_sas(iface, Protocol::Esp, remote, |sa| {
+        if encryption {
+            sa.set_encryption(&TEST_ENCRYPTION, KEY);
+        }
+        sa.set_integrity(&TEST_INTEGRITY, KEY).unwrap();
+    });
+}
+
+#[cfg(feature = "proto-ipsec-ah")]
+pub(super) fn add_ah_sas(iface: &mut Interface, remote: IpAddress) {
+    add_sas(iface, Protocol::Ah, remote, |sa| {
+        sa.set_integrity(&HMAC_SHA256, b"0123456789abcdef0123456789abcdef")
+            .unwrap();
+    });
+}
+
+pub(super) fn protect(iface: &mut Interface, remote: IpCidr) {
+    iface.ipsec_policies_mut().update(|policies| {
+        policies
+            .push(SecurityPolicy::new(
+                remote,
+                PolicyAction::Protect { spi: SPI },
+            ))
+            .unwrap();
+    });
+}
+
+/// Dispatch `packet` and return the transmitted IP packet.
+pub(super) fn transmit(
+    iface: &mut Interface,
+    device: &mut crate::tests::TestingDevice,
+    packet: Packet,
+) -> std::vec::Vec<u8> {
+    #[cfg(feature = "medium-ethernet")]
+    if iface.inner.caps.medium == Medium::Ethernet {
+        let remote = packet.ip_repr().dst_addr();
+        iface.inner.neighbor_cache.fill(
+            remote,
+            HardwareAddress::Ethernet(EthernetAddress([0x52, 0, 0, 0, 0, 2])),
+            Instant::ZERO,
+        );
+    }
+
+    let tx_token = device.transmit(Instant::ZERO).unwrap();
+    iface
+        .inner
+        .dispatch_ip(
+            tx_token,
+            PacketMeta::default(),
+            packet,
+            &mut iface.fragmenter,
+        )
+        .unwrap();
+
+    let frame = device.queue.pop_front().unwrap();
+    match iface.inner.caps.medium {
+        #[cfg(feature = "medium-ethernet")]
+        Medium::Ethernet => frame[EthernetFrame::<&[u8]>::header_len()..].to_vec(),
+        _ => frame.to_vec(),
+    }
+}
+
+#[cfg(feature = "proto-ipv4")]
+fn echo_request_v4<'a>(ident: u16, data: &'a [u8]) -> Packet<'a> {
+    let icmp_repr = Icmpv4Repr::EchoRequest {
+        ident,
+        seq_no: 1,
+        data,
+    };
+    Packet::new_ipv4(
+        Ipv4Repr {
+            src_addr: Ipv4Address([192, 168, 1, 1]),
+            dst_addr: Ipv4Address([192, 168, 1, 2]),
+            next_header: IpProtocol::Icmp,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+        },
+        IpPayload::Icmpv4(icmp_repr),
+    )
+}
+
+/// Swap source and destination, as if the packet was sent by the peer.
+#[cfg(feature = "proto-ipv4")]
+fn reflect_v4(bytes: &mut [u8]) {
+    let mut packet = Ipv4Packet::new_unchecked(bytes);
+    let (src_addr, dst_addr) = (packet.src_addr(), packet.dst_addr());
+    packet.set_src_addr(dst_addr);
+    packet.set_dst_addr(src_addr);
+    packet.fill_checksum();
+}
+
+#[cfg(feature = "proto-ipsec-esp")]
+#[rstest]
+#[case::ip(Medium::Ip, false)]
+#[case::ip_encrypted(Medium::Ip, true)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet, false)]
+#[case::ethernet_encrypted(Medium::Ethernet, true)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+fn test_esp_roundtrip_ipv4(#[case] medium: Medium, #[case] encryption: bool) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+    add_esp_sas(&mut iface, remote, encryption);
+    protect(&mut iface, IpCidr::new(remote, 32));
+
+    let mut bytes = transmit(&mut iface, &mut device, echo_request_v4(0x1234, b"Hello"));
+
+    let ipv4_packet = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    assert_eq!(ipv4_packet.next_header(), IpProtocol::IpSecEsp);
+    let esp_packet = IpSecEspPacket::new_checked(ipv4_packet.payload()).unwrap();
+    assert_eq!(esp_packet.security_parameters_index(), SPI);
+    assert_eq!(esp_packet.sequence_number(), 1);
+    assert_eq!(
+        iface
+            .ipsec_associations()
+            .get(SPI, Direction::Outbound)
+            .unwrap()
+            .sequence_number(),
+        1
+    );
+
+    // ESP header + IV + ICMP (13 octets) + padding + trailer + ICV
+    let expected_len = if encryption {
+        8 + 4 + 16 + 4
+    } else {
+        8 + 16 + 4
+    };
+    assert_eq!(ipv4_packet.payload().len(), expected_len);
+    assert_eq!(
+        ipv4_packet.payload().windows(5).any(|w| w == b"Hello"),
+        !encryption
+    );
+
+    reflect_v4(&mut bytes);
+
+    let icmp_repr = Icmpv4Repr::EchoReply {
+        ident: 0x1234,
+        seq_no: 1,
+        data: b"Hello",
+    };
+    let expected = Packet::new_ipv4(
+        Ipv4Repr {
+            src_addr: Ipv4Address([192, 168, 1, 1]),
+            dst_addr: Ipv4Address([192, 168, 1, 2]),
+            next_header: IpProtocol::Icmp,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+        },
+        IpPayload::Icmpv4(icmp_repr),
+    );
+
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        ),
+        Some(expected)
+    );
+}
+
+#[cfg(feature = "proto-ipsec-esp")]
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+fn test_esp_invalid_icv_ipv4(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+    add_esp_sas(&mut iface, remote, true);
+    protect(&mut iface, IpCidr::new(remote, 32));
+
+    let mut bytes = transmit(&mut iface, &mut device, echo_request_v4(0x1234, b"Hello"));
+    reflect_v4(&mut bytes);
+
+    let len = bytes.len();
+    bytes[len - 5] ^= 0x01;
+
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        ),
+        None
+    );
+}
+
+#[cfg(feature = "proto-ipsec-esp")]
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+fn test_esp_unknown_spi_ipv4(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+    add_esp_sas(&mut iface, remote, true);
+    protect(&mut iface, IpCidr::new(remote, 32));
+
+    let mut bytes = transmit(&mut iface, &mut device, echo_request_v4(0x1234, b"Hello"));
+    reflect_v4(&mut bytes);
+
+    iface
+        .ipsec_associations_mut()
+        .remove(SPI, Direction::Inbound)
+        .unwrap();
+
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        ),
+        None
+    );
+}
+
+#[cfg(feature = "proto-ipsec-esp")]
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+fn test_esp_wrong_sa_ipv4(#[case] medium: Medium) {
+    const OTHER_SPI: u32 = 0x0000_5678;
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+    add_esp_sas(&mut iface, remote, true);
+    for direction in [Direction::Inbound, Direction::Outbound] {
+        let mut sa = SecurityAssociation::new(OTHER_SPI, direction, Protocol::Esp, remote);
+        sa.set_encryption(&TEST_ENCRYPTION, KEY);
+        sa.set_integrity(&TEST_INTEGRITY, KEY).unwrap();
+        iface.ipsec_associations_mut().add(sa).unwrap();
+    }
+
+    // The packet is protected with a valid security association, but not the one the policy
+    // requires.
+    iface.ipsec_policies_mut().update(|policies| {
+        policies
+            .push(SecurityPolicy::new(
+                IpCidr::new(remote, 32),
+                PolicyAction::Protect { spi: OTHER_SPI },
+            ))
+            .unwrap();
+    });
+    let mut bytes = transmit(&mut iface, &mut device, echo_request_v4(0x1234, b"Hello"));
+    reflect_v4(&mut bytes);
+
+    iface
+        .ipsec_policies_mut()
+        .update(|policies| policies.clear());
+    protect(&mut iface, IpCidr::new(remote, 32));
+
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        ),
+        None
+    );
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+fn test_unprotected_packet_dropped_ipv4(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+
+    // Without a policy, the echo request is sent in the clear.
+    let mut bytes = transmit(&mut iface, &mut device, echo_request_v4(0x1234, b"Hello"));
+    assert_eq!(
+        Ipv4Packet::new_checked(&bytes[..]).unwrap().next_header(),
+        IpProtocol::Icmp
+    );
+    reflect_v4(&mut bytes);
+
+    protect(&mut iface, IpCidr::new(remote, 32));
+
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        ),
+        None
+    );
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+fn test_discard_policy_ipv4(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+
+    iface.ipsec_policies_mut().update(|policies| {
+        policies
+            .push(SecurityPolicy::new(
+                IpCidr::new(remote, 32),
+                PolicyAction::Discard,
+            ))
+            .unwrap();
+    });
+
+    let tx_token = device.transmit(Instant::ZERO).unwrap();
+    assert_eq!(
+        iface.inner.dispatch_ip(
+            tx_token,
+            PacketMeta::default(),
+            echo_request_v4(0x1234, b"Hello"),
+            &mut iface.fragmenter
+        ),
+        Ok(())
+    );
+    assert!(device.queue.is_empty());
+}
+
+#[cfg(feature = "proto-ipsec-esp")]
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+fn test_esp_rejected_key_ipv4(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+    add_sas(&mut iface, Protocol::Esp, remote, |sa| {
+        sa.set_encryption(&TEST_ENCRYPTION, &[]);
+    });
+    protect(&mut iface, IpCidr::new(remote, 32));
+
+    let tx_token = device.transmit(Instant::ZERO).unwrap();
+    assert_eq!(
+        iface.inner.dispatch_ip(
+            tx_token,
+            PacketMeta::default(),
+            echo_request_v4(0x1234, b"Hello"),
+            &mut iface.fragmenter
+        ),
+        Ok(())
+    );
+    assert!(device.queue.is_empty());
+    assert_eq!(
+        iface
+            .ipsec_associations()
+            .get(SPI, Direction::Outbound)
+            .unwrap()
+            .sequence_number(),
+        0
+    );
+}
+
+#[cfg(feature = "proto-ipsec-esp")]
+#[rstest]
+#[case::ip(Medium::Ip, false)]
+#[case::ip_encrypted(Medium::Ip, true)]
+#[cfg(all(feature = "proto-ipv6", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet, false)]
+#[case::ethernet_encrypted(Medium::Ethernet, true)]
+#[cfg(all(feature = "proto-ipv6", feature = "medium-ethernet"))]
+fn test_esp_roundtrip_ipv6(#[case] medium: Medium, #[case] encryption: bool) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let local = Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 1]);
+    let remote = Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 2]);
+    add_esp_sas(&mut iface, remote.into(), encryption);
+    protect(&mut iface, IpCidr::new(remote.into(), 128));
+
+    let icmp_repr = Icmpv6Repr::EchoRequest {
+        ident: 0x1234,
+        seq_no: 1,
+        data: b"Hello",
+    };
+    let packet = Packet::new_ipv6(
+        Ipv6Repr {
+            src_addr: local,
+            dst_addr: remote,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+        },
+        IpPayload::Icmpv6(icmp_repr),
+    );
+
+    let mut bytes = transmit(&mut iface, &mut device, packet);
+    assert_eq!(
+        Ipv6Packet::new_checked(&bytes[..]).unwrap().next_header(),
+        IpProtocol::IpSecEsp
+    );
+
+    // Swap source and destination, as if the packet was sent by the peer. The ICMPv6 checksum
+    // is not affected, since the pseudo header sums both addresses.
+    {
+        let mut packet = Ipv6Packet::new_unchecked(&mut bytes[..]);
+        packet.set_src_addr(remote);
+        packet.set_dst_addr(local);
+    }
+
+    let icmp_reply = Icmpv6Repr::EchoReply {
+        ident: 0x1234,
+        seq_no: 1,
+        data: b"Hello",
+    };
+    let expected = Packet::new_ipv6(
+        Ipv6Repr {
+            src_addr: local,
+            dst_addr: remote,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_reply.buffer_len(),
+            hop_limit: 64,
+        },
+        IpPayload::Icmpv6(icmp_reply),
+    );
+
+    assert_eq!(
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&bytes[..]).unwrap(),
+            Some(&mut iface.fragments)
+        ),
+        Some(expected)
+    );
+}
+
+/// An echo request sent by the peer, which is protected by the AH of the peer's interface.
+#[cfg(all(feature = "proto-ipsec-ah", feature = "proto-ipv4"))]
+fn ah_echo_request_v4(
+    iface: &mut Interface,
+    device: &mut crate::tests::TestingDevice,
+) -> std::vec::Vec<u8> {
+    let icmp_repr = Icmpv4Repr::EchoRequest {
+        ident: 0x1234,
+        seq_no: 1,
+        data: b"Hello",
+    };
+    let packet = Packet::new_ipv4(
+        Ipv4Repr {
+            src_addr: Ipv4Address([192, 168, 1, 2]),
+            dst_addr: Ipv4Address([192, 168, 1, 1]),
+            next_header: IpProtocol::Icmp,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+        },
+        IpPayload::Icmpv4(icmp_repr),
+    );
+    transmit(iface, device, packet)
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+#[cfg(feature = "proto-ipsec-ah")]
+fn test_ah_roundtrip_ipv4(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+    add_ah_sas(&mut iface, remote);
+    protect(&mut iface, IpCidr::new(remote, 24));
+
+    let mut bytes = ah_echo_request_v4(&mut iface, &mut device);
+
+    let ipv4_packet = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    assert_eq!(ipv4_packet.next_header(), IpProtocol::IpSecAh);
+    let ah_packet = IpSecAuthHeaderPacket::new_checked(ipv4_packet.payload()).unwrap();
+    assert_eq!(ah_packet.next_header(), IpProtocol::Icmp);
+    assert_eq!(ah_packet.security_parameters_index(), SPI);
+    assert_eq!(ah_packet.sequence_number(), 1);
+    // Fixed fields + HMAC-SHA-256-128
+    assert_eq!(ah_packet.header_len(), 12 + 16);
+    assert_eq!(&ah_packet.payload()[8..], b"Hello");
+
+    // The mutable fields are not covered by the ICV.
+    {
+        let mut packet = Ipv4Packet::new_unchecked(&mut bytes[..]);
+        packet.set_hop_limit(17);
+        packet.set_dscp(10);
+        packet.set_dont_frag(false);
+        packet.fill_checksum();
+    }
+
+    let icmp_repr = Icmpv4Repr::EchoReply {
+        ident: 0x1234,
+        seq_no: 1,
+        data: b"Hello",
+    };
+    let expected = Packet::new_ipv4(
+        Ipv4Repr {
+            src_addr: Ipv4Address([192, 168, 1, 1]),
+            dst_addr: Ipv4Address([192, 168, 1, 2]),
+            next_header: IpProtocol::Icmp,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+        },
+        IpPayload::Icmpv4(icmp_repr),
+    );
+
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        ),
+        Some(expected)
+    );
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+#[cfg(feature = "proto-ipsec-ah")]
+fn test_ah_invalid_icv_ipv4(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+    add_ah_sas(&mut iface, remote);
+    protect(&mut iface, IpCidr::new(remote, 24));
+
+    let bytes = ah_echo_request_v4(&mut iface, &mut device);
+
+    // Modify the payload.
+    let mut payload_modified = bytes.clone();
+    *payload_modified.last_mut().unwrap() ^= 0x01;
+
+    // Modify the identification, which is an immutable field.
+    let mut ident_modified = bytes.clone();
+    {
+        let mut packet = Ipv4Packet::new_unchecked(&mut ident_modified[..]);
+        packet.set_ident(0x4242);
+        packet.fill_checksum();
+    }
+
+    for bytes in [payload_modified, ident_modified] {
+        assert_eq!(
+            iface.inner.process_ipv4(
+                &mut sockets,
+                PacketMeta::default(),
+                &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+                &mut iface.fragments
+            ),
+            None
+        );
+    }
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+#[cfg(feature = "proto-ipsec-ah")]
+fn test_ah_wrong_protocol_ipv4(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+    add_ah_sas(&mut iface, remote);
+    protect(&mut iface, IpCidr::new(remote, 24));
+
+    let bytes = ah_echo_request_v4(&mut iface, &mut device);
+
+    // An ESP security association with the same SPI must not verify AH packets.
+    add_sas(&mut iface, Protocol::Esp, remote, |sa| {
+        sa.set_integrity(&HMAC_SHA256, b"0123456789abcdef0123456789abcdef")
+            .unwrap();
+    });
+
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        ),
+        None
+    );
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+#[cfg(feature = "proto-ipsec-esp")]
+fn test_esp_replay_ipv4(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+    add_esp_sas(&mut iface, remote, true);
+    protect(&mut iface, IpCidr::new(remote, 32));
+
+    let mut bytes = transmit(&mut iface, &mut device, echo_request_v4(0x1234, b"Hello"));
+    reflect_v4(&mut bytes);
+
+    assert!(iface
+        .inner
+        .process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        )
+        .is_some());
+
+    // The same packet is dropped the second time.
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        ),
+        None
+    );
+
+    let window = iface
+        .ipsec_associations()
+        .get(SPI, Direction::Inbound)
+        .unwrap()
+        .replay_window();
+    assert_eq!(window.top(), 1);
+    assert_eq!(window.accepted(), 1);
+    assert_eq!(window.duplicates(), 1);
+    assert_eq!(window.too_old(), 0);
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+#[cfg(feature = "proto-ipsec-ah")]
+fn test_ah_esn_ipv4(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let remote = IpAddress::v4(192, 168, 1, 2);
+    add_sas(&mut iface, Protocol::Ah, remote, |sa| {
+        sa.set_integrity(&HMAC_SHA256, b"0123456789abcdef0123456789abcdef")
+            .unwrap();
+        sa.set_extended_sequence_numbers(true);
+    });
+    protect(&mut iface, IpCidr::new(remote, 24));
+
+    let bytes = ah_echo_request_v4(&mut iface, &mut device);
+
+    assert!(iface
+        .inner
+        .process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        )
+        .is_some());
+
+    // The high-order bits of the sequence number are covered by the ICV, so a peer without
+    // ESN cannot verify the packet.
+    let bytes = ah_echo_request_v4(&mut iface, &mut device);
+    let mut sa = SecurityAssociation::new(SPI, Direction::Inbound, Protocol::Ah, remote);
+    sa.set_integrity(&HMAC_SHA256, b"0123456789abcdef0123456789abcdef")
+        .unwrap();
+    iface.ipsec_associations_mut().add(sa).unwrap();
+
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_checked(&bytes[..]).unwrap(),
+            &mut iface.fragments
+        ),
+        None
+    );
+}
@@ -1,5 +1,10 @@
 use super::*;
 
+#[cfg(feature = "medium-ethernet")]
+use crate::config::IFACE_NEIGHBOR_PENDING_COUNT;
+#[cfg(feature = "medium-ethernet")]
+use crate::iface::neighbor::State as NeighborState;
+
 #[rstest]
 #[case(Medium::Ethernet)]
 #[cfg(feature = "medium-ethernet")]
@@ -657,41 +662,101 @@ fn test_icmpv4_socket(#[case] medium: Medium) {
     );
 }
 
+#[cfg(feature = "proto-igmp")]
+type IgmpRecord = (IgmpRecordType, Ipv4Address, Vec<Ipv4Address>);
+
+/// Receive the IGMP packets sent by the interface, with their IGMP payload.
+#[cfg(feature = "proto-igmp")]
+fn recv_igmp(
+    device: &mut crate::tests::TestingDevice,
+    timestamp: Instant,
+) -> Vec<(Ipv4Repr, Vec<u8>)> {
+    let caps = device.capabilities();
+    let checksum_caps = &caps.checksum;
+    recv_all(device, timestamp)
+        .iter()
+        .filter_map(|frame| {
+            let ipv4_packet = match caps.medium {
+                #[cfg(feature = "medium-ethernet")]
+                Medium::Ethernet => {
+                    let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+                    Ipv4Packet::new_checked(eth_frame.payload()).ok()?
+                }
+                #[cfg(feature = "medium-ip")]
+                Medium::Ip => Ipv4Packet::new_checked(&frame[..]).ok()?,
+                #[cfg(feature = "medium-ieee802154")]
+                Medium::Ieee802154 => todo!(),
+            };
+            let ipv4_repr = Ipv4Repr::parse(&ipv4_packet, checksum_caps).ok()?;
+            (ipv4_repr.next_header == IpProtocol::Igmp)
+                .then(|| (ipv4_repr, ipv4_packet.payload().to_vec()))
+        })
+        .collect::<Vec<_>>()
+}
+
+#[cfg(feature = "proto-igmp")]
+fn parse_igmp(payload: &[u8]) -> IgmpRepr<'_> {
+    IgmpRepr::parse(&IgmpPacket::new_checked(payload).unwrap()).unwrap()
+}
+
+/// Return the group records of an IGMPv3 report.
+#[cfg(feature = "proto-igmp")]
+fn igmp_records(payload: &[u8]) -> Vec<IgmpRecord> {
+    let IgmpRepr::MembershipReportV3 {
+        nr_group_records,
+        mut data,
+    } = parse_igmp(payload)
+    else {
+        panic!("not an IGMPv3 report");
+    };
+
+    let mut records = Vec::new();
+    for _ in 0..nr_group_records {
+        let record = IgmpGroupRecord::new_checked(data).unwrap();
+        let sources = (0..record.num_srcs() as usize)
+            .map(|index| record.source(index))
+            .collect();
+        records.push((record.record_type(), record.mcast_addr(), sources));
+        data = &data[record.record_len()..];
+    }
+    records
+}
+
+#[cfg(feature = "proto-igmp")]
+fn igmp_query(
+    iface: &mut Interface,
+    sockets: &mut SocketSet,
+    repr: IgmpRepr,
+    dst_addr: Ipv4Address,
+) {
+    let ipv4_repr = Ipv4Repr {
+        src_addr: Ipv4Address::new(192, 168, 1, 100),
+        dst_addr,
+        next_header: IpProtocol::Igmp,
+        payload_len: repr.buffer_len(),
+        hop_limit: 1,
+    };
+    let mut bytes = vec![0; ipv4_repr.buffer_len() + repr.buffer_len()];
+    let mut packet = Ipv4Packet::new_unchecked(&mut bytes[..]);
+    ipv4_repr.emit(&mut packet, &ChecksumCapabilities::default());
+    repr.emit(&mut IgmpPacket::new_unchecked(packet.payload_mut()));
+
+    let frag = &mut iface.fragments;
+    let packet = Ipv4Packet::new_checked(&bytes[..]).unwrap();
+    assert_eq!(
+        iface
+            .inner
+            .process_ipv4(sockets, PacketMeta::default(), &packet, frag),
+        None
+    );
+}
+
 #[rstest]
 #[case(Medium::Ip)]
 #[cfg(all(feature = "proto-igmp", feature = "medium-ip"))]
 #[case(Medium::Ethernet)]
 #[cfg(all(feature = "proto-igmp", feature = "medium-ethernet"))]
 fn test_handle_igmp(#[case] medium: Medium) {
-    fn recv_igmp(
-        device: &mut crate::tests::TestingDevice,
-        timestamp: Instant,
-    ) -> Vec<(Ipv4Repr, IgmpRepr)> {
-        let caps = device.capabilities();
-        let checksum_caps = &caps.checksum;
-        recv_all(device, timestamp)
-            .iter()
-            .filter_map(|frame| {
-                let ipv4_packet = match caps.medium {
-                    #[cfg(feature = "medium-ethernet")]
-                    Medium::Ethernet => {
-                        let eth_frame = EthernetFrame::new_checked(frame).ok()?;
-                        Ipv4Packet::new_checked(eth_frame.payload()).ok()?
-                    }
-                    #[cfg(feature = "medium-ip")]
-                    Medium::Ip => Ipv4Packet::new_checked(&frame[..]).ok()?,
-                    #[cfg(feature = "medium-ieee802154")]
-                    Medium::Ieee802154 => todo!(),
-                };
-                let ipv4_repr = Ipv4Repr::parse(&ipv4_packet, checksum_caps).ok()?;
-                let ip_payload = ipv4_packet.payload();
-                let igmp_packet = IgmpPacket::new_checked(ip_payload).ok()?;
-                let igmp_repr = IgmpRepr::parse(&igmp_packet).ok()?;
-                Some((ipv4_repr, igmp_repr))
-            })
-            .collect::<Vec<_>>()
-    }
-
     let groups = [
         Ipv4Address::new(224, 0, 0, 22),
         Ipv4Address::new(224, 0, 0, 56),
@@ -707,21 +772,22 @@ fn test_handle_igmp(#[case] medium: Medium) {
             .unwrap();
     }
 
+    // Without an older querier, IGMPv3 state change reports are sent.
     let reports = recv_igmp(&mut device, timestamp);
     assert_eq!(reports.len(), 2);
     for (i, group_addr) in groups.iter().enumerate() {
         assert_eq!(reports[i].0.next_header, IpProtocol::Igmp);
-        assert_eq!(reports[i].0.dst_addr, *group_addr);
         assert_eq!(
-            reports[i].1,
-            IgmpRepr::MembershipReport {
-                group_addr: *group_addr,
-                version: IgmpVersion::Version2,
-            }
+            reports[i].0.dst_addr,
+            Ipv4Address::MULTICAST_ALL_IGMPV3_ROUTERS
+        );
+        assert_eq!(
+            igmp_records(&reports[i].1),
+            vec![(IgmpRecordType::ChangeToExclude, *group_addr, vec![])]
         );
     }
 
-    // General query
+    // IGMPv3 general query
     let timestamp = Instant::ZERO;
     const GENERAL_QUERY_BYTES: &[u8] = &[
         0x46, 0xc0, 0x00, 0x24, 0xed, 0xb4, 0x00, 0x00, 0x01, 0x02, 0x47, 0x43, 0xac, 0x16, 0x63,
@@ -740,7 +806,7 @@ fn test_handle_igmp(#[case] medium: Medium) {
     // loopback have been processed, including responses to
     // GENERAL_QUERY_BYTES. Therefore `recv_all()` would return 0
     // pkts that could be checked.
-    iface.socket_ingress(&mut device, &mut sockets);
+    iface.socket_ingress(&mut device, &mut sockets, &mut |_, packet| Some(packet));
 
     // Leave multicast groups
     let timestamp = Instant::ZERO;
@@ -754,11 +820,321 @@ fn test_handle_igmp(#[case] medium: Medium) {
     assert_eq!(leaves.len(), 2);
     for (i, group_addr) in groups.iter().cloned().enumerate() {
         assert_eq!(leaves[i].0.next_header, IpProtocol::Igmp);
-        assert_eq!(leaves[i].0.dst_addr, Ipv4Address::MULTICAST_ALL_ROUTERS);
-        assert_eq!(leaves[i].1, IgmpRepr::LeaveGroup { group_addr });
+        assert_eq!(
+            leaves[i].0.dst_addr,
+            Ipv4Address::MULTICAST_ALL_IGMPV3_ROUTERS
+        );
+        assert_eq!(
+            igmp_records(&leaves[i].1),
+            vec![(IgmpRecordType::ChangeToInclude, group_addr, vec![])]
+        );
     }
 }
 
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "proto-igmp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "proto-igmp", feature = "medium-ethernet"))]
+fn test_igmpv3_join_leave(#[case] medium: Medium) {
+    let group = Ipv4Address::new(232, 1, 2, 3);
+    let source = Ipv4Address::new(10, 0, 0, 1);
+
+    let (mut iface, _sockets, mut device) = setup(medium);
+
+    assert_eq!(
+        iface.join_multicast_group_with_sources(
+            &mut device,
+            group,
+            MulticastFilterMode::Include,
+            &[source],
+            Instant::ZERO
+        ),
+        Ok(true)
+    );
+    assert!(iface.has_multicast_group(group));
+
+    let reports = recv_igmp(&mut device, Instant::ZERO);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].0.src_addr, Ipv4Address::new(192, 168, 1, 1));
+    assert_eq!(
+        reports[0].0.dst_addr,
+        Ipv4Address::MULTICAST_ALL_IGMPV3_ROUTERS
+    );
+    assert_eq!(reports[0].0.hop_limit, 1);
+    assert_eq!(
+        igmp_records(&reports[0].1),
+        vec![(IgmpRecordType::ChangeToInclude, group, vec![source])]
+    );
+
+    // The report is sent once more, within the unsolicited report interval.
+    let poll_at = iface.inner.igmp_poll_at().unwrap();
+    assert!(poll_at < Instant::from_secs(1));
+    iface.inner.now = Instant::from_secs(1);
+    assert!(iface.igmp_egress(&mut device));
+    let reports = recv_igmp(&mut device, Instant::from_secs(1));
+    assert_eq!(
+        igmp_records(&reports[0].1),
+        vec![(IgmpRecordType::ChangeToInclude, group, vec![source])]
+    );
+    assert_eq!(iface.inner.igmp_poll_at(), None);
+
+    // Leave, with a report that is also sent twice.
+    assert_eq!(
+        iface.leave_multicast_group(&mut device, group, Instant::from_secs(2)),
+        Ok(true)
+    );
+    assert!(!iface.has_multicast_group(group));
+    let reports = recv_igmp(&mut device, Instant::from_secs(2));
+    assert_eq!(
+        igmp_records(&reports[0].1),
+        vec![(IgmpRecordType::ChangeToInclude, group, vec![])]
+    );
+
+    iface.inner.now = Instant::from_secs(3);
+    assert!(iface.igmp_egress(&mut device));
+    assert_eq!(recv_igmp(&mut device, Instant::from_secs(3)).len(), 1);
+    assert!(iface.inner.ipv4_multicast_groups.is_empty());
+    assert_eq!(iface.inner.igmp_poll_at(), None);
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "proto-igmp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "proto-igmp", feature = "medium-ethernet"))]
+fn test_igmpv3_query(#[case] medium: Medium) {
+    let ssm_group = Ipv4Address::new(232, 1, 2, 3);
+    let asm_group = Ipv4Address::new(239, 1, 2, 3);
+    let source = Ipv4Address::new(10, 0, 0, 1);
+    let other_source = Ipv4Address::new(10, 0, 0, 2);
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    iface
+        .join_multicast_group_with_sources(
+            &mut device,
+            ssm_group,
+            MulticastFilterMode::Include,
+            &[source],
+            Instant::ZERO,
+        )
+        .unwrap();
+    iface
+        .join_multicast_group(&mut device, asm_group, Instant::ZERO)
+        .unwrap();
+    assert_eq!(recv_igmp(&mut device, Instant::ZERO).len(), 2);
+    iface.inner.now = Instant::from_secs(1);
+    while iface.igmp_egress(&mut device) {}
+    assert_eq!(recv_igmp(&mut device, Instant::from_secs(1)).len(), 2);
+
+    // A general query is answered with the current state of all the groups.
+    let query = IgmpRepr::MembershipQueryV3 {
+        max_resp_time: Duration::from_secs(10),
+        group_addr: Ipv4Address::UNSPECIFIED,
+        s_flag: false,
+        qrv: 2,
+        qqic: 125,
+        num_srcs: 0,
+        data: &[],
+    };
+    igmp_query(
+        &mut iface,
+        &mut sockets,
+        query,
+        Ipv4Address::MULTICAST_ALL_SYSTEMS,
+    );
+    assert!(iface.inner.igmp_poll_at().unwrap() <= Instant::from_secs(11));
+
+    iface.inner.now = Instant::from_secs(11);
+    assert!(iface.igmp_egress(&mut device));
+    let reports = recv_igmp(&mut device, Instant::from_secs(11));
+    assert_eq!(reports.len(), 1);
+    let mut records = igmp_records(&reports[0].1);
+    records.sort_by_key(|(_, addr, _)| *addr);
+    assert_eq!(
+        records,
+        vec![
+            (IgmpRecordType::ModeIsInclude, ssm_group, vec![source]),
+            (IgmpRecordType::ModeIsExclude, asm_group, vec![]),
+        ]
+    );
+    assert_eq!(iface.inner.igmp_poll_at(), None);
+
+    // A group-and-source specific query is answered with the queried sources that are
+    // received.
+    let query = IgmpRepr::MembershipQueryV3 {
+        max_resp_time: Duration::from_secs(1),
+        group_addr: ssm_group,
+        s_flag: false,
+        qrv: 2,
+        qqic: 125,
+        num_srcs: 2,
+        data: &[10, 0, 0, 1, 10, 0, 0, 2],
+    };
+    igmp_query(&mut iface, &mut sockets, query, ssm_group);
+
+    iface.inner.now = Instant::from_secs(12);
+    assert!(iface.igmp_egress(&mut device));
+    let reports = recv_igmp(&mut device, Instant::from_secs(12));
+    assert_eq!(
+        igmp_records(&reports[0].1),
+        vec![(IgmpRecordType::ModeIsInclude, ssm_group, vec![source])]
+    );
+
+    // A query about sources that are all blocked is not answered.
+    let query = IgmpRepr::MembershipQueryV3 {
+        max_resp_time: Duration::from_secs(1),
+        group_addr: ssm_group,
+        s_flag: false,
+        qrv: 2,
+        qqic: 125,
+        num_srcs: 1,
+        data: &other_source.0,
+    };
+    igmp_query(&mut iface, &mut sockets, query, ssm_group);
+
+    iface.inner.now = Instant::from_secs(13);
+    assert!(iface.igmp_egress(&mut device));
+    assert!(recv_igmp(&mut device, Instant::from_secs(13)).is_empty());
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "proto-igmp", feature = "medium-ip"))]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "proto-igmp", feature = "medium-ethernet"))]
+fn test_igmp_older_querier(#[case] medium: Medium) {
+    let group = Ipv4Address::new(239, 1, 2, 3);
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    // An IGMPv2 querier is present.
+    let query = IgmpRepr::MembershipQuery {
+        max_resp_time: Duration::from_secs(10),
+        group_addr: Ipv4Address::UNSPECIFIED,
+        version: IgmpVersion::Version2,
+    };
+    igmp_query(
+        &mut iface,
+        &mut sockets,
+        query,
+        Ipv4Address::MULTICAST_ALL_SYSTEMS,
+    );
+    assert_eq!(
+        iface.inner.igmp_older_version(),
+        Some(IgmpVersion::Version2)
+    );
+
+    // Joining and leaving the group sends IGMPv2 messages, once.
+    iface
+        .join_multicast_group(&mut device, group, Instant::ZERO)
+        .unwrap();
+    let reports = recv_igmp(&mut device, Instant::ZERO);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].0.dst_addr, group);
+    assert_eq!(
+        parse_igmp(&reports[0].1),
+        IgmpRepr::MembershipReport {
+            group_addr: group,
+            version: IgmpVersion::Version2,
+        }
+    );
+
+    // Source filters are only applied locally.
+    assert_eq!(
+        iface.join_multicast_group_with_sources(
+            &mut device,
+            group,
+            MulticastFilterMode::Exclude,
+            &[Ipv4Address::new(10, 0, 0, 1)],
+            Instant::ZERO
+        ),
+        Ok(false)
+    );
+    assert!(recv_igmp(&mut device, Instant::ZERO).is_empty());
+
+    // IGMPv3 queries are answered with IGMPv2 reports.
+    let query = IgmpRepr::MembershipQueryV3 {
+        max_resp_time: Duration::from_secs(1),
+        group_addr: group,
+        s_flag: false,
+        qrv: 2,
+        qqic: 125,
+        num_srcs: 0,
+        data: &[],
+    };
+    igmp_query(&mut iface, &mut sockets, query, group);
+    iface.inner.now = Instant::from_secs(1);
+    assert!(iface.igmp_egress(&mut device));
+    let reports = recv_igmp(&mut device, Instant::from_secs(1));
+    assert_eq!(
+        parse_igmp(&reports[0].1),
+        IgmpRepr::MembershipReport {
+            group_addr: group,
+            version: IgmpVersion::Version2,
+        }
+    );
+
+    assert_eq!(
+        iface.leave_multicast_group(&mut device, group, Instant::from_secs(1)),
+        Ok(true)
+    );
+    let leaves = recv_igmp(&mut device, Instant::from_secs(1));
+    assert_eq!(leaves.len(), 1);
+    assert_eq!(leaves[0].0.dst_addr, Ipv4Address::MULTICAST_ALL_ROUTERS);
+    assert_eq!(
+        parse_igmp(&leaves[0].1),
+        IgmpRepr::LeaveGroup { group_addr: group }
+    );
+    assert!(iface.inner.ipv4_multicast_groups.is_empty());
+    assert_eq!(iface.inner.igmp_poll_at(), None);
+
+    // Once the querier is gone, IGMPv3 is spoken again.
+    let timestamp = Instant::from_secs(261);
+    assert_eq!(
+        iface.join_multicast_group(&mut device, group, timestamp),
+        Ok(true)
+    );
+    assert_eq!(iface.inner.igmp_older_version(), None);
+    let reports = recv_igmp(&mut device, timestamp);
+    assert_eq!(
+        reports[0].0.dst_addr,
+        Ipv4Address::MULTICAST_ALL_IGMPV3_ROUTERS
+    );
+    assert_eq!(
+        igmp_records(&reports[0].1),
+        vec![(IgmpRecordType::ChangeToExclude, group, vec![])]
+    );
+
+    // An IGMPv1 querier cancels the pending IGMPv3 reports, and leaving is then silent.
+    let query = IgmpRepr::MembershipQuery {
+        max_resp_time: Duration::from_secs(10),
+        group_addr: Ipv4Address::UNSPECIFIED,
+        version: IgmpVersion::Version1,
+    };
+    igmp_query(
+        &mut iface,
+        &mut sockets,
+        query,
+        Ipv4Address::MULTICAST_ALL_SYSTEMS,
+    );
+    assert_eq!(
+        iface.inner.igmp_older_version(),
+        Some(IgmpVersion::Version1)
+    );
+    assert!(iface.inner.ipv4_multicast_groups[&group]
+        .state_change
+        .is_none());
+
+    assert_eq!(
+        iface.leave_multicast_group(&mut device, group, timestamp),
+        Ok(false)
+    );
+    assert!(recv_igmp(&mut device, timestamp).is_empty());
+    assert!(!iface.has_multicast_group(group));
+}
+
 #[rstest]
 #[case(Medium::Ip)]
 #[cfg(all(feature = "socket-raw", feature = "medium-ip"))]
@@ -1019,3 +1395,999 @@ fn test_icmp_reply_size(#[case] medium: Medium) {
         ))
     );
 }
+
+#[rstest]
+#[case(Medium::Ip, 1400, 1400)]
+#[cfg(feature = "medium-ip")]
+#[case(Medium::Ip, 0, 1006)]
+#[cfg(feature = "medium-ip")]
+#[case(Medium::Ethernet, 1400, 1400)]
+#[cfg(feature = "medium-ethernet")]
+fn test_icmp_frag_required(
+    #[case] medium: Medium,
+    #[case] next_hop_mtu: u16,
+    #[case] path_mtu: usize,
+) {
+    let local = Ipv4Address([0x7f, 0x00, 0x00, 0x01]);
+    let remote = Ipv4Address([0x7f, 0x00, 0x00, 0x02]);
+    let router = Ipv4Address([0x7f, 0x00, 0x00, 0x03]);
+
+    let (mut iface, mut sockets, _device) = setup(medium);
+    assert_eq!(iface.path_mtu(&remote.into()), 1500);
+
+    // A router reports that a 1480 octets packet needs to be fragmented.
+    let icmp_repr = Icmpv4Repr::DstUnreachable {
+        reason: Icmpv4DstUnreachable::FragRequired,
+        header: Ipv4Repr {
+            src_addr: local,
+            dst_addr: remote,
+            next_header: IpProtocol::Udp,
+            payload_len: 1460,
+            hop_limit: 64,
+        },
+        data: &[0; 8],
+    };
+    let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+        src_addr: router,
+        dst_addr: local,
+        next_header: IpProtocol::Icmp,
+        payload_len: icmp_repr.buffer_len(),
+        hop_limit: 64,
+    });
+
+    let mut bytes = vec![0u8; ip_repr.buffer_len()];
+    ip_repr.emit(&mut bytes, &ChecksumCapabilities::default());
+    let mut icmp_packet = Icmpv4Packet::new_unchecked(&mut bytes[ip_repr.header_len()..]);
+    icmp_repr.emit(&mut icmp_packet, &ChecksumCapabilities::default());
+    icmp_packet.set_next_hop_mtu(next_hop_mtu);
+    icmp_packet.fill_checksum();
+
+    assert_eq!(
+        iface.inner.process_ipv4(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv4Packet::new_unchecked(&bytes[..]),
+            &mut iface.fragments
+        ),
+        None
+    );
+    assert_eq!(iface.path_mtu(&remote.into()), path_mtu);
+    assert_eq!(iface.path_mtu(&router.into()), 1500);
+}
+
+#[cfg(feature = "proto-ipv4-acd")]
+fn acd_setup(
+    policy: Option<AcdPolicy>,
+    link_local: bool,
+) -> (Interface, SocketSet<'static>, crate::tests::TestingDevice) {
+    let mut device = crate::tests::TestingDevice::new(Medium::Ethernet);
+    let mut config = Config::new(EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]).into());
+    config.acd_policy = policy;
+    config.ipv4_link_local = link_local;
+    let iface = Interface::new(config, &mut device, Instant::ZERO);
+    (iface, SocketSet::new(vec![]), device)
+}
+
+/// Run the conflict detection until `until`, and return the ARP packets sent by the interface
+/// with the time they were sent at.
+#[cfg(feature = "proto-ipv4-acd")]
+fn acd_run(
+    iface: &mut Interface,
+    device: &mut crate::tests::TestingDevice,
+    until: Instant,
+) -> Vec<(Instant, ArpRepr)> {
+    let mut sent = Vec::new();
+    while let Some(at) = iface.inner.acd_poll_at().filter(|at| *at <= until) {
+        iface.inner.now = at.max(iface.inner.now);
+        iface.acd_egress(device);
+        for frame in recv_all(device, iface.inner.now) {
+            let eth_frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+            assert_eq!(eth_frame.dst_addr(), EthernetAddress::BROADCAST);
+            assert_eq!(eth_frame.ethertype(), EthernetProtocol::Arp);
+            let arp_repr =
+                ArpRepr::parse(&ArpPacket::new_checked(eth_frame.payload()).unwrap()).unwrap();
+            sent.push((iface.inner.now, arp_repr));
+        }
+    }
+    iface.inner.now = until;
+    sent
+}
+
+#[cfg(feature = "medium-ethernet")]
+fn process_arp(
+    iface: &mut Interface,
+    sockets: &mut SocketSet,
+    arp_repr: ArpRepr,
+) -> Option<ArpRepr> {
+    let ArpRepr::EthernetIpv4 {
+        source_hardware_addr,
+        ..
+    } = arp_repr;
+
+    let mut eth_bytes = vec![0u8; 42];
+    let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes[..]);
+    frame.set_dst_addr(EthernetAddress::BROADCAST);
+    frame.set_src_addr(source_hardware_addr);
+    frame.set_ethertype(EthernetProtocol::Arp);
+    arp_repr.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+
+    let response = iface.inner.process_ethernet(
+        sockets,
+        PacketMeta::default(),
+        &eth_bytes,
+        &mut iface.fragments,
+    );
+    match response {
+        Some(EthernetPacket::Arp(arp_repr)) => Some(arp_repr),
+        _ => None,
+    }
+}
+
+/// An ARP packet from another host using `addr`, or probing it.
+#[cfg(feature = "proto-ipv4-acd")]
+fn acd_other_host(operation: ArpOperation, source: Ipv4Address, target: Ipv4Address) -> ArpRepr {
+    ArpRepr::EthernetIpv4 {
+        operation,
+        source_hardware_addr: EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x03]),
+        source_protocol_addr: source,
+        target_hardware_addr: EthernetAddress([0; 6]),
+        target_protocol_addr: target,
+    }
+}
+
+#[test]
+#[cfg(feature = "proto-ipv4-acd")]
+fn test_acd_probe_announce() {
+    let addr = Ipv4Address::new(192, 168, 1, 1);
+    let hardware_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+
+    let (mut iface, _sockets, mut device) = acd_setup(Some(AcdPolicy::DefendOnce), false);
+    iface.update_ip_addrs(|addrs| {
+        addrs.push(IpCidr::new(addr.into(), 24)).unwrap();
+    });
+
+    // The address is not used while it is probed.
+    assert_eq!(
+        iface.ipv4_addr_state(&addr),
+        Some(Ipv4AddressState::Probing)
+    );
+    assert!(!iface.has_ip_addr(addr));
+    assert_eq!(iface.get_source_address_ipv4(&addr), None);
+    assert!(iface.inner.acd_poll_at().unwrap() <= Instant::from_secs(1));
+
+    // Three probes are sent from the unspecified address, one to two seconds apart, then two
+    // announcements from the address, the first one two seconds after the last probe.
+    let sent = acd_run(&mut iface, &mut device, Instant::from_secs(20));
+    assert_eq!(sent.len(), 5);
+    let (probes, announcements) = sent.split_at(3);
+    for (_, arp_repr) in probes.iter() {
+        assert_eq!(
+            *arp_repr,
+            ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Request,
+                source_hardware_addr: hardware_addr,
+                source_protocol_addr: Ipv4Address::UNSPECIFIED,
+                target_hardware_addr: EthernetAddress([0; 6]),
+                target_protocol_addr: addr,
+            }
+        );
+    }
+    for pair in probes.windows(2) {
+        let interval = pair[1].0 - pair[0].0;
+        assert!(interval >= Duration::from_secs(1) && interval <= Duration::from_secs(2));
+    }
+    assert_eq!(announcements[0].0, probes[2].0 + Duration::from_secs(2));
+    assert_eq!(announcements[1].0, probes[2].0 + Duration::from_secs(4));
+    for (_, arp_repr) in announcements.iter() {
+        assert_eq!(
+            *arp_repr,
+            ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Request,
+                source_hardware_addr: hardware_addr,
+                source_protocol_addr: addr,
+                target_hardware_addr: EthernetAddress([0; 6]),
+                target_protocol_addr: addr,
+            }
+        );
+    }
+    assert_eq!(
+        iface.ipv4_addr_state(&addr),
+        Some(Ipv4AddressState::Assigned)
+    );
+    assert!(iface.has_ip_addr(addr));
+    assert_eq!(iface.get_source_address_ipv4(&addr), Some(addr));
+    assert_eq!(iface.poll_acd_event(), Some(AcdEvent::Assigned(addr)));
+    assert_eq!(iface.poll_acd_event(), None);
+    assert_eq!(iface.inner.acd_poll_at(), None);
+}
+
+#[rstest]
+#[case::reply(ArpOperation::Reply, true)]
+#[case::probe(ArpOperation::Request, false)]
+#[cfg(feature = "proto-ipv4-acd")]
+fn test_acd_probe_conflict(#[case] operation: ArpOperation, #[case] from_addr: bool) {
+    let addr = Ipv4Address::new(192, 168, 1, 1);
+
+    let (mut iface, mut sockets, mut device) = acd_setup(Some(AcdPolicy::DefendOnce), false);
+    iface.update_ip_addrs(|addrs| {
+        addrs.push(IpCidr::new(addr.into(), 24)).unwrap();
+    });
+    assert_eq!(
+        acd_run(&mut iface, &mut device, Instant::from_secs(1)).len(),
+        1
+    );
+
+    // Another host answers the probe, or probes the address too.
+    let source = if from_addr {
+        addr
+    } else {
+        Ipv4Address::UNSPECIFIED
+    };
+    process_arp(
+        &mut iface,
+        &mut sockets,
+        acd_other_host(operation, source, addr),
+    );
+
+    assert_eq!(
+        iface.ipv4_addr_state(&addr),
+        Some(Ipv4AddressState::Conflict)
+    );
+    assert!(!iface.has_ip_addr(addr));
+    assert_eq!(iface.poll_acd_event(), Some(AcdEvent::Conflict(addr)));
+    assert!(acd_run(&mut iface, &mut device, Instant::from_secs(20)).is_empty());
+}
+
+#[rstest]
+#[case::abandon(AcdPolicy::Abandon)]
+#[case::defend_once(AcdPolicy::DefendOnce)]
+#[case::defend(AcdPolicy::Defend)]
+#[cfg(feature = "proto-ipv4-acd")]
+fn test_acd_defend(#[case] policy: AcdPolicy) {
+    let addr = Ipv4Address::new(192, 168, 1, 1);
+    let other = Ipv4Address::new(192, 168, 1, 2);
+
+    let (mut iface, mut sockets, mut device) = acd_setup(Some(policy), false);
+    iface.update_ip_addrs(|addrs| {
+        addrs.push(IpCidr::new(addr.into(), 24)).unwrap();
+    });
+    acd_run(&mut iface, &mut device, Instant::from_secs(20));
+    assert_eq!(iface.poll_acd_event(), Some(AcdEvent::Assigned(addr)));
+
+    // Another host uses the address.
+    let conflict = acd_other_host(ArpOperation::Request, addr, other);
+    process_arp(&mut iface, &mut sockets, conflict);
+    let announcements = acd_run(&mut iface, &mut device, Instant::from_secs(25));
+    match policy {
+        AcdPolicy::Abandon => {
+            assert_eq!(iface.poll_acd_event(), Some(AcdEvent::Conflict(addr)));
+            assert_eq!(
+                iface.ipv4_addr_state(&addr),
+                Some(Ipv4AddressState::Conflict)
+            );
+            assert!(announcements.is_empty());
+            return;
+        }
+        AcdPolicy::DefendOnce | AcdPolicy::Defend => {
+            assert_eq!(iface.poll_acd_event(), Some(AcdEvent::Defended(addr)));
+            assert_eq!(
+                iface.ipv4_addr_state(&addr),
+                Some(Ipv4AddressState::Assigned)
+            );
+            assert_eq!(announcements.len(), 1);
+        }
+    }
+
+    // A second conflict within ten seconds.
+    process_arp(&mut iface, &mut sockets, conflict);
+    let announcements = acd_run(&mut iface, &mut device, Instant::from_secs(29));
+    assert!(announcements.is_empty());
+    match policy {
+        AcdPolicy::DefendOnce => {
+            assert_eq!(iface.poll_acd_event(), Some(AcdEvent::Conflict(addr)));
+            assert_eq!(
+                iface.ipv4_addr_state(&addr),
+                Some(Ipv4AddressState::Conflict)
+            );
+        }
+        _ => {
+            assert_eq!(iface.poll_acd_event(), None);
+            assert_eq!(
+                iface.ipv4_addr_state(&addr),
+                Some(Ipv4AddressState::Assigned)
+            );
+
+            // The address is defended again after ten seconds.
+            acd_run(&mut iface, &mut device, Instant::from_secs(30));
+            process_arp(&mut iface, &mut sockets, conflict);
+            assert_eq!(iface.poll_acd_event(), Some(AcdEvent::Defended(addr)));
+            assert_eq!(
+                acd_run(&mut iface, &mut device, Instant::from_secs(31)).len(),
+                1
+            );
+        }
+    }
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_handle_arp_probe(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    let mut eth_bytes = vec![0u8; 42];
+
+    let local_ip_addr = Ipv4Address([192, 168, 1, 1]);
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+
+    let repr = ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Request,
+        source_hardware_addr: remote_hw_addr,
+        source_protocol_addr: Ipv4Address::UNSPECIFIED,
+        target_hardware_addr: EthernetAddress::default(),
+        target_protocol_addr: local_ip_addr,
+    };
+
+    let mut frame = EthernetFrame::new_unchecked(&mut eth_bytes);
+    frame.set_dst_addr(EthernetAddress::BROADCAST);
+    frame.set_src_addr(remote_hw_addr);
+    frame.set_ethertype(EthernetProtocol::Arp);
+    let mut packet = ArpPacket::new_unchecked(frame.payload_mut());
+    repr.emit(&mut packet);
+
+    // Ensure an ARP probe for our address is answered, so that the prober sees the conflict
+    assert_eq!(
+        iface.inner.process_ethernet(
+            &mut sockets,
+            PacketMeta::default(),
+            frame.into_inner(),
+            &mut iface.fragments
+        ),
+        Some(EthernetPacket::Arp(ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Reply,
+            source_hardware_addr: local_hw_addr,
+            source_protocol_addr: local_ip_addr,
+            target_hardware_addr: remote_hw_addr,
+            target_protocol_addr: Ipv4Address::UNSPECIFIED,
+        }))
+    );
+}
+
+#[test]
+#[cfg(feature = "proto-ipv4-acd")]
+fn test_ipv4_link_local() {
+    let (mut iface, mut sockets, mut device) = acd_setup(None, true);
+
+    // The first address is derived from the hardware address.
+    assert_eq!(iface.inner.acd_poll_at(), Some(Instant::ZERO));
+    acd_run(&mut iface, &mut device, Instant::ZERO);
+    let addr = Ipv4Address::new(169, 254, 7, 0);
+    assert_eq!(iface.ip_addrs(), &[IpCidr::new(addr.into(), 16)]);
+    assert_eq!(
+        iface.ipv4_addr_state(&addr),
+        Some(Ipv4AddressState::Probing)
+    );
+
+    // Another address is picked after a conflict.
+    process_arp(
+        &mut iface,
+        &mut sockets,
+        acd_other_host(ArpOperation::Reply, addr, addr),
+    );
+    assert_eq!(iface.poll_acd_event(), Some(AcdEvent::Conflict(addr)));
+    assert!(iface.ip_addrs().is_empty());
+
+    let sent = acd_run(&mut iface, &mut device, Instant::from_secs(20));
+    let IpCidr::Ipv4(cidr) = iface.ip_addrs()[0] else {
+        unreachable!()
+    };
+    let new_addr = cidr.address();
+    assert_ne!(new_addr, addr);
+    assert!(new_addr.is_link_local());
+    assert_eq!(cidr.prefix_len(), 16);
+    assert_eq!(sent.len(), 5);
+    assert_eq!(iface.poll_acd_event(), Some(AcdEvent::Assigned(new_addr)));
+    assert_eq!(
+        iface.ipv4_addr_state(&new_addr),
+        Some(Ipv4AddressState::Assigned)
+    );
+}
+
+#[rstest]
+#[case::reply(true)]
+#[case::no_reply(false)]
+#[cfg(feature = "medium-ethernet")]
+fn test_arp_neighbor_unreachability(#[case] reply: bool) {
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ethernet);
+
+    let local_ip_addr = Ipv4Address([192, 168, 1, 1]);
+    let remote_ip_addr = Ipv4Address([192, 168, 1, 2]);
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+
+    iface
+        .inner
+        .neighbor_cache
+        .fill(remote_ip_addr.into(), remote_hw_addr.into(), Instant::ZERO);
+
+    // The neighbor is stale after the reachable time, a packet sent to it delays a probe.
+    iface.inner.now = Instant::from_secs(30);
+    assert_eq!(
+        iface.inner.lookup_hardware_addr(
+            MockTxToken,
+            &IpAddress::Ipv4(local_ip_addr),
+            &IpAddress::Ipv4(remote_ip_addr),
+            &mut iface.fragmenter,
+        ),
+        Ok((HardwareAddress::Ethernet(remote_hw_addr), MockTxToken))
+    );
+    assert!(!iface.neighbor_egress(&mut device, &mut sockets));
+    assert_eq!(iface.inner.neighbor_poll_at(), Some(Instant::from_secs(35)));
+
+    // Unicast probes are sent to the cached hardware address.
+    let probe = |iface: &mut Interface,
+                 device: &mut crate::tests::TestingDevice,
+                 sockets: &mut SocketSet| {
+        assert!(iface.neighbor_egress(device, sockets));
+        let frames = recv_all(device, iface.inner.now);
+        assert_eq!(frames.len(), 1);
+        let eth_frame = EthernetFrame::new_checked(&frames[0][..]).unwrap();
+        assert_eq!(eth_frame.dst_addr(), remote_hw_addr);
+        assert_eq!(
+            ArpRepr::parse(&ArpPacket::new_checked(eth_frame.payload()).unwrap()).unwrap(),
+            ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Request,
+                source_hardware_addr: local_hw_addr,
+                source_protocol_addr: local_ip_addr,
+                target_hardware_addr: remote_hw_addr,
+                target_protocol_addr: remote_ip_addr,
+            }
+        );
+    };
+    iface.inner.now = Instant::from_secs(35);
+    probe(&mut iface, &mut device, &mut sockets);
+
+    if reply {
+        // A reply confirms the neighbor is reachable.
+        process_arp(
+            &mut iface,
+            &mut sockets,
+            ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Reply,
+                source_hardware_addr: remote_hw_addr,
+                source_protocol_addr: remote_ip_addr,
+                target_hardware_addr: local_hw_addr,
+                target_protocol_addr: local_ip_addr,
+            },
+        );
+        assert_eq!(
+            iface
+                .inner
+                .neighbor_cache
+                .state(&remote_ip_addr.into(), iface.inner.now),
+            Some(NeighborState::Reachable)
+        );
+        assert_eq!(iface.inner.neighbor_poll_at(), None);
+        return;
+    }
+
+    for secs in 36..38 {
+        iface.inner.now = Instant::from_secs(secs);
+        probe(&mut iface, &mut device, &mut sockets);
+    }
+
+    // The neighbor is evicted when no probe is answered.
+    iface.inner.now = Instant::from_secs(38);
+    assert!(!iface.neighbor_egress(&mut device, &mut sockets));
+    assert!(!iface.inner.has_neighbor(&remote_ip_addr.into()));
+    assert_eq!(iface.inner.neighbor_poll_at(), None);
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_arp_gratuitous_update(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    let remote_ip_addr = Ipv4Address([192, 168, 1, 2]);
+    let old_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+    let new_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x01]);
+
+    iface
+        .inner
+        .neighbor_cache
+        .fill(remote_ip_addr.into(), old_hw_addr.into(), Instant::ZERO);
+
+    // A gratuitous ARP request updates the cached hardware address, although it is not for us.
+    process_arp(
+        &mut iface,
+        &mut sockets,
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: new_hw_addr,
+            source_protocol_addr: remote_ip_addr,
+            target_hardware_addr: EthernetAddress::default(),
+            target_protocol_addr: remote_ip_addr,
+        },
+    );
+    assert_eq!(
+        iface
+            .inner
+            .neighbor_cache
+            .lookup(&remote_ip_addr.into(), iface.inner.now),
+        NeighborAnswer::Found(new_hw_addr.into())
+    );
+    assert_eq!(
+        iface
+            .inner
+            .neighbor_cache
+            .state(&remote_ip_addr.into(), iface.inner.now),
+        Some(NeighborState::Stale)
+    );
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(all(feature = "medium-ethernet", feature = "socket-tcp"))]
+fn test_confirm_reachable_gateway(#[case] medium: Medium) {
+    let (mut iface, _sockets, _device) = setup(medium);
+
+    let gateway = Ipv4Address([192, 168, 1, 254]);
+    iface.routes_mut().add_default_ipv4_route(gateway).unwrap();
+    iface.inner.neighbor_cache.fill_stale(
+        gateway.into(),
+        EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]).into(),
+        Instant::ZERO,
+    );
+
+    // Forward progress with a remote host confirms the reachability of the gateway.
+    iface
+        .inner
+        .confirm_reachable(&Ipv4Address([8, 8, 8, 8]).into());
+    assert_eq!(
+        iface
+            .inner
+            .neighbor_cache
+            .state(&gateway.into(), iface.inner.now),
+        Some(NeighborState::Reachable)
+    );
+}
+
+#[cfg(feature = "medium-ethernet")]
+fn dispatch_echo_request(
+    iface: &mut Interface,
+    device: &mut crate::tests::TestingDevice,
+    src_addr: Ipv4Address,
+    dst_addr: Ipv4Address,
+    seq_no: u16,
+) -> Result<(), DispatchError> {
+    let icmp_repr = Icmpv4Repr::EchoRequest {
+        ident: 0x1234,
+        seq_no,
+        data: &[0xaa; 8],
+    };
+    let packet = Packet::new_ipv4(
+        Ipv4Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::Icmp,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+        },
+        IpPayload::Icmpv4(icmp_repr),
+    );
+
+    let tx_token = device.transmit(iface.inner.now).unwrap();
+    iface.inner.dispatch_ip(
+        tx_token,
+        PacketMeta::default(),
+        packet,
+        &mut iface.fragmenter,
+    )
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_neighbor_pending_flush(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    let local_ip_addr = Ipv4Address([192, 168, 1, 1]);
+    let remote_ip_addr = Ipv4Address([192, 168, 1, 2]);
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+
+    // The packets are queued while the address is resolved, until the queue is full.
+    for seq_no in 0..IFACE_NEIGHBOR_PENDING_COUNT as u16 {
+        assert_eq!(
+            dispatch_echo_request(
+                &mut iface,
+                &mut device,
+                local_ip_addr,
+                remote_ip_addr,
+                seq_no
+            ),
+            Ok(())
+        );
+    }
+    assert_eq!(
+        dispatch_echo_request(&mut iface, &mut device, local_ip_addr, remote_ip_addr, 0xff),
+        Err(DispatchError::NeighborPending)
+    );
+    let frames = recv_all(&mut device, iface.inner.now);
+    assert_eq!(frames.len(), 1);
+    assert_eq!(
+        EthernetFrame::new_checked(&frames[0][..])
+            .unwrap()
+            .ethertype(),
+        EthernetProtocol::Arp
+    );
+    assert_eq!(iface.inner.neighbor_poll_at(), Some(Instant::from_secs(1)));
+
+    // The queued packets are sent in order when the reply arrives.
+    process_arp(
+        &mut iface,
+        &mut sockets,
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Reply,
+            source_hardware_addr: remote_hw_addr,
+            source_protocol_addr: remote_ip_addr,
+            target_hardware_addr: local_hw_addr,
+            target_protocol_addr: local_ip_addr,
+        },
+    );
+    assert_eq!(iface.inner.neighbor_poll_at(), Some(Instant::ZERO));
+    assert!(iface.neighbor_egress(&mut device, &mut sockets));
+
+    let frames = recv_all(&mut device, iface.inner.now);
+    assert_eq!(frames.len(), IFACE_NEIGHBOR_PENDING_COUNT);
+    for (seq_no, frame) in frames.iter().enumerate() {
+        let eth_frame = EthernetFrame::new_checked(&frame[..]).unwrap();
+        assert_eq!(eth_frame.dst_addr(), remote_hw_addr);
+        assert_eq!(eth_frame.ethertype(), EthernetProtocol::Ipv4);
+
+        let ipv4_packet = Ipv4Packet::new_checked(eth_frame.payload()).unwrap();
+        assert_eq!(ipv4_packet.dst_addr(), remote_ip_addr);
+        let icmp_packet = Icmpv4Packet::new_checked(ipv4_packet.payload()).unwrap();
+        assert_eq!(icmp_packet.echo_seq_no(), seq_no as u16);
+    }
+    assert_eq!(iface.inner.neighbor_poll_at(), None);
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_neighbor_pending_unreachable(#[case] medium: Medium) {
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    let local_ip_addr = Ipv4Address([192, 168, 1, 1]);
+    let remote_ip_addr = Ipv4Address([192, 168, 1, 2]);
+    let other_ip_addr = Ipv4Address([192, 168, 1, 3]);
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+    let other_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x03]);
+
+    iface
+        .inner
+        .neighbor_cache
+        .fill(other_ip_addr.into(), other_hw_addr.into(), Instant::ZERO);
+
+    // A packet from another host, and one of ours, wait for the address resolution.
+    for src_addr in [other_ip_addr, local_ip_addr] {
+        assert_eq!(
+            dispatch_echo_request(&mut iface, &mut device, src_addr, remote_ip_addr, 0),
+            Ok(())
+        );
+    }
+    assert_eq!(recv_all(&mut device, iface.inner.now).len(), 1);
+
+    // The request is retransmitted from one of our addresses.
+    for secs in 1..NeighborCache::MAX_MULTICAST_SOLICIT as i64 {
+        iface.inner.now = Instant::from_secs(secs);
+        assert_eq!(iface.inner.neighbor_poll_at(), Some(iface.inner.now));
+        assert!(iface.neighbor_egress(&mut device, &mut sockets));
+
+        let frames = recv_all(&mut device, iface.inner.now);
+        assert_eq!(frames.len(), 1);
+        let eth_frame = EthernetFrame::new_checked(&frames[0][..]).unwrap();
+        assert_eq!(eth_frame.dst_addr(), EthernetAddress::BROADCAST);
+        assert_eq!(
+            ArpRepr::parse(&ArpPacket::new_checked(eth_frame.payload()).unwrap()).unwrap(),
+            ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Request,
+                source_hardware_addr: local_hw_addr,
+                source_protocol_addr: local_ip_addr,
+                target_hardware_addr: EthernetAddress::BROADCAST,
+                target_protocol_addr: remote_ip_addr,
+            }
+        );
+    }
+
+    // The packets are discarded when the resolution fails, the other host is notified.
+    iface.inner.now = Instant::from_secs(NeighborCache::MAX_MULTICAST_SOLICIT as i64);
+    assert!(iface.neighbor_egress(&mut device, &mut sockets));
+
+    let frames = recv_all(&mut device, iface.inner.now);
+    assert_eq!(frames.len(), 1);
+    let eth_frame = EthernetFrame::new_checked(&frames[0][..]).unwrap();
+    assert_eq!(eth_frame.dst_addr(), other_hw_addr);
+    let ipv4_packet = Ipv4Packet::new_checked(eth_frame.payload()).unwrap();
+    let ipv4_repr = Ipv4Repr::parse(&ipv4_packet, &ChecksumCapabilities::default()).unwrap();
+    assert_eq!(ipv4_repr.src_addr, local_ip_addr);
+    assert_eq!(ipv4_repr.dst_addr, other_ip_addr);
+    let icmp_packet = Icmpv4Packet::new_checked(ipv4_packet.payload()).unwrap();
+    assert_eq!(icmp_packet.msg_type(), Icmpv4Message::DstUnreachable);
+    assert_eq!(
+        icmp_packet.msg_code(),
+        u8::from(Icmpv4DstUnreachable::HostUnreachable)
+    );
+    assert_eq!(iface.inner.neighbor_poll_at(), None);
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(all(
+    feature = "socket-icmp",
+    feature = "socket-udp",
+    feature = "medium-ethernet"
+))]
+fn test_neighbor_pending_unreachable_local(#[case] medium: Medium) {
+    use crate::socket::udp;
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    let remote_ip_addr = Ipv4Address([192, 168, 1, 2]);
+
+    let udp_rx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 15]);
+    let udp_tx_buffer = udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY], vec![0; 15]);
+    let mut udp_socket = udp::Socket::new(udp_rx_buffer, udp_tx_buffer);
+    udp_socket.bind(68).unwrap();
+    udp_socket
+        .send_slice(b"hello", (IpAddress::Ipv4(remote_ip_addr), 67))
+        .unwrap();
+    sockets.add(udp_socket);
+
+    let icmp_rx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 128]);
+    let icmp_tx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![0; 128]);
+    let mut icmp_socket = icmp::Socket::new(icmp_rx_buffer, icmp_tx_buffer);
+    icmp_socket.bind(icmp::Endpoint::Udp(68.into())).unwrap();
+    let icmp_handle = sockets.add(icmp_socket);
+
+    // The datagram waits for the address resolution, which fails.
+    for secs in 0..NeighborCache::MAX_MULTICAST_SOLICIT as i64 {
+        iface.poll(Instant::from_secs(secs), &mut device, &mut sockets);
+        assert!(!sockets.get_mut::<icmp::Socket>(icmp_handle).can_recv());
+        recv_all(&mut device, iface.inner.now);
+    }
+
+    // The datagram is discarded, and the error is delivered to our sockets.
+    iface.poll(
+        Instant::from_secs(NeighborCache::MAX_MULTICAST_SOLICIT as i64),
+        &mut device,
+        &mut sockets,
+    );
+    assert!(recv_all(&mut device, iface.inner.now).is_empty());
+
+    let (data, addr) = sockets.get_mut::<icmp::Socket>(icmp_handle).recv().unwrap();
+    assert_eq!(addr, IpAddress::Ipv4(Ipv4Address([192, 168, 1, 1])));
+    let icmp_packet = Icmpv4Packet::new_checked(data).unwrap();
+    assert_eq!(icmp_packet.msg_type(), Icmpv4Message::DstUnreachable);
+    assert_eq!(
+        icmp_packet.msg_code(),
+        u8::from(Icmpv4DstUnreachable::HostUnreachable)
+    );
+    let ipv4_packet = Ipv4Packet::new_checked(icmp_packet.data()).unwrap();
+    assert_eq!(ipv4_packet.dst_addr(), remote_ip_addr);
+}
+
+#[rstest]
+#[case(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn test_static_neighbor(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    let local_ip_addr = Ipv4Address([192, 168, 1, 1]);
+    let gateway = Ipv4Address([192, 168, 1, 254]);
+    let local_hw_addr = EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]);
+    let gateway_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+    let spoofed_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x66]);
+
+    assert_eq!(
+        iface.add_static_neighbor(gateway.into(), gateway_hw_addr.into()),
+        Ok(())
+    );
+
+    // An ARP reply does not override the static neighbor.
+    process_arp(
+        &mut iface,
+        &mut sockets,
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Reply,
+            source_hardware_addr: spoofed_hw_addr,
+            source_protocol_addr: gateway,
+            target_hardware_addr: local_hw_addr,
+            target_protocol_addr: local_ip_addr,
+        },
+    );
+    iface.flush_neighbors();
+    iface.inner.now = Instant::from_secs(3600);
+
+    let neighbors: Vec<_> = iface.neighbors().collect();
+    assert_eq!(neighbors.len(), 1);
+    let (protocol_addr, neighbor) = neighbors[0];
+    assert_eq!(protocol_addr, IpAddress::Ipv4(gateway));
+    assert_eq!(neighbor.hardware_addr(), Some(gateway_hw_addr.into()));
+    assert_eq!(neighbor.state(), NeighborState::Static);
+    assert_eq!(neighbor.expires_at(), None);
+    assert_eq!(
+        iface.inner.lookup_hardware_addr(
+            MockTxToken,
+            &IpAddress::Ipv4(local_ip_addr),
+            &IpAddress::Ipv4(gateway),
+            &mut iface.fragmenter,
+        ),
+        Ok((HardwareAddress::Ethernet(gateway_hw_addr), MockTxToken))
+    );
+
+    // The static neighbor is removed explicitly.
+    assert_eq!(iface.remove_neighbor(&gateway.into()), Some(neighbor));
+    assert_eq!(iface.neighbors().count(), 0);
+}
+
+#[cfg(all(feature = "medium-ip", feature = "socket-tcp"))]
+fn process_tcp_segment(
+    iface: &mut Interface,
+    sockets: &mut SocketSet,
+    tcp_repr: &TcpRepr,
+) -> Option<TcpRepr<'static>> {
+    let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+        src_addr: Ipv4Address([192, 168, 1, 2]),
+        dst_addr: Ipv4Address([192, 168, 1, 1]),
+        next_header: IpProtocol::Tcp,
+        payload_len: tcp_repr.buffer_len(),
+        hop_limit: 64,
+    });
+    let mut bytes = vec![0u8; tcp_repr.buffer_len()];
+    tcp_repr.emit(
+        &mut TcpPacket::new_unchecked(&mut bytes),
+        &ip_repr.src_addr(),
+        &ip_repr.dst_addr(),
+        &ChecksumCapabilities::default(),
+    );
+
+    let reply = iface.inner.process_tcp(sockets, ip_repr, &bytes)?;
+    match reply.payload() {
+        IpPayload::Tcp(repr) => Some(TcpRepr {
+            payload: &[],
+            ..*repr
+        }),
+        _ => unreachable!(),
+    }
+}
+
+#[rstest]
+#[case(Medium::Ip)]
+#[cfg(all(feature = "medium-ip", feature = "socket-tcp"))]
+fn test_tcp_syn_cookies(#[case] medium: Medium) {
+    use crate::socket::tcp::{self, State, SynCookie};
+
+    let (mut iface, mut sockets, _device) = setup(medium);
+    iface.inner.syn_cookies = Some(SynCookies::new(&mut iface.inner.rand));
+
+    let listen = |sockets: &mut SocketSet<'_>| {
+        let mut socket = tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0; 64]),
+            tcp::SocketBuffer::new(vec![0; 64]),
+        );
+        socket.listen(80).unwrap();
+        sockets.add(socket)
+    };
+    let syn = |src_port, seq_number| TcpRepr {
+        src_port,
+        dst_port: 80,
+        control: TcpControl::Syn,
+        seq_number: TcpSeqNumber(seq_number),
+        ack_number: None,
+        window_len: 256,
+        window_scale: Some(7),
+        max_seg_size: Some(1300),
+        sack_permitted: true,
+        sack_ranges: [None, None, None],
+        timestamp: None,
+        payload: &[],
+    };
+    let ack = |src_port, seq_number, ack_number| TcpRepr {
+        src_port,
+        dst_port: 80,
+        control: TcpControl::None,
+        seq_number: TcpSeqNumber(seq_number),
+        ack_number: Some(ack_number),
+        window_len: 256,
+        window_scale: None,
+        max_seg_size: None,
+        sack_permitted: false,
+        sack_ranges: [None, None, None],
+        timestamp: None,
+        payload: &[],
+    };
+    let handle = listen(&mut sockets);
+
+    // The first SYN is accepted by the listening socket.
+    let reply = process_tcp_segment(&mut iface, &mut sockets, &syn(49152, 1000));
+    assert_eq!(reply, None);
+    assert_eq!(
+        sockets.get::<tcp::Socket>(handle).state(),
+        State::SynReceived
+    );
+
+    // No socket listens anymore, the next SYN is answered with a SYN cookie.
+    let reply = process_tcp_segment(&mut iface, &mut sockets, &syn(49153, 2000)).unwrap();
+    assert_eq!(reply.control, TcpControl::Syn);
+    assert_eq!(reply.ack_number, Some(TcpSeqNumber(2001)));
+    assert_eq!(reply.window_len, 64);
+    assert_eq!(reply.window_scale, Some(0));
+    assert!(reply.sack_permitted);
+    assert_eq!(reply.max_seg_size, Some((iface.inner.ip_mtu() - 40) as u16));
+    let cookie = reply.seq_number;
+    assert_eq!(
+        sockets.get::<tcp::Socket>(handle).remote_endpoint(),
+        Some(IpEndpoint::new(Ipv4Address([192, 168, 1, 2]).into(), 49152))
+    );
+
+    // The cookie encodes the options of the SYN.
+    let ip_repr = IpRepr::Ipv4(Ipv4Repr {
+        src_addr: Ipv4Address([192, 168, 1, 2]),
+        dst_addr: Ipv4Address([192, 168, 1, 1]),
+        next_header: IpProtocol::Tcp,
+        payload_len: 20,
+        hop_limit: 64,
+    });
+    assert_eq!(
+        iface.inner.syn_cookies.as_ref().unwrap().decode(
+            iface.inner.now,
+            &ip_repr,
+            &ack(49153, 2001, cookie + 1)
+        ),
+        Some(SynCookie {
+            mss: 1220,
+            window_scale: Some(7),
+            sack_permitted: true,
+        })
+    );
+
+    // A forged cookie is reset.
+    let reply = process_tcp_segment(&mut iface, &mut sockets, &ack(49153, 2001, cookie + 2));
+    assert_eq!(reply.map(|reply| reply.control), Some(TcpControl::Rst));
+
+    // A valid cookie never replaces the half-open connection, and is reset while no socket
+    // listens.
+    let reply = process_tcp_segment(&mut iface, &mut sockets, &ack(49153, 2001, cookie + 1));
+    assert_eq!(reply.map(|reply| reply.control), Some(TcpControl::Rst));
+    let socket = sockets.get::<tcp::Socket>(handle);
+    assert_eq!(socket.state(), State::SynReceived);
+    assert_eq!(
+        socket.remote_endpoint(),
+        Some(IpEndpoint::new(Ipv4Address([192, 168, 1, 2]).into(), 49152))
+    );
+
+    // A cookie is valid for two periods.
+    let expired = process_tcp_segment(&mut iface, &mut sockets, &syn(49154, 3000)).unwrap();
+    iface.inner.now = Instant::from_secs(127);
+    let valid = process_tcp_segment(&mut iface, &mut sockets, &syn(49155, 4000)).unwrap();
+    let handle = listen(&mut sockets);
+    iface.inner.now = Instant::from_secs(128);
+
+    let ack_expired = ack(49154, 3001, expired.seq_number + 1);
+    let reply = process_tcp_segment(&mut iface, &mut sockets, &ack_expired);
+    assert_eq!(reply.map(|reply| reply.control), Some(TcpControl::Rst));
+    assert_eq!(sockets.get::<tcp::Socket>(handle).state(), State::Listen);
+
+    // The connection is accepted by the listening socket.
+    let ack_valid = ack(49155, 4001, valid.seq_number + 1);
+    let reply = process_tcp_segment(&mut iface, &mut sockets, &ack_valid);
+    assert_eq!(reply, None);
+    assert_eq!(
+        sockets.get::<tcp::Socket>(handle).state(),
+        State::Established
+    );
+}
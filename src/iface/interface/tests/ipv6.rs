@@ -13,6 +13,30 @@ fn parse_ipv6(data: &[u8]) -> crate::wire::Result<Packet<'_>> {
         IpProtocol::Ipv6Route => todo!(),
         IpProtocol::Ipv6Frag => todo!(),
         IpProtocol::IpSecEsp => todo!(),
+        #[cfg(feature = "proto-ipsec-ah")]
+        IpProtocol::IpSecAh => {
+            // Strip the AH, without verifying it.
+            let ah_header = IpSecAuthHeaderPacket::new_checked(ipv6_header.payload())?;
+            let ah = IpSecAuthHeaderRepr::parse(&ah_header)?;
+            match ah.next_header {
+                IpProtocol::Icmpv6 => {
+                    let ipv6 = Ipv6Repr {
+                        next_header: ah.next_header,
+                        payload_len: ah_header.payload().len(),
+                        ..ipv6
+                    };
+                    let icmp = Icmpv6Repr::parse(
+                        &ipv6.src_addr,
+                        &ipv6.dst_addr,
+                        &Icmpv6Packet::new_checked(ah_header.payload())?,
+                        &Default::default(),
+                    )?;
+                    Ok(Packet::new_ipv6(ipv6, IpPayload::Icmpv6(icmp)))
+                }
+                _ => todo!(),
+            }
+        }
+        #[cfg(not(feature = "proto-ipsec-ah"))]
         IpProtocol::IpSecAh => todo!(),
         IpProtocol::Icmpv6 => {
             let icmp = Icmpv6Repr::parse(
@@ -51,7 +75,8 @@ fn multicast_source_address(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -99,7 +124,8 @@ fn hop_by_hop_skip_with_icmp(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -134,7 +160,8 @@ fn hop_by_hop_discard_with_icmp(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -188,7 +215,8 @@ fn hop_by_hop_discard_param_problem(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -245,7 +273,8 @@ fn hop_by_hop_discard_with_multicast(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -304,7 +333,8 @@ fn imcp_empty_echo_request(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -364,7 +394,8 @@ fn icmp_echo_request(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -411,7 +442,8 @@ fn icmp_echo_reply_as_input(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -459,7 +491,8 @@ fn unknown_proto_with_multicast_dst_address(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -508,7 +541,8 @@ fn unknown_proto(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -552,7 +586,8 @@ fn ndsic_neighbor_advertisement_ethernet(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -608,7 +643,8 @@ fn ndsic_neighbor_advertisement_ethernet_multicast_addr(#[case] medium: Medium)
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -660,7 +696,8 @@ fn ndsic_neighbor_advertisement_ieee802154(#[case] medium: Medium) {
         iface.inner.process_ipv6(
             &mut sockets,
             PacketMeta::default(),
-            &Ipv6Packet::new_checked(&data[..]).unwrap()
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
         ),
         response
     );
@@ -1169,3 +1206,1441 @@ fn get_source_address_empty_interface() {
         Ipv6Address::LOOPBACK
     );
 }
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+#[cfg(feature = "proto-ipsec-ah")]
+fn ipsec_ah_echo_request(#[case] medium: Medium) {
+    use super::ipsec::{add_ah_sas, protect, transmit};
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    let local = Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 1]);
+    let remote = Ipv6Address::from_parts(&[0xfdbe, 0, 0, 0, 0, 0, 0, 2]);
+    add_ah_sas(&mut iface, remote.into());
+    protect(&mut iface, IpCidr::new(remote.into(), 64));
+
+    // The echo request of the peer, protected with the same security associations.
+    let icmp_request = Icmpv6Repr::EchoRequest {
+        ident: 0x1234,
+        seq_no: 1,
+        data: b"Hello",
+    };
+    let request = transmit(
+        &mut iface,
+        &mut device,
+        Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: remote,
+                dst_addr: local,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_request.buffer_len(),
+                hop_limit: 64,
+            },
+            IpPayload::Icmpv6(icmp_request),
+        ),
+    );
+
+    let icmp_reply = Icmpv6Repr::EchoReply {
+        ident: 0x1234,
+        seq_no: 1,
+        data: b"Hello",
+    };
+    let expected = || {
+        Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: local,
+                dst_addr: remote,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_reply.buffer_len(),
+                hop_limit: 64,
+            },
+            IpPayload::Icmpv6(icmp_reply),
+        )
+    };
+
+    assert_eq!(
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&request[..]).unwrap(),
+            Some(&mut iface.fragments)
+        ),
+        Some(expected())
+    );
+
+    // The reply is protected as well.
+    let reply = transmit(&mut iface, &mut device, expected());
+    let ipv6_packet = Ipv6Packet::new_checked(&reply[..]).unwrap();
+    assert_eq!(ipv6_packet.next_header(), IpProtocol::IpSecAh);
+    // Fixed fields + HMAC-SHA-256-128 + padding to 8 octets
+    assert_eq!(
+        IpSecAuthHeaderPacket::new_checked(ipv6_packet.payload())
+            .unwrap()
+            .header_len(),
+        32
+    );
+    assert_eq!(parse_ipv6(&reply), Ok(expected()));
+}
+
+#[cfg(feature = "proto-mld")]
+type MldRecord = (MldRecordType, Ipv6Address, Vec<Ipv6Address>);
+
+#[cfg(feature = "proto-mld")]
+fn recv_mld(
+    device: &mut crate::tests::TestingDevice,
+    timestamp: Instant,
+) -> Vec<(Ipv6Repr, Vec<MldRecord>)> {
+    let medium = device.capabilities().medium;
+    recv_all(device, timestamp)
+        .iter()
+        .filter_map(|frame| {
+            let ipv6_packet = match medium {
+                #[cfg(feature = "medium-ethernet")]
+                Medium::Ethernet => {
+                    let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+                    Ipv6Packet::new_checked(eth_frame.payload()).ok()?
+                }
+                #[cfg(feature = "medium-ip")]
+                Medium::Ip => Ipv6Packet::new_checked(&frame[..]).ok()?,
+                #[cfg(feature = "medium-ieee802154")]
+                Medium::Ieee802154 => todo!(),
+            };
+            let ipv6_repr = Ipv6Repr::parse(&ipv6_packet).ok()?;
+
+            // The reports carry a Router Alert option.
+            assert_eq!(ipv6_repr.next_header, IpProtocol::HopByHop);
+            let ext_header = Ipv6ExtHeader::new_checked(ipv6_packet.payload()).ok()?;
+            let ext_repr = Ipv6ExtHeaderRepr::parse(&ext_header).ok()?;
+            let hbh_header = Ipv6HopByHopHeader::new_checked(ext_repr.data).ok()?;
+            let hbh_repr = Ipv6HopByHopRepr::parse(&hbh_header).ok()?;
+            assert_eq!(
+                hbh_repr.options[0],
+                Ipv6OptionRepr::RouterAlert(Ipv6OptionRouterAlert::MulticastListenerDiscovery)
+            );
+
+            let icmp_repr = Icmpv6Repr::parse(
+                &ipv6_repr.src_addr,
+                &ipv6_repr.dst_addr,
+                &Icmpv6Packet::new_checked(&ipv6_packet.payload()[8..]).ok()?,
+                &Default::default(),
+            )
+            .ok()?;
+            let Icmpv6Repr::Mld(MldRepr::Report {
+                nr_mcast_addr_rcrds,
+                mut data,
+            }) = icmp_repr
+            else {
+                return None;
+            };
+
+            let mut records = Vec::new();
+            for _ in 0..nr_mcast_addr_rcrds {
+                let record = MldAddressRecord::new_checked(data).ok()?;
+                let sources = record.payload()[..16 * record.num_srcs() as usize]
+                    .chunks(16)
+                    .map(Ipv6Address::from_bytes)
+                    .collect();
+                records.push((record.record_type(), record.mcast_addr(), sources));
+                data = &data[20 + 16 * record.num_srcs() as usize..];
+            }
+            Some((ipv6_repr, records))
+        })
+        .collect()
+}
+
+#[cfg(feature = "proto-mld")]
+fn mld_query_bytes(src_addr: Ipv6Address, group: Ipv6Address, sources: &[Ipv6Address]) -> Vec<u8> {
+    let data: Vec<u8> = sources
+        .iter()
+        .flat_map(|s| s.as_bytes().iter().copied())
+        .collect();
+    let icmp_repr = Icmpv6Repr::Mld(MldRepr::Query {
+        max_resp_code: 1000,
+        mcast_addr: group,
+        s_flag: false,
+        qrv: 2,
+        qqic: 125,
+        num_srcs: sources.len() as u16,
+        data: &data,
+    });
+    // Queries about a group are sent to the group, see RFC 3810 § 5.1.15.
+    let dst_addr = if group.is_unspecified() {
+        Ipv6Address::LINK_LOCAL_ALL_NODES
+    } else {
+        group
+    };
+    let ipv6_repr = Ipv6Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: icmp_repr.buffer_len(),
+        hop_limit: 1,
+    };
+
+    let mut bytes = vec![0; ipv6_repr.buffer_len() + icmp_repr.buffer_len()];
+    ipv6_repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes[..]));
+    icmp_repr.emit(
+        &ipv6_repr.src_addr,
+        &ipv6_repr.dst_addr,
+        &mut Icmpv6Packet::new_unchecked(&mut bytes[ipv6_repr.buffer_len()..]),
+        &ChecksumCapabilities::default(),
+    );
+    bytes
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-mld", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-mld", feature = "medium-ethernet"))]
+fn mld_join_leave(#[case] medium: Medium) {
+    let group = Ipv6Address::new(0xff0e, 0, 0, 0, 0, 0, 0, 0x1234);
+    let link_local = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+
+    let (mut iface, _sockets, mut device) = setup(medium);
+
+    assert_eq!(
+        iface.join_multicast_group(&mut device, group, Instant::ZERO),
+        Ok(true)
+    );
+    assert!(iface.has_multicast_group(group));
+
+    let reports = recv_mld(&mut device, Instant::ZERO);
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].0.src_addr, link_local);
+    assert_eq!(
+        reports[0].0.dst_addr,
+        Ipv6Address::LINK_LOCAL_ALL_MLDV2_ROUTERS
+    );
+    assert_eq!(reports[0].0.hop_limit, 1);
+    assert_eq!(
+        reports[0].1,
+        vec![(MldRecordType::ChangeToExclude, group, vec![])]
+    );
+
+    // Joining again changes nothing.
+    assert_eq!(
+        iface.join_multicast_group(&mut device, group, Instant::ZERO),
+        Ok(false)
+    );
+
+    // The report is sent once more, within the unsolicited report interval.
+    let poll_at = iface.inner.mld_poll_at().unwrap();
+    assert!(poll_at < Instant::from_secs(1));
+    iface.inner.now = Instant::from_secs(1);
+    assert!(iface.mld_egress(&mut device));
+    let reports = recv_mld(&mut device, Instant::from_secs(1));
+    assert_eq!(
+        reports[0].1,
+        vec![(MldRecordType::ChangeToExclude, group, vec![])]
+    );
+    assert_eq!(iface.inner.mld_poll_at(), None);
+
+    // Leave, with a done message that is also sent twice.
+    assert_eq!(
+        iface.leave_multicast_group(&mut device, group, Instant::from_secs(2)),
+        Ok(true)
+    );
+    assert!(!iface.has_multicast_group(group));
+    let reports = recv_mld(&mut device, Instant::from_secs(2));
+    assert_eq!(
+        reports[0].1,
+        vec![(MldRecordType::ChangeToInclude, group, vec![])]
+    );
+
+    iface.inner.now = Instant::from_secs(3);
+    assert!(iface.mld_egress(&mut device));
+    assert_eq!(recv_mld(&mut device, Instant::from_secs(3)).len(), 1);
+    assert!(iface.inner.ipv6_multicast_groups.is_empty());
+    assert_eq!(iface.inner.mld_poll_at(), None);
+
+    // Leaving a group that is not joined sends nothing.
+    assert_eq!(
+        iface.leave_multicast_group(&mut device, group, Instant::from_secs(3)),
+        Ok(false)
+    );
+
+    // The all-nodes group is never reported.
+    assert_eq!(
+        iface.join_multicast_group(
+            &mut device,
+            Ipv6Address::LINK_LOCAL_ALL_NODES,
+            Instant::from_secs(3)
+        ),
+        Ok(false)
+    );
+    assert!(recv_mld(&mut device, Instant::from_secs(3)).is_empty());
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-mld", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-mld", feature = "medium-ethernet"))]
+fn mld_query(#[case] medium: Medium) {
+    let group = Ipv6Address::new(0xff0e, 0, 0, 0, 0, 0, 0, 0x1234);
+    let other_group = Ipv6Address::new(0xff0e, 0, 0, 0, 0, 0, 0, 0x5678);
+    let source = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0x10);
+    let other_source = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0x20);
+    let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x100);
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    iface
+        .join_multicast_group_with_sources(
+            &mut device,
+            group,
+            MulticastFilterMode::Include,
+            &[source],
+            Instant::ZERO,
+        )
+        .unwrap();
+    let reports = recv_mld(&mut device, Instant::ZERO);
+    assert_eq!(
+        reports[0].1,
+        vec![(MldRecordType::ChangeToInclude, group, vec![source])]
+    );
+    iface.inner.now = Instant::from_secs(1);
+    iface.mld_egress(&mut device);
+    recv_mld(&mut device, Instant::from_secs(1));
+
+    let mut process = |iface: &mut Interface, data: &[u8]| {
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(data).unwrap(),
+            Some(&mut iface.fragments),
+        );
+    };
+
+    // A general query is answered with the joined groups and the solicited-node group of the
+    // addresses of the interface, within the maximum response delay.
+    process(
+        &mut iface,
+        &mld_query_bytes(router, Ipv6Address::UNSPECIFIED, &[]),
+    );
+    assert!(iface.inner.mld_poll_at().unwrap() < Instant::from_secs(2));
+    iface.inner.now = Instant::from_secs(2);
+    assert!(iface.mld_egress(&mut device));
+    let reports = recv_mld(&mut device, Instant::from_secs(2));
+    assert_eq!(reports.len(), 1);
+    assert_eq!(
+        reports[0].1,
+        vec![
+            (MldRecordType::ModeIsInclude, group, vec![source]),
+            (
+                MldRecordType::ModeIsExclude,
+                Ipv6Address::new(0xff02, 0, 0, 0, 0, 1, 0xff00, 1),
+                vec![]
+            ),
+        ]
+    );
+    assert_eq!(iface.inner.mld_poll_at(), None);
+
+    // A query about a group is only answered when we listen to it.
+    process(&mut iface, &mld_query_bytes(router, other_group, &[]));
+    assert_eq!(iface.inner.mld_poll_at(), None);
+    process(&mut iface, &mld_query_bytes(router, group, &[]));
+    iface.inner.now = Instant::from_secs(3);
+    assert!(iface.mld_egress(&mut device));
+    let reports = recv_mld(&mut device, Instant::from_secs(3));
+    assert_eq!(
+        reports[0].1,
+        vec![(MldRecordType::ModeIsInclude, group, vec![source])]
+    );
+
+    // A query about sources is answered with the ones we listen to.
+    process(&mut iface, &mld_query_bytes(router, group, &[other_source]));
+    iface.inner.now = Instant::from_secs(4);
+    iface.mld_egress(&mut device);
+    assert!(recv_mld(&mut device, Instant::from_secs(4)).is_empty());
+    process(
+        &mut iface,
+        &mld_query_bytes(router, group, &[source, other_source]),
+    );
+    iface.inner.now = Instant::from_secs(5);
+    iface.mld_egress(&mut device);
+    let reports = recv_mld(&mut device, Instant::from_secs(5));
+    assert_eq!(
+        reports[0].1,
+        vec![(MldRecordType::ModeIsInclude, group, vec![source])]
+    );
+
+    // Queries from outside the link are ignored.
+    process(
+        &mut iface,
+        &mld_query_bytes(source, Ipv6Address::UNSPECIFIED, &[]),
+    );
+    assert_eq!(iface.inner.mld_poll_at(), None);
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(feature = "proto-mld", feature = "medium-ip"))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-mld", feature = "medium-ethernet"))]
+fn mld_source_filter(#[case] medium: Medium) {
+    let group = Ipv6Address::new(0xff0e, 0, 0, 0, 0, 0, 0, 0x1234);
+    let source = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0x10);
+    let other_source = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 0x20);
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+
+    let mut echo_from = |iface: &mut Interface, src_addr: Ipv6Address| {
+        let icmp_repr = Icmpv6Repr::EchoRequest {
+            ident: 1,
+            seq_no: 1,
+            data: b"ping",
+        };
+        let ipv6_repr = Ipv6Repr {
+            src_addr,
+            dst_addr: group,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+        };
+        let mut bytes = vec![0; ipv6_repr.buffer_len() + icmp_repr.buffer_len()];
+        ipv6_repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes[..]));
+        icmp_repr.emit(
+            &src_addr,
+            &group,
+            &mut Icmpv6Packet::new_unchecked(&mut bytes[ipv6_repr.buffer_len()..]),
+            &ChecksumCapabilities::default(),
+        );
+        let accepted = iface
+            .inner
+            .process_ipv6(
+                &mut sockets,
+                PacketMeta::default(),
+                &Ipv6Packet::new_checked(&bytes[..]).unwrap(),
+                Some(&mut iface.fragments),
+            )
+            .is_some();
+        accepted
+    };
+
+    assert!(!echo_from(&mut iface, source));
+
+    iface
+        .join_multicast_group_with_sources(
+            &mut device,
+            group,
+            MulticastFilterMode::Exclude,
+            &[other_source],
+            Instant::ZERO,
+        )
+        .unwrap();
+    assert!(echo_from(&mut iface, source));
+    assert!(!echo_from(&mut iface, other_source));
+
+    // Changing the filter is reported.
+    recv_mld(&mut device, Instant::ZERO);
+    assert_eq!(
+        iface.join_multicast_group_with_sources(
+            &mut device,
+            group,
+            MulticastFilterMode::Include,
+            &[other_source],
+            Instant::ZERO,
+        ),
+        Ok(true)
+    );
+    let reports = recv_mld(&mut device, Instant::ZERO);
+    assert_eq!(
+        reports[0].1,
+        vec![(MldRecordType::ChangeToInclude, group, vec![other_source])]
+    );
+    assert!(!echo_from(&mut iface, source));
+    assert!(echo_from(&mut iface, other_source));
+
+    // The sources must be unicast addresses of the same family.
+    assert_eq!(
+        iface.join_multicast_group_with_sources(
+            &mut device,
+            group,
+            MulticastFilterMode::Include,
+            &[group],
+            Instant::ZERO,
+        ),
+        Err(MulticastError::InvalidSource)
+    );
+}
+
+/// Receive the Router Solicitations sent by the interface.
+#[cfg(all(feature = "proto-slaac", feature = "medium-ethernet"))]
+fn recv_router_solicits(
+    device: &mut crate::tests::TestingDevice,
+    timestamp: Instant,
+) -> Vec<(Ipv6Repr, Option<RawHardwareAddress>)> {
+    let medium = device.capabilities().medium;
+    recv_all(device, timestamp)
+        .iter()
+        .filter_map(|frame| {
+            let ipv6_packet = match medium {
+                #[cfg(feature = "medium-ethernet")]
+                Medium::Ethernet => {
+                    let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+                    Ipv6Packet::new_checked(eth_frame.payload()).ok()?
+                }
+                #[cfg(feature = "medium-ip")]
+                Medium::Ip => Ipv6Packet::new_checked(&frame[..]).ok()?,
+                #[cfg(feature = "medium-ieee802154")]
+                Medium::Ieee802154 => todo!(),
+            };
+            let ipv6_repr = Ipv6Repr::parse(&ipv6_packet).ok()?;
+            let icmp_repr = Icmpv6Repr::parse(
+                &ipv6_repr.src_addr,
+                &ipv6_repr.dst_addr,
+                &Icmpv6Packet::new_checked(ipv6_packet.payload()).ok()?,
+                &Default::default(),
+            )
+            .ok()?;
+            match icmp_repr {
+                Icmpv6Repr::Ndisc(NdiscRepr::RouterSolicit { lladdr }) => Some((ipv6_repr, lladdr)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(feature = "proto-slaac", feature = "medium-ethernet"))]
+fn router_advert_bytes(
+    router: Ipv6Address,
+    router_lifetime: Duration,
+    prefix_info: Option<NdiscPrefixInformation>,
+) -> Vec<u8> {
+    let icmp_repr = Icmpv6Repr::Ndisc(NdiscRepr::RouterAdvert {
+        hop_limit: 64,
+        flags: NdiscRouterFlags::empty(),
+        router_lifetime,
+        reachable_time: Duration::ZERO,
+        retrans_time: Duration::ZERO,
+        lladdr: Some(EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x03]).into()),
+        mtu: None,
+        prefix_info,
+    });
+    let ipv6_repr = Ipv6Repr {
+        src_addr: router,
+        dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: icmp_repr.buffer_len(),
+        hop_limit: 0xff,
+    };
+
+    let mut bytes = vec![0; ipv6_repr.buffer_len() + icmp_repr.buffer_len()];
+    ipv6_repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes[..]));
+    icmp_repr.emit(
+        &ipv6_repr.src_addr,
+        &ipv6_repr.dst_addr,
+        &mut Icmpv6Packet::new_unchecked(&mut bytes[ipv6_repr.buffer_len()..]),
+        &ChecksumCapabilities::default(),
+    );
+    bytes
+}
+
+#[cfg(all(feature = "proto-slaac", feature = "medium-ethernet"))]
+fn slaac_prefix(prefix: Ipv6Address, valid: u64, preferred: u64) -> NdiscPrefixInformation {
+    NdiscPrefixInformation {
+        prefix_len: 64,
+        flags: NdiscPrefixInfoFlags::ON_LINK | NdiscPrefixInfoFlags::ADDRCONF,
+        valid_lifetime: Duration::from_secs(valid),
+        preferred_lifetime: Duration::from_secs(preferred),
+        prefix,
+    }
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(
+    feature = "proto-slaac",
+    feature = "medium-ethernet",
+    feature = "medium-ip"
+))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-slaac", feature = "medium-ethernet"))]
+fn slaac_router_solicit(#[case] medium: Medium) {
+    let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x100);
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    iface.inner.slaac = Some(Slaac::new(
+        SlaacConfig::Eui64,
+        Instant::ZERO,
+        &mut iface.inner.rand,
+    ));
+
+    // The first solicitation is sent after a random delay of up to one second.
+    assert!(iface.inner.slaac_poll_at().unwrap() <= Instant::from_secs(1));
+    iface.inner.now = Instant::from_secs(1);
+    assert!(iface.slaac_egress(&mut device));
+    let solicits = recv_router_solicits(&mut device, Instant::from_secs(1));
+    assert_eq!(solicits.len(), 1);
+    assert_eq!(
+        solicits[0].0.src_addr,
+        Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)
+    );
+    assert_eq!(solicits[0].0.dst_addr, Ipv6Address::LINK_LOCAL_ALL_ROUTERS);
+    assert_eq!(solicits[0].0.hop_limit, 0xff);
+    match medium {
+        #[cfg(feature = "medium-ethernet")]
+        Medium::Ethernet => assert_eq!(
+            solicits[0].1,
+            Some(EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]).into())
+        ),
+        _ => assert_eq!(solicits[0].1, None),
+    }
+
+    // The next one is sent after the solicitation interval.
+    assert_eq!(iface.inner.slaac_poll_at(), Some(Instant::from_secs(5)));
+    iface.inner.now = Instant::from_secs(4);
+    assert!(!iface.slaac_egress(&mut device));
+    iface.inner.now = Instant::from_secs(5);
+    assert!(iface.slaac_egress(&mut device));
+    assert_eq!(
+        recv_router_solicits(&mut device, Instant::from_secs(5)).len(),
+        1
+    );
+
+    // A Router Advertisement stops the solicitations.
+    iface.inner.process_ipv6(
+        &mut sockets,
+        PacketMeta::default(),
+        &Ipv6Packet::new_checked(&router_advert_bytes(router, Duration::ZERO, None)[..]).unwrap(),
+        Some(&mut iface.fragments),
+    );
+    assert_eq!(iface.inner.slaac_poll_at(), None);
+    iface.inner.now = Instant::from_secs(9);
+    assert!(!iface.slaac_egress(&mut device));
+    assert!(recv_router_solicits(&mut device, Instant::from_secs(9)).is_empty());
+}
+
+#[rstest]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-slaac", feature = "medium-ethernet"))]
+fn slaac_router_advert(#[case] medium: Medium) {
+    let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x100);
+    let prefix = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0);
+    let addr = Ipv6Cidr::new(
+        Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0x0002, 0x02ff, 0xfe02, 0x0202),
+        64,
+    );
+    let remote = Ipv6Address::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 1);
+
+    let (mut iface, mut sockets, _device) = setup(medium);
+    iface.inner.slaac = Some(Slaac::new(
+        SlaacConfig::Eui64,
+        Instant::ZERO,
+        &mut iface.inner.rand,
+    ));
+
+    let mut process = |iface: &mut Interface, now: i64, lifetime: u64, prefix_info| {
+        iface.inner.now = Instant::from_secs(now);
+        let data = router_advert_bytes(router, Duration::from_secs(lifetime), prefix_info);
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments),
+        );
+    };
+
+    // An address is built from the advertised prefix, and a default route is installed.
+    process(&mut iface, 0, 1800, Some(slaac_prefix(prefix, 3600, 1800)));
+    assert!(iface.has_ip_addr(addr.address()));
+    assert_eq!(
+        iface.slaac_addresses(),
+        &[SlaacAddress {
+            cidr: addr,
+            preferred_until: Some(Instant::from_secs(1800)),
+            valid_until: Some(Instant::from_secs(3600)),
+            #[cfg(feature = "proto-ipv6-dad")]
+            dad_counter: 0,
+        }]
+    );
+    assert_eq!(
+        iface.inner.routes.lookup(&remote.into(), Instant::ZERO),
+        Some(router.into())
+    );
+    assert!(iface.inner.has_neighbor(&router.into()));
+
+    // Advertisements do not shorten the valid lifetime below two hours, but extend it.
+    process(&mut iface, 10, 1800, Some(slaac_prefix(prefix, 600, 300)));
+    assert_eq!(
+        iface.slaac_addresses()[0].valid_until,
+        Some(Instant::from_secs(3600))
+    );
+    assert_eq!(
+        iface.slaac_addresses()[0].preferred_until,
+        Some(Instant::from_secs(310))
+    );
+    process(
+        &mut iface,
+        20,
+        1800,
+        Some(slaac_prefix(prefix, 0xffff_ffff, 0xffff_ffff)),
+    );
+    assert_eq!(iface.slaac_addresses()[0].valid_until, None);
+    process(&mut iface, 30, 1800, Some(slaac_prefix(prefix, 60, 60)));
+    assert_eq!(
+        iface.slaac_addresses()[0].valid_until,
+        Some(Instant::from_secs(30 + 2 * 60 * 60))
+    );
+    process(&mut iface, 40, 1800, Some(slaac_prefix(prefix, 3600, 1800)));
+    assert_eq!(
+        iface.slaac_addresses()[0].valid_until,
+        Some(Instant::from_secs(30 + 2 * 60 * 60))
+    );
+    process(
+        &mut iface,
+        40,
+        1800,
+        Some(slaac_prefix(prefix, 3 * 60 * 60, 1800)),
+    );
+    assert_eq!(
+        iface.slaac_addresses()[0].valid_until,
+        Some(Instant::from_secs(40 + 3 * 60 * 60))
+    );
+
+    // Prefixes that are not for autoconfiguration or not 64 bits long are ignored.
+    let other_prefix = Ipv6Address::new(0x2001, 0xdb8, 3, 0, 0, 0, 0, 0);
+    let mut prefix_info = slaac_prefix(other_prefix, 3600, 1800);
+    prefix_info.flags = NdiscPrefixInfoFlags::ON_LINK;
+    process(&mut iface, 50, 1800, Some(prefix_info));
+    let mut prefix_info = slaac_prefix(other_prefix, 3600, 1800);
+    prefix_info.prefix_len = 48;
+    process(&mut iface, 50, 1800, Some(prefix_info));
+    assert_eq!(iface.slaac_addresses().len(), 1);
+
+    // A router lifetime of zero removes the default route.
+    process(&mut iface, 60, 0, None);
+    assert_eq!(
+        iface
+            .inner
+            .routes
+            .lookup(&remote.into(), Instant::from_secs(60)),
+        None
+    );
+
+    // The address is removed when its valid lifetime ends.
+    let valid_until = Instant::from_secs(40 + 3 * 60 * 60);
+    assert_eq!(iface.inner.slaac_poll_at(), Some(valid_until));
+    iface.inner.now = valid_until;
+    let mut device = crate::tests::TestingDevice::new(medium);
+    assert!(iface.slaac_egress(&mut device));
+    assert!(!iface.has_ip_addr(addr.address()));
+    assert!(iface.slaac_addresses().is_empty());
+    assert_eq!(iface.inner.slaac_poll_at(), None);
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(all(
+    feature = "proto-slaac",
+    feature = "medium-ethernet",
+    feature = "medium-ip"
+))]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-slaac", feature = "medium-ethernet"))]
+fn slaac_stable_privacy(#[case] medium: Medium) {
+    let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x100);
+    let prefix = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0);
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    iface.update_ip_addrs(|addrs| {
+        addrs.retain(|addr| !matches!(addr, IpCidr::Ipv6(addr) if addr.address().is_link_local()))
+    });
+    iface.inner.slaac = Some(Slaac::new(
+        SlaacConfig::StablePrivacy {
+            secret_key: [0x2a; 16],
+        },
+        Instant::ZERO,
+        &mut iface.inner.rand,
+    ));
+
+    // A link-local address is configured before soliciting routers.
+    iface.inner.now = Instant::from_secs(1);
+    assert!(iface.slaac_egress(&mut device));
+    let solicits = recv_router_solicits(&mut device, Instant::from_secs(1));
+    let link_local = solicits[0].0.src_addr;
+    assert!(link_local.is_link_local());
+    assert!(iface.has_ip_addr(link_local));
+
+    iface.inner.process_ipv6(
+        &mut sockets,
+        PacketMeta::default(),
+        &Ipv6Packet::new_checked(
+            &router_advert_bytes(
+                router,
+                Duration::from_secs(1800),
+                Some(slaac_prefix(prefix, 3600, 1800)),
+            )[..],
+        )
+        .unwrap(),
+        Some(&mut iface.fragments),
+    );
+
+    // The interface identifiers depend on the prefix.
+    let addr = iface.slaac_addresses()[0].cidr.address();
+    assert_eq!(addr.as_bytes()[..8], prefix.as_bytes()[..8]);
+    assert_ne!(addr.as_bytes()[8..], link_local.as_bytes()[8..]);
+    assert_ne!(
+        addr.as_bytes()[8..],
+        [0x00, 0x02, 0x02, 0xff, 0xfe, 0x02, 0x02, 0x02]
+    );
+}
+
+#[cfg(all(feature = "proto-ipv6-dad", feature = "medium-ethernet"))]
+fn dad_setup(optimistic: bool) -> (Interface, SocketSet<'static>, crate::tests::TestingDevice) {
+    let mut device = crate::tests::TestingDevice::new(Medium::Ethernet);
+    let mut config = Config::new(EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]).into());
+    config.dad_transmits = 2;
+    config.optimistic_dad = optimistic;
+    let iface = Interface::new(config, &mut device, Instant::ZERO);
+    (iface, SocketSet::new(vec![]), device)
+}
+
+/// Receive the Neighbor Solicitations and Advertisements sent by the interface.
+#[cfg(feature = "medium-ethernet")]
+fn recv_neighbor_ndisc(
+    device: &mut crate::tests::TestingDevice,
+    timestamp: Instant,
+) -> Vec<(Ipv6Repr, NdiscRepr<'static>)> {
+    recv_all(device, timestamp)
+        .iter()
+        .filter_map(|frame| {
+            let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+            let ipv6_packet = Ipv6Packet::new_checked(eth_frame.payload()).ok()?;
+            let ipv6_repr = Ipv6Repr::parse(&ipv6_packet).ok()?;
+            let icmp_repr = Icmpv6Repr::parse(
+                &ipv6_repr.src_addr,
+                &ipv6_repr.dst_addr,
+                &Icmpv6Packet::new_checked(ipv6_packet.payload()).ok()?,
+                &Default::default(),
+            )
+            .ok()?;
+            match icmp_repr {
+                Icmpv6Repr::Ndisc(NdiscRepr::NeighborSolicit {
+                    target_addr,
+                    lladdr,
+                }) => Some((
+                    ipv6_repr,
+                    NdiscRepr::NeighborSolicit {
+                        target_addr,
+                        lladdr,
+                    },
+                )),
+                Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+                    flags,
+                    target_addr,
+                    lladdr,
+                }) => Some((
+                    ipv6_repr,
+                    NdiscRepr::NeighborAdvert {
+                        flags,
+                        target_addr,
+                        lladdr,
+                    },
+                )),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(feature = "medium-ethernet")]
+fn ndisc_bytes(src_addr: Ipv6Address, dst_addr: Ipv6Address, repr: NdiscRepr) -> Vec<u8> {
+    let icmp_repr = Icmpv6Repr::Ndisc(repr);
+    let ipv6_repr = Ipv6Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: icmp_repr.buffer_len(),
+        hop_limit: 0xff,
+    };
+
+    let mut bytes = vec![0; ipv6_repr.buffer_len() + icmp_repr.buffer_len()];
+    ipv6_repr.emit(&mut Ipv6Packet::new_unchecked(&mut bytes[..]));
+    icmp_repr.emit(
+        &ipv6_repr.src_addr,
+        &ipv6_repr.dst_addr,
+        &mut Icmpv6Packet::new_unchecked(&mut bytes[ipv6_repr.buffer_len()..]),
+        &ChecksumCapabilities::default(),
+    );
+    bytes
+}
+
+#[test]
+#[cfg(all(feature = "proto-ipv6-dad", feature = "medium-ethernet"))]
+fn dad_assign() {
+    let link_local = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    let global = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let remote = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+
+    let (mut iface, _sockets, mut device) = dad_setup(false);
+    iface.update_ip_addrs(|addrs| {
+        addrs.push(IpCidr::new(link_local.into(), 64)).unwrap();
+        addrs.push(IpCidr::new(global.into(), 64)).unwrap();
+    });
+
+    // The addresses are not used while they are tentative.
+    assert_eq!(
+        iface.ipv6_addr_state(&global),
+        Some(Ipv6AddressState::Tentative)
+    );
+    assert!(!iface.has_ip_addr(global));
+    assert_eq!(
+        iface.get_source_address_ipv6(&remote),
+        Ipv6Address::LOOPBACK
+    );
+    assert_eq!(iface.inner.dad_poll_at(), Some(Instant::ZERO));
+
+    // A solicitation is sent from the unspecified address for each address, every second.
+    for secs in [0, 1] {
+        iface.inner.now = Instant::from_secs(secs);
+        assert!(iface.dad_egress(&mut device));
+        let solicits = recv_neighbor_ndisc(&mut device, iface.inner.now);
+        assert_eq!(solicits.len(), 2);
+        for ((ip_repr, ndisc_repr), addr) in solicits.iter().zip([link_local, global]) {
+            assert_eq!(ip_repr.src_addr, Ipv6Address::UNSPECIFIED);
+            assert_eq!(ip_repr.dst_addr, addr.solicited_node());
+            assert_eq!(ip_repr.hop_limit, 0xff);
+            assert_eq!(
+                *ndisc_repr,
+                NdiscRepr::NeighborSolicit {
+                    target_addr: addr,
+                    lladdr: None
+                }
+            );
+        }
+        assert_eq!(
+            iface.inner.dad_poll_at(),
+            Some(Instant::from_secs(secs + 1))
+        );
+    }
+
+    // The addresses are assigned one second after the last solicitation.
+    iface.inner.now = Instant::from_millis(1500);
+    assert!(!iface.dad_egress(&mut device));
+    iface.inner.now = Instant::from_secs(2);
+    assert!(iface.dad_egress(&mut device));
+    assert!(recv_neighbor_ndisc(&mut device, iface.inner.now).is_empty());
+    assert_eq!(iface.inner.dad_poll_at(), None);
+
+    assert_eq!(
+        iface.ipv6_addr_state(&global),
+        Some(Ipv6AddressState::Preferred)
+    );
+    assert!(iface.has_ip_addr(global));
+    assert_eq!(iface.get_source_address_ipv6(&remote), global);
+    assert_eq!(iface.poll_dad_event(), Some(DadEvent::Assigned(link_local)));
+    assert_eq!(iface.poll_dad_event(), Some(DadEvent::Assigned(global)));
+    assert_eq!(iface.poll_dad_event(), None);
+}
+
+#[rstest]
+#[case::advert(false)]
+#[case::solicit(true)]
+#[cfg(all(feature = "proto-ipv6-dad", feature = "medium-ethernet"))]
+fn dad_duplicate(#[case] solicit: bool) {
+    let addr = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+
+    let (mut iface, mut sockets, mut device) = dad_setup(false);
+    iface.update_ip_addrs(|addrs| {
+        addrs.push(IpCidr::new(addr.into(), 64)).unwrap();
+    });
+    assert!(iface.dad_egress(&mut device));
+    assert_eq!(recv_neighbor_ndisc(&mut device, Instant::ZERO).len(), 1);
+
+    // Another node advertises the address, or tests it too.
+    let bytes = if solicit {
+        ndisc_bytes(
+            Ipv6Address::UNSPECIFIED,
+            addr.solicited_node(),
+            NdiscRepr::NeighborSolicit {
+                target_addr: addr,
+                lladdr: None,
+            },
+        )
+    } else {
+        ndisc_bytes(
+            addr,
+            Ipv6Address::LINK_LOCAL_ALL_NODES,
+            NdiscRepr::NeighborAdvert {
+                flags: NdiscNeighborFlags::OVERRIDE,
+                target_addr: addr,
+                lladdr: Some(EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x03]).into()),
+            },
+        )
+    };
+    iface.inner.now = Instant::from_millis(500);
+    assert_eq!(
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&bytes[..]).unwrap(),
+            Some(&mut iface.fragments),
+        ),
+        None
+    );
+
+    // The address is not used, and the detection stops.
+    assert_eq!(
+        iface.ipv6_addr_state(&addr),
+        Some(Ipv6AddressState::Duplicate)
+    );
+    assert!(!iface.has_ip_addr(addr));
+    assert!(!iface.inner.has_neighbor(&addr.into()));
+    assert_eq!(iface.poll_dad_event(), Some(DadEvent::Duplicate(addr)));
+    assert_eq!(iface.poll_dad_event(), None);
+    assert_eq!(iface.inner.dad_poll_at(), None);
+    iface.inner.now = Instant::from_secs(5);
+    assert!(!iface.dad_egress(&mut device));
+}
+
+#[rstest]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(all(feature = "proto-ipv6-dad", feature = "medium-ethernet"))]
+fn dad_defend_address(#[case] medium: Medium) {
+    let addr = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+
+    let (mut iface, mut sockets, _device) = setup(medium);
+
+    // A node testing an address of the interface can not receive unicast packets, the
+    // advertisement goes to all the nodes.
+    let bytes = ndisc_bytes(
+        Ipv6Address::UNSPECIFIED,
+        addr.solicited_node(),
+        NdiscRepr::NeighborSolicit {
+            target_addr: addr,
+            lladdr: None,
+        },
+    );
+
+    let icmpv6_expected = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+        flags: NdiscNeighborFlags::empty(),
+        target_addr: addr,
+        lladdr: Some(EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]).into()),
+    });
+    let ipv6_expected = Ipv6Repr {
+        src_addr: addr,
+        dst_addr: Ipv6Address::LINK_LOCAL_ALL_NODES,
+        next_header: IpProtocol::Icmpv6,
+        hop_limit: 0xff,
+        payload_len: icmpv6_expected.buffer_len(),
+    };
+
+    assert_eq!(
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&bytes[..]).unwrap(),
+            Some(&mut iface.fragments),
+        ),
+        Some(Packet::new_ipv6(
+            ipv6_expected,
+            IpPayload::Icmpv6(icmpv6_expected)
+        ))
+    );
+}
+
+#[test]
+#[cfg(all(
+    feature = "proto-ipv6-dad",
+    feature = "proto-slaac",
+    feature = "medium-ethernet"
+))]
+fn dad_optimistic_slaac() {
+    let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x100);
+    let prefix = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0);
+    let remote = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+
+    let (mut iface, mut sockets, mut device) = dad_setup(true);
+    iface.inner.slaac = Some(Slaac::new(
+        SlaacConfig::StablePrivacy {
+            secret_key: [0x2a; 16],
+        },
+        Instant::ZERO,
+        &mut iface.inner.rand,
+    ));
+
+    let mut process = |iface: &mut Interface, bytes: &[u8]| {
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(bytes).unwrap(),
+            Some(&mut iface.fragments),
+        );
+    };
+
+    // The autoconfigured address is used during the detection.
+    process(
+        &mut iface,
+        &router_advert_bytes(
+            router,
+            Duration::from_secs(1800),
+            Some(slaac_prefix(prefix, 3600, 1800)),
+        ),
+    );
+    let addr = iface.slaac_addresses()[0].cidr.address();
+    assert_eq!(
+        iface.ipv6_addr_state(&addr),
+        Some(Ipv6AddressState::Optimistic)
+    );
+    assert!(iface.has_ip_addr(addr));
+    assert_eq!(iface.get_source_address_ipv6(&remote), addr);
+
+    // A duplicate stable address is replaced with the next one in the prefix.
+    process(
+        &mut iface,
+        &ndisc_bytes(
+            Ipv6Address::UNSPECIFIED,
+            addr.solicited_node(),
+            NdiscRepr::NeighborSolicit {
+                target_addr: addr,
+                lladdr: None,
+            },
+        ),
+    );
+    assert_eq!(iface.poll_dad_event(), Some(DadEvent::Duplicate(addr)));
+    assert_eq!(iface.ipv6_addr_state(&addr), None);
+    assert_eq!(iface.slaac_addresses().len(), 1);
+    let new_addr = iface.slaac_addresses()[0].cidr.address();
+    assert_ne!(new_addr, addr);
+    assert_eq!(new_addr.as_bytes()[..8], prefix.as_bytes()[..8]);
+    assert_eq!(
+        iface.ipv6_addr_state(&new_addr),
+        Some(Ipv6AddressState::Optimistic)
+    );
+
+    assert!(iface.dad_egress(&mut device));
+    let solicits = recv_neighbor_ndisc(&mut device, Instant::ZERO);
+    assert_eq!(solicits.len(), 1);
+    assert_eq!(
+        solicits[0].1,
+        NdiscRepr::NeighborSolicit {
+            target_addr: new_addr,
+            lladdr: None
+        }
+    );
+}
+
+#[test]
+#[cfg(all(
+    feature = "proto-ipv6-dad",
+    feature = "proto-slaac",
+    feature = "medium-ethernet"
+))]
+fn dad_deprecated_source() {
+    let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x100);
+    let prefix = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0);
+    let manual = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+
+    let (mut iface, mut sockets, _device) = setup(Medium::Ethernet);
+    iface.inner.slaac = Some(Slaac::new(
+        SlaacConfig::Eui64,
+        Instant::ZERO,
+        &mut iface.inner.rand,
+    ));
+    iface.inner.process_ipv6(
+        &mut sockets,
+        PacketMeta::default(),
+        &Ipv6Packet::new_checked(
+            &router_advert_bytes(
+                router,
+                Duration::from_secs(1800),
+                Some(slaac_prefix(prefix, 3600, 1800)),
+            )[..],
+        )
+        .unwrap(),
+        Some(&mut iface.fragments),
+    );
+    let addr = iface.slaac_addresses()[0].cidr.address();
+    iface.update_ip_addrs(|addrs| {
+        addrs.push(IpCidr::new(manual.into(), 64)).unwrap();
+    });
+    let remote = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 2);
+
+    // The first address of the prefix is used, until it is deprecated.
+    assert_eq!(
+        iface.ipv6_addr_state(&addr),
+        Some(Ipv6AddressState::Preferred)
+    );
+    assert_eq!(iface.get_source_address_ipv6(&remote), addr);
+
+    iface.inner.now = Instant::from_secs(1800);
+    assert_eq!(
+        iface.ipv6_addr_state(&addr),
+        Some(Ipv6AddressState::Deprecated)
+    );
+    assert!(iface.has_ip_addr(addr));
+    assert_eq!(iface.get_source_address_ipv6(&remote), manual);
+}
+
+#[test]
+#[cfg(feature = "medium-ethernet")]
+fn ndisc_neighbor_unreachability() {
+    let local = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    let remote = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+    let remote_hw_addr = EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]);
+
+    let (mut iface, mut sockets, mut device) = setup(Medium::Ethernet);
+    iface
+        .inner
+        .neighbor_cache
+        .fill(remote.into(), remote_hw_addr.into(), Instant::ZERO);
+
+    // A packet sent to the stale neighbor delays a probe.
+    iface.inner.now = Instant::from_secs(30);
+    assert!(iface
+        .inner
+        .lookup_hardware_addr(
+            MockTxToken,
+            &local.into(),
+            &remote.into(),
+            &mut iface.fragmenter
+        )
+        .is_ok());
+
+    // The probe is a Neighbor Solicitation sent to the cached hardware address.
+    iface.inner.now = Instant::from_secs(35);
+    assert!(iface.neighbor_egress(&mut device, &mut sockets));
+    let eth_frame = EthernetFrame::new_checked(&device.queue.front().unwrap()[..]).unwrap();
+    assert_eq!(eth_frame.dst_addr(), remote_hw_addr);
+    let solicits = recv_neighbor_ndisc(&mut device, iface.inner.now);
+    assert_eq!(solicits.len(), 1);
+    let (ipv6_repr, ndisc_repr) = &solicits[0];
+    assert_eq!((ipv6_repr.src_addr, ipv6_repr.dst_addr), (local, remote));
+    assert_eq!(
+        *ndisc_repr,
+        NdiscRepr::NeighborSolicit {
+            target_addr: remote,
+            lladdr: Some(EthernetAddress([0x02, 0x02, 0x02, 0x02, 0x02, 0x02]).into()),
+        }
+    );
+
+    // A solicited advertisement confirms the neighbor is reachable.
+    let advert = ndisc_bytes(
+        remote,
+        local,
+        NdiscRepr::NeighborAdvert {
+            flags: NdiscNeighborFlags::SOLICITED,
+            target_addr: remote,
+            lladdr: None,
+        },
+    );
+    iface.inner.process_ipv6(
+        &mut sockets,
+        PacketMeta::default(),
+        &Ipv6Packet::new_checked(&advert[..]).unwrap(),
+        Some(&mut iface.fragments),
+    );
+    assert_eq!(
+        iface
+            .inner
+            .neighbor_cache
+            .state(&remote.into(), iface.inner.now),
+        Some(crate::iface::neighbor::State::Reachable)
+    );
+    assert_eq!(iface.inner.neighbor_poll_at(), None);
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+#[cfg(feature = "proto-ipv6-fragmentation")]
+fn ipv6_fragmentation(#[case] medium: Medium) {
+    let local = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+    let remote = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2);
+
+    let (mut iface, mut sockets, mut device) = setup(medium);
+    // Use the minimum IPv6 MTU, such that the packet fits in the fragmentation buffer.
+    iface.inner.caps.max_transmission_unit -= 1500 - IPV6_MIN_MTU;
+    #[cfg(feature = "medium-ethernet")]
+    if medium == Medium::Ethernet {
+        iface.inner.neighbor_cache.fill(
+            remote.into(),
+            EthernetAddress([0x52, 0x54, 0x00, 0x00, 0x00, 0x00]).into(),
+            Instant::ZERO,
+        );
+    }
+
+    let data: Vec<u8> = (0..1400).map(|i| i as u8).collect();
+    let icmp_request = Icmpv6Repr::EchoRequest {
+        ident: 0x1234,
+        seq_no: 1,
+        data: &data,
+    };
+    let request = Packet::new_ipv6(
+        Ipv6Repr {
+            src_addr: local,
+            dst_addr: remote,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_request.buffer_len(),
+            hop_limit: 64,
+        },
+        IpPayload::Icmpv6(icmp_request),
+    );
+
+    // The first fragment is sent right away, the second one on the next poll.
+    let tx_token = device.transmit(iface.inner.now).unwrap();
+    assert_eq!(
+        iface.inner.dispatch_ip(
+            tx_token,
+            PacketMeta::default(),
+            request,
+            &mut iface.fragmenter
+        ),
+        Ok(())
+    );
+    assert!(iface.ipv6_egress(&mut device));
+    assert!(!iface.ipv6_egress(&mut device));
+
+    let mut fragments = recv_all(&mut device, iface.inner.now);
+    assert_eq!(fragments.len(), 2);
+    let mut offsets = Vec::new();
+    for fragment in fragments.iter_mut() {
+        let fragment = match medium {
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet => &mut fragment[EthernetFrame::<&[u8]>::header_len()..],
+            _ => &mut fragment[..],
+        };
+        assert!(fragment.len() <= IPV6_MIN_MTU);
+
+        let ipv6_packet = Ipv6Packet::new_checked(&fragment[..]).unwrap();
+        assert_eq!(ipv6_packet.next_header(), IpProtocol::Ipv6Frag);
+        let ext_header = Ipv6ExtHeader::new_checked(ipv6_packet.payload()).unwrap();
+        let ext_repr = Ipv6ExtHeaderRepr::parse(&ext_header).unwrap();
+        assert_eq!(ext_repr.next_header, IpProtocol::Icmpv6);
+        let frag_header = Ipv6FragmentHeader::new_checked(ext_repr.data).unwrap();
+        let frag_repr = Ipv6FragmentRepr::parse(&frag_header).unwrap();
+        offsets.push((frag_repr.frag_offset, frag_repr.more_frags));
+
+        // Swap the addresses, such that the fragments are sent back to us. This doesn't change
+        // the ICMPv6 checksum.
+        let mut ipv6_packet = Ipv6Packet::new_unchecked(&mut fragment[..]);
+        ipv6_packet.set_src_addr(remote);
+        ipv6_packet.set_dst_addr(local);
+    }
+    assert_eq!(offsets, [(0, true), (154, false)]);
+
+    // The fragments are reassembled, also when received out of order.
+    let fragments: Vec<_> = fragments
+        .iter()
+        .map(|fragment| match medium {
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet => &fragment[EthernetFrame::<&[u8]>::header_len()..],
+            _ => &fragment[..],
+        })
+        .collect();
+    assert_eq!(
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(fragments[1]).unwrap(),
+            Some(&mut iface.fragments)
+        ),
+        None
+    );
+
+    let icmp_reply = Icmpv6Repr::EchoReply {
+        ident: 0x1234,
+        seq_no: 1,
+        data: &data,
+    };
+    assert_eq!(
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(fragments[0]).unwrap(),
+            Some(&mut iface.fragments)
+        ),
+        Some(Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: local,
+                dst_addr: remote,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_reply.buffer_len(),
+                hop_limit: 64,
+            },
+            IpPayload::Icmpv6(icmp_reply),
+        ))
+    );
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+#[case::ethernet(Medium::Ethernet)]
+#[cfg(feature = "medium-ethernet")]
+fn packet_too_big(#[case] medium: Medium) {
+    let local = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1);
+    let remote = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 2);
+    let router = Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 3);
+
+    let (mut iface, mut sockets, _device) = setup(medium);
+    assert_eq!(iface.path_mtu(&remote.into()), 1500);
+
+    let packet_too_big = |mtu| {
+        let icmp_repr = Icmpv6Repr::PktTooBig {
+            mtu,
+            header: Ipv6Repr {
+                src_addr: local,
+                dst_addr: remote,
+                next_header: IpProtocol::Udp,
+                payload_len: 1480,
+                hop_limit: 64,
+            },
+            data: &[0; 8],
+        };
+        let packet = Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: router,
+                dst_addr: local,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: icmp_repr.buffer_len(),
+                hop_limit: 64,
+            },
+            IpPayload::Icmpv6(icmp_repr),
+        );
+
+        let ip_repr = packet.ip_repr();
+        let caps = DeviceCapabilities::default();
+        let mut buffer = vec![0u8; ip_repr.buffer_len()];
+        ip_repr.emit(&mut buffer[..], &caps.checksum);
+        packet.emit_payload(&ip_repr, &mut buffer[ip_repr.header_len()..], &caps);
+        buffer
+    };
+
+    let data = packet_too_big(1400);
+    assert_eq!(
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments)
+        ),
+        None
+    );
+    assert_eq!(iface.path_mtu(&remote.into()), 1400);
+    assert_eq!(iface.path_mtu(&router.into()), 1500);
+
+    // The path MTU is never raised, and never lowered below the minimum IPv6 MTU.
+    for (mtu, path_mtu) in [(1450, 1400), (1000, IPV6_MIN_MTU)] {
+        let data = packet_too_big(mtu);
+        iface.inner.process_ipv6(
+            &mut sockets,
+            PacketMeta::default(),
+            &Ipv6Packet::new_checked(&data[..]).unwrap(),
+            Some(&mut iface.fragments),
+        );
+        assert_eq!(iface.path_mtu(&remote.into()), path_mtu);
+    }
+
+    // The path MTU is reset after a while.
+    iface.inner.now += Duration::from_secs(600);
+    assert_eq!(iface.path_mtu(&remote.into()), 1500);
+}
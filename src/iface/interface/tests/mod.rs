@@ -1,11 +1,22 @@
+#[cfg(feature = "_proto-ipsec")]
+mod ipsec;
 #[cfg(feature = "proto-ipv4")]
 mod ipv4;
 #[cfg(feature = "proto-ipv6")]
 mod ipv6;
+#[cfg(feature = "proto-rpl")]
+mod rpl;
 #[cfg(feature = "proto-sixlowpan")]
 mod sixlowpan;
 
-#[cfg(feature = "proto-igmp")]
+#[cfg(any(
+    feature = "proto-igmp",
+    feature = "proto-mld",
+    feature = "proto-slaac",
+    feature = "proto-ipv6-dad",
+    feature = "proto-ipv6-fragmentation",
+    feature = "medium-ethernet"
+))]
 use std::vec::Vec;
 
 use crate::tests::setup;
@@ -27,7 +38,14 @@ fn fill_slice(s: &mut [u8], val: u8) {
     }
 }
 
-#[cfg(feature = "proto-igmp")]
+#[cfg(any(
+    feature = "proto-igmp",
+    feature = "proto-mld",
+    feature = "proto-slaac",
+    feature = "proto-ipv6-dad",
+    feature = "proto-ipv6-fragmentation",
+    feature = "medium-ethernet"
+))]
 fn recv_all(device: &mut crate::tests::TestingDevice, timestamp: Instant) -> Vec<Vec<u8>> {
     let mut pkts = Vec::new();
     while let Some((rx, _tx)) = device.receive(timestamp) {
@@ -169,6 +187,7 @@ pub fn tcp_not_accepted() {
         max_seg_size: None,
         sack_permitted: false,
         sack_ranges: [None, None, None],
+        timestamp: None,
         payload: &[],
     };
 
@@ -212,6 +231,7 @@ pub fn tcp_not_accepted() {
                 max_seg_size: None,
                 sack_permitted: false,
                 sack_ranges: [None, None, None],
+                timestamp: None,
                 payload: &[],
             })
         ))
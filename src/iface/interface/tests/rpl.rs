@@ -0,0 +1,801 @@
+use super::*;
+
+use crate::iface::rpl::*;
+use crate::iface::{RplConfig, RplRootConfig};
+
+const DODAG_ID: Ipv6Address = Ipv6Address([0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+const NEIGHBOR: Ipv6Address = Ipv6Address([0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+const NODE: Ipv6Address = Ipv6Address([0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+const TARGET: Ipv6Address = Ipv6Address([0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+const CHILD: Ipv6Address = Ipv6Address([0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3]);
+const GLOBAL: Ipv6Address = Ipv6Address([0xfd, 0xbe, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+fn enable_rpl(iface: &mut Interface, config: RplConfig) {
+    iface.inner.rpl = Some(Rpl::new(config, iface.inner.now, &mut iface.inner.rand));
+}
+
+fn rpl_root_config() -> RplConfig {
+    rpl_root_config_with_mode(RplModeOfOperation::StoringModeWithoutMulticast)
+}
+
+fn rpl_root_config_with_mode(mode_of_operation: RplModeOfOperation) -> RplConfig {
+    RplConfig::new(mode_of_operation)
+        .add_root_config(RplRootConfig::new(RplInstanceId::from(30), DODAG_ID))
+}
+
+fn ip_repr(src_addr: Ipv6Address, dst_addr: Ipv6Address) -> Ipv6Repr {
+    Ipv6Repr {
+        src_addr,
+        dst_addr,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: 0,
+        hop_limit: 64,
+    }
+}
+
+/// Poll the RPL engine until it transmits a message, and return it.
+fn rpl_egress(
+    iface: &mut Interface,
+    device: &mut crate::tests::TestingDevice,
+    until: Instant,
+) -> Option<(Ipv6Address, std::vec::Vec<u8>)> {
+    while iface.inner.now <= until {
+        if iface.rpl_egress(device) {
+            let frame = device.queue.pop_front().unwrap();
+            let packet = Ipv6Packet::new_checked(&frame[..]).unwrap();
+            assert_eq!(packet.next_header(), IpProtocol::Icmpv6);
+            return Some((packet.dst_addr(), packet.payload().to_vec()));
+        }
+        iface.inner.now += Duration::from_millis(100);
+    }
+    None
+}
+
+fn parse_rpl<'a>(src_addr: Ipv6Address, dst_addr: Ipv6Address, payload: &'a [u8]) -> RplRepr<'a> {
+    let packet = Icmpv6Packet::new_checked(payload).unwrap();
+    match Icmpv6Repr::parse(
+        &src_addr,
+        &dst_addr,
+        &packet,
+        &ChecksumCapabilities::default(),
+    )
+    .unwrap()
+    {
+        Icmpv6Repr::Rpl(repr) => repr,
+        repr => panic!("expected a RPL message, got {repr:?}"),
+    }
+}
+
+fn dio(rank: u16, dtsn: u8, options: &[u8]) -> RplRepr {
+    dio_with_mode(
+        RplModeOfOperation::StoringModeWithoutMulticast,
+        rank,
+        dtsn,
+        options,
+    )
+}
+
+fn dio_with_mode(
+    mode_of_operation: RplModeOfOperation,
+    rank: u16,
+    dtsn: u8,
+    options: &[u8],
+) -> RplRepr {
+    RplRepr::DodagInformationObject {
+        rpl_instance_id: RplInstanceId::from(30),
+        version_number: SequenceCounter::default().value(),
+        rank,
+        grounded: false,
+        mode_of_operation,
+        dodag_preference: 0,
+        dtsn,
+        dodag_id: DODAG_ID,
+        options,
+    }
+}
+
+fn dodag_configuration(buffer: &mut [u8]) -> &[u8] {
+    dodag_configuration_with_ocp(buffer, ObjectiveFunction0::OCP)
+}
+
+fn dodag_configuration_with_ocp(buffer: &mut [u8], objective_code_point: u16) -> &[u8] {
+    let option = RplOptionRepr::DodagConfiguration {
+        authentication_enabled: false,
+        path_control_size: 0,
+        dio_interval_doublings: DEFAULT_DIO_INTERVAL_DOUBLINGS as u8,
+        dio_interval_min: DEFAULT_DIO_INTERVAL_MIN as u8,
+        dio_redundancy_constant: DEFAULT_DIO_REDUNDANCY_CONSTANT as u8,
+        max_rank_increase: DEFAULT_MAX_RANK_INCREASE,
+        minimum_hop_rank_increase: DEFAULT_MIN_HOP_RANK_INCREASE,
+        objective_code_point,
+        default_lifetime: DEFAULT_LIFETIME,
+        lifetime_unit: DEFAULT_LIFETIME_UNIT,
+    };
+    let len = option.buffer_len();
+    option.emit(&mut RplOptionPacket::new_unchecked(&mut buffer[..len]));
+    &buffer[..len]
+}
+
+fn dao(options: &[u8]) -> RplRepr {
+    RplRepr::DestinationAdvertisementObject {
+        rpl_instance_id: RplInstanceId::from(30),
+        expect_ack: true,
+        sequence: 42,
+        dodag_id: Some(DODAG_ID),
+        options,
+    }
+}
+
+/// Emit a RPL Target option for `TARGET` followed by a Transit Information option.
+fn dao_options(buffer: &mut [u8], path_lifetime: u8) -> &[u8] {
+    dao_options_with_parent(buffer, TARGET, path_lifetime, None)
+}
+
+/// Emit a RPL Target option followed by a Transit Information option with a parent address.
+fn dao_options_with_parent(
+    buffer: &mut [u8],
+    target: Ipv6Address,
+    path_lifetime: u8,
+    parent_address: Option<Ipv6Address>,
+) -> &[u8] {
+    let mut len = 0;
+    for option in [
+        RplOptionRepr::RplTarget {
+            prefix_length: 128,
+            prefix: target,
+        },
+        RplOptionRepr::TransitInformation {
+            external: false,
+            path_control: 0,
+            path_sequence: 0,
+            path_lifetime,
+            parent_address,
+        },
+    ] {
+        let option_len = option.buffer_len();
+        option.emit(&mut RplOptionPacket::new_unchecked(
+            &mut buffer[len..][..option_len],
+        ));
+        len += option_len;
+    }
+    &buffer[..len]
+}
+
+/// Emit a Prefix Information option advertising the address of a router.
+fn router_address(buffer: &mut [u8], address: Ipv6Address) -> &[u8] {
+    let option = RplOptionRepr::PrefixInformation {
+        prefix_length: 128,
+        on_link: false,
+        autonomous_address_configuration: false,
+        router_address: true,
+        valid_lifetime: u32::MAX,
+        preferred_lifetime: u32::MAX,
+        destination_prefix: address.as_bytes(),
+    };
+    let len = option.buffer_len();
+    option.emit(&mut RplOptionPacket::new_unchecked(&mut buffer[..len]));
+    &buffer[..len]
+}
+
+/// Emit an IPv6 packet carrying an ICMPv6 echo request, with an optional routing header.
+fn echo_request(
+    src_addr: Ipv6Address,
+    dst_addr: Ipv6Address,
+    hop_limit: u8,
+    routing: Option<Ipv6RoutingRepr>,
+) -> std::vec::Vec<u8> {
+    let icmp_repr = Icmpv6Repr::EchoRequest {
+        ident: 1,
+        seq_no: 2,
+        data: b"RPL",
+    };
+    let packet = Packet::Ipv6(PacketV6 {
+        header: Ipv6Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit,
+        },
+        hop_by_hop: None,
+        #[cfg(feature = "proto-ipv6-fragmentation")]
+        fragment: None,
+        routing,
+        payload: IpPayload::Icmpv6(icmp_repr),
+    });
+
+    let ip_repr = packet.ip_repr();
+    let caps = DeviceCapabilities::default();
+    let mut buffer = vec![0u8; ip_repr.buffer_len()];
+    ip_repr.emit(&mut buffer[..], &caps.checksum);
+    packet.emit_payload(&ip_repr, &mut buffer[ip_repr.header_len()..], &caps);
+    buffer
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+fn test_rpl_dis(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    enable_rpl(
+        &mut iface,
+        RplConfig::new(RplModeOfOperation::StoringModeWithoutMulticast),
+    );
+
+    let (dst_addr, payload) =
+        rpl_egress(&mut iface, &mut device, Instant::from_secs(1)).expect("no DIS");
+    assert_eq!(dst_addr, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES);
+    assert!(matches!(
+        parse_rpl(NODE, dst_addr, &payload),
+        RplRepr::DodagInformationSolicitation { .. }
+    ));
+
+    // The next DIS is only sent after the DIS interval.
+    let now = iface.inner.now;
+    assert_eq!(
+        rpl_egress(
+            &mut iface,
+            &mut device,
+            now + DIS_INTERVAL - Duration::from_secs(1)
+        ),
+        None
+    );
+    assert!(rpl_egress(&mut iface, &mut device, now + DIS_INTERVAL).is_some());
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+fn test_rpl_root_dio(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    enable_rpl(&mut iface, rpl_root_config());
+
+    let (dst_addr, payload) =
+        rpl_egress(&mut iface, &mut device, Instant::from_secs(60)).expect("no DIO");
+    assert_eq!(dst_addr, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES);
+
+    let RplRepr::DodagInformationObject {
+        rank,
+        dodag_id,
+        mode_of_operation,
+        options,
+        ..
+    } = parse_rpl(NODE, dst_addr, &payload)
+    else {
+        panic!("expected a DIO");
+    };
+    assert_eq!(rank, Rank::ROOT.raw_value());
+    assert_eq!(dodag_id, DODAG_ID);
+    assert_eq!(
+        mode_of_operation,
+        RplModeOfOperation::StoringModeWithoutMulticast
+    );
+    assert!(matches!(
+        RplOptionsIterator::new(options).next(),
+        Some(Ok(RplOptionRepr::DodagConfiguration {
+            objective_code_point: 0,
+            ..
+        }))
+    ));
+
+    // A unicast DIS is answered with a unicast DIO.
+    assert_eq!(
+        iface.inner.process_rpl(
+            ip_repr(NEIGHBOR, NODE),
+            RplRepr::DodagInformationSolicitation { options: &[] }
+        ),
+        None
+    );
+    let now = iface.inner.now;
+    let (dst_addr, payload) = rpl_egress(&mut iface, &mut device, now).expect("no DIO");
+    assert_eq!(dst_addr, NEIGHBOR);
+    assert!(matches!(
+        parse_rpl(NODE, dst_addr, &payload),
+        RplRepr::DodagInformationObject { .. }
+    ));
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+fn test_rpl_join(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    enable_rpl(
+        &mut iface,
+        RplConfig::new(RplModeOfOperation::StoringModeWithoutMulticast),
+    );
+
+    // A DIO without DODAG configuration is not enough to join.
+    iface.inner.process_rpl(
+        ip_repr(NEIGHBOR, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES),
+        dio(Rank::ROOT.raw_value(), 0, &[]),
+    );
+    assert_eq!(iface.rpl().unwrap().dodag_id(), None);
+
+    let mut buffer = [0u8; 16];
+    let configuration = dodag_configuration(&mut buffer);
+    iface.inner.process_rpl(
+        ip_repr(NEIGHBOR, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES),
+        dio(Rank::ROOT.raw_value(), 0, configuration),
+    );
+
+    let rpl = iface.rpl().unwrap();
+    assert_eq!(rpl.dodag_id(), Some(DODAG_ID));
+    assert_eq!(rpl.parent(), Some(NEIGHBOR));
+    assert_eq!(rpl.rank(), Some(Rank::ROOT.raw_value() + 3 * 256));
+    assert_eq!(rpl.next_hop(&TARGET), Some(NEIGHBOR));
+    assert_eq!(rpl.next_hop(&NEIGHBOR), None);
+
+    // The node advertises its global address to its parent.
+    let (dst_addr, payload) =
+        rpl_egress(&mut iface, &mut device, Instant::from_secs(10)).expect("no DAO");
+    let (dst_addr, payload) = match parse_rpl(NODE, dst_addr, &payload) {
+        RplRepr::DodagInformationObject { .. } => {
+            rpl_egress(&mut iface, &mut device, Instant::from_secs(10)).expect("no DAO")
+        }
+        _ => (dst_addr, payload),
+    };
+    assert_eq!(dst_addr, NEIGHBOR);
+
+    let RplRepr::DestinationAdvertisementObject {
+        expect_ack,
+        sequence,
+        dodag_id,
+        options,
+        ..
+    } = parse_rpl(NODE, dst_addr, &payload)
+    else {
+        panic!("expected a DAO");
+    };
+    assert!(expect_ack);
+    assert_eq!(dodag_id, Some(DODAG_ID));
+
+    let mut options = RplOptionsIterator::new(options);
+    assert_eq!(
+        options.next(),
+        Some(Ok(RplOptionRepr::RplTarget {
+            prefix_length: 128,
+            prefix: Ipv6Address::new(0xfdbe, 0, 0, 0, 0, 0, 0, 1),
+        }))
+    );
+    assert!(matches!(
+        options.next(),
+        Some(Ok(RplOptionRepr::TransitInformation {
+            path_lifetime: DEFAULT_LIFETIME,
+            ..
+        }))
+    ));
+    assert_eq!(options.next(), None);
+
+    // Once acknowledged, the DAO is only refreshed before the routes expire.
+    iface.inner.process_rpl(
+        ip_repr(NEIGHBOR, NODE),
+        RplRepr::DestinationAdvertisementObjectAck {
+            rpl_instance_id: RplInstanceId::from(30),
+            sequence,
+            status: 0,
+            dodag_id,
+        },
+    );
+    let dodag = iface.inner.rpl.as_ref().unwrap().dodag.as_ref().unwrap();
+    assert_eq!(dodag.dao_retransmissions, 0);
+    assert!(dodag.dao_expiration.unwrap() > iface.inner.now + Duration::from_secs(60));
+
+    // The parent left the DODAG, and so do we.
+    iface.inner.process_rpl(
+        ip_repr(NEIGHBOR, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES),
+        dio(Rank::INFINITE.raw_value(), 0, configuration),
+    );
+    assert_eq!(iface.rpl().unwrap().dodag_id(), None);
+    assert_eq!(iface.rpl().unwrap().next_hop(&TARGET), None);
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+fn test_rpl_join_mrhof(#[case] medium: Medium) {
+    let (mut iface, _sockets, _device) = setup(medium);
+    enable_rpl(
+        &mut iface,
+        RplConfig::new(RplModeOfOperation::StoringModeWithoutMulticast),
+    );
+
+    let other = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 4);
+    let mut buffer = [0u8; 16];
+    let configuration = dodag_configuration_with_ocp(&mut buffer, Mrhof::OCP);
+    for src_addr in [NEIGHBOR, other] {
+        iface.inner.process_rpl(
+            ip_repr(src_addr, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES),
+            dio(Rank::ROOT.raw_value(), 0, configuration),
+        );
+    }
+
+    // Without link statistics, the ETX of both links is 2.
+    let rpl = iface.rpl().unwrap();
+    assert_eq!(rpl.parent(), Some(NEIGHBOR));
+    assert_eq!(rpl.rank(), Some(Rank::ROOT.raw_value() + 2 * 128));
+
+    // Statistics of other nodes are ignored.
+    iface.rpl_link_transmission(TARGET, 1, false);
+
+    // Both links are good, a single lost packet is not enough to switch parents.
+    for _ in 0..4 {
+        iface.rpl_link_transmission(NEIGHBOR, 1, true);
+        iface.rpl_link_transmission(other, 1, true);
+    }
+    assert_eq!(
+        iface.rpl().unwrap().rank(),
+        Some(2 * Rank::ROOT.raw_value())
+    );
+
+    iface.rpl_link_transmission(NEIGHBOR, 3, false);
+    let rpl = iface.rpl().unwrap();
+    assert_eq!(rpl.parent(), Some(NEIGHBOR));
+    assert!(rpl.rank().unwrap() > 2 * Rank::ROOT.raw_value());
+
+    // The link with the preferred parent is lossy, another parent is selected.
+    iface.rpl_link_transmission(NEIGHBOR, 3, false);
+    let rpl = iface.rpl().unwrap();
+    assert_eq!(rpl.parent(), Some(other));
+    assert_eq!(rpl.rank(), Some(2 * Rank::ROOT.raw_value()));
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+fn test_rpl_dao_storing(#[case] medium: Medium) {
+    let (mut iface, _sockets, _device) = setup(medium);
+    enable_rpl(&mut iface, rpl_root_config());
+
+    let mut buffer = [0u8; 32];
+    assert_eq!(
+        iface
+            .inner
+            .process_rpl(ip_repr(NEIGHBOR, NODE), dao(dao_options(&mut buffer, 30))),
+        Some(Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: NODE,
+                dst_addr: NEIGHBOR,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: 24,
+                hop_limit: 64,
+            },
+            IpPayload::Icmpv6(Icmpv6Repr::Rpl(
+                RplRepr::DestinationAdvertisementObjectAck {
+                    rpl_instance_id: RplInstanceId::from(30),
+                    sequence: 42,
+                    status: 0,
+                    dodag_id: Some(DODAG_ID),
+                }
+            )),
+        ))
+    );
+    assert_eq!(iface.rpl().unwrap().next_hop(&TARGET), Some(NEIGHBOR));
+    assert_eq!(
+        iface.inner.route(&IpAddress::Ipv6(TARGET), iface.inner.now),
+        Some(IpAddress::Ipv6(NEIGHBOR))
+    );
+
+    // A zero lifetime removes the route.
+    iface
+        .inner
+        .process_rpl(ip_repr(NEIGHBOR, NODE), dao(dao_options(&mut buffer, 0)));
+    assert_eq!(iface.rpl().unwrap().next_hop(&TARGET), None);
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+fn test_rpl_dao_non_storing(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    enable_rpl(
+        &mut iface,
+        rpl_root_config_with_mode(RplModeOfOperation::NonStoringMode),
+    );
+
+    // The root advertises the DODAG ID as its address.
+    let (dst_addr, payload) =
+        rpl_egress(&mut iface, &mut device, Instant::from_secs(60)).expect("no DIO");
+    let RplRepr::DodagInformationObject { options, .. } = parse_rpl(NODE, dst_addr, &payload)
+    else {
+        panic!("expected a DIO");
+    };
+    assert!(RplOptionsIterator::new(options).any(|option| matches!(
+        option,
+        Ok(RplOptionRepr::PrefixInformation {
+            router_address: true,
+            destination_prefix,
+            ..
+        }) if destination_prefix == DODAG_ID.as_bytes()
+    )));
+
+    // A DAO without parent address is rejected in non-storing mode.
+    let mut buffer = [0u8; 48];
+    assert!(matches!(
+        iface.inner.process_rpl(
+            ip_repr(CHILD, DODAG_ID),
+            dao(dao_options_with_parent(&mut buffer, TARGET, 30, None)),
+        ),
+        Some(Packet::Ipv6(PacketV6 {
+            payload: IpPayload::Icmpv6(Icmpv6Repr::Rpl(
+                RplRepr::DestinationAdvertisementObjectAck { status: 128, .. }
+            )),
+            ..
+        }))
+    ));
+
+    // The root learns that CHILD is its child, and that TARGET is a child of CHILD.
+    iface.inner.process_rpl(
+        ip_repr(CHILD, DODAG_ID),
+        dao(dao_options_with_parent(
+            &mut buffer,
+            CHILD,
+            30,
+            Some(DODAG_ID),
+        )),
+    );
+    iface.inner.process_rpl(
+        ip_repr(TARGET, DODAG_ID),
+        dao(dao_options_with_parent(
+            &mut buffer,
+            TARGET,
+            30,
+            Some(CHILD),
+        )),
+    );
+
+    let rpl = iface.rpl().unwrap();
+    assert_eq!(rpl.next_hop(&CHILD), Some(CHILD));
+    assert_eq!(rpl.next_hop(&TARGET), Some(CHILD));
+
+    // Packets to TARGET are sent to CHILD with a source route.
+    let icmp_repr = Icmpv6Repr::EchoRequest {
+        ident: 1,
+        seq_no: 2,
+        data: b"RPL",
+    };
+    let packet = Packet::new_ipv6(
+        Ipv6Repr {
+            src_addr: DODAG_ID,
+            dst_addr: TARGET,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: 64,
+        },
+        IpPayload::Icmpv6(icmp_repr),
+    );
+    let tx_token = device.transmit(iface.inner.now).unwrap();
+    iface
+        .inner
+        .dispatch_ip(
+            tx_token,
+            PacketMeta::default(),
+            packet,
+            &mut iface.fragmenter,
+        )
+        .unwrap();
+
+    let frame = device.queue.pop_front().unwrap();
+    let packet = Ipv6Packet::new_checked(&frame[..]).unwrap();
+    assert_eq!(packet.dst_addr(), CHILD);
+    assert_eq!(packet.next_header(), IpProtocol::Ipv6Route);
+
+    let ext_header = Ipv6ExtHeader::new_checked(packet.payload()).unwrap();
+    let ext_repr = Ipv6ExtHeaderRepr::parse(&ext_header).unwrap();
+    assert_eq!(ext_repr.next_header, IpProtocol::Icmpv6);
+    let routing_header = Ipv6RoutingHeader::new_checked(ext_repr.data).unwrap();
+    let routing_repr = Ipv6RoutingRepr::parse(&routing_header).unwrap();
+    assert_eq!(routing_repr.rpl_address_count(), Some(1));
+    assert_eq!(routing_repr.rpl_address(1, &CHILD), Some(TARGET));
+    assert!(matches!(
+        routing_repr,
+        Ipv6RoutingRepr::Rpl {
+            segments_left: 1,
+            ..
+        }
+    ));
+
+    // The checksum of the upper layer uses the final destination.
+    let icmp_packet =
+        Icmpv6Packet::new_checked(&packet.payload()[2 + ext_repr.data.len()..]).unwrap();
+    assert_eq!(
+        Icmpv6Repr::parse(
+            &DODAG_ID,
+            &TARGET,
+            &icmp_packet,
+            &ChecksumCapabilities::default()
+        ),
+        Ok(icmp_repr)
+    );
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+fn test_rpl_join_non_storing(#[case] medium: Medium) {
+    let (mut iface, _sockets, mut device) = setup(medium);
+    enable_rpl(
+        &mut iface,
+        RplConfig::new(RplModeOfOperation::NonStoringMode),
+    );
+
+    let mut buffer = [0u8; 48];
+    let len = dodag_configuration(&mut buffer).len();
+    let len = len + router_address(&mut buffer[len..], DODAG_ID).len();
+    iface.inner.process_rpl(
+        ip_repr(NEIGHBOR, Ipv6Address::LINK_LOCAL_ALL_RPL_NODES),
+        dio_with_mode(
+            RplModeOfOperation::NonStoringMode,
+            Rank::ROOT.raw_value(),
+            0,
+            &buffer[..len],
+        ),
+    );
+    assert_eq!(iface.rpl().unwrap().parent(), Some(NEIGHBOR));
+
+    // The DAO is sent to the root, with the address of the parent.
+    let (dst_addr, payload) = loop {
+        let (dst_addr, payload) =
+            rpl_egress(&mut iface, &mut device, Instant::from_secs(10)).expect("no DAO");
+        if dst_addr != Ipv6Address::LINK_LOCAL_ALL_RPL_NODES {
+            break (dst_addr, payload);
+        }
+    };
+    assert_eq!(dst_addr, DODAG_ID);
+
+    let RplRepr::DestinationAdvertisementObject { options, .. } =
+        parse_rpl(GLOBAL, dst_addr, &payload)
+    else {
+        panic!("expected a DAO");
+    };
+    let mut options = RplOptionsIterator::new(options);
+    assert_eq!(
+        options.next(),
+        Some(Ok(RplOptionRepr::RplTarget {
+            prefix_length: 128,
+            prefix: GLOBAL,
+        }))
+    );
+    assert!(matches!(
+        options.next(),
+        Some(Ok(RplOptionRepr::TransitInformation {
+            parent_address: Some(DODAG_ID),
+            ..
+        }))
+    ));
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+fn test_rpl_forward_source_route(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _device) = setup(medium);
+    enable_rpl(
+        &mut iface,
+        RplConfig::new(RplModeOfOperation::NonStoringMode),
+    );
+
+    let addresses = TARGET.as_bytes();
+    let routing = Ipv6RoutingRepr::Rpl {
+        segments_left: 1,
+        cmpr_i: 0,
+        cmpr_e: 0,
+        pad: 0,
+        addresses,
+    };
+
+    // The next address of the route becomes the destination.
+    let frame = echo_request(DODAG_ID, GLOBAL, 64, Some(routing));
+    let packet = Ipv6Packet::new_checked(&frame[..]).unwrap();
+    let Some(Packet::Ipv6(forwarded)) =
+        iface
+            .inner
+            .process_ipv6(&mut sockets, PacketMeta::default(), &packet, None)
+    else {
+        panic!("packet not forwarded");
+    };
+    assert_eq!(forwarded.header.src_addr, DODAG_ID);
+    assert_eq!(forwarded.header.dst_addr, TARGET);
+    assert_eq!(forwarded.header.hop_limit, 63);
+    assert_eq!(forwarded.header.next_header, IpProtocol::Icmpv6);
+    assert_eq!(
+        forwarded.routing,
+        Some(Ipv6RoutingRepr::Rpl {
+            segments_left: 0,
+            cmpr_i: 0,
+            cmpr_e: 0,
+            pad: 0,
+            addresses,
+        })
+    );
+    assert_eq!(forwarded.payload, IpPayload::Raw(&frame[40 + 24..]));
+
+    // The hop limit is exceeded.
+    let frame = echo_request(DODAG_ID, GLOBAL, 1, Some(routing));
+    let packet = Ipv6Packet::new_checked(&frame[..]).unwrap();
+    assert!(matches!(
+        iface
+            .inner
+            .process_ipv6(&mut sockets, PacketMeta::default(), &packet, None),
+        Some(Packet::Ipv6(PacketV6 {
+            payload: IpPayload::Icmpv6(Icmpv6Repr::TimeExceeded { .. }),
+            ..
+        }))
+    ));
+
+    // A route through our own address again is a loop.
+    let mut addresses = [0u8; 32];
+    addresses[..16].copy_from_slice(TARGET.as_bytes());
+    addresses[16..].copy_from_slice(GLOBAL.as_bytes());
+    let routing = Ipv6RoutingRepr::Rpl {
+        segments_left: 2,
+        cmpr_i: 0,
+        cmpr_e: 0,
+        pad: 0,
+        addresses: &addresses,
+    };
+    let frame = echo_request(DODAG_ID, GLOBAL, 64, Some(routing));
+    let packet = Ipv6Packet::new_checked(&frame[..]).unwrap();
+    assert!(matches!(
+        iface
+            .inner
+            .process_ipv6(&mut sockets, PacketMeta::default(), &packet, None),
+        Some(Packet::Ipv6(PacketV6 {
+            payload: IpPayload::Icmpv6(Icmpv6Repr::ParamProblem { .. }),
+            ..
+        }))
+    ));
+}
+
+#[rstest]
+#[case::ip(Medium::Ip)]
+#[cfg(feature = "medium-ip")]
+fn test_rpl_forward(#[case] medium: Medium) {
+    let (mut iface, mut sockets, _device) = setup(medium);
+    enable_rpl(&mut iface, rpl_root_config());
+
+    let mut buffer = [0u8; 32];
+    iface
+        .inner
+        .process_rpl(ip_repr(NEIGHBOR, NODE), dao(dao_options(&mut buffer, 30)));
+
+    // Packets to a node of the DODAG are forwarded.
+    let frame = echo_request(CHILD, TARGET, 64, None);
+    let packet = Ipv6Packet::new_checked(&frame[..]).unwrap();
+    assert_eq!(
+        iface
+            .inner
+            .process_ipv6(&mut sockets, PacketMeta::default(), &packet, None),
+        Some(Packet::new_ipv6(
+            Ipv6Repr {
+                src_addr: CHILD,
+                dst_addr: TARGET,
+                next_header: IpProtocol::Icmpv6,
+                payload_len: frame.len() - 40,
+                hop_limit: 63,
+            },
+            IpPayload::Raw(&frame[40..]),
+        ))
+    );
+
+    // The root has no route to other destinations.
+    let unknown = Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x99);
+    let frame = echo_request(CHILD, unknown, 64, None);
+    let packet = Ipv6Packet::new_checked(&frame[..]).unwrap();
+    let Some(Packet::Ipv6(reply)) =
+        iface
+            .inner
+            .process_ipv6(&mut sockets, PacketMeta::default(), &packet, None)
+    else {
+        panic!("no ICMPv6 error");
+    };
+    assert_eq!(reply.header.src_addr, GLOBAL);
+    assert_eq!(reply.header.dst_addr, CHILD);
+    assert!(matches!(
+        reply.payload,
+        IpPayload::Icmpv6(Icmpv6Repr::DstUnreachable {
+            reason: Icmpv6DstUnreachable::NoRoute,
+            ..
+        })
+    ));
+}
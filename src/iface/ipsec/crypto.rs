@@ -0,0 +1,213 @@
+use core::fmt;
+
+use crate::sha256;
+
+/// A cryptographic operation failed.
+///
+/// This is returned by [EncryptionAlgorithm] implementations when a key is rejected, for
+/// example because it has the wrong length, or when a packet cannot be decrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CryptoError;
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CryptoError")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CryptoError {}
+
+/// An encryption algorithm used by an ESP security association.
+///
+/// _smoltcp_ does not ship any ciphers besides [NullEncryption]; bring your own implementation
+/// (for example one backed by a hardware crypto engine) by implementing this trait.
+pub trait EncryptionAlgorithm {
+    /// Length of the initialization vector carried in front of every ESP payload.
+    fn iv_len(&self) -> usize;
+
+    /// The plaintext, including the ESP trailer, is padded to a multiple of this size.
+    ///
+    /// The value must be a power of two. ESP always aligns to at least 4 octets.
+    fn block_size(&self) -> usize;
+
+    /// Check that `key` can be used with this algorithm.
+    ///
+    /// This is called before an outgoing packet is built, so that a packet which cannot be
+    /// encrypted is discarded instead of transmitted.
+    fn check_key(&self, key: &[u8]) -> Result<(), CryptoError>;
+
+    /// Encrypt `data` in place, with a key accepted by [check_key](Self::check_key).
+    ///
+    /// The implementation is responsible for filling in `iv`, which is transmitted in the clear.
+    fn encrypt(&self, key: &[u8], iv: &mut [u8], data: &mut [u8]);
+
+    /// Decrypt `data` in place, using the `iv` received with the packet.
+    fn decrypt(&self, key: &[u8], iv: &[u8], data: &mut [u8]) -> Result<(), CryptoError>;
+}
+
+/// An integrity algorithm used by an ESP or AH security association.
+pub trait IntegrityAlgorithm {
+    /// Length of the integrity check value (ICV) in octets, at most 64.
+    fn icv_len(&self) -> usize;
+
+    /// Compute the ICV over the concatenation of all slices in `data` and write it into `icv`.
+    ///
+    /// `icv` is exactly [icv_len](IntegrityAlgorithm::icv_len) octets long.
+    fn compute(&self, key: &[u8], data: &[&[u8]], icv: &mut [u8]);
+}
+
+/// The NULL encryption algorithm, as specified in [RFC 2410].
+///
+/// It provides no confidentiality and is meant to be combined with an integrity algorithm.
+///
+/// [RFC 2410]: https://www.rfc-editor.org/rfc/rfc2410
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NullEncryption;
+
+impl EncryptionAlgorithm for NullEncryption {
+    fn iv_len(&self) -> usize {
+        0
+    }
+
+    fn block_size(&self) -> usize {
+        1
+    }
+
+    fn check_key(&self, _key: &[u8]) -> Result<(), CryptoError> {
+        Ok(())
+    }
+
+    fn encrypt(&self, _key: &[u8], _iv: &mut [u8], _data: &mut [u8]) {}
+
+    fn decrypt(&self, _key: &[u8], _iv: &[u8], _data: &mut [u8]) -> Result<(), CryptoError> {
+        Ok(())
+    }
+}
+
+/// HMAC-SHA-256-128, as specified in [RFC 4868].
+///
+/// This is a portable software implementation. The ICV is the HMAC-SHA-256 output truncated
+/// to 128 bits, and the key should be 32 octets long.
+///
+/// [RFC 4868]: https://www.rfc-editor.org/rfc/rfc4868
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HmacSha256;
+
+impl IntegrityAlgorithm for HmacSha256 {
+    fn icv_len(&self) -> usize {
+        16
+    }
+
+    fn compute(&self, key: &[u8], data: &[&[u8]], icv: &mut [u8]) {
+        let mac = hmac_sha256(key, data);
+        icv.copy_from_slice(&mac[..icv.len()]);
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[&[u8]]) -> [u8; 32] {
+    let mut block = [0u8; sha256::BLOCK_LEN];
+    if key.len() > sha256::BLOCK_LEN {
+        let mut hash = sha256::Sha256::new();
+        hash.update(key);
+        block[..32].copy_from_slice(&hash.finalize());
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = sha256::Sha256::new();
+    let mut outer = sha256::Sha256::new();
+    for b in block.iter_mut() {
+        *b ^= 0x36;
+    }
+    inner.update(&block);
+    for b in block.iter_mut() {
+        *b ^= 0x36 ^ 0x5c;
+    }
+    outer.update(&block);
+
+    for d in data {
+        inner.update(d);
+    }
+    outer.update(&inner.finalize());
+    outer.finalize()
+}
+
+/// Compare two integrity check values without leaking the position of the first mismatch.
+pub(crate) fn icv_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_icv_eq() {
+        assert!(icv_eq(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!icv_eq(&[1, 2, 3], &[1, 2, 4]));
+        assert!(!icv_eq(&[1, 2, 3], &[1, 2]));
+    }
+
+    // Test cases 1, 2 and 6 from RFC 4231.
+    #[test]
+    fn test_hmac_sha256() {
+        assert_eq!(
+            hmac_sha256(&[0x0b; 20], &[b"Hi There"]),
+            [
+                0xb0, 0x34, 0x4c, 0x61, 0xd8, 0xdb, 0x38, 0x53, 0x5c, 0xa8, 0xaf, 0xce, 0xaf, 0x0b,
+                0xf1, 0x2b, 0x88, 0x1d, 0xc2, 0x00, 0xc9, 0x83, 0x3d, 0xa7, 0x26, 0xe9, 0x37, 0x6c,
+                0x2e, 0x32, 0xcf, 0xf7
+            ]
+        );
+        assert_eq!(
+            hmac_sha256(b"Jefe", &[b"what do ya want ", b"for nothing?"]),
+            [
+                0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+                0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+                0x64, 0xec, 0x38, 0x43
+            ]
+        );
+        assert_eq!(
+            hmac_sha256(
+                &[0xaa; 131],
+                &[b"Test Using Larger Than Block-Size Key - Hash Key First"]
+            ),
+            [
+                0x60, 0xe4, 0x31, 0x59, 0x1e, 0xe0, 0xb6, 0x7f, 0x0d, 0x8a, 0x26, 0xaa, 0xcb, 0xf5,
+                0xb7, 0x7f, 0x8e, 0x0b, 0xc6, 0x21, 0x37, 0x28, 0xc5, 0x14, 0x05, 0x46, 0x04, 0x0f,
+                0x0e, 0xe3, 0x7f, 0x54
+            ]
+        );
+
+        let mut icv = [0u8; 16];
+        HmacSha256.compute(&[0x0b; 20], &[b"Hi There"], &mut icv);
+        assert_eq!(
+            icv,
+            [
+                0xb0, 0x34, 0x4c, 0x61, 0xd8, 0xdb, 0x38, 0x53, 0x5c, 0xa8, 0xaf, 0xce, 0xaf, 0x0b,
+                0xf1, 0x2b
+            ]
+        );
+    }
+
+    #[test]
+    fn test_null_encryption() {
+        let mut data = *b"Hello";
+        NullEncryption.check_key(&[]).unwrap();
+        NullEncryption.encrypt(&[], &mut [], &mut data);
+        assert_eq!(&data, b"Hello");
+        NullEncryption.decrypt(&[], &[], &mut data).unwrap();
+        assert_eq!(&data, b"Hello");
+    }
+}
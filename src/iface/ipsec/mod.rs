@@ -0,0 +1,596 @@
+// Heads up! Before working on this module you should read RFC 4301, which describes
+// the security association and security policy databases, RFC 4302 for AH and RFC 4303
+// for ESP.
+
+use core::fmt;
+
+use heapless::Vec;
+
+use crate::config::{IFACE_MAX_IPSEC_POLICY_COUNT, IFACE_MAX_IPSEC_SA_COUNT};
+use crate::wire::{IpAddress, IpCidr, IpProtocol};
+
+mod crypto;
+mod replay;
+
+pub(crate) use self::crypto::icv_eq;
+pub use self::crypto::{
+    CryptoError, EncryptionAlgorithm, HmacSha256, IntegrityAlgorithm, NullEncryption,
+};
+pub use self::replay::{ReplayError, ReplayWindow, REPLAY_WINDOW_SIZE};
+
+/// Maximum length of a key stored in a security association.
+pub(crate) const MAX_KEY_LEN: usize = 64;
+
+/// Maximum length of an integrity check value produced by an [IntegrityAlgorithm].
+pub(crate) const MAX_ICV_LEN: usize = 64;
+
+/// Key material of a security association.
+pub(crate) type Key = Vec<u8, MAX_KEY_LEN>;
+
+/// Direction of traffic a security association applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// The security association protects packets received from the peer.
+    Inbound,
+    /// The security association protects packets sent to the peer.
+    Outbound,
+}
+
+/// The IPsec protocol used by a security association.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protocol {
+    /// Encapsulating Security Payload, RFC 4303.
+    Esp,
+    /// Authentication Header, RFC 4302.
+    Ah,
+}
+
+/// A transport mode security association.
+///
+/// A security association holds the keys and the state that are needed to protect the
+/// traffic exchanged with a single peer in a single direction. Inbound security associations
+/// are looked up by the SPI carried in received packets, outbound security associations are
+/// selected by a [SecurityPolicy] with a [PolicyAction::Protect] action.
+#[derive(Clone)]
+pub struct SecurityAssociation {
+    spi: u32,
+    direction: Direction,
+    protocol: Protocol,
+    remote_addr: IpAddress,
+    encryption: Option<(&'static dyn EncryptionAlgorithm, Key)>,
+    integrity: Option<(&'static dyn IntegrityAlgorithm, Key)>,
+    esn: bool,
+    sequence_number: u64,
+    replay_window: ReplayWindow,
+}
+
+impl SecurityAssociation {
+    /// Create a security association without any algorithms.
+    ///
+    /// Use [set_encryption](Self::set_encryption) and [set_integrity](Self::set_integrity)
+    /// to configure the algorithms. An ESP security association without an encryption
+    /// algorithm behaves like one using [NullEncryption]. AH security associations ignore
+    /// the encryption algorithm, and require an integrity algorithm.
+    pub fn new(spi: u32, direction: Direction, protocol: Protocol, remote_addr: IpAddress) -> Self {
+        Self {
+            spi,
+            direction,
+            protocol,
+            remote_addr,
+            encryption: None,
+            integrity: None,
+            esn: false,
+            sequence_number: 0,
+            replay_window: ReplayWindow::new(false),
+        }
+    }
+
+    /// Set the encryption algorithm and key.
+    ///
+    /// # Panics
+    /// This function panics if the key is longer than 64 octets.
+    pub fn set_encryption(&mut self, algorithm: &'static dyn EncryptionAlgorithm, key: &[u8]) {
+        self.encryption = Some((algorithm, Self::key(key)));
+    }
+
+    /// Set the integrity algorithm and key.
+    ///
+    /// Returns an error, and keeps the current algorithm, if the ICV of the algorithm is longer
+    /// than 64 octets.
+    ///
+    /// # Panics
+    /// This function panics if the key is longer than 64 octets.
+    pub fn set_integrity(
+        &mut self,
+        algorithm: &'static dyn IntegrityAlgorithm,
+        key: &[u8],
+    ) -> Result<(), IcvTooLong> {
+        if algorithm.icv_len() > MAX_ICV_LEN {
+            return Err(IcvTooLong);
+        }
+        self.integrity = Some((algorithm, Self::key(key)));
+        Ok(())
+    }
+
+    /// Enable or disable 64-bit extended sequence numbers (ESN), see RFC 4303 § 2.2.1.
+    ///
+    /// Only the low-order 32 bits of the sequence number are transmitted, the high-order
+    /// bits are included in the ICV. This must be configured before any packet is processed,
+    /// as it resets the sequence number and the anti-replay window.
+    pub fn set_extended_sequence_numbers(&mut self, enabled: bool) {
+        self.esn = enabled;
+        self.sequence_number = 0;
+        self.replay_window = ReplayWindow::new(enabled);
+    }
+
+    fn key(key: &[u8]) -> Key {
+        match Vec::from_slice(key) {
+            Ok(key) => key,
+            Err(()) => panic!("IPsec key is longer than {} octets", MAX_KEY_LEN),
+        }
+    }
+
+    /// Return the security parameters index.
+    pub fn spi(&self) -> u32 {
+        self.spi
+    }
+
+    /// Return the direction of the security association.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Return the IPsec protocol of the security association.
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Return the address of the peer.
+    pub fn remote_addr(&self) -> IpAddress {
+        self.remote_addr
+    }
+
+    /// Return whether extended sequence numbers are enabled.
+    pub fn extended_sequence_numbers(&self) -> bool {
+        self.esn
+    }
+
+    /// Return the sequence number of the last packet sent with this security association.
+    pub fn sequence_number(&self) -> u64 {
+        self.sequence_number
+    }
+
+    /// Return the anti-replay window of an inbound security association.
+    pub fn replay_window(&self) -> &ReplayWindow {
+        &self.replay_window
+    }
+
+    pub(crate) fn replay_window_mut(&mut self) -> &mut ReplayWindow {
+        &mut self.replay_window
+    }
+
+    #[cfg(feature = "proto-ipsec-esp")]
+    pub(crate) fn encryption(&self) -> Option<&(&'static dyn EncryptionAlgorithm, Key)> {
+        self.encryption.as_ref()
+    }
+
+    pub(crate) fn integrity(&self) -> Option<&(&'static dyn IntegrityAlgorithm, Key)> {
+        self.integrity.as_ref()
+    }
+
+    /// Return the sequence number for the next outgoing packet, or `None` when the
+    /// sequence number space is exhausted and the security association must be rekeyed.
+    pub(crate) fn next_sequence_number(&mut self) -> Option<u64> {
+        let max = if self.esn { u64::MAX } else { u32::MAX as u64 };
+        if self.sequence_number == max {
+            return None;
+        }
+        self.sequence_number += 1;
+        Some(self.sequence_number)
+    }
+}
+
+impl fmt::Debug for SecurityAssociation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecurityAssociation")
+            .field("spi", &self.spi)
+            .field("direction", &self.direction)
+            .field("protocol", &self.protocol)
+            .field("remote_addr", &self.remote_addr)
+            .field("encryption", &self.encryption.is_some())
+            .field("integrity", &self.integrity.is_some())
+            .field("esn", &self.esn)
+            .field("sequence_number", &self.sequence_number)
+            .field("replay_window", &self.replay_window)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IcvTooLong;
+
+impl fmt::Display for IcvTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ICV is longer than {} octets", MAX_ICV_LEN)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IcvTooLong {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SecurityAssociationTableFull;
+
+impl fmt::Display for SecurityAssociationTableFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Security association table full")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SecurityAssociationTableFull {}
+
+/// The security association database.
+#[derive(Debug)]
+pub struct SecurityAssociations {
+    storage: Vec<SecurityAssociation, IFACE_MAX_IPSEC_SA_COUNT>,
+}
+
+impl SecurityAssociations {
+    /// Creates a new empty security association database.
+    pub fn new() -> Self {
+        Self {
+            storage: Vec::new(),
+        }
+    }
+
+    /// Add a security association.
+    ///
+    /// A security association with the same SPI and direction is replaced, and returned.
+    pub fn add(
+        &mut self,
+        sa: SecurityAssociation,
+    ) -> Result<Option<SecurityAssociation>, SecurityAssociationTableFull> {
+        let old = self.remove(sa.spi, sa.direction);
+        self.storage
+            .push(sa)
+            .map_err(|_| SecurityAssociationTableFull)?;
+        Ok(old)
+    }
+
+    /// Remove the security association with the given SPI and direction.
+    pub fn remove(&mut self, spi: u32, direction: Direction) -> Option<SecurityAssociation> {
+        let i = self
+            .storage
+            .iter()
+            .position(|sa| sa.spi == spi && sa.direction == direction)?;
+        Some(self.storage.swap_remove(i))
+    }
+
+    /// Return the security association with the given SPI and direction.
+    pub fn get(&self, spi: u32, direction: Direction) -> Option<&SecurityAssociation> {
+        self.storage
+            .iter()
+            .find(|sa| sa.spi == spi && sa.direction == direction)
+    }
+
+    pub(crate) fn get_mut(
+        &mut self,
+        spi: u32,
+        direction: Direction,
+    ) -> Option<&mut SecurityAssociation> {
+        self.storage
+            .iter_mut()
+            .find(|sa| sa.spi == spi && sa.direction == direction)
+    }
+
+    /// Iterate over all security associations.
+    pub fn iter(&self) -> impl Iterator<Item = &SecurityAssociation> {
+        self.storage.iter()
+    }
+
+    /// Remove all security associations.
+    pub fn clear(&mut self) {
+        self.storage.clear()
+    }
+}
+
+/// What to do with a packet matching a [SecurityPolicy].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PolicyAction {
+    /// Send and receive the packet without IPsec protection.
+    Bypass,
+    /// Drop the packet.
+    Discard,
+    /// Protect outgoing packets with the outbound security association with the given SPI.
+    /// Incoming packets must be protected; cleartext packets are dropped.
+    Protect { spi: u32 },
+}
+
+/// An entry of the security policy database.
+///
+/// The selector fields are matched against outgoing packets using their destination, and
+/// against incoming packets using their source. `None` matches everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SecurityPolicy {
+    /// Addresses of the peers this policy applies to.
+    pub remote: IpCidr,
+    /// Upper layer protocol this policy applies to.
+    pub protocol: Option<IpProtocol>,
+    /// Local TCP or UDP port this policy applies to.
+    pub local_port: Option<u16>,
+    /// Remote TCP or UDP port this policy applies to.
+    pub remote_port: Option<u16>,
+    /// The action applied to matching packets.
+    pub action: PolicyAction,
+}
+
+impl SecurityPolicy {
+    /// Returns a policy applying `action` to all traffic exchanged with `remote`.
+    pub fn new(remote: IpCidr, action: PolicyAction) -> Self {
+        Self {
+            remote,
+            protocol: None,
+            local_port: None,
+            remote_port: None,
+            action,
+        }
+    }
+
+    fn matches(&self, selector: &Selector) -> bool {
+        fn matches<T: PartialEq>(policy: Option<T>, value: Option<T>) -> bool {
+            match policy {
+                None => true,
+                Some(policy) => value == Some(policy),
+            }
+        }
+
+        self.remote.contains_addr(&selector.remote_addr)
+            && matches(self.protocol, Some(selector.protocol))
+            && matches(self.local_port, selector.local_port)
+            && matches(self.remote_port, selector.remote_port)
+    }
+}
+
+/// The fields of a packet that are matched against the security policy database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Selector {
+    pub remote_addr: IpAddress,
+    pub protocol: IpProtocol,
+    pub local_port: Option<u16>,
+    pub remote_port: Option<u16>,
+}
+
+/// The security policy database.
+///
+/// Policies are evaluated in order, and the first matching policy applies. Packets that
+/// do not match any policy are bypassed.
+///
+/// **NOTE**: a policy without `protocol` selector also matches ICMPv6 neighbor discovery,
+/// which is needed to resolve the peer. Add a [PolicyAction::Bypass] policy for
+/// [IpProtocol::Icmpv6] in front of it if the peer does not protect these messages.
+#[derive(Debug)]
+pub struct SecurityPolicies {
+    storage: Vec<SecurityPolicy, IFACE_MAX_IPSEC_POLICY_COUNT>,
+}
+
+impl SecurityPolicies {
+    /// Creates a new empty security policy database.
+    pub fn new() -> Self {
+        Self {
+            storage: Vec::new(),
+        }
+    }
+
+    /// Update the security policies.
+    pub fn update<F: FnOnce(&mut Vec<SecurityPolicy, IFACE_MAX_IPSEC_POLICY_COUNT>)>(
+        &mut self,
+        f: F,
+    ) {
+        f(&mut self.storage);
+    }
+
+    /// Iterate over all security policies, in order of evaluation.
+    pub fn iter(&self) -> impl Iterator<Item = &SecurityPolicy> {
+        self.storage.iter()
+    }
+
+    /// Return the action for a packet.
+    pub(crate) fn lookup(&self, selector: &Selector) -> PolicyAction {
+        self.storage
+            .iter()
+            .find(|policy| policy.matches(selector))
+            .map(|policy| policy.action)
+            .unwrap_or(PolicyAction::Bypass)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "proto-ipv4")]
+    mod mock {
+        use super::super::*;
+        use crate::wire::{Ipv4Address, Ipv4Cidr};
+
+        pub const REMOTE: IpAddress = IpAddress::Ipv4(Ipv4Address([192, 168, 1, 2]));
+        pub const OTHER: IpAddress = IpAddress::Ipv4(Ipv4Address([10, 0, 0, 2]));
+        pub const CIDR: IpCidr = IpCidr::Ipv4(Ipv4Cidr::new(Ipv4Address([192, 168, 1, 0]), 24));
+    }
+
+    #[cfg(all(feature = "proto-ipv6", not(feature = "proto-ipv4")))]
+    mod mock {
+        use super::super::*;
+        use crate::wire::{Ipv6Address, Ipv6Cidr};
+
+        pub const REMOTE: IpAddress = IpAddress::Ipv6(Ipv6Address([
+            0xfd, 0xbe, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2,
+        ]));
+        pub const OTHER: IpAddress = IpAddress::Ipv6(Ipv6Address([
+            0xfd, 0xaa, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2,
+        ]));
+        pub const CIDR: IpCidr = IpCidr::Ipv6(Ipv6Cidr::new(
+            Ipv6Address([0xfd, 0xbe, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            64,
+        ));
+    }
+
+    use self::mock::*;
+
+    fn selector(remote_addr: IpAddress, protocol: IpProtocol, remote_port: u16) -> Selector {
+        Selector {
+            remote_addr,
+            protocol,
+            local_port: Some(4000),
+            remote_port: Some(remote_port),
+        }
+    }
+
+    #[test]
+    fn test_sad_add_remove() {
+        let mut sad = SecurityAssociations::new();
+        assert!(sad.get(1, Direction::Inbound).is_none());
+
+        sad.add(SecurityAssociation::new(
+            1,
+            Direction::Inbound,
+            Protocol::Esp,
+            REMOTE,
+        ))
+        .unwrap();
+        sad.add(SecurityAssociation::new(
+            1,
+            Direction::Outbound,
+            Protocol::Esp,
+            REMOTE,
+        ))
+        .unwrap();
+        assert_eq!(sad.iter().count(), 2);
+        assert_eq!(
+            sad.get(1, Direction::Inbound).map(|sa| sa.direction()),
+            Some(Direction::Inbound)
+        );
+
+        let old = sad
+            .add(SecurityAssociation::new(
+                1,
+                Direction::Inbound,
+                Protocol::Esp,
+                OTHER,
+            ))
+            .unwrap();
+        assert_eq!(old.map(|sa| sa.remote_addr()), Some(REMOTE));
+        assert_eq!(sad.iter().count(), 2);
+
+        assert!(sad.remove(1, Direction::Outbound).is_some());
+        assert!(sad.get(1, Direction::Outbound).is_none());
+        assert!(sad.get(1, Direction::Inbound).is_some());
+    }
+
+    #[test]
+    fn test_sad_full() {
+        let mut sad = SecurityAssociations::new();
+        for spi in 0..IFACE_MAX_IPSEC_SA_COUNT as u32 {
+            sad.add(SecurityAssociation::new(
+                spi,
+                Direction::Inbound,
+                Protocol::Esp,
+                REMOTE,
+            ))
+            .unwrap();
+        }
+        assert_eq!(
+            sad.add(SecurityAssociation::new(
+                1000,
+                Direction::Inbound,
+                Protocol::Esp,
+                REMOTE
+            ))
+            .unwrap_err(),
+            SecurityAssociationTableFull
+        );
+    }
+
+    struct LongIcv;
+
+    impl IntegrityAlgorithm for LongIcv {
+        fn icv_len(&self) -> usize {
+            MAX_ICV_LEN + 1
+        }
+
+        fn compute(&self, _key: &[u8], _data: &[&[u8]], _icv: &mut [u8]) {}
+    }
+
+    #[test]
+    fn test_set_integrity_icv_too_long() {
+        static LONG_ICV: LongIcv = LongIcv;
+        static HMAC_SHA256: HmacSha256 = HmacSha256;
+
+        let mut sa = SecurityAssociation::new(1, Direction::Outbound, Protocol::Ah, REMOTE);
+        assert_eq!(sa.set_integrity(&LONG_ICV, b"key"), Err(IcvTooLong));
+        assert!(sa.integrity().is_none());
+        assert_eq!(sa.set_integrity(&HMAC_SHA256, b"key"), Ok(()));
+        assert!(sa.integrity().is_some());
+    }
+
+    #[test]
+    fn test_sequence_number_exhausted() {
+        let mut sa = SecurityAssociation::new(1, Direction::Outbound, Protocol::Esp, REMOTE);
+        assert_eq!(sa.next_sequence_number(), Some(1));
+        assert_eq!(sa.next_sequence_number(), Some(2));
+        sa.sequence_number = u32::MAX as u64;
+        assert_eq!(sa.next_sequence_number(), None);
+
+        sa.set_extended_sequence_numbers(true);
+        sa.sequence_number = u32::MAX as u64;
+        assert_eq!(sa.next_sequence_number(), Some(1 << 32));
+        sa.sequence_number = u64::MAX;
+        assert_eq!(sa.next_sequence_number(), None);
+    }
+
+    #[test]
+    fn test_spd_lookup() {
+        let mut spd = SecurityPolicies::new();
+        assert_eq!(
+            spd.lookup(&selector(REMOTE, IpProtocol::Udp, 53)),
+            PolicyAction::Bypass
+        );
+
+        spd.update(|policies| {
+            policies
+                .push(SecurityPolicy {
+                    protocol: Some(IpProtocol::Udp),
+                    remote_port: Some(53),
+                    ..SecurityPolicy::new(CIDR, PolicyAction::Bypass)
+                })
+                .unwrap();
+            policies
+                .push(SecurityPolicy::new(CIDR, PolicyAction::Protect { spi: 7 }))
+                .unwrap();
+        });
+
+        assert_eq!(
+            spd.lookup(&selector(REMOTE, IpProtocol::Udp, 53)),
+            PolicyAction::Bypass
+        );
+        assert_eq!(
+            spd.lookup(&selector(REMOTE, IpProtocol::Udp, 54)),
+            PolicyAction::Protect { spi: 7 }
+        );
+        assert_eq!(
+            spd.lookup(&selector(REMOTE, IpProtocol::Tcp, 53)),
+            PolicyAction::Protect { spi: 7 }
+        );
+        assert_eq!(
+            spd.lookup(&selector(OTHER, IpProtocol::Tcp, 53)),
+            PolicyAction::Bypass
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use core::fmt;
+
+/// Size of the anti-replay window, in packets.
+///
+/// This is the default size recommended by RFC 4303 § 3.4.3.
+pub const REPLAY_WINDOW_SIZE: u32 = 64;
+
+/// A received sequence number was rejected by a [ReplayWindow].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReplayError {
+    /// A packet with this sequence number was already received.
+    Duplicate,
+    /// The sequence number is on the left of the window.
+    TooOld,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Duplicate => write!(f, "duplicate sequence number"),
+            ReplayError::TooOld => write!(f, "sequence number too old"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReplayError {}
+
+/// A sliding window anti-replay checker, as described in RFC 4303 § 3.4.3 and Appendix A.
+///
+/// The window covers the [REPLAY_WINDOW_SIZE] sequence numbers ending at the highest
+/// sequence number received so far. When extended sequence numbers (ESN) are enabled, the
+/// high-order 32 bits that are not carried in the packets are inferred from the window, as
+/// described in RFC 4303 Appendix A2.
+///
+/// Checking a packet is split in two steps: [check](Self::check) is called before the
+/// integrity check, and [accept](Self::accept) once the packet has been verified, so that
+/// forged packets cannot move the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReplayWindow {
+    esn: bool,
+    /// Highest sequence number received, or 0 if no packet was received yet.
+    top: u64,
+    /// Bit `i` is set if sequence number `top - i` was received.
+    bitmap: u64,
+    accepted: u32,
+    duplicates: u32,
+    too_old: u32,
+}
+
+impl ReplayWindow {
+    /// Create an empty anti-replay window.
+    pub const fn new(esn: bool) -> Self {
+        Self {
+            esn,
+            top: 0,
+            bitmap: 0,
+            accepted: 0,
+            duplicates: 0,
+            too_old: 0,
+        }
+    }
+
+    /// Return whether extended sequence numbers are enabled.
+    pub fn esn(&self) -> bool {
+        self.esn
+    }
+
+    /// Return the highest sequence number accepted so far.
+    pub fn top(&self) -> u64 {
+        self.top
+    }
+
+    /// Return the number of accepted packets.
+    pub fn accepted(&self) -> u32 {
+        self.accepted
+    }
+
+    /// Return the number of packets dropped because they were already received.
+    pub fn duplicates(&self) -> u32 {
+        self.duplicates
+    }
+
+    /// Return the number of packets dropped because they were on the left of the window.
+    pub fn too_old(&self) -> u32 {
+        self.too_old
+    }
+
+    /// Reconstruct the full sequence number of a received packet from its low-order 32 bits.
+    ///
+    /// Without ESN, this is the sequence number carried in the packet.
+    pub fn sequence_number(&self, seq_low: u32) -> u64 {
+        if !self.esn {
+            return seq_low as u64;
+        }
+
+        // RFC 4303 Appendix A2.2
+        let top_low = self.top as u32;
+        let top_high = (self.top >> 32) as u32;
+        let bottom = top_low.wrapping_sub(REPLAY_WINDOW_SIZE - 1);
+
+        let seq_high = if top_low >= REPLAY_WINDOW_SIZE - 1 {
+            // Case A: the window does not span a wrap of the low-order bits.
+            if seq_low >= bottom {
+                top_high
+            } else {
+                top_high.wrapping_add(1)
+            }
+        } else {
+            // Case B: the window spans a wrap of the low-order bits.
+            if seq_low >= bottom {
+                top_high.wrapping_sub(1)
+            } else {
+                top_high
+            }
+        };
+
+        // The high-order bits only wrap when the window is still at the very beginning of
+        // the sequence number space, and such a sequence number is always too old.
+        if seq_high == u32::MAX && top_high == 0 {
+            return 0;
+        }
+
+        ((seq_high as u64) << 32) | seq_low as u64
+    }
+
+    /// Check whether a packet with the given low-order 32 bits of the sequence number may
+    /// be accepted, and return its full sequence number.
+    ///
+    /// This does not update the window. Call [accept](Self::accept) once the integrity of
+    /// the packet has been verified.
+    pub fn check(&mut self, seq_low: u32) -> Result<u64, ReplayError> {
+        let seq = self.sequence_number(seq_low);
+
+        let result = if seq == 0 {
+            // The first packet has sequence number 1, 0 is never sent.
+            Err(ReplayError::TooOld)
+        } else if seq > self.top {
+            Ok(seq)
+        } else if self.top - seq >= REPLAY_WINDOW_SIZE as u64 {
+            Err(ReplayError::TooOld)
+        } else if self.bitmap & (1 << (self.top - seq)) != 0 {
+            Err(ReplayError::Duplicate)
+        } else {
+            Ok(seq)
+        };
+
+        match result {
+            Err(ReplayError::Duplicate) => self.duplicates = self.duplicates.saturating_add(1),
+            Err(ReplayError::TooOld) => self.too_old = self.too_old.saturating_add(1),
+            Ok(_) => (),
+        }
+
+        result
+    }
+
+    /// Mark the sequence number returned by [check](Self::check) as received.
+    pub fn accept(&mut self, seq: u64) {
+        if seq > self.top {
+            let shift = seq - self.top;
+            self.bitmap = if shift >= REPLAY_WINDOW_SIZE as u64 {
+                0
+            } else {
+                self.bitmap << shift
+            };
+            self.bitmap |= 1;
+            self.top = seq;
+        } else {
+            self.bitmap |= 1 << (self.top - seq);
+        }
+
+        self.accepted = self.accepted.saturating_add(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn receive(window: &mut ReplayWindow, seq_low: u32) -> Result<u64, ReplayError> {
+        let seq = window.check(seq_low)?;
+        window.accept(seq);
+        Ok(seq)
+    }
+
+    #[test]
+    fn test_in_order() {
+        let mut window = ReplayWindow::new(false);
+        for seq in 1..200 {
+            assert_eq!(receive(&mut window, seq), Ok(seq as u64));
+        }
+        assert_eq!(window.top(), 199);
+        assert_eq!(window.accepted(), 199);
+    }
+
+    #[test]
+    fn test_zero() {
+        let mut window = ReplayWindow::new(false);
+        assert_eq!(receive(&mut window, 0), Err(ReplayError::TooOld));
+        assert_eq!(window.too_old(), 1);
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let mut window = ReplayWindow::new(false);
+        assert_eq!(receive(&mut window, 1), Ok(1));
+        assert_eq!(receive(&mut window, 1), Err(ReplayError::Duplicate));
+        assert_eq!(receive(&mut window, 5), Ok(5));
+        assert_eq!(receive(&mut window, 5), Err(ReplayError::Duplicate));
+        assert_eq!(receive(&mut window, 1), Err(ReplayError::Duplicate));
+        assert_eq!(window.duplicates(), 3);
+        assert_eq!(window.accepted(), 2);
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let mut window = ReplayWindow::new(false);
+        assert_eq!(receive(&mut window, 100), Ok(100));
+        assert_eq!(receive(&mut window, 99), Ok(99));
+        assert_eq!(receive(&mut window, 37), Ok(37));
+        assert_eq!(receive(&mut window, 36), Err(ReplayError::TooOld));
+        assert_eq!(receive(&mut window, 37), Err(ReplayError::Duplicate));
+        assert_eq!(window.top(), 100);
+
+        // Moving the window forgets the old sequence numbers.
+        assert_eq!(receive(&mut window, 101), Ok(101));
+        assert_eq!(receive(&mut window, 37), Err(ReplayError::TooOld));
+        assert_eq!(receive(&mut window, 38), Ok(38));
+
+        // A large jump clears the window.
+        assert_eq!(receive(&mut window, 1000), Ok(1000));
+        assert_eq!(receive(&mut window, 937), Ok(937));
+        assert_eq!(receive(&mut window, 936), Err(ReplayError::TooOld));
+        assert_eq!(window.too_old(), 3);
+    }
+
+    #[test]
+    fn test_check_does_not_update() {
+        let mut window = ReplayWindow::new(false);
+        assert_eq!(window.check(10), Ok(10));
+        assert_eq!(window.check(10), Ok(10));
+        assert_eq!(window.top(), 0);
+        assert_eq!(window.accepted(), 0);
+    }
+
+    #[test]
+    fn test_esn_wrap() {
+        let mut window = ReplayWindow::new(true);
+        window.accept(u32::MAX as u64 - 10);
+        assert_eq!(receive(&mut window, u32::MAX - 1), Ok(u32::MAX as u64 - 1));
+        assert_eq!(receive(&mut window, 1), Ok((1 << 32) + 1));
+        // Case B: the window spans the wrap of the low-order bits.
+        assert_eq!(receive(&mut window, u32::MAX), Ok(u32::MAX as u64));
+        assert_eq!(receive(&mut window, 0), Ok(1 << 32));
+        assert_eq!(
+            receive(&mut window, u32::MAX - 1),
+            Err(ReplayError::Duplicate)
+        );
+        // Sequence numbers on the left of the window are assumed to be beyond the next wrap;
+        // the integrity check rejects such packets.
+        assert_eq!(
+            window.sequence_number(u32::MAX - 100),
+            (1 << 32) + (u32::MAX - 100) as u64
+        );
+
+        // Case A: the window is past the wrap.
+        for seq in 2..100 {
+            assert_eq!(receive(&mut window, seq), Ok((1 << 32) + seq as u64));
+        }
+        assert_eq!(receive(&mut window, 40), Err(ReplayError::Duplicate));
+        assert_eq!(window.sequence_number(30), (2 << 32) + 30);
+        assert_eq!(window.top(), (1 << 32) + 99);
+    }
+
+    #[test]
+    fn test_esn_start() {
+        // Without a wrap, ESN behaves like 32-bit sequence numbers.
+        let mut window = ReplayWindow::new(true);
+        assert_eq!(receive(&mut window, 1), Ok(1));
+        assert_eq!(receive(&mut window, 3), Ok(3));
+        assert_eq!(receive(&mut window, 2), Ok(2));
+        assert_eq!(receive(&mut window, 0), Err(ReplayError::TooOld));
+        assert_eq!(receive(&mut window, u32::MAX), Err(ReplayError::TooOld));
+    }
+
+    #[test]
+    fn test_no_esn_wrap() {
+        let mut window = ReplayWindow::new(false);
+        assert_eq!(receive(&mut window, u32::MAX), Ok(u32::MAX as u64));
+        assert_eq!(receive(&mut window, 1), Err(ReplayError::TooOld));
+    }
+}
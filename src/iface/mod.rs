@@ -6,9 +6,14 @@ provides lookup and caching of hardware addresses, and handles management packet
 
 mod fragmentation;
 mod interface;
+#[cfg(feature = "_proto-ipsec")]
+mod ipsec;
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
 mod neighbor;
+mod path_mtu;
 mod route;
+#[cfg(feature = "iface-forwarding")]
+mod router;
 #[cfg(feature = "proto-rpl")]
 mod rpl;
 mod socket_meta;
@@ -16,9 +21,31 @@ mod socket_set;
 
 mod packet;
 
-#[cfg(feature = "proto-igmp")]
-pub use self::interface::MulticastError;
+#[cfg(feature = "proto-ipv4-acd")]
+pub use self::interface::{AcdEvent, AcdPolicy, Ipv4AddressState};
 pub use self::interface::{Config, Interface, InterfaceInner as Context};
+#[cfg(feature = "proto-ipv6-dad")]
+pub use self::interface::{DadEvent, Ipv6AddressState};
+#[cfg(any(feature = "proto-igmp", feature = "proto-mld"))]
+pub use self::interface::{MulticastError, MulticastFilterMode};
+#[cfg(feature = "proto-slaac")]
+pub use self::interface::{SlaacAddress, SlaacConfig};
 
+#[cfg(feature = "_proto-ipsec")]
+pub use self::ipsec::{
+    CryptoError as IpSecCryptoError, Direction as IpSecDirection, EncryptionAlgorithm, HmacSha256,
+    IcvTooLong as IpSecIcvTooLong, IntegrityAlgorithm, NullEncryption,
+    PolicyAction as IpSecPolicyAction, Protocol as IpSecProtocol, ReplayError as IpSecReplayError,
+    ReplayWindow as IpSecReplayWindow, SecurityAssociation, SecurityAssociationTableFull,
+    SecurityAssociations, SecurityPolicies, SecurityPolicy,
+    REPLAY_WINDOW_SIZE as IPSEC_REPLAY_WINDOW_SIZE,
+};
+
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154"))]
+pub use self::neighbor::{Neighbor, NeighborCacheFull, State as NeighborState};
 pub use self::route::{Route, RouteTableFull, Routes};
+#[cfg(feature = "iface-forwarding")]
+pub use self::router::{Router, RouterInterface};
+#[cfg(feature = "proto-rpl")]
+pub use self::rpl::{Config as RplConfig, RootConfig as RplRootConfig, Rpl};
 pub use self::socket_set::{SocketHandle, SocketSet, SocketStorage};
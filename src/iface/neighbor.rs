@@ -2,22 +2,100 @@
 // the parts of RFC 1122 that discuss ARP.
 
 use heapless::LinearMap;
+#[cfg(feature = "medium-ethernet")]
+use heapless::Vec;
 
 use crate::config::IFACE_NEIGHBOR_CACHE_COUNT;
+#[cfg(feature = "medium-ethernet")]
+use crate::config::{IFACE_NEIGHBOR_PENDING_BUFFER_SIZE, IFACE_NEIGHBOR_PENDING_COUNT};
+#[cfg(feature = "medium-ethernet")]
+use crate::phy::PacketMeta;
 use crate::time::{Duration, Instant};
 use crate::wire::{HardwareAddress, IpAddress};
 
+/// The reachability state of a neighbor, see RFC 4861 § 7.3.2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum State {
+    /// Address resolution is in progress, the hardware address is not known yet.
+    Incomplete,
+    /// The neighbor was recently confirmed to be reachable.
+    Reachable,
+    /// The neighbor was not confirmed to be reachable recently. Its reachability is verified when
+    /// a packet is sent to it.
+    Stale,
+    /// A packet was sent to a stale neighbor, a probe is sent if no confirmation arrives soon.
+    Delay,
+    /// Unicast probes are sent to the neighbor, which is evicted if none is answered.
+    Probe,
+    /// The neighbor was added by the application. It never expires and is never evicted, and its
+    /// hardware address is not updated by ARP or NDISC.
+    Static,
+}
+
 /// A cached neighbor.
 ///
 /// A neighbor mapping translates from a protocol address to a hardware address,
-/// and contains the timestamp past which the mapping should be discarded.
-#[derive(Debug, Clone, Copy)]
+/// and tracks the reachability of the neighbor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Neighbor {
-    hardware_addr: HardwareAddress,
+    /// The hardware address, `None` while the state is incomplete.
+    hardware_addr: Option<HardwareAddress>,
+    state: State,
+    /// When the state ends: a reachable neighbor becomes stale, the next probe is due, or the
+    /// address resolution fails. For a stale neighbor, when it became stale.
     expires_at: Instant,
+    /// The number of solicitations sent in the incomplete or probe state.
+    probes: u8,
 }
 
+impl Neighbor {
+    /// Return the hardware address of the neighbor, or `None` while its address resolution is in
+    /// progress.
+    pub fn hardware_addr(&self) -> Option<HardwareAddress> {
+        self.hardware_addr
+    }
+
+    /// Return the reachability state of the neighbor.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Return the time at which the state of the neighbor ends: a reachable neighbor becomes
+    /// stale, the next solicitation is sent, or the neighbor is evicted. `None` for stale and
+    /// static neighbors, which stay in the cache until they are used or evicted.
+    pub fn expires_at(&self) -> Option<Instant> {
+        match self.state {
+            State::Stale | State::Static => None,
+            _ => Some(self.expires_at),
+        }
+    }
+
+    /// Return the state of the neighbor at `timestamp`.
+    fn state_at(&self, timestamp: Instant) -> State {
+        match self.state {
+            State::Reachable if timestamp >= self.expires_at => State::Stale,
+            state => state,
+        }
+    }
+}
+
+/// Error returned when a static neighbor cannot be added, because all the entries of the
+/// neighbor cache are static.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NeighborCacheFull;
+
+impl core::fmt::Display for NeighborCacheFull {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Neighbor cache full")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NeighborCacheFull {}
+
 /// An answer to a neighbor cache lookup.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -42,25 +120,55 @@ impl Answer {
 }
 use log::debug;
 
+/// An IP packet waiting for the address resolution of its next hop.
+#[cfg(feature = "medium-ethernet")]
+#[derive(Debug)]
+pub(crate) struct PendingPacket {
+    /// The next hop whose address is being resolved.
+    neighbor: IpAddress,
+    pub(crate) meta: PacketMeta,
+    /// The source address of the packet, which the discovery requests are sent from.
+    pub(crate) src_addr: IpAddress,
+    pub(crate) packet: Vec<u8, IFACE_NEIGHBOR_PENDING_BUFFER_SIZE>,
+}
+
 /// A neighbor cache backed by a map.
+///
+/// The reachability of the neighbors is tracked with the Neighbor Unreachability Detection
+/// state machine of RFC 4861 § 7.3, for both NDISC and ARP.
 #[derive(Debug)]
 pub struct Cache {
     storage: LinearMap<IpAddress, Neighbor, IFACE_NEIGHBOR_CACHE_COUNT>,
-    silent_until: Instant,
+    /// The packets waiting for the address resolution of their next hop, see RFC 1122
+    /// § 2.3.2.2 and RFC 4861 § 7.2.2. The queue is shared by all the neighbors, and ordered by
+    /// arrival.
+    #[cfg(feature = "medium-ethernet")]
+    pending: Vec<PendingPacket, IFACE_NEIGHBOR_PENDING_COUNT>,
 }
 
 impl Cache {
-    /// Minimum delay between discovery requests, in milliseconds.
+    /// Minimum delay between discovery requests, and between unicast probes, in milliseconds.
     pub(crate) const SILENT_TIME: Duration = Duration::from_millis(1_000);
 
-    /// Neighbor entry lifetime, in milliseconds.
-    pub(crate) const ENTRY_LIFETIME: Duration = Duration::from_millis(60_000);
+    /// Time a neighbor is reachable after a confirmation, in milliseconds.
+    pub(crate) const REACHABLE_TIME: Duration = Duration::from_millis(30_000);
+
+    /// Delay before the first unicast probe of a stale neighbor a packet was sent to, in
+    /// milliseconds.
+    pub(crate) const DELAY_FIRST_PROBE_TIME: Duration = Duration::from_millis(5_000);
+
+    /// Number of discovery requests sent before the address resolution fails.
+    pub(crate) const MAX_MULTICAST_SOLICIT: u8 = 3;
+
+    /// Number of unicast probes sent before a neighbor is evicted.
+    pub(crate) const MAX_UNICAST_SOLICIT: u8 = 3;
 
     /// Create a cache.
     pub fn new() -> Self {
         Self {
             storage: LinearMap::new(),
-            silent_until: Instant::from_millis(0),
+            #[cfg(feature = "medium-ethernet")]
+            pending: Vec::new(),
         }
     }
 
@@ -70,6 +178,7 @@ impl Cache {
         }
     }
 
+    /// Fill the cache with a neighbor confirmed to be reachable.
     pub fn fill(
         &mut self,
         protocol_addr: IpAddress,
@@ -79,10 +188,11 @@ impl Cache {
         debug_assert!(protocol_addr.is_unicast());
         debug_assert!(hardware_addr.is_unicast());
 
-        let expires_at = timestamp + Self::ENTRY_LIFETIME;
+        let expires_at = timestamp + Self::REACHABLE_TIME;
         self.fill_with_expiration(protocol_addr, hardware_addr, expires_at);
     }
 
+    /// Fill the cache with a neighbor reachable until `expires_at`.
     pub fn fill_with_expiration(
         &mut self,
         protocol_addr: IpAddress,
@@ -92,40 +202,231 @@ impl Cache {
         debug_assert!(protocol_addr.is_unicast());
         debug_assert!(hardware_addr.is_unicast());
 
-        let neighbor = Neighbor {
-            expires_at,
-            hardware_addr,
+        self.insert(
+            protocol_addr,
+            Neighbor {
+                hardware_addr: Some(hardware_addr),
+                state: State::Reachable,
+                expires_at,
+                probes: 0,
+            },
+        );
+    }
+
+    /// Fill the cache with a neighbor whose reachability is not confirmed, from a solicitation
+    /// or a router advertisement. A known neighbor is left unchanged if its hardware address did
+    /// not change, see RFC 4861 § 7.2.3.
+    pub(crate) fn fill_stale(
+        &mut self,
+        protocol_addr: IpAddress,
+        hardware_addr: HardwareAddress,
+        timestamp: Instant,
+    ) {
+        debug_assert!(protocol_addr.is_unicast());
+        debug_assert!(hardware_addr.is_unicast());
+
+        if let Some(neighbor) = self.storage.get(&protocol_addr) {
+            if neighbor.hardware_addr == Some(hardware_addr) {
+                return;
+            }
+        }
+
+        self.insert(
+            protocol_addr,
+            Neighbor {
+                hardware_addr: Some(hardware_addr),
+                state: State::Stale,
+                expires_at: timestamp,
+                probes: 0,
+            },
+        );
+    }
+
+    /// Update the hardware address of a neighbor, if the cache has it, see RFC 826.
+    #[cfg(all(feature = "medium-ethernet", feature = "proto-ipv4"))]
+    pub(crate) fn update(
+        &mut self,
+        protocol_addr: IpAddress,
+        hardware_addr: HardwareAddress,
+        timestamp: Instant,
+    ) {
+        if self.storage.contains_key(&protocol_addr) {
+            self.fill_stale(protocol_addr, hardware_addr, timestamp);
+        }
+    }
+
+    /// Update a neighbor from an advertisement, see RFC 4861 § 7.2.5. ARP replies are solicited
+    /// advertisements which override the cached hardware address.
+    pub(crate) fn advert(
+        &mut self,
+        protocol_addr: IpAddress,
+        hardware_addr: Option<HardwareAddress>,
+        solicited: bool,
+        override_: bool,
+        timestamp: Instant,
+    ) {
+        let state = if solicited {
+            State::Reachable
+        } else {
+            State::Stale
         };
+
+        let Some(neighbor) = self.storage.get_mut(&protocol_addr) else {
+            match hardware_addr {
+                Some(hardware_addr) if solicited => {
+                    self.fill(protocol_addr, hardware_addr, timestamp)
+                }
+                Some(hardware_addr) => self.fill_stale(protocol_addr, hardware_addr, timestamp),
+                None => (),
+            }
+            return;
+        };
+        if neighbor.state == State::Static {
+            return;
+        }
+
+        let changed = match (neighbor.hardware_addr, hardware_addr) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(old), Some(new)) => old != new,
+        };
+
+        if neighbor.hardware_addr.is_none() {
+            // The address resolution completed.
+            if hardware_addr.is_none() {
+                return;
+            }
+        } else if changed && !override_ {
+            // The advertisement does not update the hardware address, but casts a doubt on it.
+            if neighbor.state_at(timestamp) == State::Reachable {
+                neighbor.state = State::Stale;
+                neighbor.expires_at = timestamp;
+            }
+            return;
+        }
+
+        if changed {
+            net_trace!(
+                "replaced {} => {:?} (was {:?})",
+                protocol_addr,
+                hardware_addr,
+                neighbor.hardware_addr
+            );
+            neighbor.hardware_addr = hardware_addr;
+        }
+        if state == State::Reachable {
+            neighbor.state = State::Reachable;
+            neighbor.expires_at = timestamp + Self::REACHABLE_TIME;
+            neighbor.probes = 0;
+        } else if changed {
+            neighbor.state = State::Stale;
+            neighbor.expires_at = timestamp;
+            neighbor.probes = 0;
+        }
+    }
+
+    /// Confirm that a known neighbor is reachable, from an upper-layer protocol making forward
+    /// progress, see RFC 4861 § 7.3.1.
+    #[cfg(feature = "socket-tcp")]
+    pub(crate) fn confirm(&mut self, protocol_addr: &IpAddress, timestamp: Instant) {
+        if let Some(neighbor) = self.storage.get_mut(protocol_addr) {
+            if neighbor.hardware_addr.is_some() && neighbor.state != State::Static {
+                neighbor.state = State::Reachable;
+                neighbor.expires_at = timestamp + Self::REACHABLE_TIME;
+                neighbor.probes = 0;
+            }
+        }
+    }
+
+    /// Fill the cache with a static neighbor, which replaces any entry for `protocol_addr`.
+    pub fn fill_static(
+        &mut self,
+        protocol_addr: IpAddress,
+        hardware_addr: HardwareAddress,
+    ) -> Result<(), NeighborCacheFull> {
+        debug_assert!(protocol_addr.is_unicast());
+        debug_assert!(hardware_addr.is_unicast());
+
+        let inserted = self.insert(
+            protocol_addr,
+            Neighbor {
+                hardware_addr: Some(hardware_addr),
+                state: State::Static,
+                expires_at: Instant::ZERO,
+                probes: 0,
+            },
+        );
+        if inserted {
+            Ok(())
+        } else {
+            Err(NeighborCacheFull)
+        }
+    }
+
+    /// Return the neighbors of the cache, with their state at `timestamp`.
+    pub fn iter(&self, timestamp: Instant) -> impl Iterator<Item = (IpAddress, Neighbor)> + '_ {
+        self.storage.iter().map(move |(protocol_addr, neighbor)| {
+            let state = neighbor.state_at(timestamp);
+            (*protocol_addr, Neighbor { state, ..*neighbor })
+        })
+    }
+
+    /// Remove a neighbor, static or not, and drop the packets queued for it.
+    pub fn remove(&mut self, protocol_addr: &IpAddress) -> Option<Neighbor> {
+        #[cfg(feature = "medium-ethernet")]
+        self.pending
+            .retain(|pending| pending.neighbor != *protocol_addr);
+        self.storage.remove(protocol_addr)
+    }
+
+    /// Insert a neighbor, evicting another one if the cache is full. A static neighbor is only
+    /// replaced by a static neighbor, and is never evicted. Return `false` if the neighbor was
+    /// not inserted.
+    fn insert(&mut self, protocol_addr: IpAddress, neighbor: Neighbor) -> bool {
+        if neighbor.state != State::Static
+            && matches!(self.storage.get(&protocol_addr), Some(old) if old.state == State::Static)
+        {
+            return false;
+        }
+
         match self.storage.insert(protocol_addr, neighbor) {
             Ok(Some(old_neighbor)) => {
-                if old_neighbor.hardware_addr != hardware_addr {
+                if old_neighbor.hardware_addr != neighbor.hardware_addr {
                     net_trace!(
-                        "replaced {} => {} (was {})",
+                        "replaced {} => {:?} (was {:?})",
                         protocol_addr,
-                        hardware_addr,
+                        neighbor.hardware_addr,
                         old_neighbor.hardware_addr
                     );
                 }
             }
             Ok(None) => {
-                net_trace!("filled {} => {} (was empty)", protocol_addr, hardware_addr);
+                net_trace!(
+                    "filled {} => {:?} (was empty)",
+                    protocol_addr,
+                    neighbor.hardware_addr
+                );
             }
             Err((protocol_addr, neighbor)) => {
                 // If we're going down this branch, it means the cache is full, and we need to evict an entry.
-                let old_protocol_addr = *self
+                // Stale neighbors are evicted first, the oldest one first.
+                let Some((&old_protocol_addr, _)) = self
                     .storage
                     .iter()
+                    .filter(|(_, neighbor)| neighbor.state != State::Static)
                     .min_by_key(|(_, neighbor)| neighbor.expires_at)
-                    .expect("empty neighbor cache storage")
-                    .0;
+                else {
+                    net_trace!("not filled {}, all entries are static", protocol_addr);
+                    return false;
+                };
 
                 let _old_neighbor = self.storage.remove(&old_protocol_addr).unwrap();
                 match self.storage.insert(protocol_addr, neighbor) {
                     Ok(None) => {
                         net_trace!(
-                            "filled {} => {} (evicted {} => {})",
+                            "filled {} => {:?} (evicted {} => {:?})",
                             protocol_addr,
-                            hardware_addr,
+                            neighbor.hardware_addr,
                             old_protocol_addr,
                             _old_neighbor.hardware_addr
                         );
@@ -135,34 +436,240 @@ impl Cache {
                 }
             }
         }
+        true
     }
 
     pub(crate) fn lookup(&self, protocol_addr: &IpAddress, timestamp: Instant) -> Answer {
         assert!(protocol_addr.is_unicast());
 
-        if let Some(&Neighbor {
-            expires_at,
-            hardware_addr,
-        }) = self.storage.get(protocol_addr)
-        {
-            if timestamp < expires_at {
-                return Answer::Found(hardware_addr);
+        match self.storage.get(protocol_addr) {
+            Some(Neighbor {
+                hardware_addr: Some(hardware_addr),
+                ..
+            }) => Answer::Found(*hardware_addr),
+            Some(neighbor) if timestamp < neighbor.expires_at => Answer::RateLimited,
+            _ => Answer::NotFound,
+        }
+    }
+
+    /// Return the state of a neighbor, or `None` if the cache does not have it.
+    #[cfg(test)]
+    pub(crate) fn state(&self, protocol_addr: &IpAddress, timestamp: Instant) -> Option<State> {
+        self.storage
+            .get(protocol_addr)
+            .map(|neighbor| neighbor.state_at(timestamp))
+    }
+
+    /// Record that a packet was sent to a neighbor. The reachability of a stale neighbor is
+    /// verified if it is not confirmed soon.
+    pub(crate) fn used(&mut self, protocol_addr: &IpAddress, timestamp: Instant) {
+        if let Some(neighbor) = self.storage.get_mut(protocol_addr) {
+            if neighbor.state_at(timestamp) == State::Stale {
+                neighbor.state = State::Delay;
+                neighbor.expires_at = timestamp + Self::DELAY_FIRST_PROBE_TIME;
             }
         }
+    }
 
-        if timestamp < self.silent_until {
-            Answer::RateLimited
-        } else {
-            Answer::NotFound
+    /// Record that a discovery request was sent for a neighbor, and limit the rate of the
+    /// requests.
+    pub(crate) fn limit_rate(&mut self, protocol_addr: &IpAddress, timestamp: Instant) {
+        let probes = match self.storage.get(protocol_addr) {
+            Some(neighbor) if neighbor.hardware_addr.is_none() => neighbor.probes,
+            _ => 0,
+        };
+
+        self.insert(
+            *protocol_addr,
+            Neighbor {
+                hardware_addr: None,
+                state: State::Incomplete,
+                expires_at: timestamp + Self::SILENT_TIME,
+                probes: probes.saturating_add(1),
+            },
+        );
+    }
+
+    /// Return a neighbor which is due a unicast probe, with its hardware address.
+    pub(crate) fn probe_due(&self, timestamp: Instant) -> Option<(IpAddress, HardwareAddress)> {
+        self.storage.iter().find_map(|(protocol_addr, neighbor)| {
+            let due = match neighbor.state {
+                State::Delay => true,
+                State::Probe => neighbor.probes < Self::MAX_UNICAST_SOLICIT,
+                _ => false,
+            };
+            match neighbor.hardware_addr {
+                Some(hardware_addr) if due && timestamp >= neighbor.expires_at => {
+                    Some((*protocol_addr, hardware_addr))
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Record that a unicast probe was sent to a neighbor.
+    pub(crate) fn probe_sent(&mut self, protocol_addr: &IpAddress, timestamp: Instant) {
+        if let Some(neighbor) = self.storage.get_mut(protocol_addr) {
+            if neighbor.state == State::Delay {
+                neighbor.state = State::Probe;
+                neighbor.probes = 0;
+            }
+            neighbor.probes += 1;
+            neighbor.expires_at = timestamp + Self::SILENT_TIME;
+        }
+    }
+
+    /// Evict the neighbors which did not answer the unicast probes, and the neighbors whose
+    /// address resolution failed.
+    pub(crate) fn expire(&mut self, timestamp: Instant) {
+        while let Some(protocol_addr) = self
+            .storage
+            .iter()
+            .find(|(_, neighbor)| Self::failed(neighbor, timestamp))
+            .map(|(protocol_addr, _)| *protocol_addr)
+        {
+            net_trace!("evicted {} (unreachable)", protocol_addr);
+            self.storage.remove(&protocol_addr);
         }
     }
 
-    pub(crate) fn limit_rate(&mut self, timestamp: Instant) {
-        self.silent_until = timestamp + Self::SILENT_TIME;
+    fn failed(neighbor: &Neighbor, timestamp: Instant) -> bool {
+        let max_probes = match neighbor.state {
+            State::Incomplete => Self::MAX_MULTICAST_SOLICIT,
+            State::Probe => Self::MAX_UNICAST_SOLICIT,
+            _ => return false,
+        };
+        neighbor.probes >= max_probes && timestamp >= neighbor.expires_at
     }
 
+    /// Queue a packet of `len` octets, emitted by `emit`, until the address resolution of a
+    /// neighbor completes.
+    ///
+    /// Return `false` if the address resolution of the neighbor is not in progress, or the packet
+    /// does not fit in the queue.
+    #[cfg(feature = "medium-ethernet")]
+    pub(crate) fn enqueue<F>(
+        &mut self,
+        protocol_addr: &IpAddress,
+        meta: PacketMeta,
+        src_addr: IpAddress,
+        len: usize,
+        emit: F,
+    ) -> bool
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        match self.storage.get(protocol_addr) {
+            Some(neighbor) if neighbor.hardware_addr.is_none() => (),
+            _ => return false,
+        }
+
+        if self.pending.is_full() {
+            net_trace!(
+                "pending queue is full, dropping packet to {}",
+                protocol_addr
+            );
+            return false;
+        }
+
+        let mut packet = Vec::new();
+        if packet.resize(len, 0).is_err() {
+            return false;
+        }
+        emit(&mut packet);
+
+        // NOTE(unwrap): the queue is not full.
+        self.pending
+            .push(PendingPacket {
+                neighbor: *protocol_addr,
+                meta,
+                src_addr,
+                packet,
+            })
+            .unwrap();
+        true
+    }
+
+    /// Return a neighbor with queued packets whose address resolution ended, with its hardware
+    /// address, or `None` if the resolution failed or the neighbor was evicted.
+    #[cfg(feature = "medium-ethernet")]
+    pub(crate) fn pending_due(&self) -> Option<(IpAddress, Option<HardwareAddress>)> {
+        self.pending
+            .iter()
+            .find_map(|pending| match self.storage.get(&pending.neighbor) {
+                Some(Neighbor {
+                    hardware_addr: None,
+                    ..
+                }) => None,
+                Some(neighbor) => Some((pending.neighbor, neighbor.hardware_addr)),
+                None => Some((pending.neighbor, None)),
+            })
+    }
+
+    /// Return a neighbor with queued packets whose next discovery request is due, with the
+    /// source address of the oldest queued packet.
+    #[cfg(feature = "medium-ethernet")]
+    pub(crate) fn solicit_due(&self, timestamp: Instant) -> Option<(IpAddress, IpAddress)> {
+        // The queue is ordered by arrival, so the first packet of a neighbor is its oldest.
+        self.pending.iter().find_map(|pending| {
+            let neighbor = self.storage.get(&pending.neighbor)?;
+            (neighbor.hardware_addr.is_none()
+                && neighbor.probes < Self::MAX_MULTICAST_SOLICIT
+                && timestamp >= neighbor.expires_at)
+                .then_some((pending.neighbor, pending.src_addr))
+        })
+    }
+
+    /// Remove the oldest packet queued for a neighbor.
+    #[cfg(feature = "medium-ethernet")]
+    pub(crate) fn dequeue(&mut self, protocol_addr: &IpAddress) -> Option<PendingPacket> {
+        let index = self
+            .pending
+            .iter()
+            .position(|pending| pending.neighbor == *protocol_addr)?;
+        Some(self.pending.remove(index))
+    }
+
+    /// Return the time at which the next unicast probe is due, a neighbor is evicted, or a
+    /// discovery request is retransmitted for queued packets.
+    pub(crate) fn poll_at(&self) -> Option<Instant> {
+        #[cfg(feature = "medium-ethernet")]
+        if self.pending_due().is_some() {
+            return Some(Instant::from_millis(0));
+        }
+
+        self.storage
+            .iter()
+            .filter(|(_protocol_addr, neighbor)| match neighbor.state {
+                State::Delay | State::Probe => true,
+                #[cfg(feature = "medium-ethernet")]
+                State::Incomplete
+                    if self
+                        .pending
+                        .iter()
+                        .any(|pending| &pending.neighbor == *_protocol_addr) =>
+                {
+                    true
+                }
+                State::Incomplete => neighbor.probes >= Self::MAX_MULTICAST_SOLICIT,
+                _ => false,
+            })
+            .map(|(_, neighbor)| neighbor.expires_at)
+            .min()
+    }
+
+    /// Remove all the neighbors except the static ones, and drop the queued packets.
     pub(crate) fn flush(&mut self) {
-        self.storage.clear()
+        while let Some(protocol_addr) = self
+            .storage
+            .iter()
+            .find(|(_, neighbor)| neighbor.state != State::Static)
+            .map(|(protocol_addr, _)| *protocol_addr)
+        {
+            self.storage.remove(&protocol_addr);
+        }
+        #[cfg(feature = "medium-ethernet")]
+        self.pending.clear();
     }
 }
 
@@ -201,12 +708,13 @@ mod test {
         assert!(!cache
             .lookup(&MOCK_IP_ADDR_2.into(), Instant::from_millis(0))
             .found());
-        assert!(!cache
-            .lookup(
+        assert_eq!(
+            cache.state(
                 &MOCK_IP_ADDR_1.into(),
-                Instant::from_millis(0) + Cache::ENTRY_LIFETIME * 2
-            )
-            .found(),);
+                Instant::from_millis(0) + Cache::REACHABLE_TIME * 2
+            ),
+            Some(State::Stale)
+        );
 
         cache.fill(MOCK_IP_ADDR_1.into(), HADDR_A, Instant::from_millis(0));
         assert!(!cache
@@ -223,12 +731,123 @@ mod test {
             cache.lookup(&MOCK_IP_ADDR_1.into(), Instant::from_millis(0)),
             Answer::Found(HADDR_A)
         );
-        assert!(!cache
-            .lookup(
-                &MOCK_IP_ADDR_1.into(),
-                Instant::from_millis(0) + Cache::ENTRY_LIFETIME * 2
-            )
-            .found(),);
+
+        // A neighbor which was not confirmed recently is still used, but becomes stale.
+        let mut now = Instant::from_millis(0) + Cache::REACHABLE_TIME;
+        assert_eq!(cache.state(&MOCK_IP_ADDR_1.into(), now), Some(State::Stale));
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_1.into(), now),
+            Answer::Found(HADDR_A)
+        );
+        assert_eq!(cache.poll_at(), None);
+
+        // Sending a packet to it delays the first probe.
+        cache.used(&MOCK_IP_ADDR_1.into(), now);
+        assert_eq!(cache.state(&MOCK_IP_ADDR_1.into(), now), Some(State::Delay));
+        assert_eq!(cache.poll_at(), Some(now + Cache::DELAY_FIRST_PROBE_TIME));
+        assert_eq!(cache.probe_due(now), None);
+
+        // Unicast probes are sent one second apart, then the neighbor is evicted.
+        now += Cache::DELAY_FIRST_PROBE_TIME;
+        for _ in 0..Cache::MAX_UNICAST_SOLICIT {
+            cache.expire(now);
+            assert_eq!(cache.probe_due(now), Some((MOCK_IP_ADDR_1.into(), HADDR_A)));
+            cache.probe_sent(&MOCK_IP_ADDR_1.into(), now);
+            assert_eq!(cache.state(&MOCK_IP_ADDR_1.into(), now), Some(State::Probe));
+            assert_eq!(
+                cache.lookup(&MOCK_IP_ADDR_1.into(), now),
+                Answer::Found(HADDR_A)
+            );
+            assert_eq!(cache.probe_due(now), None);
+            now += Cache::SILENT_TIME;
+        }
+        assert_eq!(cache.probe_due(now), None);
+        cache.expire(now);
+        assert_eq!(cache.state(&MOCK_IP_ADDR_1.into(), now), None);
+        assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), now), Answer::NotFound);
+    }
+
+    #[test]
+    #[cfg(feature = "socket-tcp")]
+    fn test_confirm() {
+        let mut cache = Cache::new();
+
+        cache.fill(MOCK_IP_ADDR_1.into(), HADDR_A, Instant::from_millis(0));
+        let now = Instant::from_millis(0) + Cache::REACHABLE_TIME;
+        cache.used(&MOCK_IP_ADDR_1.into(), now);
+        cache.probe_sent(&MOCK_IP_ADDR_1.into(), now);
+
+        // A confirmation of an upper-layer protocol stops the probes.
+        cache.confirm(&MOCK_IP_ADDR_1.into(), now);
+        assert_eq!(
+            cache.state(&MOCK_IP_ADDR_1.into(), now),
+            Some(State::Reachable)
+        );
+        assert_eq!(cache.poll_at(), None);
+
+        // Unknown neighbors are not confirmed.
+        cache.confirm(&MOCK_IP_ADDR_2.into(), now);
+        assert_eq!(cache.state(&MOCK_IP_ADDR_2.into(), now), None);
+    }
+
+    #[test]
+    fn test_advert() {
+        let mut cache = Cache::new();
+        let now = Instant::from_millis(0);
+
+        // An advertisement without override casts a doubt on a different hardware address.
+        cache.fill(MOCK_IP_ADDR_1.into(), HADDR_A, now);
+        cache.advert(MOCK_IP_ADDR_1.into(), Some(HADDR_B), false, false, now);
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_1.into(), now),
+            Answer::Found(HADDR_A)
+        );
+        assert_eq!(cache.state(&MOCK_IP_ADDR_1.into(), now), Some(State::Stale));
+
+        // An unsolicited advertisement with override replaces it.
+        cache.advert(MOCK_IP_ADDR_1.into(), Some(HADDR_B), false, true, now);
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_1.into(), now),
+            Answer::Found(HADDR_B)
+        );
+        assert_eq!(cache.state(&MOCK_IP_ADDR_1.into(), now), Some(State::Stale));
+
+        // A solicited advertisement confirms the reachability.
+        cache.advert(MOCK_IP_ADDR_1.into(), None, true, false, now);
+        assert_eq!(
+            cache.state(&MOCK_IP_ADDR_1.into(), now),
+            Some(State::Reachable)
+        );
+
+        // A solicitation only fills a stale entry when the hardware address changed.
+        cache.fill_stale(MOCK_IP_ADDR_1.into(), HADDR_B, now);
+        assert_eq!(
+            cache.state(&MOCK_IP_ADDR_1.into(), now),
+            Some(State::Reachable)
+        );
+        cache.fill_stale(MOCK_IP_ADDR_1.into(), HADDR_C, now);
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_1.into(), now),
+            Answer::Found(HADDR_C)
+        );
+        assert_eq!(cache.state(&MOCK_IP_ADDR_1.into(), now), Some(State::Stale));
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn test_update() {
+        let mut cache = Cache::new();
+        let now = Instant::from_millis(0);
+
+        // Only known neighbors are updated.
+        cache.fill(MOCK_IP_ADDR_1.into(), HADDR_A, now);
+        cache.update(MOCK_IP_ADDR_2.into(), HADDR_D, now);
+        assert_eq!(cache.state(&MOCK_IP_ADDR_2.into(), now), None);
+        cache.update(MOCK_IP_ADDR_1.into(), HADDR_D, now);
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_1.into(), now),
+            Answer::Found(HADDR_D)
+        );
     }
 
     #[test]
@@ -281,17 +900,218 @@ mod test {
             Answer::NotFound
         );
 
-        cache.limit_rate(Instant::from_millis(0));
+        cache.limit_rate(&MOCK_IP_ADDR_1.into(), Instant::from_millis(0));
         assert_eq!(
             cache.lookup(&MOCK_IP_ADDR_1.into(), Instant::from_millis(100)),
             Answer::RateLimited
         );
+        assert_eq!(
+            cache.state(&MOCK_IP_ADDR_1.into(), Instant::from_millis(100)),
+            Some(State::Incomplete)
+        );
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_2.into(), Instant::from_millis(100)),
+            Answer::NotFound
+        );
         assert_eq!(
             cache.lookup(&MOCK_IP_ADDR_1.into(), Instant::from_millis(2000)),
             Answer::NotFound
         );
     }
 
+    #[test]
+    fn test_resolution_failure() {
+        let mut cache = Cache::new();
+        let mut now = Instant::from_millis(0);
+
+        for _ in 0..Cache::MAX_MULTICAST_SOLICIT {
+            cache.expire(now);
+            assert_eq!(cache.lookup(&MOCK_IP_ADDR_1.into(), now), Answer::NotFound);
+            cache.limit_rate(&MOCK_IP_ADDR_1.into(), now);
+            now += Cache::SILENT_TIME;
+        }
+        assert_eq!(cache.poll_at(), Some(now));
+        cache.expire(now);
+        assert_eq!(cache.state(&MOCK_IP_ADDR_1.into(), now), None);
+
+        // The resolution completes with a reply.
+        cache.limit_rate(&MOCK_IP_ADDR_1.into(), now);
+        cache.advert(MOCK_IP_ADDR_1.into(), Some(HADDR_A), true, true, now);
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_1.into(), now),
+            Answer::Found(HADDR_A)
+        );
+        assert_eq!(
+            cache.state(&MOCK_IP_ADDR_1.into(), now),
+            Some(State::Reachable)
+        );
+    }
+
+    #[test]
+    fn test_pending() {
+        let mut cache = Cache::new();
+        let mut now = Instant::from_millis(0);
+        let enqueue = |cache: &mut Cache, byte: u8| {
+            cache.enqueue(
+                &MOCK_IP_ADDR_1.into(),
+                PacketMeta::default(),
+                MOCK_IP_ADDR_2.into(),
+                4,
+                |buffer| buffer.fill(byte),
+            )
+        };
+
+        // Packets are only queued while the address resolution is in progress.
+        assert!(!enqueue(&mut cache, 0));
+        cache.limit_rate(&MOCK_IP_ADDR_1.into(), now);
+        for byte in 0..IFACE_NEIGHBOR_PENDING_COUNT as u8 {
+            assert!(enqueue(&mut cache, byte));
+        }
+        assert!(!enqueue(&mut cache, 0xff));
+        assert_eq!(cache.pending_due(), None);
+
+        // The queue is shared by all the neighbors.
+        cache.limit_rate(&MOCK_IP_ADDR_4.into(), now);
+        assert!(!cache.enqueue(
+            &MOCK_IP_ADDR_4.into(),
+            PacketMeta::default(),
+            MOCK_IP_ADDR_2.into(),
+            4,
+            |_| ()
+        ));
+
+        // The discovery request is retransmitted for the queued packets.
+        assert_eq!(cache.solicit_due(now), None);
+        now += Cache::SILENT_TIME;
+        assert_eq!(cache.poll_at(), Some(now));
+        assert_eq!(
+            cache.solicit_due(now),
+            Some((MOCK_IP_ADDR_1.into(), MOCK_IP_ADDR_2.into()))
+        );
+
+        // The queued packets are sent in order when the resolution completes.
+        cache.advert(MOCK_IP_ADDR_1.into(), Some(HADDR_A), true, true, now);
+        assert_eq!(cache.solicit_due(now), None);
+        assert_eq!(cache.poll_at(), Some(Instant::from_millis(0)));
+        assert_eq!(
+            cache.pending_due(),
+            Some((MOCK_IP_ADDR_1.into(), Some(HADDR_A)))
+        );
+        for byte in 0..IFACE_NEIGHBOR_PENDING_COUNT as u8 {
+            let pending = cache.dequeue(&MOCK_IP_ADDR_1.into()).unwrap();
+            assert_eq!(pending.src_addr, MOCK_IP_ADDR_2.into());
+            assert_eq!(&pending.packet[..], &[byte; 4]);
+        }
+        assert_eq!(cache.pending_due(), None);
+        assert!(!enqueue(&mut cache, 0));
+
+        // The queued packets are discarded when the resolution fails.
+        cache.limit_rate(&MOCK_IP_ADDR_3.into(), now);
+        assert!(cache.enqueue(
+            &MOCK_IP_ADDR_3.into(),
+            PacketMeta::default(),
+            MOCK_IP_ADDR_2.into(),
+            4,
+            |_| ()
+        ));
+        for _ in 1..Cache::MAX_MULTICAST_SOLICIT {
+            now += Cache::SILENT_TIME;
+            cache.limit_rate(&MOCK_IP_ADDR_3.into(), now);
+        }
+        now += Cache::SILENT_TIME;
+        assert_eq!(cache.solicit_due(now), None);
+        assert_eq!(cache.poll_at(), Some(now));
+        cache.expire(now);
+        assert_eq!(cache.pending_due(), Some((MOCK_IP_ADDR_3.into(), None)));
+        assert!(cache.dequeue(&MOCK_IP_ADDR_3.into()).is_some());
+        assert!(cache.dequeue(&MOCK_IP_ADDR_3.into()).is_none());
+        assert_eq!(cache.poll_at(), None);
+    }
+
+    #[test]
+    fn test_static() {
+        let mut cache = Cache::new();
+        let now = Instant::from_millis(0);
+
+        cache.fill(MOCK_IP_ADDR_1.into(), HADDR_A, now);
+        assert_eq!(cache.fill_static(MOCK_IP_ADDR_1.into(), HADDR_B), Ok(()));
+        assert_eq!(
+            cache.state(&MOCK_IP_ADDR_1.into(), now),
+            Some(State::Static)
+        );
+
+        // The static neighbor is not updated by ARP or NDISC, and does not expire.
+        cache.fill(MOCK_IP_ADDR_1.into(), HADDR_C, now);
+        cache.fill_stale(MOCK_IP_ADDR_1.into(), HADDR_C, now);
+        cache.advert(MOCK_IP_ADDR_1.into(), Some(HADDR_C), true, true, now);
+        let later = now + Cache::REACHABLE_TIME * 10;
+        cache.expire(later);
+        assert_eq!(
+            cache.lookup(&MOCK_IP_ADDR_1.into(), later),
+            Answer::Found(HADDR_B)
+        );
+        assert_eq!(cache.poll_at(), None);
+
+        // Static neighbors are not evicted, nor flushed.
+        cache.fill(MOCK_IP_ADDR_2.into(), HADDR_A, now);
+        cache.fill(MOCK_IP_ADDR_3.into(), HADDR_C, now);
+        cache.fill(MOCK_IP_ADDR_4.into(), HADDR_D, now);
+        assert!(cache.lookup(&MOCK_IP_ADDR_1.into(), now).found());
+        assert_eq!(cache.fill_static(MOCK_IP_ADDR_2.into(), HADDR_A), Ok(()));
+        assert_eq!(cache.fill_static(MOCK_IP_ADDR_3.into(), HADDR_C), Ok(()));
+        assert_eq!(
+            cache.fill_static(MOCK_IP_ADDR_4.into(), HADDR_D),
+            Err(NeighborCacheFull)
+        );
+        cache.fill(MOCK_IP_ADDR_4.into(), HADDR_D, now);
+        assert!(!cache.lookup(&MOCK_IP_ADDR_4.into(), now).found());
+        cache.flush();
+        assert_eq!(cache.iter(now).count(), 3);
+
+        // Static neighbors are removed explicitly.
+        let removed = cache.remove(&MOCK_IP_ADDR_1.into()).unwrap();
+        assert_eq!(removed.hardware_addr(), Some(HADDR_B));
+        assert_eq!(removed.state(), State::Static);
+        assert_eq!(removed.expires_at(), None);
+        assert!(!cache.lookup(&MOCK_IP_ADDR_1.into(), now).found());
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut cache = Cache::new();
+        let now = Instant::from_millis(0);
+
+        cache.fill(MOCK_IP_ADDR_1.into(), HADDR_A, now);
+        cache.limit_rate(&MOCK_IP_ADDR_2.into(), now);
+
+        let later = now + Cache::REACHABLE_TIME;
+        let mut neighbors: std::vec::Vec<_> = cache.iter(later).collect();
+        neighbors.sort_by_key(|(protocol_addr, _)| *protocol_addr);
+        let states: std::vec::Vec<_> = neighbors
+            .iter()
+            .map(|(protocol_addr, neighbor)| {
+                (
+                    *protocol_addr,
+                    neighbor.hardware_addr(),
+                    neighbor.state(),
+                    neighbor.expires_at(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            states,
+            [
+                (MOCK_IP_ADDR_1.into(), Some(HADDR_A), State::Stale, None),
+                (
+                    MOCK_IP_ADDR_2.into(),
+                    None,
+                    State::Incomplete,
+                    Some(now + Cache::SILENT_TIME)
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn test_flush() {
         let mut cache = Cache::new();
@@ -34,10 +34,25 @@ impl<'p> Packet<'p> {
     pub(crate) fn new_ipv4(ip_repr: Ipv4Repr, payload: IpPayload<'p>) -> Self {
         Self::Ipv4(PacketV4 {
             header: ip_repr,
+            dont_frag: false,
             payload,
         })
     }
 
+    /// Create a forwarded IPv4 packet, keeping the Don't Fragment flag it was received with.
+    #[cfg(all(feature = "proto-ipv4", feature = "iface-forwarding"))]
+    pub(crate) fn new_ipv4_forwarded(
+        ip_repr: Ipv4Repr,
+        payload: &'p [u8],
+        dont_frag: bool,
+    ) -> Self {
+        Self::Ipv4(PacketV4 {
+            header: ip_repr,
+            dont_frag,
+            payload: IpPayload::Raw(payload),
+        })
+    }
+
     #[cfg(feature = "proto-ipv6")]
     pub(crate) fn new_ipv6(ip_repr: Ipv6Repr, payload: IpPayload<'p>) -> Self {
         Self::Ipv6(PacketV6 {
@@ -57,7 +72,7 @@ impl<'p> Packet<'p> {
             #[cfg(feature = "proto-ipv4")]
             Packet::Ipv4(p) => IpRepr::Ipv4(p.header),
             #[cfg(feature = "proto-ipv6")]
-            Packet::Ipv6(p) => IpRepr::Ipv6(p.header),
+            Packet::Ipv6(p) => IpRepr::Ipv6(p.ip_repr()),
         }
     }
 
@@ -76,6 +91,21 @@ impl<'p> Packet<'p> {
         payload: &mut [u8],
         caps: &DeviceCapabilities,
     ) {
+        // The extension headers are emitted in front of the upper layer, which uses the final
+        // destination of the packet for its checksum.
+        #[cfg(any(feature = "proto-ipv6-hbh", feature = "proto-ipv6-routing"))]
+        let upper_layer_repr;
+        #[cfg(any(feature = "proto-ipv6-hbh", feature = "proto-ipv6-routing"))]
+        let (_ip_repr, payload) = match self {
+            Packet::Ipv6(packet) if packet.ext_headers_len() > 0 => {
+                let (ext_headers, payload) = payload.split_at_mut(packet.ext_headers_len());
+                packet.emit_ext_headers(ext_headers);
+                upper_layer_repr = IpRepr::Ipv6(packet.upper_layer_header());
+                (&upper_layer_repr, payload)
+            }
+            _ => (_ip_repr, payload),
+        };
+
         match self.payload() {
             #[cfg(feature = "proto-ipv4")]
             IpPayload::Icmpv4(icmpv4_repr) => {
@@ -98,7 +128,11 @@ impl<'p> Packet<'p> {
                     &caps.checksum,
                 )
             }
-            #[cfg(feature = "socket-raw")]
+            #[cfg(any(
+                feature = "socket-raw",
+                feature = "proto-rpl",
+                feature = "iface-forwarding"
+            ))]
             IpPayload::Raw(raw_packet) => payload.copy_from_slice(raw_packet),
             #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
             IpPayload::Udp(udp_repr, inner_payload) => udp_repr.emit(
@@ -154,9 +188,21 @@ impl<'p> Packet<'p> {
 #[cfg(feature = "proto-ipv4")]
 pub(crate) struct PacketV4<'p> {
     header: Ipv4Repr,
+    /// Whether a forwarded packet must not be fragmented.
+    dont_frag: bool,
     payload: IpPayload<'p>,
 }
 
+#[cfg(feature = "proto-ipv4")]
+impl PacketV4<'_> {
+    /// Return whether the packet must not be fragmented. Only the forwarded packets may have the
+    /// Don't Fragment flag, the packets of the interface are fragmented when needed.
+    #[cfg(feature = "iface-forwarding")]
+    pub(crate) fn dont_frag(&self) -> bool {
+        self.dont_frag
+    }
+}
+
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg(feature = "proto-ipv6")]
@@ -171,16 +217,149 @@ pub(crate) struct PacketV6<'p> {
     pub(crate) payload: IpPayload<'p>,
 }
 
+#[cfg(feature = "proto-ipv6")]
+impl PacketV6<'_> {
+    /// Return the IPv6 header seen by the upper layer. For a packet with a RPL Source Route
+    /// Header, the destination is the last address of the route, which is the address used in
+    /// the checksum pseudo-header.
+    #[cfg(any(
+        feature = "proto-ipv6-hbh",
+        feature = "proto-ipv6-routing",
+        feature = "proto-sixlowpan"
+    ))]
+    pub(crate) fn upper_layer_header(&self) -> Ipv6Repr {
+        #[cfg(feature = "proto-ipv6-routing")]
+        if let Some(routing) = &self.routing {
+            if let Some(dst_addr) = routing
+                .rpl_address_count()
+                .and_then(|n| routing.rpl_address(n, &self.header.dst_addr))
+            {
+                return Ipv6Repr {
+                    dst_addr,
+                    ..self.header
+                };
+            }
+        }
+
+        self.header
+    }
+
+    /// Return the length of the extension headers that are emitted between the IPv6 header and
+    /// the payload.
+    #[cfg(any(feature = "proto-ipv6-hbh", feature = "proto-ipv6-routing"))]
+    pub(crate) fn ext_headers_len(&self) -> usize {
+        #[allow(unused_mut)]
+        let mut len = 0;
+
+        #[cfg(feature = "proto-ipv6-hbh")]
+        if let Some(hbh) = &self.hop_by_hop {
+            len += 2 + hbh.buffer_len();
+        }
+
+        #[cfg(feature = "proto-ipv6-routing")]
+        if let Some(routing) = &self.routing {
+            len += 2 + routing.buffer_len();
+        }
+
+        len
+    }
+
+    /// Return the IPv6 header that is emitted in front of the extension headers.
+    pub(crate) fn ip_repr(&self) -> Ipv6Repr {
+        #[cfg(not(any(feature = "proto-ipv6-hbh", feature = "proto-ipv6-routing")))]
+        return self.header;
+
+        #[cfg(any(feature = "proto-ipv6-hbh", feature = "proto-ipv6-routing"))]
+        let mut next_header = self.header.next_header;
+
+        #[cfg(feature = "proto-ipv6-routing")]
+        if self.routing.is_some() {
+            next_header = IpProtocol::Ipv6Route;
+        }
+
+        #[cfg(feature = "proto-ipv6-hbh")]
+        if self.hop_by_hop.is_some() {
+            next_header = IpProtocol::HopByHop;
+        }
+
+        #[cfg(any(feature = "proto-ipv6-hbh", feature = "proto-ipv6-routing"))]
+        Ipv6Repr {
+            next_header,
+            payload_len: self.header.payload_len + self.ext_headers_len(),
+            ..self.header
+        }
+    }
+
+    /// Emit the extension headers into `buffer`, which should be `ext_headers_len()` long.
+    #[cfg(any(feature = "proto-ipv6-hbh", feature = "proto-ipv6-routing"))]
+    pub(crate) fn emit_ext_headers(&self, #[allow(unused_mut)] mut buffer: &mut [u8]) {
+        #[cfg(feature = "proto-ipv6-hbh")]
+        if let Some(hbh) = &self.hop_by_hop {
+            #[allow(unused_mut)]
+            let mut next_header = self.header.next_header;
+
+            #[cfg(feature = "proto-ipv6-routing")]
+            if self.routing.is_some() {
+                next_header = IpProtocol::Ipv6Route;
+            }
+
+            let len = 2 + hbh.buffer_len();
+            let ext_repr = Ipv6ExtHeaderRepr {
+                next_header,
+                length: (len / 8 - 1) as u8,
+                data: &[],
+            };
+            ext_repr.emit(&mut Ipv6ExtHeader::new_unchecked(&mut buffer[..len]));
+            hbh.emit(&mut Ipv6HopByHopHeader::new_unchecked(&mut buffer[2..len]));
+            #[cfg(feature = "proto-ipv6-routing")]
+            {
+                buffer = &mut buffer[len..];
+            }
+        }
+
+        #[cfg(feature = "proto-ipv6-routing")]
+        if let Some(routing) = &self.routing {
+            let len = 2 + routing.buffer_len();
+            let ext_repr = Ipv6ExtHeaderRepr {
+                next_header: self.header.next_header,
+                length: (len / 8 - 1) as u8,
+                data: &[],
+            };
+            ext_repr.emit(&mut Ipv6ExtHeader::new_unchecked(&mut buffer[..len]));
+            routing.emit(&mut Ipv6RoutingHeader::new_unchecked(&mut buffer[2..len]));
+        }
+    }
+
+    /// Return the 6LoWPAN next header of the payload.
+    #[cfg(feature = "proto-sixlowpan")]
+    pub(crate) fn sixlowpan_next_header(&self) -> SixlowpanNextHeader {
+        match &self.payload {
+            // A raw payload is carried inline, starting with the header given by the IPv6 header.
+            #[cfg(any(
+                feature = "socket-raw",
+                feature = "proto-rpl",
+                feature = "iface-forwarding"
+            ))]
+            IpPayload::Raw(_) => SixlowpanNextHeader::Uncompressed(self.header.next_header),
+            payload => payload.as_sixlowpan_next_header(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub(crate) enum IpPayload<'p> {
     #[cfg(feature = "proto-ipv4")]
     Icmpv4(Icmpv4Repr<'p>),
     #[cfg(feature = "proto-igmp")]
-    Igmp(IgmpRepr),
+    Igmp(IgmpRepr<'p>),
     #[cfg(feature = "proto-ipv6")]
     Icmpv6(Icmpv6Repr<'p>),
-    #[cfg(feature = "socket-raw")]
+    #[cfg(any(
+        feature = "socket-raw",
+        feature = "proto-rpl",
+        feature = "iface-forwarding"
+    ))]
     Raw(&'p [u8]),
     #[cfg(any(feature = "socket-udp", feature = "socket-dns"))]
     Udp(UdpRepr, &'p [u8]),
@@ -206,8 +385,12 @@ impl<'p> IpPayload<'p> {
             Self::Tcp(_) => SixlowpanNextHeader::Uncompressed(IpProtocol::Tcp),
             #[cfg(feature = "socket-udp")]
             Self::Udp(..) => SixlowpanNextHeader::Compressed,
-            #[cfg(feature = "socket-raw")]
-            Self::Raw(_) => todo!(),
+            #[cfg(any(
+                feature = "socket-raw",
+                feature = "proto-rpl",
+                feature = "iface-forwarding"
+            ))]
+            Self::Raw(_) => unreachable!(),
         }
     }
 }
@@ -239,4 +422,17 @@ pub(crate) enum IgmpReportState {
         timeout: crate::time::Instant,
         group: Ipv4Address,
     },
+    ToGeneralQueryV3 {
+        timeout: crate::time::Instant,
+        next_index: usize,
+    },
+}
+
+#[cfg(feature = "proto-mld")]
+pub(crate) enum MldReportState {
+    Inactive,
+    ToGeneralQuery {
+        timeout: crate::time::Instant,
+        next_index: usize,
+    },
 }
@@ -0,0 +1,134 @@
+// Heads up! Before working on this file you should read, at least,
+// RFC 1191 and RFC 8201.
+
+use heapless::LinearMap;
+
+use crate::config::IFACE_PATH_MTU_CACHE_COUNT;
+use crate::time::{Duration, Instant};
+use crate::wire::IpAddress;
+
+/// A path MTU reported by a router.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Entry {
+    mtu: usize,
+    expires_at: Instant,
+}
+
+/// A cache of the path MTU of destinations.
+///
+/// The path MTU of a destination is lowered when a router reports that a packet sent to it
+/// was too big. Entries expire after a while, so that an increase of the path MTU is
+/// eventually detected.
+#[derive(Debug)]
+pub struct Cache {
+    storage: LinearMap<IpAddress, Entry, IFACE_PATH_MTU_CACHE_COUNT>,
+}
+
+impl Cache {
+    /// Time after which a lowered path MTU is reset, see RFC 1191 § 6.3 and RFC 8201 § 4.
+    pub(crate) const EXPIRY: Duration = Duration::from_secs(600);
+
+    /// Create a path MTU cache.
+    pub fn new() -> Self {
+        Self {
+            storage: LinearMap::new(),
+        }
+    }
+
+    /// Set the path MTU of `addr`. When the cache is full, the entry expiring first is evicted.
+    pub(crate) fn fill(&mut self, addr: IpAddress, mtu: usize, timestamp: Instant) {
+        let entry = Entry {
+            mtu,
+            expires_at: timestamp + Self::EXPIRY,
+        };
+
+        if self.storage.len() == self.storage.capacity() && !self.storage.contains_key(&addr) {
+            let (&old_addr, _) = self
+                .storage
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .unwrap();
+            self.storage.remove(&old_addr);
+        }
+
+        self.storage.insert(addr, entry).unwrap();
+    }
+
+    /// Return the path MTU of `addr`, if it was lowered and didn't expire yet.
+    pub(crate) fn lookup(&self, addr: &IpAddress, timestamp: Instant) -> Option<usize> {
+        self.storage
+            .get(addr)
+            .filter(|entry| entry.expires_at > timestamp)
+            .map(|entry| entry.mtu)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(not(feature = "proto-ipv4"))]
+    use crate::wire::Ipv6Address;
+
+    #[cfg(feature = "proto-ipv4")]
+    const ADDR_1: IpAddress = IpAddress::v4(192, 168, 1, 1);
+    #[cfg(feature = "proto-ipv4")]
+    const ADDR_2: IpAddress = IpAddress::v4(192, 168, 1, 2);
+    #[cfg(feature = "proto-ipv4")]
+    const ADDR_3: IpAddress = IpAddress::v4(192, 168, 1, 3);
+    #[cfg(feature = "proto-ipv4")]
+    const ADDR_4: IpAddress = IpAddress::v4(192, 168, 1, 4);
+
+    #[cfg(not(feature = "proto-ipv4"))]
+    const ADDR_1: IpAddress = IpAddress::Ipv6(Ipv6Address([
+        0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+    ]));
+    #[cfg(not(feature = "proto-ipv4"))]
+    const ADDR_2: IpAddress = IpAddress::Ipv6(Ipv6Address([
+        0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2,
+    ]));
+    #[cfg(not(feature = "proto-ipv4"))]
+    const ADDR_3: IpAddress = IpAddress::Ipv6(Ipv6Address([
+        0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3,
+    ]));
+    #[cfg(not(feature = "proto-ipv4"))]
+    const ADDR_4: IpAddress = IpAddress::Ipv6(Ipv6Address([
+        0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4,
+    ]));
+
+    #[test]
+    fn test_fill() {
+        let mut cache = Cache::new();
+
+        assert_eq!(cache.lookup(&ADDR_1, Instant::ZERO), None);
+        cache.fill(ADDR_1, 1400, Instant::ZERO);
+        assert_eq!(cache.lookup(&ADDR_1, Instant::ZERO), Some(1400));
+        assert_eq!(cache.lookup(&ADDR_2, Instant::ZERO), None);
+
+        cache.fill(ADDR_1, 1300, Instant::from_secs(1));
+        assert_eq!(cache.lookup(&ADDR_1, Instant::from_secs(1)), Some(1300));
+    }
+
+    #[test]
+    fn test_expire() {
+        let mut cache = Cache::new();
+
+        cache.fill(ADDR_1, 1400, Instant::ZERO);
+        assert_eq!(cache.lookup(&ADDR_1, Instant::from_secs(599)), Some(1400));
+        assert_eq!(cache.lookup(&ADDR_1, Instant::ZERO + Cache::EXPIRY), None);
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut cache = Cache::new();
+
+        cache.fill(ADDR_1, 1400, Instant::from_secs(2));
+        cache.fill(ADDR_2, 1400, Instant::from_secs(1));
+        cache.fill(ADDR_3, 1400, Instant::from_secs(3));
+        cache.fill(ADDR_4, 1400, Instant::from_secs(4));
+
+        assert_eq!(cache.lookup(&ADDR_1, Instant::from_secs(4)), Some(1400));
+        assert_eq!(cache.lookup(&ADDR_2, Instant::from_secs(4)), None);
+        assert_eq!(cache.lookup(&ADDR_4, Instant::from_secs(4)), Some(1400));
+    }
+}
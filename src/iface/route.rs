@@ -147,6 +147,12 @@ impl Routes {
     }
 
     pub(crate) fn lookup(&self, addr: &IpAddress, timestamp: Instant) -> Option<IpAddress> {
+        self.lookup_route(addr, timestamp)
+            .map(|route| route.via_router)
+    }
+
+    /// Return the most specific route to an address.
+    pub(crate) fn lookup_route(&self, addr: &IpAddress, timestamp: Instant) -> Option<&Route> {
         assert!(addr.is_unicast());
 
         self.storage
@@ -162,7 +168,6 @@ impl Routes {
             })
             // pick the most specific one (highest prefix_len)
             .max_by_key(|route| route.cidr.prefix_len())
-            .map(|route| route.via_router)
     }
 }
 
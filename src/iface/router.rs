@@ -0,0 +1,644 @@
+/*! IP packet forwarding between interfaces.
+
+A [`Router`] holds several interfaces, each driving its own device. Packets received on an
+interface that are not addressed to it are forwarded to the interface with the most specific
+route to their destination, following the addresses, the [`Routes`](super::Routes) and the RPL
+routes of the interfaces.
+*/
+
+use managed::ManagedSlice;
+
+use super::interface::{ForwardError, InterfaceInner};
+use super::packet::{IpPayload, Packet};
+use super::{Interface, SocketSet};
+use crate::phy::Device;
+use crate::storage::PacketBuffer;
+use crate::time::Instant;
+use crate::wire::IpAddress;
+
+/// An interface of a router, with the queue of the packets forwarded to it.
+pub struct RouterInterface<'a> {
+    iface: Interface,
+    queue: PacketBuffer<'a, ()>,
+}
+
+impl<'a> RouterInterface<'a> {
+    /// Create a router interface. The packets forwarded to the interface wait in `queue` until
+    /// the interface is polled.
+    pub fn new(iface: Interface, queue: PacketBuffer<'a, ()>) -> Self {
+        Self { iface, queue }
+    }
+}
+
+/// An IP router, forwarding packets between its interfaces.
+///
+/// The hop limit of the forwarded packets is decremented. When a packet cannot be forwarded, an
+/// ICMP error is sent back on the interface it was received on: Time Exceeded when its hop limit
+/// expires, Destination Unreachable when no interface has a route to its destination, and Packet
+/// Too Big (Fragmentation Needed for IPv4) when it does not fit in the MTU of the outgoing
+/// interface. IPv4 packets without the Don't Fragment flag are fragmented instead when
+/// `proto-ipv4-fragmentation` is enabled (RFC 1812 § 5.2.6).
+///
+/// The forwarded IPv4 packets are emitted again from their addresses, protocol and TTL: their
+/// identification and DSCP are not kept, and they are sent with the Don't Fragment flag unless
+/// they are fragmented.
+pub struct Router<'a> {
+    interfaces: ManagedSlice<'a, RouterInterface<'a>>,
+}
+
+impl<'a> Router<'a> {
+    /// Create a router with the given interfaces. Interfaces are identified by their index in
+    /// `interfaces`.
+    pub fn new<T>(interfaces: T) -> Self
+    where
+        T: Into<ManagedSlice<'a, RouterInterface<'a>>>,
+    {
+        let mut interfaces = interfaces.into();
+        for interface in interfaces.iter_mut() {
+            interface.iface.set_forwarding(true);
+        }
+
+        Self { interfaces }
+    }
+
+    /// Get an interface of the router.
+    ///
+    /// # Panics
+    /// This function panics if there is no interface with this index.
+    pub fn iface(&self, index: usize) -> &Interface {
+        &self.interfaces[index].iface
+    }
+
+    /// Get a mutable reference to an interface of the router.
+    ///
+    /// # Panics
+    /// This function panics if there is no interface with this index.
+    pub fn iface_mut(&mut self, index: usize) -> &mut Interface {
+        &mut self.interfaces[index].iface
+    }
+
+    /// Poll an interface with its device: the packets received on the interface are processed
+    /// or forwarded, and the packets forwarded to it are transmitted.
+    ///
+    /// This function returns a boolean value indicating whether any packets were
+    /// processed or emitted, like [`Interface::poll`].
+    ///
+    /// # Panics
+    /// This function panics if there is no interface with this index.
+    pub fn poll<D>(
+        &mut self,
+        timestamp: Instant,
+        index: usize,
+        device: &mut D,
+        sockets: &mut SocketSet<'_>,
+    ) -> bool
+    where
+        D: Device + ?Sized,
+    {
+        let (before, rest) = self.interfaces.split_at_mut(index);
+        let (interface, after) = rest.split_first_mut().unwrap();
+        let RouterInterface { iface, queue } = interface;
+
+        let mut did_something = iface.poll_routed(timestamp, device, sockets, |ingress, packet| {
+            Self::route(index, ingress, before, after, packet)
+        });
+
+        while !queue.is_empty() && iface.can_forward() {
+            let Some(tx_token) = device.transmit(timestamp) else {
+                break;
+            };
+            let Ok(((), datagram)) = queue.dequeue() else {
+                break;
+            };
+
+            iface.dispatch_forwarded(tx_token, sockets, datagram, |ingress, packet| {
+                Self::route(index, ingress, before, after, packet)
+            });
+            did_something = true;
+        }
+
+        did_something
+    }
+
+    /// Return a _soft deadline_ for calling [`poll`](Self::poll) for an interface the next time,
+    /// like [`Interface::poll_at`].
+    ///
+    /// # Panics
+    /// This function panics if there is no interface with this index.
+    pub fn poll_at(
+        &mut self,
+        timestamp: Instant,
+        index: usize,
+        sockets: &SocketSet<'_>,
+    ) -> Option<Instant> {
+        let interface = &mut self.interfaces[index];
+        if !interface.queue.is_empty() {
+            return Some(Instant::from_millis(0));
+        }
+
+        interface.iface.poll_at(timestamp, sockets)
+    }
+
+    /// Route a packet sent by the interface with the given index, whose context is `ingress`.
+    /// The other interfaces of the router are in `before` and `after`.
+    ///
+    /// The packet is returned when it is sent on the ingress interface, otherwise it is queued
+    /// on the interface with the most specific route to its destination. An ICMP error is
+    /// returned instead of a forwarded packet that cannot be sent.
+    fn route<'p>(
+        index: usize,
+        ingress: &InterfaceInner,
+        before: &mut [RouterInterface<'a>],
+        after: &mut [RouterInterface<'a>],
+        packet: Packet<'p>,
+    ) -> Option<Packet<'p>> {
+        let forwarded = matches!(packet.payload(), IpPayload::Raw(_));
+        let ip_repr = packet.ip_repr();
+        let dst_addr = ip_repr.dst_addr();
+
+        // Link-local and multicast destinations are on the ingress link.
+        if !dst_addr.is_unicast() || is_link_local(&dst_addr) {
+            return Some(packet);
+        }
+
+        // Select the interface with the most specific route, preferring the ingress interface.
+        let mut egress = None;
+        let mut prefix_len = ingress.route_prefix_len(&dst_addr);
+        let others = before.iter().enumerate().chain(
+            after
+                .iter()
+                .enumerate()
+                .map(|(i, interface)| (index + 1 + i, interface)),
+        );
+        for (i, interface) in others {
+            let len = interface.iface.inner.route_prefix_len(&dst_addr);
+            if len.is_some() && len > prefix_len {
+                prefix_len = len;
+                egress = Some(i);
+            }
+        }
+
+        if prefix_len.is_none() {
+            net_debug!("router: no route to {}", dst_addr);
+            return if forwarded {
+                ingress.forward_error(packet, ForwardError::NoRoute)
+            } else {
+                Some(packet)
+            };
+        }
+
+        let Some(egress) = egress else {
+            return Some(packet);
+        };
+        let interface = if egress < index {
+            &mut before[egress]
+        } else {
+            &mut after[egress - index - 1]
+        };
+        let egress_inner = &interface.iface.inner;
+
+        if is_link_local(&ip_repr.src_addr()) {
+            net_debug!("router: not forwarding {} off-link", ip_repr.src_addr());
+            return ingress.forward_error(packet, ForwardError::BeyondScope);
+        }
+
+        if egress_inner.is_broadcast(&dst_addr) {
+            net_debug!("router: not forwarding directed broadcast to {}", dst_addr);
+            return None;
+        }
+
+        let len = ip_repr.buffer_len();
+        let mtu = egress_inner.forwarding_mtu();
+        #[cfg(feature = "proto-ipv4-fragmentation")]
+        let fragmented = matches!(&packet, Packet::Ipv4(packet) if !packet.dont_frag());
+        #[cfg(not(feature = "proto-ipv4-fragmentation"))]
+        let fragmented = false;
+        if len > mtu && !fragmented {
+            net_debug!(
+                "router: packet of {} bytes does not fit in MTU {}",
+                len,
+                mtu
+            );
+            return ingress.forward_error(packet, ForwardError::PacketTooBig(mtu));
+        }
+
+        match interface.queue.enqueue(len, ()) {
+            Ok(buffer) => interface.iface.inner.emit_forwarded(&packet, buffer),
+            Err(_) => net_debug!("router: queue of interface {} is full, dropping", egress),
+        }
+
+        None
+    }
+}
+
+fn is_link_local(addr: &IpAddress) -> bool {
+    match addr {
+        #[cfg(feature = "proto-ipv4")]
+        IpAddress::Ipv4(_) => false,
+        #[cfg(feature = "proto-ipv6")]
+        IpAddress::Ipv6(addr) => addr.is_link_local(),
+    }
+}
+
+#[cfg(all(test, feature = "medium-ip"))]
+mod tests {
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::iface::Config;
+    use crate::phy::{self, ChecksumCapabilities, DeviceCapabilities, Medium};
+    use crate::storage::PacketMetadata;
+    use crate::wire::*;
+
+    /// A device receiving the packets pushed to `rx` and keeping the packets it transmits.
+    struct Link {
+        rx: VecDeque<Vec<u8>>,
+        tx: Vec<Vec<u8>>,
+        mtu: usize,
+    }
+
+    impl Link {
+        fn new(mtu: usize) -> Self {
+            Self {
+                rx: VecDeque::new(),
+                tx: Vec::new(),
+                mtu,
+            }
+        }
+    }
+
+    impl Device for Link {
+        type RxToken<'a> = RxToken;
+        type TxToken<'a> = TxToken<'a>;
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            DeviceCapabilities {
+                medium: Medium::Ip,
+                max_transmission_unit: self.mtu,
+                ..DeviceCapabilities::default()
+            }
+        }
+
+        fn receive(&mut self, _: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+            let buffer = self.rx.pop_front()?;
+            Some((RxToken(buffer), TxToken(&mut self.tx)))
+        }
+
+        fn transmit(&mut self, _: Instant) -> Option<Self::TxToken<'_>> {
+            Some(TxToken(&mut self.tx))
+        }
+    }
+
+    struct RxToken(Vec<u8>);
+
+    impl phy::RxToken for RxToken {
+        fn consume<R, F>(mut self, f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            f(&mut self.0)
+        }
+    }
+
+    struct TxToken<'a>(&'a mut Vec<Vec<u8>>);
+
+    impl<'a> phy::TxToken for TxToken<'a> {
+        fn consume<R, F>(self, len: usize, f: F) -> R
+        where
+            F: FnOnce(&mut [u8]) -> R,
+        {
+            let mut buffer = vec![0; len];
+            let result = f(&mut buffer);
+            self.0.push(buffer);
+            result
+        }
+    }
+
+    const PAYLOAD: [u8; 8] = [0xaa; 8];
+
+    /// Create a router with an interface on 10.0.0.0/24 and 2001:db8::/64, and an interface
+    /// on 10.0.1.0/24 and 2001:db8:1::/64.
+    fn setup(mtu: usize) -> (Router<'static>, Link, Link) {
+        let mut links = [Link::new(1500), Link::new(mtu)];
+        let mut interfaces = Vec::new();
+
+        for (i, link) in links.iter_mut().enumerate() {
+            let mut iface = Interface::new(Config::new(HardwareAddress::Ip), link, Instant::ZERO);
+            iface.update_ip_addrs(|addrs| {
+                #[cfg(feature = "proto-ipv4")]
+                addrs
+                    .push(IpCidr::new(IpAddress::v4(10, 0, i as u8, 1), 24))
+                    .unwrap();
+                #[cfg(feature = "proto-ipv6")]
+                addrs
+                    .push(IpCidr::new(
+                        IpAddress::v6(0x2001, 0xdb8, i as u16, 0, 0, 0, 0, 1),
+                        64,
+                    ))
+                    .unwrap();
+            });
+            let queue = PacketBuffer::new(vec![PacketMetadata::EMPTY; 4], vec![0; 4096]);
+            interfaces.push(RouterInterface::new(iface, queue));
+        }
+
+        let [link0, link1] = links;
+        (Router::new(interfaces), link0, link1)
+    }
+
+    #[cfg(feature = "proto-ipv4")]
+    fn ipv4_packet(src_addr: Ipv4Address, dst_addr: Ipv4Address, hop_limit: u8) -> Vec<u8> {
+        let repr = Ipv4Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::Udp,
+            payload_len: PAYLOAD.len(),
+            hop_limit,
+        };
+        let mut bytes = vec![0; repr.buffer_len() + PAYLOAD.len()];
+        let mut packet = Ipv4Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet, &ChecksumCapabilities::default());
+        packet.payload_mut().copy_from_slice(&PAYLOAD);
+        bytes
+    }
+
+    #[cfg(feature = "proto-ipv6")]
+    fn ipv6_packet(
+        src_addr: Ipv6Address,
+        dst_addr: Ipv6Address,
+        hop_limit: u8,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let repr = Ipv6Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::Udp,
+            payload_len: payload.len(),
+            hop_limit,
+        };
+        let mut bytes = vec![0; repr.buffer_len() + payload.len()];
+        let mut packet = Ipv6Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet);
+        packet.payload_mut().copy_from_slice(payload);
+        bytes
+    }
+
+    /// Return the type and code of the ICMP error in a packet.
+    #[cfg(feature = "proto-ipv4")]
+    fn icmpv4_error(bytes: &[u8]) -> (Ipv4Repr, Icmpv4Message, u8) {
+        let packet = Ipv4Packet::new_checked(bytes).unwrap();
+        let repr = Ipv4Repr::parse(&packet, &ChecksumCapabilities::default()).unwrap();
+        let icmp = Icmpv4Packet::new_checked(packet.payload()).unwrap();
+        (repr, icmp.msg_type(), icmp.msg_code())
+    }
+
+    #[cfg(feature = "proto-ipv6")]
+    fn icmpv6_error(bytes: &[u8]) -> (Ipv6Repr, Icmpv6Packet<&[u8]>) {
+        let packet = Ipv6Packet::new_checked(bytes).unwrap();
+        let repr = Ipv6Repr::parse(&packet).unwrap();
+        (repr, Icmpv6Packet::new_checked(packet.payload()).unwrap())
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn forward_ipv4() {
+        let (mut router, mut link0, mut link1) = setup(1500);
+        let mut sockets = SocketSet::new(vec![]);
+
+        let src_addr = Ipv4Address::new(10, 0, 0, 2);
+        let dst_addr = Ipv4Address::new(10, 0, 1, 2);
+        link0.rx.push_back(ipv4_packet(src_addr, dst_addr, 64));
+
+        assert!(router.poll(Instant::ZERO, 0, &mut link0, &mut sockets));
+        assert!(link0.tx.is_empty());
+        assert_eq!(
+            router.poll_at(Instant::ZERO, 1, &sockets),
+            Some(Instant::ZERO)
+        );
+
+        assert!(router.poll(Instant::ZERO, 1, &mut link1, &mut sockets));
+        assert_eq!(link1.tx, vec![ipv4_packet(src_addr, dst_addr, 63)]);
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn ipv4_ttl_expired() {
+        let (mut router, mut link0, mut link1) = setup(1500);
+        let mut sockets = SocketSet::new(vec![]);
+
+        let src_addr = Ipv4Address::new(10, 0, 0, 2);
+        link0
+            .rx
+            .push_back(ipv4_packet(src_addr, Ipv4Address::new(10, 0, 1, 2), 1));
+
+        router.poll(Instant::ZERO, 0, &mut link0, &mut sockets);
+        router.poll(Instant::ZERO, 1, &mut link1, &mut sockets);
+        assert!(link1.tx.is_empty());
+
+        assert_eq!(link0.tx.len(), 1);
+        let (repr, msg_type, code) = icmpv4_error(&link0.tx[0]);
+        assert_eq!(repr.src_addr, Ipv4Address::new(10, 0, 0, 1));
+        assert_eq!(repr.dst_addr, src_addr);
+        assert_eq!(msg_type, Icmpv4Message::TimeExceeded);
+        assert_eq!(code, u8::from(Icmpv4TimeExceeded::TtlExpired));
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn ipv4_no_route() {
+        let (mut router, mut link0, _) = setup(1500);
+        let mut sockets = SocketSet::new(vec![]);
+
+        let src_addr = Ipv4Address::new(10, 0, 0, 2);
+        link0
+            .rx
+            .push_back(ipv4_packet(src_addr, Ipv4Address::new(10, 0, 9, 2), 64));
+
+        router.poll(Instant::ZERO, 0, &mut link0, &mut sockets);
+        assert_eq!(link0.tx.len(), 1);
+        let (repr, msg_type, code) = icmpv4_error(&link0.tx[0]);
+        assert_eq!(repr.dst_addr, src_addr);
+        assert_eq!(msg_type, Icmpv4Message::DstUnreachable);
+        assert_eq!(code, u8::from(Icmpv4DstUnreachable::NetUnreachable));
+
+        // No error is sent about an ICMP error.
+        link0.tx.clear();
+        let mut error = ipv4_packet(src_addr, Ipv4Address::new(10, 0, 9, 2), 64);
+        let mut packet = Ipv4Packet::new_unchecked(&mut error);
+        packet.set_next_header(IpProtocol::Icmp);
+        packet.payload_mut()[0] = Icmpv4Message::DstUnreachable.into();
+        packet.fill_checksum();
+        link0.rx.push_back(error);
+        router.poll(Instant::ZERO, 0, &mut link0, &mut sockets);
+        assert!(link0.tx.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn ipv4_to_other_interface() {
+        let (mut router, mut link0, mut link1) = setup(1500);
+        let mut sockets = SocketSet::new(vec![]);
+
+        // An echo request to the address of the other interface is answered by it.
+        let src_addr = Ipv4Address::new(10, 0, 0, 2);
+        let dst_addr = Ipv4Address::new(10, 0, 1, 1);
+        let echo = Icmpv4Repr::EchoRequest {
+            ident: 1,
+            seq_no: 2,
+            data: &PAYLOAD,
+        };
+        let repr = Ipv4Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::Icmp,
+            payload_len: echo.buffer_len(),
+            hop_limit: 64,
+        };
+        let mut bytes = vec![0; repr.buffer_len() + echo.buffer_len()];
+        let mut packet = Ipv4Packet::new_unchecked(&mut bytes);
+        repr.emit(&mut packet, &ChecksumCapabilities::default());
+        echo.emit(
+            &mut Icmpv4Packet::new_unchecked(packet.payload_mut()),
+            &ChecksumCapabilities::default(),
+        );
+        link0.rx.push_back(bytes);
+
+        router.poll(Instant::ZERO, 0, &mut link0, &mut sockets);
+        router.poll(Instant::ZERO, 1, &mut link1, &mut sockets);
+        assert!(link1.tx.is_empty());
+        router.poll(Instant::ZERO, 0, &mut link0, &mut sockets);
+
+        assert_eq!(link0.tx.len(), 1);
+        let (repr, msg_type, _) = icmpv4_error(&link0.tx[0]);
+        assert_eq!(repr.src_addr, dst_addr);
+        assert_eq!(repr.dst_addr, src_addr);
+        assert_eq!(msg_type, Icmpv4Message::EchoReply);
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv4")]
+    fn ipv4_packet_too_big() {
+        let (mut router, mut link0, mut link1) = setup(IPV4_MIN_MTU);
+        let mut sockets = SocketSet::new(vec![]);
+
+        let src_addr = Ipv4Address::new(10, 0, 0, 2);
+        let repr = Ipv4Repr {
+            src_addr,
+            dst_addr: Ipv4Address::new(10, 0, 1, 2),
+            next_header: IpProtocol::Udp,
+            payload_len: 1000,
+            hop_limit: 64,
+        };
+        let mut bytes = vec![0; repr.buffer_len() + 1000];
+        repr.emit(
+            &mut Ipv4Packet::new_unchecked(&mut bytes),
+            &ChecksumCapabilities::default(),
+        );
+
+        // A packet with the Don't Fragment flag is answered with Fragmentation Needed.
+        link0.rx.push_back(bytes.clone());
+        router.poll(Instant::ZERO, 0, &mut link0, &mut sockets);
+        router.poll(Instant::ZERO, 1, &mut link1, &mut sockets);
+        assert!(link1.tx.is_empty());
+
+        assert_eq!(link0.tx.len(), 1);
+        let (repr, msg_type, code) = icmpv4_error(&link0.tx[0]);
+        assert_eq!(repr.dst_addr, src_addr);
+        assert_eq!(msg_type, Icmpv4Message::DstUnreachable);
+        assert_eq!(code, u8::from(Icmpv4DstUnreachable::FragRequired));
+
+        // Without the flag, the packet is fragmented.
+        #[cfg(feature = "proto-ipv4-fragmentation")]
+        {
+            link0.tx.clear();
+            let mut packet = Ipv4Packet::new_unchecked(&mut bytes);
+            packet.set_dont_frag(false);
+            packet.fill_checksum();
+            link0.rx.push_back(bytes);
+            router.poll(Instant::ZERO, 0, &mut link0, &mut sockets);
+            assert!(link0.tx.is_empty());
+
+            while router.poll(Instant::ZERO, 1, &mut link1, &mut sockets) {}
+            assert!(link1.tx.len() > 1);
+            for fragment in link1.tx.iter() {
+                let fragment = Ipv4Packet::new_checked(&fragment[..]).unwrap();
+                assert!(!fragment.dont_frag());
+                assert!(fragment.total_len() as usize <= IPV4_MIN_MTU);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv6")]
+    fn forward_ipv6() {
+        let (mut router, mut link0, mut link1) = setup(1500);
+        let mut sockets = SocketSet::new(vec![]);
+
+        let src_addr = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+        let dst_addr = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 2);
+        link0
+            .rx
+            .push_back(ipv6_packet(src_addr, dst_addr, 64, &PAYLOAD));
+
+        router.poll(Instant::ZERO, 0, &mut link0, &mut sockets);
+        router.poll(Instant::ZERO, 1, &mut link1, &mut sockets);
+        assert!(link0.tx.is_empty());
+        assert_eq!(
+            link1.tx,
+            vec![ipv6_packet(src_addr, dst_addr, 63, &PAYLOAD)]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "proto-ipv6")]
+    fn ipv6_errors() {
+        let (mut router, mut link0, mut link1) = setup(IPV6_MIN_MTU);
+        let mut sockets = SocketSet::new(vec![]);
+
+        let src_addr = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+        let dst_addr = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 2);
+        let link_local = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+        let unrouted = Ipv6Address::new(0x2001, 0xdb8, 9, 0, 0, 0, 0, 2);
+        let cases = [
+            (
+                ipv6_packet(src_addr, dst_addr, 1, &PAYLOAD),
+                Icmpv6Message::TimeExceeded,
+                u8::from(Icmpv6TimeExceeded::HopLimitExceeded),
+            ),
+            (
+                ipv6_packet(src_addr, unrouted, 64, &PAYLOAD),
+                Icmpv6Message::DstUnreachable,
+                u8::from(Icmpv6DstUnreachable::NoRoute),
+            ),
+            (
+                ipv6_packet(link_local, dst_addr, 64, &PAYLOAD),
+                Icmpv6Message::DstUnreachable,
+                u8::from(Icmpv6DstUnreachable::BeyondScope),
+            ),
+            (
+                ipv6_packet(src_addr, dst_addr, 64, &[0; IPV6_MIN_MTU]),
+                Icmpv6Message::PktTooBig,
+                0,
+            ),
+        ];
+
+        for (packet, msg_type, code) in cases {
+            link0.tx.clear();
+            let src = Ipv6Packet::new_unchecked(&packet).src_addr();
+            link0.rx.push_back(packet);
+
+            router.poll(Instant::ZERO, 0, &mut link0, &mut sockets);
+            router.poll(Instant::ZERO, 1, &mut link1, &mut sockets);
+            assert!(link1.tx.is_empty());
+
+            assert_eq!(link0.tx.len(), 1);
+            let (repr, icmp) = icmpv6_error(&link0.tx[0]);
+            assert_eq!(repr.dst_addr, src);
+            assert_eq!(icmp.msg_type(), msg_type);
+            assert_eq!(icmp.msg_code(), code);
+            if msg_type == Icmpv6Message::PktTooBig {
+                assert_eq!(icmp.pkt_too_big_mtu(), IPV6_MIN_MTU as u32);
+            }
+        }
+    }
+}
@@ -1,8 +1,26 @@
+use crate::time::Duration;
+
 pub const SEQUENCE_WINDOW: u8 = 16;
 
 pub const DEFAULT_MIN_HOP_RANK_INCREASE: u16 = 256;
+pub const DEFAULT_MAX_RANK_INCREASE: u16 = 7 * DEFAULT_MIN_HOP_RANK_INCREASE;
 
 pub const DEFAULT_DIO_INTERVAL_MIN: u32 = 12;
 pub const DEFAULT_DIO_REDUNDANCY_CONSTANT: usize = 10;
 /// This is 20 in the standard, but in Contiki they use:
 pub const DEFAULT_DIO_INTERVAL_DOUBLINGS: u32 = 8;
+
+/// The lifetime of routes is `DEFAULT_LIFETIME * DEFAULT_LIFETIME_UNIT` seconds. The standard
+/// uses an infinite lifetime, but then routes are never purged. These are the Contiki values.
+pub const DEFAULT_LIFETIME: u8 = 30;
+pub const DEFAULT_LIFETIME_UNIT: u16 = 60;
+
+/// Interval between DIS messages when the node is not part of a DODAG.
+pub const DIS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum delay before sending a DAO after a change of parent.
+pub const DAO_DELAY: Duration = Duration::from_secs(4);
+/// Time to wait for a DAO-ACK before sending the DAO again.
+pub const DAO_RETRANSMISSION_TIMEOUT: Duration = Duration::from_secs(5);
+/// Number of times a DAO is sent again when no DAO-ACK is received.
+pub const DAO_MAX_RETRANSMISSIONS: u8 = 4;
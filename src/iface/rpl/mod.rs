@@ -1,9 +1,618 @@
-#![allow(unused)]
+//! Implementation of the IPv6 Routing Protocol for Low-Power and Lossy Networks (RPL), defined in
+//! [RFC 6550].
+//!
+//! This module holds the state of the protocol. The messages are sent and processed by the
+//! interface.
+//!
+//! [RFC 6550]: https://datatracker.ietf.org/doc/html/rfc6550
 
 mod consts;
 mod lollipop;
+mod mrhof;
 mod of0;
 mod parents;
 mod rank;
 mod relations;
 mod trickle;
+
+use crate::config::RPL_RELATIONS_BUFFER_COUNT;
+use crate::rand::Rand;
+use crate::time::{Duration, Instant};
+use crate::wire::{Ipv6Address, RplInstanceId, RplModeOfOperation};
+
+pub(crate) use self::consts::*;
+pub(crate) use self::lollipop::SequenceCounter;
+pub(crate) use self::mrhof::Mrhof;
+pub(crate) use self::of0::{ObjectiveFunction, ObjectiveFunction0};
+pub(crate) use self::parents::{Parent, ParentSet};
+pub(crate) use self::rank::Rank;
+pub(crate) use self::relations::Relations;
+pub(crate) use self::trickle::TrickleTimer;
+
+/// The hops of a source route, built by a non-storing root.
+pub(crate) type SourceRoute = heapless::Vec<Ipv6Address, { RPL_RELATIONS_BUFFER_COUNT }>;
+
+/// Configuration of the RPL routing protocol on an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// The mode of operation of the DODAG. A node only joins DODAGs using this mode of operation.
+    pub mode_of_operation: RplModeOfOperation,
+    /// The configuration of the DODAG when the node is a DODAG root.
+    pub root: Option<RootConfig>,
+}
+
+impl Config {
+    /// Create a configuration for a node that joins a DODAG with the given mode of operation.
+    pub fn new(mode_of_operation: RplModeOfOperation) -> Self {
+        Self {
+            mode_of_operation,
+            root: None,
+        }
+    }
+
+    /// Make the node the root of a DODAG.
+    pub fn add_root_config(mut self, root: RootConfig) -> Self {
+        self.root = Some(root);
+        self
+    }
+
+    /// Return `true` when the node is a DODAG root.
+    pub fn is_root(&self) -> bool {
+        self.root.is_some()
+    }
+}
+
+/// Configuration of a DODAG root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RootConfig {
+    /// The RPL instance of the DODAG.
+    pub instance_id: RplInstanceId,
+    /// The DODAG ID, which is a global IPv6 address of the root.
+    pub dodag_id: Ipv6Address,
+    /// The preference of the DODAG, between 0 (least preferred) and 7.
+    pub preference: u8,
+    /// Whether the DODAG can reach a set of application-defined goals.
+    pub grounded: bool,
+    /// The Objective Code Point of the objective function used in the DODAG: 0 for OF0
+    /// (RFC 6552), 1 for MRHOF (RFC 6719).
+    pub objective_code_point: u16,
+}
+
+impl RootConfig {
+    /// Create a root configuration for the given instance and DODAG ID.
+    pub fn new(instance_id: RplInstanceId, dodag_id: Ipv6Address) -> Self {
+        Self {
+            instance_id,
+            dodag_id,
+            preference: 0,
+            grounded: false,
+            objective_code_point: ObjectiveFunction0::OCP,
+        }
+    }
+}
+
+/// The parameters distributed by the root in the DODAG Configuration option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct DodagConfiguration {
+    pub(crate) dio_interval_doublings: u8,
+    pub(crate) dio_interval_min: u8,
+    pub(crate) dio_redundancy_constant: u8,
+    pub(crate) max_rank_increase: u16,
+    pub(crate) min_hop_rank_increase: u16,
+    pub(crate) objective_code_point: u16,
+    pub(crate) default_lifetime: u8,
+    pub(crate) lifetime_unit: u16,
+}
+
+impl Default for DodagConfiguration {
+    fn default() -> Self {
+        Self {
+            dio_interval_doublings: DEFAULT_DIO_INTERVAL_DOUBLINGS as u8,
+            dio_interval_min: DEFAULT_DIO_INTERVAL_MIN as u8,
+            dio_redundancy_constant: DEFAULT_DIO_REDUNDANCY_CONSTANT as u8,
+            max_rank_increase: DEFAULT_MAX_RANK_INCREASE,
+            min_hop_rank_increase: DEFAULT_MIN_HOP_RANK_INCREASE,
+            objective_code_point: ObjectiveFunction0::OCP,
+            default_lifetime: DEFAULT_LIFETIME,
+            lifetime_unit: DEFAULT_LIFETIME_UNIT,
+        }
+    }
+}
+
+impl DodagConfiguration {
+    /// Return `true` when a node can operate with this configuration.
+    pub(crate) fn is_supported(&self) -> bool {
+        [ObjectiveFunction0::OCP, Mrhof::OCP].contains(&self.objective_code_point)
+            && self.min_hop_rank_increase > 0
+            // The Trickle timer intervals must fit in 32 bits of milliseconds.
+            && (self.dio_interval_min as u32 + self.dio_interval_doublings as u32) < 32
+    }
+
+    /// Return the lifetime of routes.
+    pub(crate) fn route_lifetime(&self) -> Duration {
+        Duration::from_secs(self.default_lifetime as u64 * self.lifetime_unit as u64)
+    }
+
+    /// Create the Trickle timer used for sending DIO messages.
+    pub(crate) fn dio_timer(&self, now: Instant, rand: &mut Rand) -> TrickleTimer {
+        TrickleTimer::new(
+            self.dio_interval_min as u32,
+            self.dio_interval_min as u32 + self.dio_interval_doublings as u32,
+            self.dio_redundancy_constant as usize,
+            now,
+            rand,
+        )
+    }
+}
+
+/// The DODAG a node is part of.
+#[derive(Debug)]
+pub(crate) struct Dodag {
+    pub(crate) instance_id: RplInstanceId,
+    pub(crate) id: Ipv6Address,
+    pub(crate) version_number: SequenceCounter,
+    pub(crate) preference: u8,
+    pub(crate) grounded: bool,
+    pub(crate) configuration: DodagConfiguration,
+    pub(crate) rank: Rank,
+    /// The Destination Advertisement Trigger Sequence Number, included in our DIO messages.
+    pub(crate) dtsn: SequenceCounter,
+
+    pub(crate) dio_timer: TrickleTimer,
+    /// When set, a DIO is sent to this address, in response to a unicast DIS.
+    pub(crate) unicast_dio: Option<Ipv6Address>,
+
+    pub(crate) parent: Option<Ipv6Address>,
+    /// The last DTSN received from the preferred parent.
+    pub(crate) parent_dtsn: SequenceCounter,
+    pub(crate) parent_set: ParentSet,
+    /// In storing mode, the next hop towards each destination in our sub-DODAG. In non-storing
+    /// mode, only the root keeps relations, which map each destination to its DAO parent.
+    pub(crate) relations: Relations,
+
+    pub(crate) dao_sequence: SequenceCounter,
+    pub(crate) dao_path_sequence: SequenceCounter,
+    /// When the next DAO is sent, if any.
+    pub(crate) dao_expiration: Option<Instant>,
+    pub(crate) dao_retransmissions: u8,
+}
+
+impl Dodag {
+    fn new(
+        instance_id: RplInstanceId,
+        id: Ipv6Address,
+        version_number: SequenceCounter,
+        configuration: DodagConfiguration,
+        now: Instant,
+        rand: &mut Rand,
+    ) -> Self {
+        Self {
+            instance_id,
+            id,
+            version_number,
+            preference: 0,
+            grounded: false,
+            configuration,
+            rank: Rank::INFINITE,
+            dtsn: SequenceCounter::default(),
+            dio_timer: configuration.dio_timer(now, rand),
+            unicast_dio: None,
+            parent: None,
+            parent_dtsn: SequenceCounter::default(),
+            parent_set: ParentSet::default(),
+            relations: Relations::default(),
+            dao_sequence: SequenceCounter::default(),
+            dao_path_sequence: SequenceCounter::default(),
+            dao_expiration: None,
+            dao_retransmissions: 0,
+        }
+    }
+
+    /// Select the preferred parent from the parent set using the objective function, and update
+    /// the Rank of the node.
+    ///
+    /// Returns `false` when the parent set is empty.
+    pub(crate) fn select_parent(
+        &mut self,
+        mode_of_operation: RplModeOfOperation,
+        now: Instant,
+        rand: &mut Rand,
+    ) -> bool {
+        let preferred_parent = if self.configuration.objective_code_point == Mrhof::OCP {
+            self.preferred_parent::<Mrhof>()
+        } else {
+            self.preferred_parent::<ObjectiveFunction0>()
+        };
+
+        let Some((address, rank)) = preferred_parent else {
+            self.parent = None;
+            self.rank = Rank::INFINITE;
+            return false;
+        };
+
+        if self.parent != Some(address) {
+            net_debug!("rpl: selected {} as preferred parent, {}", address, rank);
+            self.parent = Some(address);
+            self.dio_timer.hear_inconsistency(now, rand);
+
+            if mode_of_operation != RplModeOfOperation::NoDownwardRoutesMaintained {
+                // Our children need to advertise their routes again through us.
+                self.dtsn.increment();
+                self.dao_path_sequence.increment();
+                self.schedule_dao(now, rand);
+            }
+        }
+
+        if rank != self.rank {
+            self.dio_timer.hear_inconsistency(now, rand);
+        }
+
+        self.rank = rank;
+        true
+    }
+
+    /// Return the address of the preferred parent selected by the objective function, together
+    /// with the Rank of the node through it.
+    fn preferred_parent<OF: ObjectiveFunction>(&self) -> Option<(Ipv6Address, Rank)> {
+        let (address, parent) = OF::preferred_parent(&self.parent_set, self.parent.as_ref())?;
+        Some((*address, OF::rank(self.rank, parent)))
+    }
+
+    /// Schedule the transmission of a DAO, after a random delay.
+    pub(crate) fn schedule_dao(&mut self, now: Instant, rand: &mut Rand) {
+        let delay = rand.rand_u32() as u64 % (DAO_DELAY.total_millis() + 1);
+        self.dao_expiration = Some(now + Duration::from_millis(delay));
+        self.dao_retransmissions = 0;
+    }
+
+    /// Schedule the refresh of the routes advertised in our last DAO.
+    pub(crate) fn schedule_dao_refresh(&mut self, now: Instant) {
+        self.dao_expiration = Some(now + self.configuration.route_lifetime() / 2);
+        self.dao_retransmissions = 0;
+    }
+
+    /// Update the DAO timer after a DAO was sent.
+    pub(crate) fn dao_sent(&mut self, now: Instant) {
+        if self.dao_retransmissions < DAO_MAX_RETRANSMISSIONS {
+            self.dao_retransmissions += 1;
+            self.dao_expiration = Some(now + DAO_RETRANSMISSION_TIMEOUT);
+        } else {
+            net_debug!("rpl: no DAO-ACK received");
+            self.schedule_dao_refresh(now);
+        }
+    }
+}
+
+/// The state of the RPL routing protocol on an interface.
+#[derive(Debug)]
+pub struct Rpl {
+    pub(crate) is_root: bool,
+    pub(crate) mode_of_operation: RplModeOfOperation,
+    /// When the next DIS is sent, when the node is not part of a DODAG.
+    pub(crate) dis_expiration: Instant,
+    pub(crate) dodag: Option<Dodag>,
+}
+
+impl Rpl {
+    pub(crate) fn new(config: Config, now: Instant, rand: &mut Rand) -> Self {
+        let dodag = config.root.map(|root| {
+            let mut dodag = Dodag::new(
+                root.instance_id,
+                root.dodag_id,
+                SequenceCounter::default(),
+                DodagConfiguration {
+                    objective_code_point: root.objective_code_point,
+                    ..Default::default()
+                },
+                now,
+                rand,
+            );
+            dodag.preference = root.preference;
+            dodag.grounded = root.grounded;
+            dodag.rank = Rank::ROOT;
+            dodag
+        });
+
+        Self {
+            is_root: config.is_root(),
+            mode_of_operation: config.mode_of_operation,
+            dis_expiration: now,
+            dodag,
+        }
+    }
+
+    /// Return `true` when the node is a DODAG root.
+    pub fn is_root(&self) -> bool {
+        self.is_root
+    }
+
+    /// Return the mode of operation.
+    pub fn mode_of_operation(&self) -> RplModeOfOperation {
+        self.mode_of_operation
+    }
+
+    /// Return the RPL instance of the DODAG the node is part of.
+    pub fn instance_id(&self) -> Option<RplInstanceId> {
+        self.dodag.as_ref().map(|dodag| dodag.instance_id)
+    }
+
+    /// Return the ID of the DODAG the node is part of.
+    pub fn dodag_id(&self) -> Option<Ipv6Address> {
+        self.dodag.as_ref().map(|dodag| dodag.id)
+    }
+
+    /// Return the Rank of the node in the DODAG.
+    pub fn rank(&self) -> Option<u16> {
+        self.dodag.as_ref().map(|dodag| dodag.rank.raw_value())
+    }
+
+    /// Return the address of the preferred parent.
+    pub fn parent(&self) -> Option<Ipv6Address> {
+        self.dodag.as_ref().and_then(|dodag| dodag.parent)
+    }
+
+    /// Return the next hop towards a destination, following the routes learned through DAO
+    /// messages and the preferred parent.
+    ///
+    /// Returns `None` for link-local and multicast destinations, and when the root has no route.
+    pub fn next_hop(&self, dst_addr: &Ipv6Address) -> Option<Ipv6Address> {
+        let dodag = self.dodag.as_ref()?;
+
+        if !dst_addr.is_unicast() || dst_addr.is_link_local() {
+            return None;
+        }
+
+        if self.is_root && self.mode_of_operation == RplModeOfOperation::NonStoringMode {
+            return self
+                .source_route(dst_addr)
+                .and_then(|route| route.first().copied());
+        }
+
+        if let Some(next_hop) = dodag.relations.find_next_hop(*dst_addr) {
+            return Some(next_hop);
+        }
+
+        if self.is_root {
+            None
+        } else {
+            dodag.parent
+        }
+    }
+
+    /// Return the route from the root to a destination in non-storing mode, following the DAO
+    /// parents up to the root. The route starts with the first hop and ends with the
+    /// destination.
+    ///
+    /// Returns `None` when the route is incomplete, contains a loop or is too long.
+    pub(crate) fn source_route(&self, dst_addr: &Ipv6Address) -> Option<SourceRoute> {
+        let dodag = self.dodag.as_ref()?;
+
+        let mut route = SourceRoute::new();
+        let mut address = *dst_addr;
+        loop {
+            if route.contains(&address) {
+                net_debug!("rpl: loop in the source route to {}", dst_addr);
+                return None;
+            }
+            route.push(address).ok()?;
+
+            let parent = dodag.relations.find_next_hop(address)?;
+            if parent == dodag.id {
+                break;
+            }
+            address = parent;
+        }
+
+        route.reverse();
+        Some(route)
+    }
+
+    /// Return the next time RPL messages need to be sent.
+    pub(crate) fn poll_at(&self) -> Instant {
+        match &self.dodag {
+            None => self.dis_expiration,
+            Some(dodag) if dodag.unicast_dio.is_some() => Instant::ZERO,
+            Some(dodag) => match dodag.dao_expiration {
+                Some(dao_expiration) => dodag.dio_timer.poll_at().min(dao_expiration),
+                None => dodag.dio_timer.poll_at(),
+            },
+        }
+    }
+
+    /// Join the given DODAG, without a parent yet.
+    pub(crate) fn join(
+        &mut self,
+        instance_id: RplInstanceId,
+        dodag_id: Ipv6Address,
+        version_number: SequenceCounter,
+        configuration: DodagConfiguration,
+        now: Instant,
+        rand: &mut Rand,
+    ) -> &mut Dodag {
+        net_debug!("rpl: joining DODAG {}", dodag_id);
+        self.dodag.insert(Dodag::new(
+            instance_id,
+            dodag_id,
+            version_number,
+            configuration,
+            now,
+            rand,
+        ))
+    }
+
+    /// Leave the DODAG, and start soliciting DIO messages.
+    pub(crate) fn leave(&mut self, now: Instant) {
+        if let Some(dodag) = self.dodag.take() {
+            net_debug!("rpl: leaving DODAG {}", dodag.id);
+        }
+        self.dis_expiration = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(last: u8) -> Ipv6Address {
+        let mut address = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0);
+        address.0[15] = last;
+        address
+    }
+
+    #[test]
+    fn root() {
+        let mut rand = Rand::new(1234);
+        let dodag_id = Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+        let config = Config::new(RplModeOfOperation::StoringModeWithoutMulticast)
+            .add_root_config(RootConfig::new(RplInstanceId::Global(30), dodag_id));
+        let rpl = Rpl::new(config, Instant::ZERO, &mut rand);
+
+        assert!(rpl.is_root());
+        assert_eq!(rpl.dodag_id(), Some(dodag_id));
+        assert_eq!(rpl.rank(), Some(Rank::ROOT.raw_value()));
+        assert_eq!(rpl.parent(), None);
+        assert_eq!(
+            rpl.next_hop(&Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 2)),
+            None
+        );
+    }
+
+    #[test]
+    fn select_parent() {
+        let mut rand = Rand::new(1234);
+        let now = Instant::ZERO;
+        let mode = RplModeOfOperation::StoringModeWithoutMulticast;
+        let mut rpl = Rpl::new(Config::new(mode), now, &mut rand);
+        assert_eq!(rpl.instance_id(), None);
+        assert_eq!(rpl.poll_at(), now);
+
+        let dodag = rpl.join(
+            RplInstanceId::Global(30),
+            Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 1),
+            SequenceCounter::default(),
+            DodagConfiguration::default(),
+            now,
+            &mut rand,
+        );
+        assert!(!dodag.select_parent(mode, now, &mut rand));
+
+        let version = SequenceCounter::default();
+        dodag.parent_set.add(
+            address(2),
+            Parent::new(0, Rank::new(1024, 256), version, dodag.id),
+        );
+        assert!(dodag.select_parent(mode, now, &mut rand));
+        assert_eq!(dodag.parent, Some(address(2)));
+        assert_eq!(dodag.rank, Rank::new(1024 + 3 * 256, 256));
+        assert!(dodag.dao_expiration.unwrap() <= now + DAO_DELAY);
+
+        // A parent with a better Rank is preferred.
+        dodag
+            .parent_set
+            .add(address(1), Parent::new(0, Rank::ROOT, version, dodag.id));
+        assert!(dodag.select_parent(mode, now, &mut rand));
+        assert_eq!(dodag.parent, Some(address(1)));
+        assert_eq!(dodag.rank, Rank::new(256 + 3 * 256, 256));
+
+        // Upward routes go through the preferred parent, link-local destinations are on-link.
+        let dst_addr = Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 42);
+        assert_eq!(rpl.next_hop(&dst_addr), Some(address(1)));
+        assert_eq!(rpl.next_hop(&address(42)), None);
+
+        // Downward routes go through the relations.
+        let dodag = rpl.dodag.as_mut().unwrap();
+        dodag
+            .relations
+            .add_relation(dst_addr, address(3), now + Duration::from_secs(60));
+        assert_eq!(rpl.next_hop(&dst_addr), Some(address(3)));
+
+        rpl.leave(now);
+        assert_eq!(rpl.next_hop(&dst_addr), None);
+        assert_eq!(rpl.poll_at(), now);
+    }
+
+    #[test]
+    fn source_route() {
+        let mut rand = Rand::new(1234);
+        let now = Instant::ZERO;
+        let dodag_id = Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+        let config = Config::new(RplModeOfOperation::NonStoringMode)
+            .add_root_config(RootConfig::new(RplInstanceId::Global(30), dodag_id));
+        let mut rpl = Rpl::new(config, now, &mut rand);
+
+        // The relations of a non-storing root hold the DAO parent of each node.
+        let node = |last| Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, last);
+        let expiration = now + Duration::from_secs(60);
+        let relations = &mut rpl.dodag.as_mut().unwrap().relations;
+        relations.add_relation(node(2), dodag_id, expiration);
+        relations.add_relation(node(3), node(2), expiration);
+        relations.add_relation(node(4), node(3), expiration);
+
+        assert_eq!(
+            rpl.source_route(&node(4)).as_deref(),
+            Some(&[node(2), node(3), node(4)][..])
+        );
+        assert_eq!(rpl.next_hop(&node(4)), Some(node(2)));
+        assert_eq!(rpl.next_hop(&node(2)), Some(node(2)));
+
+        // Routes with an unknown node or a loop are not used.
+        assert_eq!(rpl.source_route(&node(5)), None);
+        let relations = &mut rpl.dodag.as_mut().unwrap().relations;
+        relations.add_relation(node(2), node(4), expiration);
+        assert_eq!(rpl.source_route(&node(4)), None);
+        assert_eq!(rpl.next_hop(&node(4)), None);
+    }
+
+    #[test]
+    fn dao_retransmissions() {
+        let mut rand = Rand::new(1234);
+        let now = Instant::ZERO;
+        let mut dodag = Dodag::new(
+            RplInstanceId::Global(30),
+            Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 1),
+            SequenceCounter::default(),
+            DodagConfiguration::default(),
+            now,
+            &mut rand,
+        );
+
+        for _ in 0..DAO_MAX_RETRANSMISSIONS {
+            dodag.dao_sent(now);
+            assert_eq!(dodag.dao_expiration, Some(now + DAO_RETRANSMISSION_TIMEOUT));
+        }
+
+        // Without a DAO-ACK, the DAO is sent again when the routes need a refresh.
+        dodag.dao_sent(now);
+        assert_eq!(
+            dodag.dao_expiration,
+            Some(now + Duration::from_secs(30 * 60 / 2))
+        );
+    }
+
+    #[test]
+    fn unsupported_configuration() {
+        assert!(DodagConfiguration::default().is_supported());
+
+        let mrhof = DodagConfiguration {
+            objective_code_point: Mrhof::OCP,
+            ..Default::default()
+        };
+        assert!(mrhof.is_supported());
+
+        let ocp = DodagConfiguration {
+            objective_code_point: 2,
+            ..Default::default()
+        };
+        assert!(!ocp.is_supported());
+
+        let interval = DodagConfiguration {
+            dio_interval_min: 20,
+            dio_interval_doublings: 12,
+            ..Default::default()
+        };
+        assert!(!interval.is_supported());
+    }
+}
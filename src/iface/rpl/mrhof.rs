@@ -0,0 +1,205 @@
+use super::of0::ObjectiveFunction;
+use super::parents::*;
+use super::rank::Rank;
+use crate::wire::Ipv6Address;
+
+/// The Minimum Rank with Hysteresis Objective Function (RFC 6719), using the ETX of the links
+/// as metric.
+pub struct Mrhof;
+
+impl Mrhof {
+    /// The maximum ETX of the link with a parent, 4 times the ETX divisor.
+    const MAX_LINK_METRIC: u16 = 4 * ETX_DIVISOR;
+    const MAX_PATH_COST: u16 = 0x8000;
+    /// The difference in path cost needed to switch to another parent, 1.5 times the ETX
+    /// divisor.
+    const PARENT_SWITCH_THRESHOLD: u16 = 3 * ETX_DIVISOR / 2;
+
+    fn link_metric(parent: &Parent) -> u16 {
+        parent.link_stats().etx()
+    }
+
+    /// Return the cost of the path through a parent, which is the Rank advertised by the parent
+    /// plus the metric of the link with it.
+    fn path_cost(parent: &Parent) -> u16 {
+        parent
+            .rank()
+            .value
+            .saturating_add(Self::link_metric(parent))
+            .min(Self::MAX_PATH_COST)
+    }
+
+    /// Return `true` when a parent can be selected as preferred parent.
+    fn is_acceptable(parent: &Parent) -> bool {
+        Self::link_metric(parent) <= Self::MAX_LINK_METRIC
+            && Self::path_cost(parent) < Self::MAX_PATH_COST
+    }
+}
+
+impl ObjectiveFunction for Mrhof {
+    const OCP: u16 = 1;
+
+    fn rank(_: Rank, parent: &Parent) -> Rank {
+        let parent_rank = *parent.rank();
+        assert_ne!(parent_rank, Rank::INFINITE);
+
+        let value = parent_rank
+            .value
+            .saturating_add(parent_rank.min_hop_rank_increase)
+            .max(Self::path_cost(parent));
+
+        Rank::new(value, parent_rank.min_hop_rank_increase)
+    }
+
+    fn preferred_parent<'p>(
+        parent_set: &'p ParentSet,
+        current: Option<&Ipv6Address>,
+    ) -> Option<(&'p Ipv6Address, &'p Parent)> {
+        let best = parent_set
+            .parents()
+            .filter(|(_, parent)| Self::is_acceptable(parent))
+            .min_by_key(|(_, parent)| Self::path_cost(parent))?;
+
+        // Hysteresis: the current parent is kept unless the path through the best parent is
+        // cheaper by more than the switch threshold.
+        let current = parent_set
+            .parents()
+            .find(|(address, _)| Some(*address) == current)
+            .filter(|(_, parent)| Self::is_acceptable(parent));
+
+        match current {
+            Some(current)
+                if Self::path_cost(current.1)
+                    < Self::path_cost(best.1).saturating_add(Self::PARENT_SWITCH_THRESHOLD) =>
+            {
+                Some(current)
+            }
+            _ => Some(best),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::iface::rpl::consts::DEFAULT_MIN_HOP_RANK_INCREASE;
+
+    use super::*;
+
+    fn address(last: u8) -> Ipv6Address {
+        let mut address = Ipv6Address::default();
+        address.0[15] = last;
+        address
+    }
+
+    fn parent(rank: u16) -> Parent {
+        Parent::new(
+            0,
+            Rank::new(rank, DEFAULT_MIN_HOP_RANK_INCREASE),
+            Default::default(),
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn rank_increase() {
+        // 256 (root) + 256, the path cost with the initial ETX of 2 is 256 + 2 * 128.
+        assert_eq!(
+            Mrhof::rank(Rank::INFINITE, &parent(256)),
+            Rank::new(512, DEFAULT_MIN_HOP_RANK_INCREASE)
+        );
+
+        // A bad link increases the Rank more than the minimum hop Rank increase.
+        let mut bad = parent(256);
+        for _ in 0..10 {
+            bad.link_stats_mut().update(3, true);
+        }
+        let etx = bad.link_stats().etx();
+        assert!(etx > 2 * ETX_DIVISOR);
+        assert_eq!(
+            Mrhof::rank(Rank::INFINITE, &bad),
+            Rank::new(256 + etx, DEFAULT_MIN_HOP_RANK_INCREASE)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn rank_increase_infinite() {
+        Mrhof::rank(Rank::INFINITE, &parent(0xffff));
+    }
+
+    #[test]
+    fn empty_set() {
+        assert_eq!(Mrhof::preferred_parent(&ParentSet::default(), None), None);
+    }
+
+    #[test]
+    fn lowest_path_cost() {
+        let mut parents = ParentSet::default();
+        parents.add(address(1), parent(256));
+        parents.add(address(2), parent(512));
+        assert_eq!(
+            Mrhof::preferred_parent(&parents, None).map(|(a, _)| *a),
+            Some(address(1))
+        );
+
+        // A parent with a lower Rank but a lossy link costs more.
+        for _ in 0..10 {
+            parents
+                .find_mut(&address(1))
+                .unwrap()
+                .link_stats_mut()
+                .update(4, true);
+        }
+        for _ in 0..10 {
+            parents
+                .find_mut(&address(2))
+                .unwrap()
+                .link_stats_mut()
+                .update(1, true);
+        }
+        assert_eq!(
+            Mrhof::preferred_parent(&parents, None).map(|(a, _)| *a),
+            Some(address(2))
+        );
+
+        // A link with a metric above the maximum is not used.
+        for _ in 0..10 {
+            parents
+                .find_mut(&address(2))
+                .unwrap()
+                .link_stats_mut()
+                .update(1, false);
+        }
+        assert_eq!(
+            Mrhof::preferred_parent(&parents, Some(&address(2))).map(|(a, _)| *a),
+            Some(address(1))
+        );
+        parents.remove(&address(1));
+        assert_eq!(Mrhof::preferred_parent(&parents, None), None);
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut parents = ParentSet::default();
+        parents.add(address(1), parent(512));
+        parents.add(address(2), parent(512 + 128));
+
+        // The current parent is kept when the path through the other one is not much cheaper.
+        assert_eq!(
+            Mrhof::preferred_parent(&parents, Some(&address(2))).map(|(a, _)| *a),
+            Some(address(2))
+        );
+
+        parents.add(address(2), parent(512 + 256));
+        assert_eq!(
+            Mrhof::preferred_parent(&parents, Some(&address(2))).map(|(a, _)| *a),
+            Some(address(1))
+        );
+
+        // An unknown current parent does not prevent switching.
+        assert_eq!(
+            Mrhof::preferred_parent(&parents, Some(&address(3))).map(|(a, _)| *a),
+            Some(address(1))
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use super::parents::*;
 use super::rank::Rank;
+use crate::wire::Ipv6Address;
 
 pub struct ObjectiveFunction0;
 
@@ -7,43 +8,51 @@ pub(crate) trait ObjectiveFunction {
     const OCP: u16;
 
     /// Return the new calculated Rank, based on information from the parent.
-    fn rank(current_rank: Rank, parent_rank: Rank) -> Rank;
-
-    /// Return the preferred parent from a given parent set.
-    fn preferred_parent(parent_set: &ParentSet) -> Option<&Parent>;
+    fn rank(current_rank: Rank, parent: &Parent) -> Rank;
+
+    /// Return the address of the preferred parent from a given parent set, together with the
+    /// parent. `current` is the address of the current preferred parent, if any.
+    fn preferred_parent<'p>(
+        parent_set: &'p ParentSet,
+        current: Option<&Ipv6Address>,
+    ) -> Option<(&'p Ipv6Address, &'p Parent)>;
 }
 
 impl ObjectiveFunction0 {
-    const OCP: u16 = 0;
-
     const RANK_STRETCH: u16 = 0;
     const RANK_FACTOR: u16 = 1;
     const RANK_STEP: u16 = 3;
 
     fn rank_increase(parent_rank: Rank) -> u16 {
         (Self::RANK_FACTOR * Self::RANK_STEP + Self::RANK_STRETCH)
-            * parent_rank.min_hop_rank_increase
+            .saturating_mul(parent_rank.min_hop_rank_increase)
     }
 }
 
 impl ObjectiveFunction for ObjectiveFunction0 {
     const OCP: u16 = 0;
 
-    fn rank(_: Rank, parent_rank: Rank) -> Rank {
+    fn rank(_: Rank, parent: &Parent) -> Rank {
+        let parent_rank = *parent.rank();
         assert_ne!(parent_rank, Rank::INFINITE);
 
         Rank::new(
-            parent_rank.value + Self::rank_increase(parent_rank),
+            parent_rank
+                .value
+                .saturating_add(Self::rank_increase(parent_rank)),
             parent_rank.min_hop_rank_increase,
         )
     }
 
-    fn preferred_parent(parent_set: &ParentSet) -> Option<&Parent> {
-        let mut pref_parent: Option<&Parent> = None;
+    fn preferred_parent<'p>(
+        parent_set: &'p ParentSet,
+        _: Option<&Ipv6Address>,
+    ) -> Option<(&'p Ipv6Address, &'p Parent)> {
+        let mut pref_parent: Option<(&Ipv6Address, &Parent)> = None;
 
-        for (_, parent) in parent_set.parents() {
-            if pref_parent.is_none() || parent.rank() < pref_parent.unwrap().rank() {
-                pref_parent = Some(parent);
+        for (address, parent) in parent_set.parents() {
+            if pref_parent.is_none() || parent.rank() < pref_parent.unwrap().1.rank() {
+                pref_parent = Some((address, parent));
             }
         }
 
@@ -57,11 +66,15 @@ mod tests {
 
     use super::*;
 
+    fn parent(rank: Rank) -> Parent {
+        Parent::new(0, rank, Default::default(), Default::default())
+    }
+
     #[test]
     fn rank_increase() {
         // 256 (root) + 3 * 256
         assert_eq!(
-            ObjectiveFunction0::rank(Rank::INFINITE, Rank::ROOT),
+            ObjectiveFunction0::rank(Rank::INFINITE, &parent(Rank::ROOT)),
             Rank::new(256 + 3 * 256, DEFAULT_MIN_HOP_RANK_INCREASE)
         );
 
@@ -69,7 +82,7 @@ mod tests {
         assert_eq!(
             ObjectiveFunction0::rank(
                 Rank::INFINITE,
-                Rank::new(1024, DEFAULT_MIN_HOP_RANK_INCREASE)
+                &parent(Rank::new(1024, DEFAULT_MIN_HOP_RANK_INCREASE))
             ),
             Rank::new(1024 + 3 * 256, DEFAULT_MIN_HOP_RANK_INCREASE)
         );
@@ -79,7 +92,7 @@ mod tests {
     #[should_panic]
     fn rank_increase_infinite() {
         assert_eq!(
-            ObjectiveFunction0::rank(Rank::INFINITE, Rank::INFINITE),
+            ObjectiveFunction0::rank(Rank::INFINITE, &parent(Rank::INFINITE)),
             Rank::INFINITE
         );
     }
@@ -87,7 +100,7 @@ mod tests {
     #[test]
     fn empty_set() {
         assert_eq!(
-            ObjectiveFunction0::preferred_parent(&ParentSet::default()),
+            ObjectiveFunction0::preferred_parent(&ParentSet::default(), None),
             None
         );
     }
@@ -117,12 +130,10 @@ mod tests {
         );
 
         assert_eq!(
-            ObjectiveFunction0::preferred_parent(&parents),
-            Some(&Parent::new(
-                0,
-                Rank::ROOT,
-                Default::default(),
-                Ipv6Address::default(),
+            ObjectiveFunction0::preferred_parent(&parents, None),
+            Some((
+                &Ipv6Address::default(),
+                &Parent::new(0, Rank::ROOT, Default::default(), Ipv6Address::default())
             ))
         );
     }
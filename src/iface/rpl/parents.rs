@@ -3,12 +3,79 @@ use crate::wire::Ipv6Address;
 use super::{lollipop::SequenceCounter, rank::Rank};
 use crate::config::RPL_PARENTS_BUFFER_COUNT;
 
+/// The fixed-point divisor of the ETX, which is a value of 1.
+pub(crate) const ETX_DIVISOR: u16 = 128;
+
+/// Link-layer transmission statistics of a neighbor, used to estimate the Expected Transmission
+/// Count (ETX) of the link with an exponentially weighted moving average.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LinkStats {
+    /// The ETX of the link, multiplied by [`ETX_DIVISOR`].
+    etx: u16,
+    /// The number of transmissions reported for this neighbor, saturating.
+    transmissions: u8,
+}
+
+impl LinkStats {
+    /// The ETX of a link without statistics.
+    const ETX_INIT: u16 = 2 * ETX_DIVISOR;
+    /// The ETX of a packet that was not acknowledged.
+    const ETX_NOACK_PENALTY: u16 = 12 * ETX_DIVISOR;
+    /// The number of transmissions after which the statistics are considered fresh.
+    const FRESHNESS_TARGET: u8 = 4;
+
+    const EWMA_SCALE: u32 = 100;
+    const EWMA_ALPHA: u32 = 10;
+    /// A larger weight of new samples, as long as the statistics are not fresh.
+    const EWMA_BOOTSTRAP_ALPHA: u32 = 25;
+
+    /// Return the ETX of the link, multiplied by [`ETX_DIVISOR`].
+    pub(crate) fn etx(&self) -> u16 {
+        self.etx
+    }
+
+    /// Update the statistics with the outcome of a packet, sent in `transmissions` attempts.
+    pub(crate) fn update(&mut self, transmissions: u8, acknowledged: bool) {
+        if transmissions == 0 {
+            return;
+        }
+
+        let packet_etx = if acknowledged {
+            transmissions as u32 * ETX_DIVISOR as u32
+        } else {
+            Self::ETX_NOACK_PENALTY as u32
+        };
+
+        let alpha = if self.transmissions < Self::FRESHNESS_TARGET {
+            Self::EWMA_BOOTSTRAP_ALPHA
+        } else {
+            Self::EWMA_ALPHA
+        };
+
+        self.etx = ((self.etx as u32 * (Self::EWMA_SCALE - alpha) + packet_etx * alpha)
+            / Self::EWMA_SCALE) as u16;
+        self.transmissions = self.transmissions.saturating_add(1);
+    }
+}
+
+impl Default for LinkStats {
+    fn default() -> Self {
+        Self {
+            etx: Self::ETX_INIT,
+            transmissions: 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub(crate) struct Parent {
     rank: Rank,
     preference: u8,
     version_number: SequenceCounter,
     dodag_id: Ipv6Address,
+    /// The global address advertised by the parent, used as DAO parent in non-storing mode.
+    global_address: Option<Ipv6Address>,
+    link_stats: LinkStats,
 }
 
 impl Parent {
@@ -24,6 +91,8 @@ impl Parent {
             preference,
             version_number,
             dodag_id,
+            global_address: None,
+            link_stats: LinkStats::default(),
         }
     }
 
@@ -31,6 +100,26 @@ impl Parent {
     pub(crate) fn rank(&self) -> &Rank {
         &self.rank
     }
+
+    /// Return the global address advertised by the parent, if any.
+    pub(crate) fn global_address(&self) -> Option<Ipv6Address> {
+        self.global_address
+    }
+
+    /// Set the global address advertised by the parent.
+    pub(crate) fn set_global_address(&mut self, address: Option<Ipv6Address>) {
+        self.global_address = address;
+    }
+
+    /// Return the link-layer transmission statistics of the parent.
+    pub(crate) fn link_stats(&self) -> &LinkStats {
+        &self.link_stats
+    }
+
+    /// Return a mutable reference to the link-layer transmission statistics of the parent.
+    pub(crate) fn link_stats_mut(&mut self) -> &mut LinkStats {
+        &mut self.link_stats
+    }
 }
 
 #[derive(Debug, Default)]
@@ -40,11 +129,15 @@ pub(crate) struct ParentSet {
 
 impl ParentSet {
     /// Add a new parent to the parent set. The Rank of the new parent should be lower than the
-    /// Rank of the node that holds this parent set.
+    /// Rank of the node that holds this parent set. The link statistics of a parent that is
+    /// already in the set are kept.
     pub(crate) fn add(&mut self, address: Ipv6Address, parent: Parent) {
         if let Some(p) = self.parents.get_mut(&address) {
-            *p = parent;
-        } else if let Err(p) = self.parents.insert(address, parent) {
+            *p = Parent {
+                link_stats: p.link_stats,
+                ..parent
+            };
+        } else if self.parents.insert(address, parent).is_err() {
             if let Some((w_a, w_p)) = self.worst_parent() {
                 if w_p.rank.dag_rank() > parent.rank.dag_rank() {
                     self.parents.remove(&w_a.clone()).unwrap();
@@ -58,6 +151,16 @@ impl ParentSet {
         }
     }
 
+    /// Remove a parent from the parent set.
+    pub(crate) fn remove(&mut self, address: &Ipv6Address) -> Option<Parent> {
+        self.parents.remove(address)
+    }
+
+    /// Remove all the parents from the parent set.
+    pub(crate) fn clear(&mut self) {
+        self.parents.clear()
+    }
+
     /// Find a parent based on its address.
     pub(crate) fn find(&self, address: &Ipv6Address) -> Option<&Parent> {
         self.parents.get(address)
@@ -75,7 +178,7 @@ impl ParentSet {
 
     /// Find the worst parent that is currently in the parent set.
     fn worst_parent(&self) -> Option<(&Ipv6Address, &Parent)> {
-        self.parents.iter().max_by_key(|(k, v)| v.rank.dag_rank())
+        self.parents.iter().max_by_key(|(_, v)| v.rank.dag_rank())
     }
 }
 
@@ -173,4 +276,57 @@ mod tests {
         );
         assert_eq!(set.find(&last_address), None);
     }
+
+    #[test]
+    fn link_stats() {
+        let mut stats = LinkStats::default();
+        assert_eq!(stats.etx(), 2 * ETX_DIVISOR);
+
+        // Transmissions without attempts are ignored.
+        stats.update(0, true);
+        assert_eq!(stats.etx(), 2 * ETX_DIVISOR);
+
+        // New statistics have a larger weight as long as the link is not well known.
+        stats.update(1, true);
+        assert_eq!(stats.etx(), (256 * 75 + 128 * 25) / 100);
+
+        for _ in 0..100 {
+            stats.update(1, true);
+        }
+        assert!(stats.etx() < ETX_DIVISOR + ETX_DIVISOR / 8);
+
+        // Lost packets are penalized.
+        let etx = stats.etx();
+        stats.update(3, false);
+        assert_eq!(
+            stats.etx(),
+            ((etx as u32 * 90 + 12 * 128 * 10) / 100) as u16
+        );
+    }
+
+    #[test]
+    fn add_parent_keeps_link_stats() {
+        let mut set = ParentSet::default();
+        let address = Ipv6Address::default();
+        set.add(
+            address,
+            Parent::new(0, Rank::ROOT, Default::default(), Default::default()),
+        );
+        set.find_mut(&address)
+            .unwrap()
+            .link_stats_mut()
+            .update(4, true);
+        let etx = set.find(&address).unwrap().link_stats().etx();
+        assert_ne!(etx, 2 * ETX_DIVISOR);
+
+        // A new DIO of the parent updates its Rank, the link statistics are kept.
+        let rank = Rank::new(512, Rank::ROOT.min_hop_rank_increase);
+        set.add(
+            address,
+            Parent::new(0, rank, Default::default(), Default::default()),
+        );
+        let parent = set.find(&address).unwrap();
+        assert_eq!(parent.rank(), &rank);
+        assert_eq!(parent.link_stats().etx(), etx);
+    }
 }
@@ -18,12 +18,14 @@ pub struct Relations {
 impl Relations {
     /// Add a new relation to the buffer. If there was already a relation in the buffer, then
     /// update it.
+    ///
+    /// Returns `false` when the relation could not be added because the buffer is full.
     pub fn add_relation(
         &mut self,
         destination: Ipv6Address,
         next_hop: Ipv6Address,
         expiration: Instant,
-    ) {
+    ) -> bool {
         if let Some(r) = self
             .relations
             .iter_mut()
@@ -31,6 +33,7 @@ impl Relations {
         {
             r.next_hop = next_hop;
             r.expiration = expiration;
+            true
         } else {
             let relation = Relation {
                 destination,
@@ -38,9 +41,12 @@ impl Relations {
                 expiration,
             };
 
-            if let Err(e) = self.relations.push(relation) {
+            if self.relations.push(relation).is_err() {
                 net_debug!("Unable to add relation, buffer is full");
+                return false;
             }
+
+            true
         }
     }
 
@@ -50,7 +56,7 @@ impl Relations {
     }
 
     /// Return the next hop for a specific IPv6 address, if there is one.
-    pub fn find_next_hop(&mut self, destination: Ipv6Address) -> Option<Ipv6Address> {
+    pub fn find_next_hop(&self, destination: Ipv6Address) -> Option<Ipv6Address> {
         self.relations.iter().find_map(|r| {
             if r.destination == destination {
                 Some(r.next_hop)
@@ -60,6 +66,11 @@ impl Relations {
         })
     }
 
+    /// Return an iterator over the destinations that have a relation.
+    pub fn destinations(&self) -> impl Iterator<Item = &Ipv6Address> {
+        self.relations.iter().map(|r| &r.destination)
+    }
+
     /// Purge expired relations.
     pub fn purge(&mut self, now: Instant) {
         self.relations.retain(|r| r.expiration > now)
@@ -39,6 +39,7 @@ impl TrickleTimer {
     /// don't use the default values from the standard, but the values from the _Enhanced Trickle
     /// Algorithm for Low-Power and Lossy Networks_ from Baraq Ghaleb et al. This is also what the
     /// Contiki Trickle timer does.
+    #[allow(unused)]
     pub(crate) fn default(now: Instant, rand: &mut Rand) -> Self {
         use super::consts::{
             DEFAULT_DIO_INTERVAL_DOUBLINGS, DEFAULT_DIO_INTERVAL_MIN,
@@ -138,10 +139,12 @@ impl TrickleTimer {
         self.set_t(now, rand);
     }
 
+    #[allow(unused)]
     pub(crate) const fn max_expiration(&self) -> Duration {
         Duration::from_millis(2u32.pow(self.i_max) as u64)
     }
 
+    #[allow(unused)]
     pub(crate) const fn min_expiration(&self) -> Duration {
         Duration::from_millis(2u32.pow(self.i_min) as u64)
     }
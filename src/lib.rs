@@ -121,6 +121,18 @@ compile_error!("If you enable the socket feature, you must enable at least one o
 ))]
 compile_error!("If you enable the socket feature, you must enable at least one of the following features: medium-ip, medium-ethernet, medium-ieee802154");
 
+#[cfg(all(
+    feature = "proto-slaac",
+    not(any(feature = "medium-ethernet", feature = "medium-ieee802154"))
+))]
+compile_error!("If you enable the proto-slaac feature, you must enable at least one of the following features: medium-ethernet, medium-ieee802154");
+
+#[cfg(all(
+    feature = "proto-ipv6-dad",
+    not(any(feature = "medium-ethernet", feature = "medium-ieee802154"))
+))]
+compile_error!("If you enable the proto-ipv6-dad feature, you must enable at least one of the following features: medium-ethernet, medium-ieee802154");
+
 #[cfg(all(feature = "defmt", feature = "log"))]
 compile_error!("You must enable at most one of the following features: defmt, log");
 
@@ -128,6 +140,12 @@ compile_error!("You must enable at most one of the following features: defmt, lo
 mod macros;
 mod parsers;
 mod rand;
+#[cfg(any(
+    feature = "_proto-ipsec",
+    feature = "proto-slaac",
+    feature = "socket-tcp"
+))]
+mod sha256;
 
 #[cfg(test)]
 pub mod config {
@@ -139,14 +157,21 @@ pub mod config {
     pub const FRAGMENTATION_BUFFER_SIZE: usize = 1500;
     pub const IFACE_MAX_ADDR_COUNT: usize = 8;
     pub const IFACE_MAX_MULTICAST_GROUP_COUNT: usize = 4;
+    pub const IFACE_MAX_MULTICAST_SOURCE_COUNT: usize = 4;
     pub const IFACE_MAX_ROUTE_COUNT: usize = 4;
     pub const IFACE_MAX_SIXLOWPAN_ADDRESS_CONTEXT_COUNT: usize = 4;
     pub const IFACE_NEIGHBOR_CACHE_COUNT: usize = 3;
+    pub const IFACE_NEIGHBOR_PENDING_COUNT: usize = 2;
+    pub const IFACE_NEIGHBOR_PENDING_BUFFER_SIZE: usize = 1500;
+    pub const IFACE_PATH_MTU_CACHE_COUNT: usize = 3;
     pub const REASSEMBLY_BUFFER_COUNT: usize = 4;
     pub const REASSEMBLY_BUFFER_SIZE: usize = 1500;
     pub const RPL_RELATIONS_BUFFER_COUNT: usize = 16;
     pub const RPL_PARENTS_BUFFER_COUNT: usize = 8;
     pub const IPV6_HBH_MAX_OPTIONS: usize = 2;
+    pub const IFACE_MAX_IPSEC_SA_COUNT: usize = 4;
+    pub const IFACE_MAX_IPSEC_POLICY_COUNT: usize = 4;
+    pub const IPSEC_BUFFER_SIZE: usize = 1500;
 }
 
 #[cfg(not(test))]
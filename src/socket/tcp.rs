@@ -6,6 +6,7 @@ use core::fmt::Display;
 #[cfg(feature = "async")]
 use core::task::Waker;
 use core::{cmp, fmt, mem};
+use managed::Managed;
 
 #[cfg(feature = "async")]
 use crate::socket::WakerRegistration;
@@ -14,12 +15,16 @@ use crate::storage::{Assembler, RingBuffer};
 use crate::time::{Duration, Instant};
 use crate::wire::{
     IpAddress, IpEndpoint, IpListenEndpoint, IpProtocol, IpRepr, TcpControl, TcpRepr, TcpSeqNumber,
-    TCP_HEADER_LEN,
+    TcpTimestampRepr, TCP_HEADER_LEN,
 };
 
 use log::debug;
 
 mod congestion;
+mod listener;
+
+pub use self::congestion::Controller;
+pub use self::listener::{Listener, ListenerSlot};
 
 macro_rules! tcp_trace {
     ($($arg:expr),*) => (net_log!(trace, $($arg),*));
@@ -154,12 +159,17 @@ const RTTE_MIN_MARGIN: u32 = 5;
 const RTTE_MIN_RTO: u32 = 10;
 const RTTE_MAX_RTO: u32 = 10000;
 
+/// An estimator of the round-trip time of a connection, see RFC 6298.
+///
+/// It is given to the [Controller] of the socket.
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-struct RttEstimator {
+pub struct RttEstimator {
     // Using u32 instead of Duration to save space (Duration is i64)
     rtt: u32,
     deviation: u32,
+    /// The last round-trip time measured, unsmoothed.
+    latest_rtt: Option<u32>,
     timestamp: Option<(Instant, TcpSeqNumber)>,
     max_seq_sent: Option<TcpSeqNumber>,
     rto_count: u8,
@@ -170,6 +180,7 @@ impl Default for RttEstimator {
         Self {
             rtt: RTTE_INITIAL_RTT,
             deviation: RTTE_INITIAL_DEV,
+            latest_rtt: None,
             timestamp: None,
             max_seq_sent: None,
             rto_count: 0,
@@ -178,7 +189,24 @@ impl Default for RttEstimator {
 }
 
 impl RttEstimator {
-    fn retransmission_timeout(&self) -> Duration {
+    /// Return the smoothed round-trip time.
+    pub fn rtt(&self) -> Duration {
+        Duration::from_millis(self.rtt as u64)
+    }
+
+    /// Return the mean deviation of the round-trip time.
+    pub fn deviation(&self) -> Duration {
+        Duration::from_millis(self.deviation as u64)
+    }
+
+    /// Return the last round-trip time measured, if any.
+    pub fn latest_rtt(&self) -> Option<Duration> {
+        self.latest_rtt
+            .map(|latest_rtt| Duration::from_millis(latest_rtt as u64))
+    }
+
+    /// Return the retransmission timeout.
+    pub fn retransmission_timeout(&self) -> Duration {
         let margin = RTTE_MIN_MARGIN.max(self.deviation * 4);
         let ms = (self.rtt + margin).clamp(RTTE_MIN_RTO, RTTE_MAX_RTO);
         Duration::from_millis(ms as u64)
@@ -187,6 +215,7 @@ impl RttEstimator {
     fn sample(&mut self, new_rtt: u32) {
         // "Congestion Avoidance and Control", Van Jacobson, Michael J. Karels, 1988
         self.rtt = (self.rtt * 7 + new_rtt + 7) / 8;
+        self.latest_rtt = Some(new_rtt);
         let diff = (self.rtt as i32 - new_rtt as i32).unsigned_abs();
         self.deviation = (self.deviation * 3 + diff + 3) / 4;
 
@@ -225,6 +254,19 @@ impl RttEstimator {
         }
     }
 
+    fn on_echo(&mut self, rtt: u32) {
+        // The timestamp echoed by the remote gives a sample for every acknowledgement, there is
+        // no need to time a segment anymore.
+        self.timestamp = None;
+        self.sample(rtt);
+    }
+
+    #[cfg(feature = "socket-tcp-rack")]
+    fn on_probe(&mut self) {
+        // The acknowledgement of a retransmitted probe is ambiguous, see RFC 6298 § 3.
+        self.timestamp = None;
+    }
+
     fn on_retransmit(&mut self) {
         if self.timestamp.is_some() {
             tcp_trace!("rtte: abort sampling due to retransmit");
@@ -270,6 +312,10 @@ enum Timer {
 const ACK_DELAY_DEFAULT: Duration = Duration::from_millis(10);
 const CLOSE_DELAY: Duration = Duration::from_millis(10_000);
 
+/// Time after which the last timestamp received is no longer used to reject old segments,
+/// see RFC 7323 § 5.5.
+const PAWS_IDLE: Duration = Duration::from_secs(24 * 24 * 60 * 60);
+
 impl Timer {
     fn new() -> Timer {
         Timer::Idle {
@@ -381,6 +427,390 @@ enum AckDelayTimer {
     Immediate,
 }
 
+/// Smallest gap between the largest acknowledged probe and the smallest lost probe, below
+/// which the path MTU search stops.
+const PLPMTUD_SEARCH_STEP: usize = 32;
+
+/// Amount of consecutive retransmission timeouts after which the segments are assumed to be
+/// dropped because of their size.
+const PLPMTUD_MAX_TIMEOUTS: u8 = 2;
+
+/// State of the Packetization Layer Path MTU Discovery, see RFC 4821.
+///
+/// The segments are limited to a conservative size, and larger segments are sent as probes.
+/// An acknowledged probe raises the segment size, and a lost probe narrows the search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PathMtuProbe {
+    /// The largest segment size known to reach the remote.
+    mss: usize,
+    /// The smallest segment size known not to reach the remote.
+    search_high: Option<usize>,
+    /// The sequence number following the probe in flight, and its size.
+    probe: Option<(TcpSeqNumber, usize)>,
+    /// The sequence number following the last new data sent. Retransmissions are not probes.
+    high_seq: TcpSeqNumber,
+    /// The amount of consecutive retransmission timeouts.
+    timeouts: u8,
+}
+
+impl PathMtuProbe {
+    fn new(mss: usize, seq: TcpSeqNumber) -> Self {
+        PathMtuProbe {
+            mss,
+            search_high: None,
+            probe: None,
+            high_seq: seq,
+            timeouts: 0,
+        }
+    }
+
+    /// Return the size of the next probe, unless the search is over.
+    fn probe_size(&self, max_mss: usize) -> Option<usize> {
+        let size = match self.search_high {
+            // Try the largest segment first, it usually works.
+            None => max_mss,
+            Some(search_high) => ((self.mss + search_high) / 2).min(max_mss),
+        };
+        (size >= self.mss + PLPMTUD_SEARCH_STEP).then_some(size)
+    }
+
+    fn on_transmit(&mut self, seq: TcpSeqNumber, len: usize) {
+        // Only the probes are larger than the segment size.
+        if len > self.mss {
+            net_debug!("sending a path MTU probe of {} octets", len);
+            self.probe = Some((seq + len, len));
+        }
+        if seq + len > self.high_seq {
+            self.high_seq = seq + len;
+        }
+    }
+
+    fn on_ack(&mut self, ack_number: TcpSeqNumber) {
+        self.timeouts = 0;
+        if let Some((probe_end, size)) = self.probe {
+            if ack_number >= probe_end {
+                net_debug!("path MTU probe of {} octets acknowledged", size);
+                self.mss = size;
+                self.probe = None;
+            }
+        }
+    }
+
+    /// Update the state when retransmitting after a timeout or duplicate ACKs. The segment size
+    /// falls back to `base_mss`, or `min_mss` if it is already lower, when repeated timeouts
+    /// indicate a black hole, see RFC 4821 § 7.7.
+    fn on_retransmit(&mut self, timeout: bool, base_mss: usize, min_mss: usize) {
+        if let Some((_, size)) = self.probe.take() {
+            net_debug!("path MTU probe of {} octets lost", size);
+            self.search_high = Some(size);
+        } else if timeout {
+            self.timeouts += 1;
+            if self.timeouts >= PLPMTUD_MAX_TIMEOUTS {
+                net_debug!("path MTU black hole detected");
+                self.search_high = Some(self.mss);
+                self.mss = if self.mss > base_mss {
+                    base_mss
+                } else {
+                    min_mss
+                };
+                self.timeouts = 0;
+            }
+        }
+    }
+}
+
+/// Number of duplicate acknowledgements after which a segment is considered lost, see RFC 5681
+/// § 3.2 and RFC 6675 § 2.
+const DUP_THRESH: u8 = 3;
+
+/// Maximum number of selectively acknowledged ranges recorded by the sender, which is the
+/// number of SACK blocks an acknowledgement carries alongside the Timestamps option, see
+/// RFC 2018 § 3.
+const SACK_SCOREBOARD_LEN: usize = 3;
+
+/// The ranges of sequence space selectively acknowledged by the remote, see RFC 6675.
+///
+/// During a loss recovery, only the holes between these ranges are retransmitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct SackScoreboard {
+    /// Selectively acknowledged ranges, sorted and disjoint.
+    ranges: [Option<(TcpSeqNumber, TcpSeqNumber)>; SACK_SCOREBOARD_LEN],
+    /// The highest sequence number sent when the loss recovery started, if any.
+    recovery_point: Option<TcpSeqNumber>,
+}
+
+impl SackScoreboard {
+    /// Record a selectively acknowledged range. When the scoreboard is full, the highest range
+    /// is forgotten, since the holes below the others are retransmitted first.
+    fn add(&mut self, mut left: TcpSeqNumber, mut right: TcpSeqNumber) {
+        let mut ranges = [None; SACK_SCOREBOARD_LEN + 1];
+        let mut len = 0;
+        for &(range_left, range_right) in self.ranges.iter().flatten() {
+            if range_right < left || right < range_left {
+                ranges[len] = Some((range_left, range_right));
+                len += 1;
+            } else {
+                // Merge the overlapping or adjacent range.
+                left = left.min(range_left);
+                right = right.max(range_right);
+            }
+        }
+        let index = ranges[..len]
+            .iter()
+            .flatten()
+            .take_while(|&&(range_left, _)| range_left < left)
+            .count();
+        ranges.copy_within(index..len, index + 1);
+        ranges[index] = Some((left, right));
+        self.ranges.copy_from_slice(&ranges[..SACK_SCOREBOARD_LEN]);
+    }
+
+    /// Forget the ranges below the cumulative acknowledgement, and end the loss recovery once
+    /// all the data sent before it is acknowledged.
+    fn on_ack(&mut self, ack_number: TcpSeqNumber) {
+        for range in self.ranges.iter_mut() {
+            match *range {
+                Some((_, right)) if right <= ack_number => *range = None,
+                Some((left, right)) if left < ack_number => *range = Some((ack_number, right)),
+                _ => (),
+            }
+        }
+        self.ranges.sort_unstable_by_key(|range| range.is_none());
+        if matches!(self.recovery_point, Some(point) if point <= ack_number) {
+            self.recovery_point = None;
+        }
+    }
+
+    /// Return the amount of octets selectively acknowledged.
+    fn sacked_len(&self) -> usize {
+        self.ranges
+            .iter()
+            .flatten()
+            .map(|&(left, right)| right - left)
+            .sum()
+    }
+
+    /// Return the sequence number to send next during a loss recovery, skipping the selectively
+    /// acknowledged ranges. The data sent above the highest range isn't considered lost, and
+    /// isn't retransmitted.
+    fn next_seq(&self, mut seq: TcpSeqNumber) -> TcpSeqNumber {
+        let Some(recovery_point) = self.recovery_point else {
+            return seq;
+        };
+        for &(left, right) in self.ranges.iter().flatten() {
+            if left <= seq && seq < right {
+                seq = right;
+            }
+        }
+        match self.ranges.iter().flatten().last() {
+            Some(&(_, right)) if seq >= right => seq.max(recovery_point),
+            _ => seq,
+        }
+    }
+
+    /// Return the start of the next selectively acknowledged range above `seq`, which limits
+    /// the size of the retransmitted segment.
+    fn next_sacked(&self, seq: TcpSeqNumber) -> Option<TcpSeqNumber> {
+        self.recovery_point?;
+        self.ranges
+            .iter()
+            .flatten()
+            .map(|&(left, _)| left)
+            .find(|&left| left > seq)
+    }
+}
+
+#[cfg(feature = "socket-tcp-rack")]
+/// Maximum number of segments in flight whose transmission time is recorded by RACK. Above it,
+/// the most recently sent segments are tracked together.
+const RACK_SEGMENT_COUNT: usize = 4;
+
+/// Delay of the acknowledgement of a single segment assumed in the probe timeout, see RFC 8985
+/// § 7.2.
+#[cfg(feature = "socket-tcp-rack")]
+const TLP_MAX_ACK_DELAY: Duration = Duration::from_millis(200);
+
+/// A range of sent data, and its last transmission time.
+#[cfg(feature = "socket-tcp-rack")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RackSegment {
+    seq: TcpSeqNumber,
+    end: TcpSeqNumber,
+    xmit_ts: Instant,
+    retransmitted: bool,
+}
+
+/// The state of the RACK-TLP loss detection, see RFC 8985.
+///
+/// A segment is lost once a segment sent after it is delivered, and a reordering window elapsed
+/// since. A tail loss probe retransmits the last segment when no acknowledgement is received for
+/// two round-trip times, so that a loss at the end of a flight is detected without waiting for
+/// a retransmission timeout.
+#[cfg(feature = "socket-tcp-rack")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Rack {
+    /// The segments in flight, sorted and disjoint.
+    segments: [Option<RackSegment>; RACK_SEGMENT_COUNT],
+    /// The transmission time and end of the most recently sent segment which was delivered.
+    delivered: Option<(Instant, TcpSeqNumber)>,
+    /// The round-trip time of that segment.
+    rtt: Duration,
+    /// The minimum round-trip time measured.
+    min_rtt: Option<Duration>,
+    /// The expiry of the reordering window of a segment which is not yet lost.
+    reo_timer: Option<Instant>,
+    /// The expiry of the probe timeout.
+    pto_timer: Option<Instant>,
+    /// The range of the tail loss probe in flight.
+    probe: Option<(TcpSeqNumber, TcpSeqNumber)>,
+}
+
+#[cfg(feature = "socket-tcp-rack")]
+impl Rack {
+    /// Record the transmission of `seq..end`, replacing the ranges it retransmits.
+    fn on_transmit(&mut self, timestamp: Instant, seq: TcpSeqNumber, end: TcpSeqNumber) {
+        let mut segment = RackSegment {
+            seq,
+            end,
+            xmit_ts: timestamp,
+            retransmitted: false,
+        };
+
+        let mut segments = [None; RACK_SEGMENT_COUNT + 1];
+        let mut len = 0;
+        for &other in self.segments.iter().flatten() {
+            if other.end <= seq || end <= other.seq {
+                segments[len] = Some(other);
+                len += 1;
+            } else {
+                segment.seq = segment.seq.min(other.seq);
+                segment.end = segment.end.max(other.end);
+                segment.retransmitted = true;
+            }
+        }
+        let index = segments[..len]
+            .iter()
+            .flatten()
+            .take_while(|other| other.seq < seq)
+            .count();
+        segments.copy_within(index..len, index + 1);
+        segments[index] = Some(segment);
+
+        if let [.., Some(last), Some(overflow)] = segments {
+            // Track the two most recently sent ranges together.
+            segments[RACK_SEGMENT_COUNT - 1] = Some(RackSegment {
+                seq: last.seq,
+                end: overflow.end,
+                xmit_ts: last.xmit_ts.max(overflow.xmit_ts),
+                retransmitted: last.retransmitted || overflow.retransmitted,
+            });
+        }
+        self.segments
+            .copy_from_slice(&segments[..RACK_SEGMENT_COUNT]);
+    }
+
+    /// Forget the segments acknowledged cumulatively or selectively, and update the most
+    /// recently sent segment which was delivered, see RFC 8985 § 6.2.
+    fn on_ack(&mut self, timestamp: Instant, ack_number: TcpSeqNumber, sacked: &SackScoreboard) {
+        for slot in self.segments.iter_mut() {
+            let Some(segment) = *slot else { continue };
+            let delivered = segment.end <= ack_number
+                || sacked
+                    .ranges
+                    .iter()
+                    .flatten()
+                    .any(|&(left, right)| left <= segment.seq && segment.end <= right);
+            if !delivered {
+                continue;
+            }
+            *slot = None;
+
+            let rtt = timestamp - segment.xmit_ts;
+            // The acknowledgement of a retransmitted segment may be for its original
+            // transmission, in which case it doesn't tell when the segment was delivered.
+            if segment.retransmitted && self.min_rtt.map_or(false, |min_rtt| rtt < min_rtt) {
+                continue;
+            }
+            self.min_rtt = Some(self.min_rtt.map_or(rtt, |min_rtt| min_rtt.min(rtt)));
+            let sent_after = match self.delivered {
+                Some((xmit_ts, end)) => {
+                    segment.xmit_ts > xmit_ts || (segment.xmit_ts == xmit_ts && segment.end > end)
+                }
+                None => true,
+            };
+            if sent_after {
+                self.delivered = Some((segment.xmit_ts, segment.end));
+                self.rtt = rtt;
+            }
+        }
+        self.segments
+            .sort_unstable_by_key(|segment| segment.is_none());
+
+        if self.segments[0].is_none() {
+            self.reo_timer = None;
+            self.pto_timer = None;
+        }
+    }
+
+    /// Return whether a segment in flight is lost, and arm the reordering timer for the
+    /// segments which may still be delivered out of order, see RFC 8985 § 6.2.
+    fn detect_loss(&mut self, timestamp: Instant) -> bool {
+        self.reo_timer = None;
+        let Some((delivered_ts, delivered_end)) = self.delivered else {
+            return false;
+        };
+        let reo_wnd = self.min_rtt.unwrap_or_default() / 4;
+
+        let mut lost = false;
+        for segment in self.segments.iter().flatten() {
+            let sent_before = segment.xmit_ts < delivered_ts
+                || (segment.xmit_ts == delivered_ts && segment.end < delivered_end);
+            if !sent_before {
+                continue;
+            }
+            let lost_at = segment.xmit_ts + self.rtt + reo_wnd;
+            if timestamp >= lost_at {
+                lost = true;
+            } else {
+                self.reo_timer = Some(self.reo_timer.map_or(lost_at, |at| at.min(lost_at)));
+            }
+        }
+        lost
+    }
+
+    /// Arm the probe timeout, see RFC 8985 § 7.2.
+    fn arm_pto(&mut self, timestamp: Instant, rtte: &RttEstimator, single_segment: bool) {
+        let mut pto = Duration::from_millis(rtte.rtt as u64) * 2;
+        if single_segment {
+            pto += TLP_MAX_ACK_DELAY;
+        }
+        self.pto_timer = Some(timestamp + pto.min(rtte.retransmission_timeout()));
+    }
+
+    /// Forget the timers and the probe in flight after a retransmission timeout.
+    fn on_timeout(&mut self) {
+        self.reo_timer = None;
+        self.pto_timer = None;
+        self.probe = None;
+    }
+
+    fn poll_at(&self) -> PollAt {
+        match (self.reo_timer, self.pto_timer) {
+            (Some(reo), Some(pto)) => PollAt::Time(reo.min(pto)),
+            (Some(at), None) | (None, Some(at)) => PollAt::Time(at),
+            (None, None) => PollAt::Ingress,
+        }
+    }
+}
+
+/// The options of a connection encoded in a SYN cookie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SynCookie {
+    pub(crate) mss: u16,
+    pub(crate) window_scale: Option<u8>,
+    pub(crate) sack_permitted: bool,
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 struct Tuple {
@@ -405,6 +835,14 @@ pub enum CongestionControl {
 
     #[cfg(feature = "socket-tcp-cubic")]
     Cubic,
+
+    #[cfg(feature = "socket-tcp-bbr")]
+    Bbr,
+
+    /// A controller installed with [Socket::set_congestion_controller].
+    ///
+    /// Only reported by [Socket::congestion_control], [Socket::set_congestion_control] ignores it.
+    Custom,
 }
 
 /// A Transmission Control Protocol socket.
@@ -412,7 +850,7 @@ pub enum CongestionControl {
 /// A TCP socket may passively listen for connections or actively connect to another endpoint.
 /// Note that, for listening sockets, there is no "backlog"; to be able to simultaneously
 /// accept several connections, as many sockets must be allocated, or any new connection
-/// attempts will be reset.
+/// attempts will be reset. A [Listener] manages such a pool of sockets.
 #[derive(Debug)]
 pub struct Socket<'a> {
     state: State,
@@ -457,6 +895,15 @@ pub struct Socket<'a> {
     remote_win_scale: Option<u8>,
     /// Whether or not the remote supports selective ACK as described in RFC 2018.
     remote_has_sack: bool,
+    /// The ranges of sent data selectively acknowledged by the remote.
+    sack_scoreboard: SackScoreboard,
+    /// Whether the TCP Timestamps option described in RFC 7323 is offered and accepted.
+    timestamps: bool,
+    /// The offset of the timestamp clock of the connection.
+    tsval_offset: u32,
+    /// The last timestamp received from the remote, which is echoed, and when it was received.
+    /// None if the remote doesn't use the TCP Timestamps option.
+    ts_recent: Option<(u32, Instant)>,
     /// The maximum number of data octets that the remote side may receive.
     remote_mss: usize,
     /// The timestamp of the last packet received.
@@ -480,9 +927,23 @@ pub struct Socket<'a> {
 
     /// Nagle's Algorithm enabled.
     nagle: bool,
+    /// Whether the path MTU is discovered by probing.
+    path_mtu_probing: bool,
+    /// The state of the path MTU probing, once the connection is established.
+    path_mtu_probe: Option<PathMtuProbe>,
+    /// Whether losses are detected with RACK-TLP.
+    #[cfg(feature = "socket-tcp-rack")]
+    rack_tlp: bool,
+    /// The state of the RACK-TLP loss detection.
+    #[cfg(feature = "socket-tcp-rack")]
+    rack: Rack,
+    /// Whether the data segments are paced.
+    pacing: bool,
+    /// When the next data segment may be sent, when pacing.
+    pacing_release: Option<Instant>,
 
     /// The congestion control algorithm.
-    congestion_controller: congestion::AnyController,
+    congestion_controller: congestion::AnyController<'a>,
 
     #[cfg(feature = "async")]
     rx_waker: WakerRegistration,
@@ -533,6 +994,10 @@ impl<'a> Socket<'a> {
             remote_win_shift: rx_cap_log2.saturating_sub(16) as u8,
             remote_win_scale: None,
             remote_has_sack: false,
+            sack_scoreboard: SackScoreboard::default(),
+            timestamps: false,
+            tsval_offset: 0,
+            ts_recent: None,
             remote_mss: DEFAULT_MSS,
             remote_last_ts: None,
             local_rx_last_ack: None,
@@ -542,6 +1007,14 @@ impl<'a> Socket<'a> {
             ack_delay_timer: AckDelayTimer::Idle,
             challenge_ack_timer: Instant::from_secs(0),
             nagle: true,
+            path_mtu_probing: false,
+            path_mtu_probe: None,
+            #[cfg(feature = "socket-tcp-rack")]
+            rack_tlp: false,
+            #[cfg(feature = "socket-tcp-rack")]
+            rack: Rack::default(),
+            pacing: false,
+            pacing_release: None,
             congestion_controller: congestion::AnyController::new(),
 
             #[cfg(feature = "async")]
@@ -570,10 +1043,21 @@ impl<'a> Socket<'a> {
     /// * Interrupt handlers should almost always avoid floating-point operations.
     /// * Kernel-mode code on desktop processors usually avoids FPU operations to reduce the penalty of saving and restoring FPU registers.
     /// In all these cases, `CongestionControl::Reno` is a better choice of congestion control algorithm.
+    ///
+    /// `CongestionControl::Bbr` estimates the bottleneck bandwidth and the round-trip time of the
+    /// path instead of reacting to losses, and keeps the amount of data in flight close to their
+    /// product, so that it doesn't fill the queues along the path. It only uses integer arithmetic.
+    /// To use it, please enable the `socket-tcp-bbr` feature.
+    ///
+    /// `CongestionControl::Custom` is ignored and the current controller, built-in or not, is
+    /// kept. A controller of your own is installed with
+    /// [set_congestion_controller](#method.set_congestion_controller) instead.
     pub fn set_congestion_control(&mut self, congestion_control: CongestionControl) {
         use congestion::*;
 
         self.congestion_controller = match congestion_control {
+            CongestionControl::Custom => return,
+
             CongestionControl::None => AnyController::None(no_control::NoControl),
 
             #[cfg(feature = "socket-tcp-reno")]
@@ -581,6 +1065,9 @@ impl<'a> Socket<'a> {
 
             #[cfg(feature = "socket-tcp-cubic")]
             CongestionControl::Cubic => AnyController::Cubic(cubic::Cubic::new()),
+
+            #[cfg(feature = "socket-tcp-bbr")]
+            CongestionControl::Bbr => AnyController::Bbr(bbr::Bbr::new()),
         }
     }
 
@@ -596,9 +1083,31 @@ impl<'a> Socket<'a> {
 
             #[cfg(feature = "socket-tcp-cubic")]
             AnyController::Cubic(_) => CongestionControl::Cubic,
+
+            #[cfg(feature = "socket-tcp-bbr")]
+            AnyController::Bbr(_) => CongestionControl::Bbr,
+
+            AnyController::Custom(_) => CongestionControl::Custom,
         }
     }
 
+    /// Install a congestion controller implementing an algorithm of your own.
+    ///
+    /// The controller is either borrowed or boxed, e.g.
+    /// `Box::new(controller) as Box<dyn Controller>`. Once installed, the congestion control
+    /// algorithm is `CongestionControl::Custom`.
+    pub fn set_congestion_controller<C>(&mut self, controller: C)
+    where
+        C: Into<Managed<'a, dyn Controller + 'a>>,
+    {
+        self.congestion_controller = congestion::AnyController::Custom(controller.into());
+    }
+
+    /// Return the congestion controller of the socket.
+    pub fn congestion_controller(&self) -> &dyn Controller {
+        self.congestion_controller.inner()
+    }
+
     /// Register a waker for receive operations.
     ///
     /// The waker is woken on state changes that might affect the return value
@@ -655,6 +1164,13 @@ impl<'a> Socket<'a> {
         self.nagle
     }
 
+    /// Return whether the TCP Timestamps option is enabled.
+    ///
+    /// See also the [set_timestamps_enabled](#method.set_timestamps_enabled) method.
+    pub fn timestamps_enabled(&self) -> bool {
+        self.timestamps
+    }
+
     /// Return the current window field value, including scaling according to RFC 1323.
     ///
     /// Used in internal calculations as well as packet generation.
@@ -705,6 +1221,93 @@ impl<'a> Socket<'a> {
         self.nagle = enabled
     }
 
+    /// Enable or disable the TCP Timestamps option, see [RFC 7323]. By default, it is disabled.
+    ///
+    /// When enabled, the option is offered when connecting, and accepted when offered by the
+    /// remote. Once both endpoints use it, every acknowledgement of new data is a sample of the
+    /// round-trip time, and segments with an older timestamp than the last one received are
+    /// dropped, which protects against wrapped sequence numbers (PAWS). The option takes 12
+    /// octets in every segment.
+    ///
+    /// Changing this setting only affects the next connections.
+    ///
+    /// [RFC 7323]: https://www.rfc-editor.org/rfc/rfc7323
+    pub fn set_timestamps_enabled(&mut self, enabled: bool) {
+        self.timestamps = enabled
+    }
+
+    /// Return whether the path MTU is discovered by probing.
+    ///
+    /// See also the [set_path_mtu_probing](#method.set_path_mtu_probing) method.
+    pub fn path_mtu_probing(&self) -> bool {
+        self.path_mtu_probing
+    }
+
+    /// Enable or disable the Packetization Layer Path MTU Discovery, see [RFC 4821]. By default,
+    /// it is disabled.
+    ///
+    /// The size of the segments is always limited by the path MTU reported by routers with ICMP
+    /// errors. Networks blocking these errors silently drop the segments that are too large.
+    /// When probing is enabled, the segments first fit in a 1024 octets (IPv4) or 1280 octets
+    /// (IPv6) packet, and larger segments are sent as probes to raise their size. Repeated
+    /// retransmission timeouts lower the size of the segments again.
+    ///
+    /// [RFC 4821]: https://www.rfc-editor.org/rfc/rfc4821
+    pub fn set_path_mtu_probing(&mut self, enabled: bool) {
+        self.path_mtu_probing = enabled;
+        if !enabled {
+            self.path_mtu_probe = None;
+        }
+    }
+
+    /// Return whether losses are detected with RACK-TLP.
+    ///
+    /// See also the [set_rack_tlp_enabled](#method.set_rack_tlp_enabled) method.
+    #[cfg(feature = "socket-tcp-rack")]
+    pub fn rack_tlp_enabled(&self) -> bool {
+        self.rack_tlp
+    }
+
+    /// Enable or disable the RACK-TLP loss detection, see [RFC 8985]. By default, it is disabled.
+    ///
+    /// Without it, a segment is retransmitted after three duplicate acknowledgements, or when the
+    /// retransmission timer expires. When enabled, a segment is also considered lost once a
+    /// segment sent after it is acknowledged and a fraction of the round-trip time elapsed, and
+    /// the last segment is retransmitted as a probe when no acknowledgement is received for two
+    /// round-trip times, so that losses at the end of a flight don't wait for a retransmission
+    /// timeout. Losses detected by RACK-TLP are reported to the congestion controller like any
+    /// other retransmission.
+    ///
+    /// [RFC 8985]: https://www.rfc-editor.org/rfc/rfc8985
+    #[cfg(feature = "socket-tcp-rack")]
+    pub fn set_rack_tlp_enabled(&mut self, enabled: bool) {
+        self.rack_tlp = enabled;
+        if !enabled {
+            self.rack = Rack::default();
+        }
+    }
+
+    /// Return whether the data segments are paced.
+    ///
+    /// See also the [set_pacing_enabled](#method.set_pacing_enabled) method.
+    pub fn pacing_enabled(&self) -> bool {
+        self.pacing
+    }
+
+    /// Enable or disable the pacing of the data segments. By default, it is disabled.
+    ///
+    /// Without pacing, the socket sends as many segments as the congestion window allows back to
+    /// back, and bursts may overflow shallow queues along the path. When enabled, the segments are
+    /// spaced at the rate supplied by the congestion controller, or else at the rate sending
+    /// the window (the congestion window, or the remote window if smaller) in a smoothed
+    /// round-trip time. Acknowledgements and control segments are not paced.
+    pub fn set_pacing_enabled(&mut self, enabled: bool) {
+        self.pacing = enabled;
+        if !enabled {
+            self.pacing_release = None;
+        }
+    }
+
     /// Return the keep-alive interval.
     ///
     /// See also the [set_keep_alive](#method.set_keep_alive) method.
@@ -801,8 +1404,16 @@ impl<'a> Socket<'a> {
         self.remote_win_shift = rx_cap_log2.saturating_sub(16) as u8;
         self.remote_mss = DEFAULT_MSS;
         self.remote_last_ts = None;
+        self.ts_recent = None;
+        self.sack_scoreboard = SackScoreboard::default();
         self.ack_delay_timer = AckDelayTimer::Idle;
         self.challenge_ack_timer = Instant::from_secs(0);
+        self.path_mtu_probe = None;
+        #[cfg(feature = "socket-tcp-rack")]
+        {
+            self.rack = Rack::default();
+        }
+        self.pacing_release = None;
 
         #[cfg(feature = "async")]
         {
@@ -940,6 +1551,7 @@ impl<'a> Socket<'a> {
         let seq = Self::random_seq_no(cx);
         self.local_seq_no = seq;
         self.remote_last_seq = seq;
+        self.tsval_offset = Self::random_tsval_offset(cx);
         Ok(())
     }
 
@@ -953,6 +1565,44 @@ impl<'a> Socket<'a> {
         TcpSeqNumber(cx.rand().rand_u32() as i32)
     }
 
+    #[cfg(test)]
+    fn random_tsval_offset(_cx: &mut Context) -> u32 {
+        0
+    }
+
+    // The timestamp clock starts at a random value, so that it doesn't reveal the uptime,
+    // see RFC 7323 § 7.1.
+    #[cfg(not(test))]
+    fn random_tsval_offset(cx: &mut Context) -> u32 {
+        cx.rand().rand_u32()
+    }
+
+    /// Return the value of the timestamp clock of the connection, which ticks every millisecond.
+    fn tsval(&self, timestamp: Instant) -> u32 {
+        (timestamp.total_millis() as u32).wrapping_add(self.tsval_offset)
+    }
+
+    /// Return the TCP Timestamps option to send, if any. It is sent in the SYN when enabled,
+    /// and then only if the remote sent it too.
+    fn timestamp_repr(&self, timestamp: Instant) -> Option<TcpTimestampRepr> {
+        let tsecr = match self.ts_recent {
+            Some((tsecr, _)) => tsecr,
+            None if self.timestamps && self.state == State::SynSent => 0,
+            None => return None,
+        };
+        Some(TcpTimestampRepr::new(self.tsval(timestamp), tsecr))
+    }
+
+    /// Return the length of the options sent in every segment, which are not accounted for by
+    /// the MSS, see RFC 6691.
+    fn options_len(&self) -> usize {
+        match self.ts_recent {
+            // The 10 octets of the TCP Timestamps option, padded to 12.
+            Some(_) => 12,
+            None => 0,
+        }
+    }
+
     /// Close the transmit half of the full-duplex connection.
     ///
     /// Note that there is no corresponding function for the receive half of the full-duplex
@@ -1304,6 +1954,7 @@ impl<'a> Socket<'a> {
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges: [None, None, None],
+            timestamp: None,
             payload: &[],
         };
         let ip_reply_repr = IpRepr::new(
@@ -1332,7 +1983,12 @@ impl<'a> Socket<'a> {
         (ip_reply_repr, reply_repr)
     }
 
-    fn ack_reply(&mut self, ip_repr: &IpRepr, repr: &TcpRepr) -> (IpRepr, TcpRepr<'static>) {
+    fn ack_reply(
+        &mut self,
+        cx: &Context,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+    ) -> (IpRepr, TcpRepr<'static>) {
         let (mut ip_reply_repr, mut reply_repr) = Self::reply(ip_repr, repr);
 
         // From RFC 793:
@@ -1348,6 +2004,7 @@ impl<'a> Socket<'a> {
         // segments, is right-shifted by [advertised scale value] bits[...]
         reply_repr.window_len = self.scaled_window();
         self.remote_last_win = reply_repr.window_len;
+        reply_repr.timestamp = self.timestamp_repr(cx.now());
 
         // If the remote supports selective acknowledgement, add the option to the outgoing
         // segment.
@@ -1402,7 +2059,7 @@ impl<'a> Socket<'a> {
         // Rate-limit to 1 per second max.
         self.challenge_ack_timer = cx.now() + Duration::from_secs(1);
 
-        Some(self.ack_reply(ip_repr, repr))
+        Some(self.ack_reply(cx, ip_repr, repr))
     }
 
     pub(crate) fn accepts(&self, _cx: &mut Context, ip_repr: &IpRepr, repr: &TcpRepr) -> bool {
@@ -1425,11 +2082,184 @@ impl<'a> Socket<'a> {
                 && repr.src_port == tuple.remote.port
         } else {
             // We're listening, reject packets not matching the listen endpoint.
-            let addr_ok = match self.listen_endpoint.addr {
-                Some(addr) => ip_repr.dst_addr() == addr,
-                None => true,
-            };
-            addr_ok && repr.dst_port != 0 && repr.dst_port == self.listen_endpoint.port
+            self.listen_endpoint_matches(ip_repr, repr)
+        }
+    }
+
+    fn listen_endpoint_matches(&self, ip_repr: &IpRepr, repr: &TcpRepr) -> bool {
+        let addr_ok = match self.listen_endpoint.addr {
+            Some(addr) => ip_repr.dst_addr() == addr,
+            None => true,
+        };
+        addr_ok && repr.dst_port != 0 && repr.dst_port == self.listen_endpoint.port
+    }
+
+    /// Return whether the socket was opened by listening on the destination of `repr`, whether
+    /// it still listens or already accepted a connection.
+    pub(crate) fn listens_on(&self, ip_repr: &IpRepr, repr: &TcpRepr) -> bool {
+        self.state != State::Closed && self.listen_endpoint_matches(ip_repr, repr)
+    }
+
+    /// Return a SYN|ACK answering the SYN `repr` statelessly, with a SYN cookie as initial
+    /// sequence number.
+    ///
+    /// The connection is accepted by a socket listening on the same endpoint once the cookie is
+    /// acknowledged, see [accept_syn_cookie](#method.accept_syn_cookie).
+    pub(crate) fn syn_cookie_reply(
+        &self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+        cookie: TcpSeqNumber,
+    ) -> (IpRepr, TcpRepr<'static>) {
+        let (mut ip_reply_repr, mut reply_repr) = Self::reply(ip_repr, repr);
+        reply_repr.control = TcpControl::Syn;
+        reply_repr.seq_number = cookie;
+        reply_repr.ack_number = Some(repr.seq_number + 1);
+        reply_repr.window_len = self.rx_buffer.capacity().min(u16::MAX as usize) as u16;
+        // The window of the accepting socket isn't known yet, so it isn't scaled.
+        reply_repr.window_scale = repr.window_scale.map(|_| 0);
+        reply_repr.sack_permitted = repr.sack_permitted;
+        let max_segment_size = cx.ip_mtu() - ip_reply_repr.header_len() - TCP_HEADER_LEN;
+        reply_repr.max_seg_size = Some(max_segment_size as u16);
+        ip_reply_repr.set_payload_len(reply_repr.buffer_len());
+        (ip_reply_repr, reply_repr)
+    }
+
+    /// Accept the connection acknowledged by `repr`, whose SYN was answered with a valid SYN
+    /// cookie, as if the listening socket had received the SYN.
+    pub(crate) fn accept_syn_cookie(
+        &mut self,
+        cx: &mut Context,
+        ip_repr: &IpRepr,
+        repr: &TcpRepr,
+        cookie: SynCookie,
+    ) -> Option<(IpRepr, TcpRepr<'static>)> {
+        debug_assert_eq!(self.state, State::Listen);
+        let listen_endpoint = self.listen_endpoint;
+        self.reset();
+        self.listen_endpoint = listen_endpoint;
+
+        self.congestion_controller
+            .inner_mut()
+            .set_mss(cookie.mss as usize);
+        self.remote_mss = cookie.mss as usize;
+        self.tuple = Some(Tuple {
+            local: IpEndpoint::new(ip_repr.dst_addr(), repr.dst_port),
+            remote: IpEndpoint::new(ip_repr.src_addr(), repr.src_port),
+        });
+        self.local_seq_no = repr.ack_number? - 1;
+        self.remote_seq_no = repr.seq_number;
+        self.remote_last_seq = self.local_seq_no + 1;
+        self.remote_last_ack = Some(repr.seq_number);
+        self.remote_has_sack = cookie.sack_permitted;
+        self.remote_win_scale = cookie.window_scale;
+        self.remote_win_shift = 0;
+        self.set_state(State::SynReceived);
+        self.timer.set_for_idle(cx.now(), self.keep_alive);
+
+        self.process(cx, ip_repr, repr)
+    }
+
+    /// Record the ranges selectively acknowledged by `repr`, and start a loss recovery once
+    /// enough data above the cumulative acknowledgement is received, see RFC 6675 § 5.
+    fn process_sack_ranges(&mut self, cx: &Context, repr: &TcpRepr, ack_number: TcpSeqNumber) {
+        if self.remote_has_sack {
+            let sent_seq = ack_number + self.tx_buffer.len();
+            for &(left, right) in repr.sack_ranges.iter().flatten() {
+                let left = TcpSeqNumber(left as i32);
+                let right = TcpSeqNumber(right as i32);
+                // Ignore the D-SACK ranges of RFC 2883, and the ranges of data never sent.
+                if left > ack_number && left < right && right <= sent_seq {
+                    self.sack_scoreboard.add(left, right);
+                }
+            }
+        }
+        self.sack_scoreboard.on_ack(ack_number);
+
+        if self.sack_scoreboard.recovery_point.is_none()
+            && !matches!(self.timer, Timer::FastRetransmit)
+            && ack_number == self.local_seq_no
+            && self.sack_scoreboard.sacked_len() > (DUP_THRESH as usize - 1) * self.send_mss(cx)
+        {
+            self.timer.set_for_fast_retransmit();
+            net_debug!("started SACK-based loss recovery");
+        }
+    }
+
+    /// Update the RACK-TLP state with an acknowledgement, see RFC 8985 § 6.2 and § 7.4.
+    #[cfg(feature = "socket-tcp-rack")]
+    fn process_rack(&mut self, cx: &Context, repr: &TcpRepr, ack_number: TcpSeqNumber) {
+        if let Some((probe_seq, probe_end)) = self.rack.probe {
+            let dsack = self.remote_has_sack
+                && repr.sack_ranges.iter().flatten().any(|&(left, right)| {
+                    let (left, right) = (TcpSeqNumber(left as i32), TcpSeqNumber(right as i32));
+                    left <= probe_seq && probe_end <= right && right <= ack_number
+                });
+            if dsack {
+                // Both the probe and the original segment were delivered.
+                self.rack.probe = None;
+            } else if ack_number >= probe_end {
+                // The probe repaired a loss, which the congestion controller reacts to.
+                net_debug!("tail loss probe recovered a loss");
+                self.rack.probe = None;
+                self.congestion_controller
+                    .inner_mut()
+                    .on_retransmit(cx.now());
+            }
+        }
+
+        self.rack
+            .on_ack(cx.now(), ack_number, &self.sack_scoreboard);
+        self.rack_detect_loss(cx.now());
+        if ack_number > self.local_seq_no && self.rack.segments[0].is_some() {
+            self.rack_arm_pto(cx);
+        }
+    }
+
+    /// Start a loss recovery if RACK detects a lost segment.
+    #[cfg(feature = "socket-tcp-rack")]
+    fn rack_detect_loss(&mut self, timestamp: Instant) {
+        if self.rack.detect_loss(timestamp)
+            && self.sack_scoreboard.recovery_point.is_none()
+            && !matches!(self.timer, Timer::FastRetransmit)
+        {
+            self.timer.set_for_fast_retransmit();
+            net_debug!("RACK detected a lost segment");
+        }
+    }
+
+    /// Arm the probe timeout, unless a probe is in flight or a loss is being recovered.
+    #[cfg(feature = "socket-tcp-rack")]
+    fn rack_arm_pto(&mut self, cx: &Context) {
+        if self.rack.probe.is_none()
+            && self.sack_scoreboard.recovery_point.is_none()
+            && self.sack_scoreboard.sacked_len() == 0
+        {
+            let in_flight = self.remote_last_seq.max(self.local_seq_no) - self.local_seq_no;
+            let single_segment = in_flight <= self.send_mss(cx);
+            self.rack.arm_pto(cx.now(), &self.rtte, single_segment);
+        }
+    }
+
+    /// Send a tail loss probe once the probe timeout expires.
+    #[cfg(feature = "socket-tcp-rack")]
+    fn rack_probe(&mut self, cx: &Context) {
+        if !matches!(self.rack.pto_timer, Some(pto) if cx.now() >= pto) {
+            return;
+        }
+        self.rack.pto_timer = None;
+        if self.remote_last_seq > self.local_seq_no {
+            // Retransmit the last segment sent, so that its acknowledgement reveals the losses
+            // at the end of the flight, see RFC 8985 § 7.3.
+            net_debug!("sending a tail loss probe");
+            let probe_len = (self.remote_last_seq - self.local_seq_no).min(self.send_mss(cx));
+            self.rack.probe = Some((self.remote_last_seq - probe_len, self.remote_last_seq));
+            self.remote_last_seq = self.remote_last_seq - probe_len;
+
+            // Restart the retransmit timer once the probe is sent.
+            self.timer.set_for_idle(cx.now(), self.keep_alive);
+            self.rtte.on_probe();
         }
     }
 
@@ -1550,6 +2380,31 @@ impl<'a> Socket<'a> {
             }
         }
 
+        // Reject old duplicate segments, with an older timestamp than the last one received, see
+        // RFC 7323 § 5.3. Once the TCP Timestamps option is used, it must be in every segment.
+        if let Some((ts_recent, ts_recent_at)) = self.ts_recent {
+            match repr.timestamp {
+                _ if repr.control == TcpControl::Rst => (),
+                Some(timestamp) if (timestamp.tsval.wrapping_sub(ts_recent) as i32) < 0 => {
+                    if cx.now() < ts_recent_at + PAWS_IDLE {
+                        net_debug!(
+                            "segment with an old timestamp ({} < {}), will send challenge ACK",
+                            timestamp.tsval,
+                            ts_recent
+                        );
+                        return self.challenge_ack_reply(cx, ip_repr, repr);
+                    }
+                    // The last timestamp received is too old to be compared with.
+                    self.ts_recent = Some((timestamp.tsval, cx.now()));
+                }
+                Some(_) => (),
+                None => {
+                    net_debug!("segment without a timestamp, dropping");
+                    return None;
+                }
+            }
+        }
+
         let window_start = self.remote_seq_no + self.rx_buffer.len();
         let window_end = self.remote_seq_no + self.rx_buffer.capacity();
         let segment_start = repr.seq_number;
@@ -1637,6 +2492,18 @@ impl<'a> Socket<'a> {
             }
         };
 
+        // Echo the timestamp of the segment unless it comes after a segment which is not
+        // acknowledged yet, see RFC 7323 § 4.3.
+        if let (Some((ts_recent, _)), Some(timestamp)) = (self.ts_recent, repr.timestamp) {
+            if (timestamp.tsval.wrapping_sub(ts_recent) as i32) >= 0
+                && self
+                    .remote_last_ack
+                    .map_or(true, |ack_number| repr.seq_number <= ack_number)
+            {
+                self.ts_recent = Some((timestamp.tsval, cx.now()));
+            }
+        }
+
         // Compute the amount of acknowledged octets, removing the SYN and FIN bits
         // from the sequence space.
         let mut ack_len = 0;
@@ -1663,7 +2530,26 @@ impl<'a> Socket<'a> {
                     ack_all = self.remote_last_seq == ack_number
                 }
 
-                self.rtte.on_ack(cx.now(), ack_number);
+                // New data is acknowledged, the remote endpoint is reachable.
+                if ack_number > self.local_seq_no {
+                    cx.confirm_reachable(&ip_repr.src_addr());
+                }
+
+                // The option is ignored unless it was negotiated, which a SYN|ACK does as it
+                // acknowledges our SYN, see RFC 7323 § 3.2.
+                let timestamps =
+                    self.ts_recent.is_some() || (self.timestamps && self.state == State::SynSent);
+                match repr.timestamp {
+                    // With the TCP Timestamps option, every acknowledgement of new data is a
+                    // sample, even of retransmitted segments, see RFC 7323 § 4.1.
+                    Some(timestamp) if timestamps && ack_number > self.local_seq_no => {
+                        let rtt = self.tsval(cx.now()).wrapping_sub(timestamp.tsecr);
+                        if (rtt as i32) >= 0 {
+                            self.rtte.on_echo(rtt);
+                        }
+                    }
+                    _ => self.rtte.on_ack(cx.now(), ack_number),
+                }
                 self.congestion_controller
                     .inner_mut()
                     .on_ack(cx.now(), ack_len, &self.rtte);
@@ -1725,6 +2611,10 @@ impl<'a> Socket<'a> {
                 self.remote_seq_no = repr.seq_number + 1;
                 self.remote_last_seq = self.local_seq_no;
                 self.remote_has_sack = repr.sack_permitted;
+                if self.timestamps {
+                    self.tsval_offset = Self::random_tsval_offset(cx);
+                    self.ts_recent = repr.timestamp.map(|timestamp| (timestamp.tsval, cx.now()));
+                }
                 self.remote_win_scale = repr.window_scale;
                 // Remote doesn't support window scaling, don't do it.
                 if self.remote_win_scale.is_none() {
@@ -1767,6 +2657,10 @@ impl<'a> Socket<'a> {
                 self.remote_seq_no = repr.seq_number + 1;
                 self.remote_last_seq = self.local_seq_no + 1;
                 self.remote_last_ack = Some(repr.seq_number);
+                if self.timestamps {
+                    self.ts_recent = repr.timestamp.map(|timestamp| (timestamp.tsval, cx.now()));
+                }
+                self.remote_has_sack = repr.sack_permitted;
                 self.remote_win_scale = repr.window_scale;
                 // Remote doesn't support window scaling, don't do it.
                 if self.remote_win_scale.is_none() {
@@ -1933,7 +2827,7 @@ impl<'a> Socket<'a> {
                         }
                     );
 
-                    if self.local_rx_dup_acks == 3 {
+                    if self.local_rx_dup_acks == DUP_THRESH {
                         self.timer.set_for_fast_retransmit();
                         net_debug!("started fast retransmit");
                     }
@@ -1947,9 +2841,19 @@ impl<'a> Socket<'a> {
                     self.local_rx_last_ack = Some(ack_number);
                 }
             };
+            self.process_sack_ranges(cx, repr, ack_number);
+            #[cfg(feature = "socket-tcp-rack")]
+            if self.rack_tlp {
+                self.process_rack(cx, repr, ack_number);
+            }
             // We've processed everything in the incoming segment, so advance the local
             // sequence number past it.
             self.local_seq_no = ack_number;
+            if ack_len > 0 {
+                if let Some(path_mtu_probe) = &mut self.path_mtu_probe {
+                    path_mtu_probe.on_ack(ack_number);
+                }
+            }
             // During retransmission, if an earlier segment got lost but later was
             // successfully received, self.local_seq_no can move past self.remote_last_seq.
             // Do not attempt to retransmit the latter segments; not only this is pointless
@@ -2040,7 +2944,7 @@ impl<'a> Socket<'a> {
             // This is fine because smoltcp assumes that it can always transmit zero or one
             // packets for every packet it receives.
             tcp_trace!("ACKing incoming segment");
-            Some(self.ack_reply(ip_repr, repr))
+            Some(self.ack_reply(cx, ip_repr, repr))
         } else {
             None
         }
@@ -2053,8 +2957,10 @@ impl<'a> Socket<'a> {
         }
     }
 
-    fn seq_to_transmit(&self, cx: &mut Context) -> bool {
-        let ip_header_len = match self.tuple.unwrap().local.addr {
+    /// Return the max segment size allowed by the path MTU and the MSS of the remote.
+    fn path_mss(&self, cx: &Context) -> usize {
+        let tuple = self.tuple.unwrap();
+        let ip_header_len = match tuple.local.addr {
             #[cfg(feature = "proto-ipv4")]
             IpAddress::Ipv4(_) => crate::wire::IPV4_HEADER_LEN,
             #[cfg(feature = "proto-ipv6")]
@@ -2062,10 +2968,68 @@ impl<'a> Socket<'a> {
         };
 
         // Max segment size we're able to send due to MTU limitations.
-        let local_mss = cx.ip_mtu() - ip_header_len - TCP_HEADER_LEN;
+        let local_mss = cx.path_mtu(&tuple.remote.addr) - ip_header_len - TCP_HEADER_LEN;
 
+        // The effective max segment size, taking into account our and remote's limits, and the
+        // options sent in every segment.
+        local_mss
+            .min(self.remote_mss)
+            .saturating_sub(self.options_len())
+    }
+
+    /// Return the max segment size, also limited by the path MTU probing.
+    fn send_mss(&self, cx: &Context) -> usize {
+        let mss = self.path_mss(cx);
+        match &self.path_mtu_probe {
+            Some(path_mtu_probe) => path_mtu_probe.mss.min(mss),
+            None => mss,
+        }
+    }
+
+    /// Return the segment sizes the path MTU probing starts from and falls back to, which are
+    /// the sizes of a 1024 octets (IPv4) or 1280 octets (IPv6) packet and of a packet of the
+    /// minimum MTU, see RFC 4821 § 7.2.
+    fn path_mtu_probe_bounds(&self) -> (usize, usize) {
+        let (ip_header_len, base_mtu, min_mtu) = match self.tuple.unwrap().local.addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(_) => (
+                crate::wire::IPV4_HEADER_LEN,
+                1024,
+                crate::wire::IPV4_MIN_MTU,
+            ),
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(_) => (
+                crate::wire::IPV6_HEADER_LEN,
+                crate::wire::IPV6_MIN_MTU,
+                crate::wire::IPV6_MIN_MTU,
+            ),
+        };
+        let header_len = ip_header_len + TCP_HEADER_LEN + self.options_len();
+        (base_mtu - header_len, min_mtu - header_len)
+    }
+
+    /// Return the size of the path MTU probe to send now, if any. Probes are sent one at a time,
+    /// with new data, see RFC 4821 § 7.5.
+    fn path_mtu_probe_size(&self, cx: &Context, win_limit: usize) -> Option<usize> {
+        let path_mtu_probe = self.path_mtu_probe.as_ref()?;
+        if self.state != State::Established
+            || path_mtu_probe.probe.is_some()
+            || self.remote_last_seq < path_mtu_probe.high_seq
+        {
+            return None;
+        }
+
+        let size = path_mtu_probe.probe_size(self.path_mss(cx))?;
+        let in_flight = self.remote_last_seq - self.local_seq_no;
+        let unsent = self.tx_buffer.len() - in_flight;
+        let congestion_window = self.congestion_controller.inner().window();
+        (size <= unsent && size <= win_limit && in_flight + size <= congestion_window)
+            .then_some(size)
+    }
+
+    fn seq_to_transmit(&self, cx: &mut Context) -> bool {
         // The effective max segment size, taking into account our and remote's limits.
-        let effective_mss = local_mss.min(self.remote_mss);
+        let effective_mss = self.send_mss(cx);
 
         // Have we sent data that hasn't been ACKed yet?
         let data_in_flight = self.remote_last_seq != self.local_seq_no;
@@ -2107,7 +3071,17 @@ impl<'a> Socket<'a> {
         // * There's no data in flight
         // * We can send a full packet
         // * We have all the data we'll ever send (we're closing send)
-        if self.nagle && data_in_flight && !can_send_full && !want_fin {
+        // A tail loss probe is sent regardless of its size.
+        #[cfg(feature = "socket-tcp-rack")]
+        let want_probe = matches!(self.rack.probe, Some((seq, _)) if seq == self.remote_last_seq);
+        #[cfg(not(feature = "socket-tcp-rack"))]
+        let want_probe = false;
+        if self.nagle && data_in_flight && !can_send_full && !want_fin && !want_probe {
+            can_send = false;
+        }
+
+        // When pacing, we don't send data before the release time of the next segment.
+        if self.is_paced(cx.now()) {
             can_send = false;
         }
 
@@ -2170,6 +3144,19 @@ impl<'a> Socket<'a> {
             .inner_mut()
             .pre_transmit(cx.now());
 
+        if self.path_mtu_probing
+            && self.path_mtu_probe.is_none()
+            && self.state == State::Established
+        {
+            let (base_mss, _) = self.path_mtu_probe_bounds();
+            self.path_mtu_probe = Some(PathMtuProbe::new(base_mss, self.remote_last_seq));
+        }
+
+        #[cfg(feature = "socket-tcp-rack")]
+        if matches!(self.rack.reo_timer, Some(reo) if cx.now() >= reo) {
+            self.rack_detect_loss(cx.now());
+        }
+
         // Check if any state needs to be changed because of a timer.
         if self.timed_out(cx.now()) {
             // If a timeout expires, we should abort the connection.
@@ -2181,6 +3168,28 @@ impl<'a> Socket<'a> {
                 // If a retransmit timer expired, we should resend data starting at the last ACK.
                 net_debug!("retransmitting at t+{}", retransmit_delta);
 
+                let timeout = !matches!(self.timer, Timer::FastRetransmit);
+                let (base_mss, min_mss) = self.path_mtu_probe_bounds();
+                if let Some(path_mtu_probe) = &mut self.path_mtu_probe {
+                    path_mtu_probe.on_retransmit(timeout, base_mss, min_mss);
+                }
+
+                // After a timeout, the remote may have discarded the data it selectively
+                // acknowledged, so forget about it. Otherwise, recover the data sent so far
+                // by retransmitting only the holes in the scoreboard.
+                if timeout {
+                    self.sack_scoreboard = SackScoreboard::default();
+                    #[cfg(feature = "socket-tcp-rack")]
+                    self.rack.on_timeout();
+                } else {
+                    let recovery_point = self
+                        .sack_scoreboard
+                        .recovery_point
+                        .unwrap_or(self.remote_last_seq);
+                    self.sack_scoreboard.recovery_point =
+                        Some(recovery_point.max(self.remote_last_seq));
+                }
+
                 // Rewind "last sequence number sent", as if we never
                 // had sent them. This will cause all data in the queue
                 // to be sent again.
@@ -2199,9 +3208,15 @@ impl<'a> Socket<'a> {
                 self.congestion_controller
                     .inner_mut()
                     .on_retransmit(cx.now());
+            } else {
+                #[cfg(feature = "socket-tcp-rack")]
+                self.rack_probe(cx);
             }
         }
 
+        // Skip the data selectively acknowledged during a loss recovery.
+        self.remote_last_seq = self.sack_scoreboard.next_seq(self.remote_last_seq);
+
         // Decide whether we're sending a packet.
         if self.seq_to_transmit(cx) {
             // If we have data to transmit and it fits into partner's window, do it.
@@ -2253,6 +3268,7 @@ impl<'a> Socket<'a> {
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges: [None, None, None],
+            timestamp: None,
             payload: &[],
         };
 
@@ -2310,10 +3326,20 @@ impl<'a> Socket<'a> {
                 // Maximum size we're allowed to send. This can be limited by 3 factors:
                 // 1. remote window
                 // 2. MSS the remote is willing to accept, probably determined by their MTU
-                // 3. MSS we can send, determined by our MTU.
-                let size = win_limit
-                    .min(self.remote_mss)
-                    .min(cx.ip_mtu() - ip_repr.header_len() - TCP_HEADER_LEN);
+                // 3. MSS we can send, determined by the path MTU.
+                // A path MTU probe is larger than the other segments. During a loss recovery,
+                // a retransmission stops at the data selectively acknowledged.
+                let mut size = match self.path_mtu_probe_size(cx, win_limit) {
+                    Some(probe_size) => probe_size,
+                    None => win_limit.min(self.send_mss(cx)),
+                };
+                if let Some(sacked_seq) = self.sack_scoreboard.next_sacked(self.remote_last_seq) {
+                    size = size.min(sacked_seq - self.remote_last_seq);
+                }
+                // A paced segment waits for its release time, even alongside an acknowledgement.
+                if self.is_paced(cx.now()) {
+                    size = 0;
+                }
 
                 let offset = self.remote_last_seq - self.local_seq_no;
                 repr.payload = self.tx_buffer.get_allocated(offset, size);
@@ -2373,6 +3399,10 @@ impl<'a> Socket<'a> {
             tcp_trace!("sending {}", flags);
         }
 
+        if repr.control != TcpControl::Rst {
+            repr.timestamp = self.timestamp_repr(cx.now());
+        }
+
         if repr.control == TcpControl::Syn {
             // Fill the MSS option. See RFC 6691 for an explanation of this calculation.
             let max_segment_size = cx.ip_mtu() - ip_repr.header_len() - TCP_HEADER_LEN;
@@ -2413,6 +3443,9 @@ impl<'a> Socket<'a> {
 
         // We've sent a packet successfully, so we can update the internal state now.
         self.remote_last_seq = repr.seq_number + repr.segment_len();
+        if let Some(path_mtu_probe) = &mut self.path_mtu_probe {
+            path_mtu_probe.on_transmit(repr.seq_number, repr.payload.len());
+        }
         self.remote_last_ack = repr.ack_number;
         self.remote_last_win = repr.window_len;
 
@@ -2424,13 +3457,34 @@ impl<'a> Socket<'a> {
                 .post_transmit(cx.now(), repr.segment_len());
         }
 
+        if self.pacing && !repr.payload.is_empty() {
+            self.pacing_release = Some(cx.now() + self.pacing_interval(repr.payload.len()));
+        }
+
+        #[cfg(feature = "socket-tcp-rack")]
+        let mut rack_new_data = false;
+        #[cfg(feature = "socket-tcp-rack")]
+        if self.rack_tlp && repr.segment_len() > 0 && repr.control != TcpControl::Syn {
+            rack_new_data = match self.rack.segments.iter().flatten().last() {
+                Some(last) => repr.seq_number >= last.end,
+                None => true,
+            };
+            self.rack
+                .on_transmit(cx.now(), repr.seq_number, self.remote_last_seq);
+        }
+
         if !self.seq_to_transmit(cx) && repr.segment_len() > 0 {
-            // If we've transmitted all data we could (and there was something at all,
+            // If we've transmitted all data we could for now (and there was something at all,
             // data or flag, to transmit, not just an ACK), wind up the retransmit timer.
             self.timer
                 .set_for_retransmit(cx.now(), self.rtte.retransmission_timeout());
         }
 
+        #[cfg(feature = "socket-tcp-rack")]
+        if rack_new_data {
+            self.rack_arm_pto(cx);
+        }
+
         if self.state == State::Closed {
             // When aborting a connection, forget about it after sending a single RST packet.
             self.tuple = None;
@@ -2444,6 +3498,44 @@ impl<'a> Socket<'a> {
         Ok(())
     }
 
+    /// Return whether the next data segment waits for its release time.
+    fn is_paced(&self, now: Instant) -> bool {
+        matches!(self.pacing_release, Some(release) if now < release)
+    }
+
+    /// Return the time it takes to send `len` bytes at the pacing rate.
+    fn pacing_interval(&self, len: usize) -> Duration {
+        let controller = self.congestion_controller.inner();
+        let micros = match controller.pacing_rate() {
+            Some(rate) => len as u64 * 1_000_000 / rate.max(1),
+            None => {
+                let window = controller.window().min(self.remote_win_len).max(1);
+                len as u64 * self.rtte.rtt().total_micros() / window as u64
+            }
+        };
+        Duration::from_micros(micros)
+    }
+
+    /// Return when the next paced data segment is released, if there is data waiting for it.
+    fn pacing_poll_at(&self, now: Instant) -> PollAt {
+        let unsent = self.tx_buffer.len() > self.remote_last_seq - self.local_seq_no;
+        match self.pacing_release {
+            Some(release) if unsent && now < release => PollAt::Time(release),
+            _ => PollAt::Ingress,
+        }
+    }
+
+    /// Return when the RACK reordering timer or the probe timeout expires.
+    #[cfg(feature = "socket-tcp-rack")]
+    fn rack_poll_at(&self) -> PollAt {
+        self.rack.poll_at()
+    }
+
+    #[cfg(not(feature = "socket-tcp-rack"))]
+    fn rack_poll_at(&self) -> PollAt {
+        PollAt::Ingress
+    }
+
     #[allow(clippy::if_same_then_else)]
     pub(crate) fn poll_at(&self, cx: &mut Context) -> PollAt {
         // The logic here mirrors the beginning of dispatch() closely.
@@ -2478,10 +3570,16 @@ impl<'a> Socket<'a> {
             };
 
             // We wait for the earliest of our timers to fire.
-            *[self.timer.poll_at(), timeout_poll_at, delayed_ack_poll_at]
-                .iter()
-                .min()
-                .unwrap_or(&PollAt::Ingress)
+            *[
+                self.timer.poll_at(),
+                self.rack_poll_at(),
+                self.pacing_poll_at(cx.now()),
+                timeout_poll_at,
+                delayed_ack_poll_at,
+            ]
+            .iter()
+            .min()
+            .unwrap_or(&PollAt::Ingress)
         }
     }
 }
@@ -2581,6 +3679,7 @@ mod test {
         max_seg_size: None,
         sack_permitted: false,
         sack_ranges: [None, None, None],
+        timestamp: None,
         payload: &[],
     };
     const _RECV_IP_TEMPL: IpRepr = IpReprIpvX(IpvXRepr {
@@ -2601,6 +3700,7 @@ mod test {
         max_seg_size: None,
         sack_permitted: false,
         sack_ranges: [None, None, None],
+        timestamp: None,
         payload: &[],
     };
 
@@ -3761,6 +4861,36 @@ mod test {
         assert_eq!(s.remote_win_len, 42);
     }
 
+    #[test]
+    fn test_syn_sent_syn_ack_sack_permitted() {
+        let mut s = socket_syn_sent();
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 1),
+                max_seg_size: Some(BASE_MSS - 80),
+                sack_permitted: true,
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+        assert!(s.remote_has_sack);
+    }
+
     // =========================================================================================//
     // Tests for the ESTABLISHED state.
     // =========================================================================================//
@@ -6027,6 +7157,284 @@ mod test {
         recv_nothing!(s);
     }
 
+    // =========================================================================================//
+    // Tests for SACK-based loss recovery.
+    // =========================================================================================//
+
+    fn socket_sack() -> TestSocket {
+        let mut s = socket_established_with_buffer_sizes(64, 64);
+        s.set_congestion_control(CongestionControl::None);
+        s.remote_has_sack = true;
+        s.remote_mss = 6;
+        s.remote_win_len = 64;
+        s
+    }
+
+    fn send_sack(s: &mut TestSocket, ack_number: TcpSeqNumber, ranges: &[(usize, usize)]) {
+        let mut sack_ranges = [None; 3];
+        for (range, &(left, right)) in sack_ranges.iter_mut().zip(ranges) {
+            *range = Some((
+                (LOCAL_SEQ + 1 + left).0 as u32,
+                (LOCAL_SEQ + 1 + right).0 as u32,
+            ));
+        }
+        let repr = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(ack_number),
+            window_len: 64,
+            sack_ranges,
+            ..SEND_TEMPL
+        };
+        assert_eq!(send(s, s.cx.now(), &repr), None);
+    }
+
+    #[test]
+    fn test_sack_scoreboard() {
+        let seq = |n: usize| TcpSeqNumber(u32::MAX as i32 - 10) + n;
+        let mut scoreboard = SackScoreboard::default();
+
+        scoreboard.add(seq(20), seq(30));
+        scoreboard.add(seq(0), seq(10));
+        scoreboard.add(seq(10), seq(15));
+        assert_eq!(
+            scoreboard.ranges,
+            [Some((seq(0), seq(15))), Some((seq(20), seq(30))), None]
+        );
+        assert_eq!(scoreboard.sacked_len(), 25);
+
+        scoreboard.add(seq(70), seq(80));
+        scoreboard.add(seq(50), seq(60));
+        scoreboard.add(seq(40), seq(45));
+        assert_eq!(
+            scoreboard.ranges,
+            [
+                Some((seq(0), seq(15))),
+                Some((seq(20), seq(30))),
+                Some((seq(40), seq(45))),
+            ]
+        );
+
+        scoreboard.recovery_point = Some(seq(65));
+        assert_eq!(scoreboard.next_seq(seq(5)), seq(15));
+        assert_eq!(scoreboard.next_seq(seq(15)), seq(15));
+        assert_eq!(scoreboard.next_sacked(seq(15)), Some(seq(20)));
+        assert_eq!(scoreboard.next_seq(seq(55)), seq(65));
+
+        scoreboard.on_ack(seq(25));
+        assert_eq!(
+            scoreboard.ranges,
+            [Some((seq(25), seq(30))), Some((seq(40), seq(45))), None]
+        );
+        assert_eq!(scoreboard.recovery_point, Some(seq(65)));
+
+        scoreboard.on_ack(seq(65));
+        assert_eq!(scoreboard, SackScoreboard::default());
+    }
+
+    #[test]
+    fn test_sack_retransmit_hole() {
+        let mut s = socket_sack();
+        s.send_slice(b"xxxxxxyyyyyywwwwwwzzzzzz").unwrap();
+        assert_eq!(recv_segments(&mut s, 1000).len(), 4);
+
+        // The second segment is lost.
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[]);
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[(12, 18)]);
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[(12, 24)]);
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[(12, 24)]);
+
+        assert_eq!(recv_segments(&mut s, 1100), [(LOCAL_SEQ + 1 + 6, 6)]);
+
+        send_sack(&mut s, LOCAL_SEQ + 1 + 24, &[]);
+        assert_eq!(s.sack_scoreboard, SackScoreboard::default());
+        assert_eq!(s.tx_buffer.len(), 0);
+    }
+
+    #[test]
+    fn test_sack_retransmit_holes() {
+        let mut s = socket_sack();
+        s.send_slice(b"xxxxxxyyyyyywwwwwwzzzzzzvvvvvv").unwrap();
+        assert_eq!(recv_segments(&mut s, 1000).len(), 5);
+
+        // The second and fourth segments are lost.
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[]);
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[(12, 18)]);
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[(24, 30), (12, 18)]);
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[(24, 30), (12, 18)]);
+
+        assert_eq!(
+            recv_segments(&mut s, 1100),
+            [(LOCAL_SEQ + 1 + 6, 6), (LOCAL_SEQ + 1 + 18, 6)]
+        );
+
+        send_sack(&mut s, LOCAL_SEQ + 1 + 30, &[]);
+        assert_eq!(s.tx_buffer.len(), 0);
+    }
+
+    #[test]
+    fn test_sack_is_lost() {
+        let mut s = socket_sack();
+        s.send_slice(b"xxxxxxyyyyyywwwwwwzzzzzzvvvvvv").unwrap();
+        assert_eq!(recv_segments(&mut s, 1000).len(), 5);
+
+        // The first segment is lost. More than two segments are selectively acknowledged above
+        // it, so it's retransmitted without waiting for more duplicate acknowledgements.
+        send_sack(&mut s, LOCAL_SEQ + 1, &[(6, 18)]);
+        assert_ne!(s.timer, Timer::FastRetransmit);
+        send_sack(&mut s, LOCAL_SEQ + 1, &[(6, 24)]);
+
+        // The last segment isn't known to be lost.
+        assert_eq!(recv_segments(&mut s, 1100), [(LOCAL_SEQ + 1, 6)]);
+    }
+
+    #[test]
+    fn test_sack_ignore_invalid_ranges() {
+        let mut s = socket_sack();
+        s.send_slice(b"xxxxxxyyyyyy").unwrap();
+        assert_eq!(recv_segments(&mut s, 1000).len(), 2);
+
+        // A D-SACK range below the cumulative acknowledgement, and a range of data never sent.
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[(0, 6), (12, 18)]);
+        assert_eq!(s.sack_scoreboard, SackScoreboard::default());
+    }
+
+    #[test]
+    fn test_sack_timeout_clears_scoreboard() {
+        let mut s = socket_sack();
+        s.send_slice(b"xxxxxxyyyyyywwwwww").unwrap();
+        assert_eq!(recv_segments(&mut s, 1000).len(), 3);
+
+        send_sack(&mut s, LOCAL_SEQ + 1, &[(6, 12)]);
+        assert_ne!(s.sack_scoreboard, SackScoreboard::default());
+
+        // After a timeout, the data selectively acknowledged is sent again.
+        assert_eq!(recv_segments(&mut s, 5000)[0], (LOCAL_SEQ + 1, 6));
+        assert_eq!(s.sack_scoreboard, SackScoreboard::default());
+    }
+
+    // =========================================================================================//
+    // Tests for RACK-TLP.
+    // =========================================================================================//
+
+    #[cfg(feature = "socket-tcp-rack")]
+    fn socket_rack() -> TestSocket {
+        let mut s = socket_sack();
+        s.set_rack_tlp_enabled(true);
+        s
+    }
+
+    #[test]
+    #[cfg(feature = "socket-tcp-rack")]
+    fn test_rack_tlp_disabled() {
+        let mut s = socket_sack();
+        assert!(!s.rack_tlp_enabled());
+        s.send_slice(b"xxxxxxyyyyyywwwwww").unwrap();
+        assert_eq!(recv_segments(&mut s, 1000).len(), 3);
+
+        // Nothing is sent until the retransmission timeout.
+        assert_eq!(recv_segments(&mut s, 1600), []);
+        assert_eq!(recv_segments(&mut s, 1700)[0], (LOCAL_SEQ + 1, 6));
+    }
+
+    #[test]
+    #[cfg(feature = "socket-tcp-rack")]
+    fn test_rack_tlp_tail_loss() {
+        let mut s = socket_rack();
+        s.send_slice(b"xxxxxxyyyyyywwwwww").unwrap();
+        assert_eq!(recv_segments(&mut s, 1000).len(), 3);
+
+        // All the segments are lost, the last one is probed before the retransmission timeout.
+        assert_eq!(
+            s.socket.poll_at(&mut s.cx),
+            PollAt::Time(Instant::from_millis(1600))
+        );
+        assert_eq!(recv_segments(&mut s, 1599), []);
+        assert_eq!(recv_segments(&mut s, 1600), [(LOCAL_SEQ + 1 + 12, 6)]);
+        assert_eq!(recv_segments(&mut s, 1610), []);
+
+        // The probe is delivered, the segments sent before it are lost.
+        send_sack(&mut s, LOCAL_SEQ + 1, &[(12, 18)]);
+        assert_eq!(
+            recv_segments(&mut s, 1650),
+            [(LOCAL_SEQ + 1, 6), (LOCAL_SEQ + 1 + 6, 6)]
+        );
+
+        send_sack(&mut s, LOCAL_SEQ + 1 + 18, &[]);
+        assert_eq!(s.tx_buffer.len(), 0);
+        assert_eq!(s.rack.probe, None);
+        assert_eq!(s.socket.poll_at(&mut s.cx), PollAt::Ingress);
+    }
+
+    #[test]
+    #[cfg(feature = "socket-tcp-rack")]
+    fn test_rack_tlp_probe_dsack() {
+        let mut s = socket_rack();
+        s.send_slice(b"aaaaaa").unwrap();
+        assert_eq!(recv_segments(&mut s, 0).len(), 1);
+        s.cx.set_now(Instant::from_millis(100));
+        send_ack(&mut s, LOCAL_SEQ + 1 + 6);
+
+        // A single segment waits for a delayed acknowledgement too.
+        s.send_slice(b"xxxxxx").unwrap();
+        assert_eq!(recv_segments(&mut s, 1000).len(), 1);
+        assert_eq!(recv_segments(&mut s, 1749), []);
+        assert_eq!(recv_segments(&mut s, 1750), [(LOCAL_SEQ + 1 + 6, 6)]);
+
+        // The original segment and the probe are delivered.
+        send_sack(&mut s, LOCAL_SEQ + 1 + 12, &[(6, 12)]);
+        assert_eq!(s.rack.probe, None);
+        assert_eq!(s.tx_buffer.len(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "socket-tcp-rack")]
+    fn test_rack_reordering_window() {
+        let mut s = socket_rack();
+        s.send_slice(b"aaaaaa").unwrap();
+        assert_eq!(recv_segments(&mut s, 0).len(), 1);
+        s.cx.set_now(Instant::from_millis(100));
+        send_ack(&mut s, LOCAL_SEQ + 1 + 6);
+
+        s.send_slice(b"xxxxxxyyyyyywwwwww").unwrap();
+        assert_eq!(recv_segments(&mut s, 1000).len(), 3);
+
+        // The last segment is delivered first. The others are lost only after a quarter of the
+        // minimum round-trip time.
+        s.cx.set_now(Instant::from_millis(1100));
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[(18, 24)]);
+        assert_eq!(
+            s.socket.poll_at(&mut s.cx),
+            PollAt::Time(Instant::from_millis(1125))
+        );
+        assert_eq!(recv_segments(&mut s, 1124), []);
+        assert_eq!(
+            recv_segments(&mut s, 1125),
+            [(LOCAL_SEQ + 1 + 6, 6), (LOCAL_SEQ + 1 + 12, 6)]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "socket-tcp-rack")]
+    fn test_rack_reordered() {
+        let mut s = socket_rack();
+        s.send_slice(b"aaaaaa").unwrap();
+        assert_eq!(recv_segments(&mut s, 0).len(), 1);
+        s.cx.set_now(Instant::from_millis(100));
+        send_ack(&mut s, LOCAL_SEQ + 1 + 6);
+
+        s.send_slice(b"xxxxxxyyyyyywwwwww").unwrap();
+        assert_eq!(recv_segments(&mut s, 1000).len(), 3);
+
+        s.cx.set_now(Instant::from_millis(1100));
+        send_sack(&mut s, LOCAL_SEQ + 1 + 6, &[(18, 24)]);
+
+        // The other segments are delivered within the reordering window.
+        s.cx.set_now(Instant::from_millis(1110));
+        send_sack(&mut s, LOCAL_SEQ + 1 + 24, &[]);
+        assert_eq!(s.rack.reo_timer, None);
+        assert_eq!(recv_segments(&mut s, 1125), []);
+    }
+
     // =========================================================================================//
     // Tests for window management.
     // =========================================================================================//
@@ -7309,47 +8717,437 @@ mod test {
     }
 
     // =========================================================================================//
-    // Tests for packet filtering.
+    // Tests for timestamps
     // =========================================================================================//
 
-    #[test]
-    fn test_doesnt_accept_wrong_port() {
-        let mut s = socket_established();
-        s.rx_buffer = SocketBuffer::new(vec![0; 6]);
-        s.assembler = Assembler::new();
-
-        let tcp_repr = TcpRepr {
-            seq_number: REMOTE_SEQ + 1,
-            ack_number: Some(LOCAL_SEQ + 1),
-            dst_port: LOCAL_PORT + 1,
-            ..SEND_TEMPL
-        };
-        assert!(!s.socket.accepts(&mut s.cx, &SEND_IP_TEMPL, &tcp_repr));
+    const REMOTE_TSVAL: u32 = 500;
 
-        let tcp_repr = TcpRepr {
-            seq_number: REMOTE_SEQ + 1,
-            ack_number: Some(LOCAL_SEQ + 1),
-            src_port: REMOTE_PORT + 1,
-            ..SEND_TEMPL
-        };
-        assert!(!s.socket.accepts(&mut s.cx, &SEND_IP_TEMPL, &tcp_repr));
+    fn socket_established_timestamps() -> TestSocket {
+        let mut s = socket_established_with_buffer_sizes(4096, 64);
+        s.timestamps = true;
+        s.ts_recent = Some((REMOTE_TSVAL, Instant::ZERO));
+        s
     }
 
     #[test]
-    fn test_doesnt_accept_wrong_ip() {
-        let mut s = socket_established();
+    fn test_timestamps_syn_sent() {
+        let mut s = socket_syn_sent();
+        s.set_timestamps_enabled(true);
+        recv!(
+            s,
+            [TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: LOCAL_SEQ,
+                ack_number: None,
+                max_seg_size: Some(BASE_MSS),
+                window_scale: Some(0),
+                sack_permitted: true,
+                timestamp: Some(TcpTimestampRepr::new(0, 0)),
+                ..RECV_TEMPL
+            }]
+        );
+        send!(
+            s,
+            time 50,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                timestamp: Some(TcpTimestampRepr::new(REMOTE_TSVAL, 0)),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+        assert_eq!(s.ts_recent, Some((REMOTE_TSVAL, Instant::from_millis(50))));
 
-        let tcp_repr = TcpRepr {
-            seq_number: REMOTE_SEQ + 1,
-            ack_number: Some(LOCAL_SEQ + 1),
-            payload: &b"abcdef"[..],
-            ..SEND_TEMPL
-        };
+        // The SYN|ACK echoes the timestamp of the SYN, which is a sample of the RTT.
+        let mut rtte = RttEstimator::default();
+        rtte.sample(50);
+        assert_eq!(s.rtte.rtt, rtte.rtt);
 
-        let ip_repr = IpReprIpvX(IpvXRepr {
-            src_addr: REMOTE_ADDR,
-            dst_addr: LOCAL_ADDR,
-            next_header: IpProtocol::Tcp,
+        recv!(
+            s,
+            time 50,
+            Ok(TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                timestamp: Some(TcpTimestampRepr::new(50, REMOTE_TSVAL)),
+                ..RECV_TEMPL
+            })
+        );
+    }
+
+    #[test]
+    fn test_timestamps_syn_sent_not_negotiated() {
+        let mut s = socket_syn_sent();
+        s.set_timestamps_enabled(true);
+        s.socket
+            .dispatch(&mut s.cx, |_, _| Ok::<(), ()>(()))
+            .unwrap();
+        send!(
+            s,
+            TcpRepr {
+                control: TcpControl::Syn,
+                seq_number: REMOTE_SEQ,
+                ack_number: Some(LOCAL_SEQ + 1),
+                max_seg_size: Some(BASE_MSS),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.state, State::Established);
+        assert_eq!(s.ts_recent, None);
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    #[test]
+    fn test_timestamps_listen() {
+        for (enabled, timestamp, reply_timestamp) in [
+            (true, None, None),
+            (false, Some(TcpTimestampRepr::new(REMOTE_TSVAL, 0)), None),
+            (
+                true,
+                Some(TcpTimestampRepr::new(REMOTE_TSVAL, 0)),
+                Some(TcpTimestampRepr::new(0, REMOTE_TSVAL)),
+            ),
+        ] {
+            let mut s = socket_listen();
+            s.set_timestamps_enabled(enabled);
+            send!(
+                s,
+                TcpRepr {
+                    control: TcpControl::Syn,
+                    seq_number: REMOTE_SEQ,
+                    ack_number: None,
+                    timestamp,
+                    ..SEND_TEMPL
+                }
+            );
+            recv!(
+                s,
+                [TcpRepr {
+                    control: TcpControl::Syn,
+                    seq_number: LOCAL_SEQ,
+                    ack_number: Some(REMOTE_SEQ + 1),
+                    max_seg_size: Some(BASE_MSS),
+                    timestamp: reply_timestamp,
+                    ..RECV_TEMPL
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn test_timestamps_mss() {
+        let mut s = socket_established_timestamps();
+        s.remote_mss = usize::from(BASE_MSS);
+        s.remote_win_len = 4096;
+
+        // The segments leave room for the option in every segment.
+        let mss = usize::from(BASE_MSS) - 12;
+        s.send_slice(&[0xaa; 4096][..mss * 2]).unwrap();
+        assert_eq!(
+            recv_segments(&mut s, 0),
+            [(LOCAL_SEQ + 1, mss), (LOCAL_SEQ + 1 + mss, mss)]
+        );
+    }
+
+    #[test]
+    fn test_timestamps_rtt_retransmit() {
+        let mut s = socket_established_timestamps();
+        s.send_slice(b"abcdef").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload: &b"abcdef"[..],
+            timestamp: Some(TcpTimestampRepr::new(0, REMOTE_TSVAL)),
+            ..RECV_TEMPL
+        }));
+        recv!(s, time 1000, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload: &b"abcdef"[..],
+            timestamp: Some(TcpTimestampRepr::new(1000, REMOTE_TSVAL)),
+            ..RECV_TEMPL
+        }));
+
+        // The acknowledgement echoes the timestamp of the retransmission, so it is a sample of
+        // the RTT.
+        send!(s, time 1100, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            timestamp: Some(TcpTimestampRepr::new(REMOTE_TSVAL + 1, 1000)),
+            ..SEND_TEMPL
+        });
+        let mut rtte = RttEstimator::default();
+        rtte.sample(100);
+        assert_eq!(s.rtte.rtt, rtte.rtt);
+        assert_eq!(
+            s.ts_recent,
+            Some((REMOTE_TSVAL + 1, Instant::from_millis(1100)))
+        );
+    }
+
+    #[test]
+    fn test_timestamps_not_negotiated_ignored() {
+        let mut s = socket_established_with_buffer_sizes(4096, 64);
+        s.set_timestamps_enabled(true);
+        s.send_slice(b"abcdef").unwrap();
+        recv!(s, time 0, Ok(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            payload: &b"abcdef"[..],
+            ..RECV_TEMPL
+        }));
+
+        // The echoed timestamp of an option which was not negotiated is not a sample, the
+        // segment is timed instead.
+        send!(s, time 100, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1 + 6),
+            timestamp: Some(TcpTimestampRepr::new(REMOTE_TSVAL, 90)),
+            ..SEND_TEMPL
+        });
+        let mut rtte = RttEstimator::default();
+        rtte.sample(100);
+        assert_eq!(s.rtte.rtt, rtte.rtt);
+        assert_eq!(s.ts_recent, None);
+    }
+
+    #[test]
+    fn test_timestamps_paws() {
+        let mut s = socket_established_timestamps();
+
+        // A segment with an older timestamp is dropped.
+        send!(s, time 0, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            payload: &b"abcdef"[..],
+            timestamp: Some(TcpTimestampRepr::new(REMOTE_TSVAL - 1, 0)),
+            ..SEND_TEMPL
+        }, Some(TcpRepr {
+            seq_number: LOCAL_SEQ + 1,
+            ack_number: Some(REMOTE_SEQ + 1),
+            timestamp: Some(TcpTimestampRepr::new(0, REMOTE_TSVAL)),
+            ..RECV_TEMPL
+        }));
+        assert_eq!(s.rx_buffer.len(), 0);
+
+        // A segment without a timestamp is dropped.
+        send!(s, time 0, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            payload: &b"abcdef"[..],
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.rx_buffer.len(), 0);
+
+        // A segment with a newer timestamp is accepted.
+        send!(s, time 0, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            payload: &b"abcdef"[..],
+            timestamp: Some(TcpTimestampRepr::new(REMOTE_TSVAL + 1, 0)),
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.rx_buffer.len(), 6);
+        assert_eq!(s.ts_recent, Some((REMOTE_TSVAL + 1, Instant::ZERO)));
+    }
+
+    #[test]
+    fn test_timestamps_paws_idle() {
+        let mut s = socket_established_timestamps();
+
+        // After a long idle period, the last timestamp received is not compared with anymore.
+        let now = (Instant::ZERO + PAWS_IDLE).total_millis();
+        send!(s, time now, TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            payload: &b"abcdef"[..],
+            timestamp: Some(TcpTimestampRepr::new(REMOTE_TSVAL - 1, 0)),
+            ..SEND_TEMPL
+        });
+        assert_eq!(s.rx_buffer.len(), 6);
+        assert_eq!(
+            s.ts_recent,
+            Some((REMOTE_TSVAL - 1, Instant::from_millis(now)))
+        );
+    }
+
+    // =========================================================================================//
+    // Tests for path MTU discovery
+    // =========================================================================================//
+
+    fn socket_path_mtu() -> TestSocket {
+        let mut s = socket_established_with_buffer_sizes(8192, 64);
+        s.set_congestion_control(CongestionControl::None);
+        s.remote_mss = usize::from(BASE_MSS);
+        s.remote_win_len = 8192;
+        s
+    }
+
+    fn recv_segments(s: &mut TestSocket, timestamp: i64) -> Vec<(TcpSeqNumber, usize)> {
+        s.cx.set_now(Instant::from_millis(timestamp));
+
+        let mut segments = vec![];
+        loop {
+            let mut segment = None;
+            let result: Result<(), ()> = s.socket.dispatch(&mut s.cx, |_, (_, repr)| {
+                segment = Some((repr.seq_number, repr.payload.len()));
+                Ok(())
+            });
+            assert_eq!(result, Ok(()));
+            match segment {
+                Some(segment) => segments.push(segment),
+                None => return segments,
+            }
+        }
+    }
+
+    fn send_ack(s: &mut TestSocket, ack_number: TcpSeqNumber) {
+        let repr = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(ack_number),
+            window_len: 8192,
+            ..SEND_TEMPL
+        };
+        assert_eq!(send(s, s.cx.now(), &repr), None);
+    }
+
+    #[test]
+    fn test_path_mtu_reduced() {
+        let mut s = socket_path_mtu();
+        s.cx.path_mtu_reduced(REMOTE_ADDR.into(), 1200);
+        let mss = usize::from(BASE_MSS) - 300;
+
+        s.send_slice(&[0xaa; 4096][..mss * 2]).unwrap();
+        assert_eq!(
+            recv_segments(&mut s, 0),
+            [(LOCAL_SEQ + 1, mss), (LOCAL_SEQ + 1 + mss, mss)]
+        );
+    }
+
+    #[test]
+    fn test_path_mtu_probe_acked() {
+        let mut s = socket_path_mtu();
+        s.set_path_mtu_probing(true);
+        let (base_mss, _) = s.path_mtu_probe_bounds();
+        let mss = usize::from(BASE_MSS);
+
+        // The largest segment is probed first, followed by segments of the base size.
+        s.send_slice(&[0xaa; 4096][..mss + base_mss]).unwrap();
+        assert_eq!(
+            recv_segments(&mut s, 0),
+            [(LOCAL_SEQ + 1, mss), (LOCAL_SEQ + 1 + mss, base_mss)]
+        );
+
+        // Once the probe is acknowledged, the segments are of the largest size.
+        send_ack(&mut s, LOCAL_SEQ + 1 + mss);
+        s.send_slice(&[0xaa; 4096][..mss * 2]).unwrap();
+        let seq = LOCAL_SEQ + 1 + mss + base_mss;
+        assert_eq!(recv_segments(&mut s, 0), [(seq, mss), (seq + mss, mss)]);
+    }
+
+    #[test]
+    fn test_path_mtu_probe_lost() {
+        let mut s = socket_path_mtu();
+        s.set_path_mtu_probing(true);
+        let (base_mss, _) = s.path_mtu_probe_bounds();
+        let mss = usize::from(BASE_MSS);
+
+        s.send_slice(&[0xaa; 4096][..mss]).unwrap();
+        assert_eq!(recv_segments(&mut s, 0), [(LOCAL_SEQ + 1, mss)]);
+
+        // The data of the lost probe is retransmitted in segments of the base size.
+        assert_eq!(recv_segments(&mut s, 1000), [(LOCAL_SEQ + 1, base_mss)]);
+        send_ack(&mut s, LOCAL_SEQ + 1 + base_mss);
+        assert_eq!(
+            recv_segments(&mut s, 1000),
+            [(LOCAL_SEQ + 1 + base_mss, mss - base_mss)]
+        );
+        send_ack(&mut s, LOCAL_SEQ + 1 + mss);
+
+        // The next probe is halfway between the base size and the size of the lost probe.
+        s.send_slice(&[0xaa; 4096][..mss]).unwrap();
+        assert_eq!(
+            recv_segments(&mut s, 1000)[0],
+            (LOCAL_SEQ + 1 + mss, (base_mss + mss) / 2)
+        );
+    }
+
+    #[test]
+    fn test_path_mtu_black_hole() {
+        let mut s = socket_path_mtu();
+        s.set_path_mtu_probing(true);
+        let (base_mss, _) = s.path_mtu_probe_bounds();
+        let mss = usize::from(BASE_MSS);
+
+        // The probe is acknowledged.
+        s.send_slice(&[0xaa; 4096][..mss]).unwrap();
+        assert_eq!(recv_segments(&mut s, 0), [(LOCAL_SEQ + 1, mss)]);
+        send_ack(&mut s, LOCAL_SEQ + 1 + mss);
+
+        // Then the path MTU drops, and the segments are lost.
+        s.send_slice(&[0xaa; 4096][..mss]).unwrap();
+        assert_eq!(recv_segments(&mut s, 0), [(LOCAL_SEQ + 1 + mss, mss)]);
+        assert_eq!(recv_segments(&mut s, 1000), [(LOCAL_SEQ + 1 + mss, mss)]);
+
+        // After repeated timeouts, the segments are of the base size.
+        assert_eq!(
+            recv_segments(&mut s, 3000)[0],
+            (LOCAL_SEQ + 1 + mss, base_mss)
+        );
+    }
+
+    // =========================================================================================//
+    // Tests for packet filtering.
+    // =========================================================================================//
+
+    #[test]
+    fn test_doesnt_accept_wrong_port() {
+        let mut s = socket_established();
+        s.rx_buffer = SocketBuffer::new(vec![0; 6]);
+        s.assembler = Assembler::new();
+
+        let tcp_repr = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            dst_port: LOCAL_PORT + 1,
+            ..SEND_TEMPL
+        };
+        assert!(!s.socket.accepts(&mut s.cx, &SEND_IP_TEMPL, &tcp_repr));
+
+        let tcp_repr = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            src_port: REMOTE_PORT + 1,
+            ..SEND_TEMPL
+        };
+        assert!(!s.socket.accepts(&mut s.cx, &SEND_IP_TEMPL, &tcp_repr));
+    }
+
+    #[test]
+    fn test_doesnt_accept_wrong_ip() {
+        let mut s = socket_established();
+
+        let tcp_repr = TcpRepr {
+            seq_number: REMOTE_SEQ + 1,
+            ack_number: Some(LOCAL_SEQ + 1),
+            payload: &b"abcdef"[..],
+            ..SEND_TEMPL
+        };
+
+        let ip_repr = IpReprIpvX(IpvXRepr {
+            src_addr: REMOTE_ADDR,
+            dst_addr: LOCAL_ADDR,
+            next_header: IpProtocol::Tcp,
             payload_len: tcp_repr.buffer_len(),
             hop_limit: 64,
         });
@@ -7433,7 +9231,197 @@ mod test {
             assert_eq!(s.congestion_control(), CongestionControl::Cubic);
         }
 
+        #[cfg(feature = "socket-tcp-bbr")]
+        {
+            s.set_congestion_control(CongestionControl::Bbr);
+            assert_eq!(s.congestion_control(), CongestionControl::Bbr);
+        }
+
         s.set_congestion_control(CongestionControl::None);
         assert_eq!(s.congestion_control(), CongestionControl::None);
     }
+
+    /// A controller allowing a fixed amount of data in flight.
+    #[derive(Debug)]
+    struct TestController {
+        limit: usize,
+        in_flight: usize,
+    }
+
+    impl Controller for TestController {
+        fn window(&self) -> usize {
+            self.limit - self.in_flight
+        }
+
+        fn on_ack(&mut self, _now: Instant, len: usize, rtt: &RttEstimator) {
+            assert!(rtt.latest_rtt().is_some());
+            self.in_flight -= len;
+        }
+
+        fn post_transmit(&mut self, _now: Instant, len: usize) {
+            self.in_flight += len;
+        }
+    }
+
+    #[test]
+    fn test_custom_congestion_controller() {
+        let mut s = socket_established();
+        let congestion_control = s.congestion_control();
+        s.set_congestion_control(CongestionControl::Custom);
+        assert_eq!(s.congestion_control(), congestion_control);
+
+        s.set_congestion_controller(Box::new(TestController {
+            limit: 6,
+            in_flight: 0,
+        }) as Box<dyn Controller>);
+        assert_eq!(s.congestion_control(), CongestionControl::Custom);
+        s.set_congestion_control(CongestionControl::Custom);
+        assert_eq!(s.congestion_control(), CongestionControl::Custom);
+
+        // Nothing is sent while the window of the controller is full.
+        s.send_slice(b"abcdef").unwrap();
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 1,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"abcdef"[..],
+                ..RECV_TEMPL
+            }]
+        );
+        assert_eq!(s.congestion_controller().window(), 0);
+        s.send_slice(b"gh").unwrap();
+        recv_nothing!(s);
+
+        // The acknowledgement opens the window.
+        send!(
+            s,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 7),
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(s.congestion_controller().window(), 6);
+        recv!(
+            s,
+            [TcpRepr {
+                seq_number: LOCAL_SEQ + 7,
+                ack_number: Some(REMOTE_SEQ + 1),
+                payload: &b"gh"[..],
+                ..RECV_TEMPL
+            }]
+        );
+    }
+
+    // =========================================================================================//
+    // Tests for pacing.
+    // =========================================================================================//
+
+    fn socket_paced() -> TestSocket {
+        let mut s = socket_sack();
+        s.remote_win_len = 60;
+        s.set_pacing_enabled(true);
+        s
+    }
+
+    #[test]
+    fn test_pacing_disabled() {
+        let mut s = socket_sack();
+        assert!(!s.pacing_enabled());
+        s.send_slice(b"xxxxxxyyyyyywwwwww").unwrap();
+        assert_eq!(recv_segments(&mut s, 0).len(), 3);
+    }
+
+    #[test]
+    fn test_pacing_window() {
+        let mut s = socket_paced();
+        s.send_slice(b"xxxxxxyyyyyywwwwww").unwrap();
+
+        // The window of 60 bytes is sent in the round-trip time of 300ms, 6 bytes every 30ms.
+        assert_eq!(recv_segments(&mut s, 0), [(LOCAL_SEQ + 1, 6)]);
+        assert_eq!(
+            s.socket.poll_at(&mut s.cx),
+            PollAt::Time(Instant::from_millis(30))
+        );
+        assert_eq!(recv_segments(&mut s, 29), []);
+        assert_eq!(recv_segments(&mut s, 30), [(LOCAL_SEQ + 1 + 6, 6)]);
+        assert_eq!(recv_segments(&mut s, 60), [(LOCAL_SEQ + 1 + 12, 6)]);
+
+        // Nothing is left to send, the socket waits for the acknowledgement.
+        assert_eq!(s.pacing_poll_at(s.cx.now()), PollAt::Ingress);
+    }
+
+    #[test]
+    fn test_pacing_window_limited() {
+        let mut s = socket_paced();
+        s.remote_win_len = 12;
+        s.send_slice(b"xxxxxxyyyyyywwwwww").unwrap();
+        assert_eq!(recv_segments(&mut s, 0), [(LOCAL_SEQ + 1, 6)]);
+        assert_eq!(recv_segments(&mut s, 150), [(LOCAL_SEQ + 1 + 6, 6)]);
+
+        // The window is full once the release time has passed, the socket waits for the
+        // acknowledgement instead of polling at the release time.
+        assert_eq!(recv_segments(&mut s, 500), []);
+        assert_eq!(s.pacing_poll_at(s.cx.now()), PollAt::Ingress);
+        match s.socket.poll_at(&mut s.cx) {
+            PollAt::Time(time) => assert!(time > Instant::from_millis(500)),
+            poll_at => assert_eq!(poll_at, PollAt::Ingress),
+        }
+    }
+
+    #[test]
+    fn test_pacing_ack_without_data() {
+        let mut s = socket_paced();
+        s.send_slice(b"xxxxxxyyyyyy").unwrap();
+        assert_eq!(recv_segments(&mut s, 0), [(LOCAL_SEQ + 1, 6)]);
+
+        // Received data is acknowledged at once, without the paced data.
+        send!(
+            s,
+            time 10,
+            TcpRepr {
+                seq_number: REMOTE_SEQ + 1,
+                ack_number: Some(LOCAL_SEQ + 1),
+                window_len: 60,
+                payload: &b"abcdef"[..],
+                ..SEND_TEMPL
+            }
+        );
+        assert_eq!(recv_segments(&mut s, 10), [(LOCAL_SEQ + 1 + 6, 0)]);
+        assert_eq!(recv_segments(&mut s, 30), [(LOCAL_SEQ + 1 + 6, 6)]);
+    }
+
+    /// A controller pacing the segments at a fixed rate.
+    #[derive(Debug)]
+    struct PacingController {
+        rate: u64,
+    }
+
+    impl Controller for PacingController {
+        fn window(&self) -> usize {
+            usize::MAX
+        }
+
+        fn pacing_rate(&self) -> Option<u64> {
+            Some(self.rate)
+        }
+    }
+
+    #[test]
+    fn test_pacing_controller_rate() {
+        let mut s = socket_paced();
+        s.set_congestion_controller(
+            Box::new(PacingController { rate: 1000 }) as Box<dyn Controller>
+        );
+        s.send_slice(b"xxxxxxyyyyyy").unwrap();
+
+        // 6 bytes are sent every 6ms at 1000 bytes per second.
+        assert_eq!(recv_segments(&mut s, 0), [(LOCAL_SEQ + 1, 6)]);
+        assert_eq!(
+            s.socket.poll_at(&mut s.cx),
+            PollAt::Time(Instant::from_millis(6))
+        );
+        assert_eq!(recv_segments(&mut s, 6), [(LOCAL_SEQ + 1 + 6, 6)]);
+    }
 }
@@ -1,3 +1,7 @@
+use core::fmt;
+
+use managed::Managed;
+
 use crate::time::Instant;
 
 use super::RttEstimator;
@@ -10,31 +14,57 @@ pub(super) mod cubic;
 #[cfg(feature = "socket-tcp-reno")]
 pub(super) mod reno;
 
+#[cfg(feature = "socket-tcp-bbr")]
+pub(super) mod bbr;
+
+/// A congestion control algorithm, limiting the amount of data a [Socket] has in flight.
+///
+/// The socket calls the hooks of its controller as it sends segments and receives
+/// acknowledgements. Only [window](#tymethod.window) has to be implemented, the other hooks do
+/// nothing by default.
+///
+/// A controller is installed with [Socket::set_congestion_controller].
+///
+/// [Socket]: super::Socket
+/// [Socket::set_congestion_controller]: super::Socket::set_congestion_controller
 #[allow(unused_variables)]
-pub(super) trait Controller {
+pub trait Controller: fmt::Debug {
     /// Returns the number of bytes that can be sent.
     fn window(&self) -> usize;
 
     /// Set the remote window size.
     fn set_remote_window(&mut self, remote_window: usize) {}
 
+    /// Called when `len` bytes of data are acknowledged, with the round-trip time estimated by
+    /// the socket.
     fn on_ack(&mut self, now: Instant, len: usize, rtt: &RttEstimator) {}
 
+    /// Called when a segment is retransmitted, after a retransmission timeout or once it is
+    /// deemed lost.
     fn on_retransmit(&mut self, now: Instant) {}
 
+    /// Called when a duplicate acknowledgement is received.
     fn on_duplicate_ack(&mut self, now: Instant) {}
 
+    /// Called every time the socket may transmit, before the window is read.
     fn pre_transmit(&mut self, now: Instant) {}
 
+    /// Called when a segment with `len` bytes in sequence space is transmitted.
     fn post_transmit(&mut self, now: Instant, len: usize) {}
 
     /// Set the maximum segment size.
     fn set_mss(&mut self, mss: usize) {}
+
+    /// Return the rate at which a socket pacing its segments sends them, in bytes per second.
+    ///
+    /// By default, the socket sends the window in a round-trip time.
+    fn pacing_rate(&self) -> Option<u64> {
+        None
+    }
 }
 
 #[derive(Debug)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub(super) enum AnyController {
+pub(super) enum AnyController<'a> {
     None(no_control::NoControl),
 
     #[cfg(feature = "socket-tcp-reno")]
@@ -42,15 +72,21 @@ pub(super) enum AnyController {
 
     #[cfg(feature = "socket-tcp-cubic")]
     Cubic(cubic::Cubic),
+
+    #[cfg(feature = "socket-tcp-bbr")]
+    Bbr(bbr::Bbr),
+
+    Custom(Managed<'a, dyn Controller + 'a>),
 }
 
-impl AnyController {
+impl<'a> AnyController<'a> {
     /// Create a new congestion controller.
     /// `AnyController::new()` selects the best congestion controller based on the features.
     ///
     /// - If `socket-tcp-cubic` feature is enabled, it will use `Cubic`.
+    /// - If `socket-tcp-bbr` feature is enabled, it will use `Bbr`.
     /// - If `socket-tcp-reno` feature is enabled, it will use `Reno`.
-    /// - If both `socket-tcp-cubic` and `socket-tcp-reno` features are enabled, it will use `Cubic`.
+    /// - If several of these features are enabled, it will use `Cubic`, then `Bbr`, then `Reno`.
     ///    - `Cubic` is more efficient regarding throughput.
     ///    - `Reno` is more conservative and is suitable for low-power devices.
     /// - If no congestion controller is available, it will use `NoControl`.
@@ -65,6 +101,11 @@ impl AnyController {
             return AnyController::Cubic(cubic::Cubic::new());
         }
 
+        #[cfg(feature = "socket-tcp-bbr")]
+        {
+            return AnyController::Bbr(bbr::Bbr::new());
+        }
+
         #[cfg(feature = "socket-tcp-reno")]
         {
             return AnyController::Reno(reno::Reno::new());
@@ -83,6 +124,11 @@ impl AnyController {
 
             #[cfg(feature = "socket-tcp-cubic")]
             AnyController::Cubic(c) => c,
+
+            #[cfg(feature = "socket-tcp-bbr")]
+            AnyController::Bbr(b) => b,
+
+            AnyController::Custom(c) => &mut **c,
         }
     }
 
@@ -96,6 +142,11 @@ impl AnyController {
 
             #[cfg(feature = "socket-tcp-cubic")]
             AnyController::Cubic(c) => c,
+
+            #[cfg(feature = "socket-tcp-bbr")]
+            AnyController::Bbr(b) => b,
+
+            AnyController::Custom(c) => &**c,
         }
     }
 }
@@ -0,0 +1,443 @@
+use crate::socket::tcp::RttEstimator;
+use crate::time::{Duration, Instant};
+
+use super::Controller;
+
+// Constants for the BBR congestion control algorithm.
+// See "BBR: Congestion-Based Congestion Control", Cardwell et al., 2016,
+// and draft-cardwell-iccrg-bbr-congestion-control-00.
+
+/// Gains are fixed-point numbers, `BBR_UNIT` is a gain of 1.
+const BBR_UNIT: u32 = 256;
+/// 2/ln(2), the smallest gain doubling the sending rate every round trip in Startup.
+const HIGH_GAIN: u32 = BBR_UNIT * 2885 / 1000;
+/// The inverse of the Startup gain, draining the queue built in Startup in a round trip.
+const DRAIN_GAIN: u32 = BBR_UNIT * 1000 / 2885;
+/// The gains cycled through in ProbeBW: probe for more bandwidth, drain the queue it built,
+/// then cruise at the estimated bandwidth.
+const PACING_GAIN_CYCLE: [u32; 8] = [
+    BBR_UNIT * 5 / 4,
+    BBR_UNIT * 3 / 4,
+    BBR_UNIT,
+    BBR_UNIT,
+    BBR_UNIT,
+    BBR_UNIT,
+    BBR_UNIT,
+    BBR_UNIT,
+];
+/// Number of round trips over which the maximum bandwidth is filtered.
+const BW_FILTER_LEN: usize = 10;
+/// The pipe is full once the bandwidth didn't grow by 25% in 3 round trips.
+const FULL_BW_THRESH: u32 = BBR_UNIT * 5 / 4;
+const FULL_BW_COUNT: u8 = 3;
+/// Time after which the minimum round-trip time is probed again.
+const MIN_RTT_EXPIRY: Duration = Duration::from_secs(10);
+/// Minimum time spent in ProbeRTT.
+const PROBE_RTT_DURATION: Duration = Duration::from_millis(200);
+/// The congestion window never goes below this many segments.
+const MIN_PIPE_SEGMENTS: usize = 4;
+/// Segments added to the estimated bandwidth-delay product, to keep the pipe full with
+/// delayed and stretched acknowledgements.
+const ACK_QUANTA_SEGMENTS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Mode {
+    /// Grow the sending rate exponentially until the bandwidth stops increasing.
+    Startup,
+    /// Drain the queue built in Startup.
+    Drain,
+    /// Send at the estimated bandwidth, probing for more periodically.
+    ProbeBw,
+    /// Send a few segments only, so that the queue empties and the minimum round-trip time is
+    /// measured again.
+    ProbeRtt,
+}
+
+/// A BBR congestion controller, version 1.
+///
+/// The bottleneck bandwidth is the maximum delivery rate measured over the last 10 round
+/// trips, and the propagation delay is the minimum round-trip time sampled over the last
+/// 10 seconds. The congestion window follows their product, scaled by the gain of the current
+/// mode, so that the queue at the bottleneck stays short. Losses don't shrink the window.
+///
+/// The pacing rate is the bottleneck bandwidth scaled by the gain. The window is scaled by the
+/// gain as well, so that the gain cycle applies to a socket which doesn't pace its segments.
+/// All the arithmetic is done with integers.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Bbr {
+    mode: Mode,
+    cwnd: usize,
+    min_cwnd: usize,
+    mss: usize,
+    rwnd: usize,
+    in_flight: usize,
+    /// The window before a loss recovery or ProbeRTT, restored after it.
+    prior_cwnd: Option<usize>,
+
+    /// Delivery rates of the last round trips, in bytes per second.
+    bw_samples: [u32; BW_FILTER_LEN],
+    round_count: u32,
+    round_start: Option<Instant>,
+    round_delivered: usize,
+    full_bw: u32,
+    full_bw_count: u8,
+    filled_pipe: bool,
+
+    /// Minimum round-trip time in milliseconds, and when it was measured.
+    min_rtt: Option<(u32, Instant)>,
+    probe_rtt_done: Instant,
+    cycle_index: usize,
+    cycle_start: Instant,
+}
+
+impl Bbr {
+    pub fn new() -> Bbr {
+        let mss = 536;
+        Bbr {
+            mode: Mode::Startup,
+            cwnd: mss * MIN_PIPE_SEGMENTS,
+            min_cwnd: mss * MIN_PIPE_SEGMENTS,
+            mss,
+            rwnd: 64 * 1024,
+            in_flight: 0,
+            prior_cwnd: None,
+            bw_samples: [0; BW_FILTER_LEN],
+            round_count: 0,
+            round_start: None,
+            round_delivered: 0,
+            full_bw: 0,
+            full_bw_count: 0,
+            filled_pipe: false,
+            min_rtt: None,
+            probe_rtt_done: Instant::ZERO,
+            cycle_index: 0,
+            cycle_start: Instant::ZERO,
+        }
+    }
+
+    /// Return the estimated bottleneck bandwidth, in bytes per second.
+    fn max_bw(&self) -> u32 {
+        self.bw_samples.iter().copied().max().unwrap_or(0)
+    }
+
+    fn gain(&self) -> u32 {
+        match self.mode {
+            Mode::Startup => HIGH_GAIN,
+            Mode::Drain => DRAIN_GAIN,
+            Mode::ProbeBw => PACING_GAIN_CYCLE[self.cycle_index],
+            Mode::ProbeRtt => BBR_UNIT,
+        }
+    }
+
+    /// Return the bandwidth-delay product scaled by `gain`, or `None` while the bandwidth or
+    /// the round-trip time are unknown.
+    fn target_cwnd(&self, gain: u32) -> Option<usize> {
+        let (min_rtt, _) = self.min_rtt?;
+        let bw = self.max_bw();
+        if bw == 0 {
+            return None;
+        }
+
+        let bdp = bw as u64 * min_rtt as u64 / 1000;
+        let target = bdp * gain as u64 / BBR_UNIT as u64;
+        let target = (target as usize).saturating_add(ACK_QUANTA_SEGMENTS * self.mss);
+        Some(target.max(self.min_cwnd))
+    }
+
+    /// Update the minimum round-trip time with the last sample, and return whether it expired.
+    fn update_min_rtt(&mut self, now: Instant, rtt: Option<u32>) -> bool {
+        let expired = match self.min_rtt {
+            Some((_, stamp)) => now >= stamp + MIN_RTT_EXPIRY,
+            None => false,
+        };
+        if let Some(rtt) = rtt {
+            if expired || self.min_rtt.map_or(true, |(min_rtt, _)| rtt < min_rtt) {
+                self.min_rtt = Some((rtt, now));
+            }
+        }
+        expired
+    }
+
+    /// Account for `len` delivered bytes, and return whether a round trip ended.
+    ///
+    /// A round trip lasts the minimum round-trip time, its delivery rate is a bandwidth sample.
+    fn update_bw(&mut self, now: Instant, len: usize, rtt: u32) -> bool {
+        let round_start = *self.round_start.get_or_insert(now);
+        self.round_delivered = self.round_delivered.saturating_add(len);
+
+        let round_len = self.min_rtt.map_or(rtt, |(min_rtt, _)| min_rtt).max(1) as u64;
+        let elapsed = (now - round_start).total_millis();
+        if now < round_start || elapsed < round_len {
+            return false;
+        }
+
+        let bw = (self.round_delivered as u64 * 1000 / elapsed).min(u32::MAX as u64) as u32;
+        self.round_count = self.round_count.wrapping_add(1);
+        self.bw_samples[self.round_count as usize % BW_FILTER_LEN] = bw;
+        self.round_start = Some(now);
+        self.round_delivered = 0;
+        true
+    }
+
+    /// Detect that Startup filled the pipe, once the bandwidth stopped growing.
+    fn check_full_pipe(&mut self) {
+        let bw = self.max_bw();
+        if bw as u64 * BBR_UNIT as u64 >= self.full_bw as u64 * FULL_BW_THRESH as u64 {
+            self.full_bw = bw;
+            self.full_bw_count = 0;
+            return;
+        }
+
+        self.full_bw_count += 1;
+        if self.full_bw_count >= FULL_BW_COUNT {
+            self.filled_pipe = true;
+        }
+    }
+
+    fn enter_probe_bw(&mut self, now: Instant) {
+        self.mode = Mode::ProbeBw;
+        self.cycle_index = 0;
+        self.cycle_start = now;
+    }
+
+    fn update_mode(&mut self, now: Instant, min_rtt_expired: bool) {
+        let min_rtt = Duration::from_millis(self.min_rtt.map_or(0, |(min_rtt, _)| min_rtt) as u64);
+        match self.mode {
+            Mode::Startup if self.filled_pipe => self.mode = Mode::Drain,
+            Mode::Drain if Some(self.in_flight) <= self.target_cwnd(BBR_UNIT) => {
+                self.enter_probe_bw(now)
+            }
+            Mode::ProbeBw if now >= self.cycle_start + min_rtt => {
+                self.cycle_index = (self.cycle_index + 1) % PACING_GAIN_CYCLE.len();
+                self.cycle_start = now;
+            }
+            Mode::ProbeRtt if now >= self.probe_rtt_done => {
+                if let Some((min_rtt, _)) = self.min_rtt {
+                    self.min_rtt = Some((min_rtt, now));
+                }
+                if let Some(prior_cwnd) = self.prior_cwnd.take() {
+                    self.cwnd = self.cwnd.max(prior_cwnd);
+                }
+                if self.filled_pipe {
+                    self.enter_probe_bw(now);
+                } else {
+                    self.mode = Mode::Startup;
+                }
+            }
+            _ => (),
+        }
+
+        if min_rtt_expired && self.mode != Mode::ProbeRtt {
+            self.mode = Mode::ProbeRtt;
+            self.prior_cwnd.get_or_insert(self.cwnd);
+            self.probe_rtt_done = now + PROBE_RTT_DURATION.max(min_rtt);
+        }
+    }
+
+    fn update_cwnd(&mut self, len: usize) {
+        if self.mode != Mode::ProbeRtt {
+            if let Some(prior_cwnd) = self.prior_cwnd.take() {
+                // The loss recovery ended.
+                self.cwnd = self.cwnd.max(prior_cwnd);
+            }
+        }
+
+        self.cwnd = match self.target_cwnd(self.gain()) {
+            Some(target) if self.filled_pipe => self.cwnd.saturating_add(len).min(target),
+            Some(target) if self.cwnd >= target => self.cwnd,
+            _ => self.cwnd.saturating_add(len),
+        };
+        if self.mode == Mode::ProbeRtt {
+            self.cwnd = self.cwnd.min(self.min_cwnd);
+        }
+        self.cwnd = self.cwnd.min(self.rwnd).max(self.min_cwnd);
+    }
+}
+
+impl Controller for Bbr {
+    fn window(&self) -> usize {
+        self.cwnd
+    }
+
+    fn on_ack(&mut self, now: Instant, len: usize, rtt: &RttEstimator) {
+        self.in_flight = self.in_flight.saturating_sub(len);
+
+        let min_rtt_expired = self.update_min_rtt(now, rtt.latest_rtt);
+        if self.update_bw(now, len, rtt.rtt) && !self.filled_pipe {
+            self.check_full_pipe();
+        }
+        self.update_mode(now, min_rtt_expired);
+        self.update_cwnd(len);
+    }
+
+    fn on_retransmit(&mut self, _now: Instant) {
+        // Send no more than the retransmitted segments until the next acknowledgement
+        // (packet conservation), then restore the window.
+        self.prior_cwnd.get_or_insert(self.cwnd);
+        self.cwnd = self.min_cwnd;
+        // The segments in flight may be lost, don't count them twice once retransmitted.
+        self.in_flight = 0;
+    }
+
+    fn post_transmit(&mut self, _now: Instant, len: usize) {
+        self.in_flight = self.in_flight.saturating_add(len);
+    }
+
+    fn set_mss(&mut self, mss: usize) {
+        self.mss = mss;
+        self.min_cwnd = mss * MIN_PIPE_SEGMENTS;
+        self.cwnd = self.cwnd.max(self.min_cwnd);
+    }
+
+    fn set_remote_window(&mut self, remote_window: usize) {
+        if self.rwnd < remote_window {
+            self.rwnd = remote_window;
+        }
+    }
+
+    fn pacing_rate(&self) -> Option<u64> {
+        let bw = self.max_bw();
+        (bw > 0).then(|| bw as u64 * self.gain() as u64 / BBR_UNIT as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Run a connection through a bottleneck of `bw` bytes per second and `rtt` milliseconds of
+    /// propagation delay, with an unlimited queue, for `duration` milliseconds.
+    fn run(bbr: &mut Bbr, now: &mut Instant, bw: usize, rtt: u32, duration: u64) {
+        let end = *now + Duration::from_millis(duration);
+        let mut rtte = RttEstimator::default();
+        while *now < end {
+            // Send a window, and receive its acknowledgement after the propagation delay and
+            // the time it waited in the queue.
+            let window = bbr.window();
+            bbr.post_transmit(*now, window);
+            let queue_delay = (window.saturating_sub(bw * rtt as usize / 1000) * 1000 / bw) as u32;
+            let sample = rtt + queue_delay;
+            rtte.sample(sample);
+            *now += Duration::from_millis(sample as u64);
+            bbr.on_ack(*now, window, &rtte);
+        }
+    }
+
+    #[test]
+    fn test_bbr_startup() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(1000);
+        bbr.set_remote_window(usize::MAX);
+        assert_eq!(bbr.window(), 4000);
+
+        // The window grows exponentially, until the pipe is full.
+        let mut now = Instant::ZERO;
+        run(&mut bbr, &mut now, 100_000, 100, 500);
+        assert_eq!(bbr.mode, Mode::Startup);
+        assert!(bbr.window() > 8000);
+
+        run(&mut bbr, &mut now, 100_000, 100, 5000);
+        assert!(bbr.filled_pipe);
+        assert_eq!(bbr.mode, Mode::ProbeBw);
+        assert!(bbr.max_bw() >= 90_000 && bbr.max_bw() <= 110_000);
+    }
+
+    #[test]
+    fn test_bbr_probe_bw() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(1000);
+        bbr.set_remote_window(usize::MAX);
+
+        let mut now = Instant::ZERO;
+        run(&mut bbr, &mut now, 100_000, 100, 5000);
+        assert_eq!(bbr.mode, Mode::ProbeBw);
+
+        // The window stays close to the bandwidth-delay product of 10000 bytes, instead of
+        // filling the queue. It is 25% larger while probing for more bandwidth.
+        let mut windows = [0; PACING_GAIN_CYCLE.len() * 2];
+        for window in windows.iter_mut() {
+            run(&mut bbr, &mut now, 100_000, 100, 100);
+            *window = bbr.window();
+            assert!(*window <= 10_500 * 5 / 4 + ACK_QUANTA_SEGMENTS * 1000);
+        }
+        assert!(windows.iter().min() < windows.iter().max());
+        assert_eq!(bbr.min_rtt.map(|(min_rtt, _)| min_rtt), Some(100));
+    }
+
+    #[test]
+    fn test_bbr_pacing_rate() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(1000);
+        bbr.set_remote_window(usize::MAX);
+        assert_eq!(bbr.pacing_rate(), None);
+
+        // The segments are paced at the bottleneck bandwidth, scaled by the gain.
+        let mut now = Instant::ZERO;
+        run(&mut bbr, &mut now, 100_000, 100, 5000);
+        assert_eq!(
+            bbr.pacing_rate(),
+            Some(bbr.max_bw() as u64 * bbr.gain() as u64 / BBR_UNIT as u64)
+        );
+        assert!(bbr.pacing_rate().unwrap() >= 90_000 * 3 / 4);
+        assert!(bbr.pacing_rate().unwrap() <= 110_000 * 5 / 4);
+    }
+
+    #[test]
+    fn test_bbr_probe_rtt() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(1000);
+        bbr.set_remote_window(usize::MAX);
+
+        let mut now = Instant::ZERO;
+        run(&mut bbr, &mut now, 100_000, 100, 5000);
+        let prior_cwnd = bbr.window();
+
+        // The minimum round-trip time expires, the window shrinks to probe it again.
+        let rtte = RttEstimator {
+            latest_rtt: Some(100),
+            ..RttEstimator::default()
+        };
+        let (_, stamp) = bbr.min_rtt.unwrap();
+        now = stamp + MIN_RTT_EXPIRY;
+        bbr.on_ack(now, 1000, &rtte);
+        assert_eq!(bbr.mode, Mode::ProbeRtt);
+        assert_eq!(bbr.window(), bbr.min_cwnd);
+
+        now += PROBE_RTT_DURATION;
+        bbr.on_ack(now, 1000, &rtte);
+        assert_eq!(bbr.mode, Mode::ProbeBw);
+        assert!(bbr.window() >= prior_cwnd);
+        assert_eq!(bbr.min_rtt.unwrap().1, now);
+    }
+
+    #[test]
+    fn test_bbr_retransmit() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(1000);
+        bbr.set_remote_window(usize::MAX);
+
+        let mut now = Instant::ZERO;
+        run(&mut bbr, &mut now, 100_000, 100, 5000);
+        let cwnd = bbr.window();
+
+        // The window is restored once the retransmitted segment is acknowledged.
+        bbr.on_retransmit(now);
+        assert_eq!(bbr.window(), bbr.min_cwnd);
+        bbr.on_ack(now, 1000, &RttEstimator::default());
+        assert!(bbr.window() >= cwnd);
+    }
+
+    #[test]
+    fn bbr_min_cwnd() {
+        let mut bbr = Bbr::new();
+        bbr.set_mss(1000);
+        bbr.set_remote_window(64 * 1024);
+
+        for _ in 0..100 {
+            bbr.on_retransmit(Instant::ZERO);
+            assert!(bbr.window() >= bbr.min_cwnd);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use managed::ManagedSlice;
+
+use super::{ListenError, Socket, State};
+use crate::iface::{SocketHandle, SocketSet};
+use crate::wire::IpListenEndpoint;
+
+/// Space for one socket of a [Listener].
+///
+/// This is public so you can use it to allocate space for the sockets of a listener.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ListenerSlot {
+    handle: Option<SocketHandle>,
+    accepted: bool,
+}
+
+impl ListenerSlot {
+    pub const EMPTY: Self = Self {
+        handle: None,
+        accepted: false,
+    };
+}
+
+/// A pool of TCP sockets accepting the connections to an endpoint.
+///
+/// A [Socket] accepts a single connection. A listener keeps the free sockets of its pool
+/// listening, so that several connections are established concurrently, and hands them out
+/// with [accept](#method.accept) once their handshake completes. The sockets belong to a
+/// [SocketSet], and are added to the listener with [add](#method.add).
+///
+/// An accepted socket belongs to the application until it is closed, it then returns to the
+/// pool. Its handle shouldn't be used anymore once it reaches the `Closed` state.
+#[derive(Debug)]
+pub struct Listener<'a> {
+    slots: ManagedSlice<'a, ListenerSlot>,
+    endpoint: Option<IpListenEndpoint>,
+    backlog: usize,
+}
+
+impl<'a> Listener<'a> {
+    /// Create a listener using the provided storage.
+    pub fn new<SlotsT>(slots: SlotsT) -> Listener<'a>
+    where
+        SlotsT: Into<ManagedSlice<'a, ListenerSlot>>,
+    {
+        Listener {
+            slots: slots.into(),
+            endpoint: None,
+            backlog: usize::MAX,
+        }
+    }
+
+    /// Add the socket with the given handle to the pool of the listener.
+    ///
+    /// The socket must be closed, it is used once the listener listens.
+    ///
+    /// # Panics
+    /// This function panics if the storage is fixed-size (not a `Vec`) and is full.
+    pub fn add(&mut self, handle: SocketHandle) {
+        let slot = ListenerSlot {
+            handle: Some(handle),
+            accepted: false,
+        };
+
+        if let Some(free) = self.slots.iter_mut().find(|slot| slot.handle.is_none()) {
+            *free = slot;
+            return;
+        }
+
+        match &mut self.slots {
+            ManagedSlice::Borrowed(_) => panic!("adding a socket to a full Listener"),
+            #[cfg(feature = "alloc")]
+            ManagedSlice::Owned(slots) => slots.push(slot),
+        }
+    }
+
+    /// Return the maximum amount of connections waiting to be accepted.
+    ///
+    /// See also the [set_backlog](#method.set_backlog) method.
+    pub fn backlog(&self) -> usize {
+        self.backlog
+    }
+
+    /// Set the maximum amount of connections waiting to be accepted.
+    ///
+    /// The sockets listening, receiving a connection, or with a connection not yet accepted
+    /// are never more than the backlog. Once it is reached, no socket listens, and new
+    /// connection attempts are reset. By default, every free socket of the pool listens.
+    pub fn set_backlog(&mut self, backlog: usize) {
+        self.backlog = backlog;
+    }
+
+    /// Return whether the listener listens.
+    pub fn is_listening(&self) -> bool {
+        self.endpoint.is_some()
+    }
+
+    /// Start listening on the given endpoint.
+    ///
+    /// This function returns `Err(Error::InvalidState)` if the listener already listens on
+    /// another endpoint, and `Err(Error::Unaddressable)` if the port in the given endpoint is
+    /// zero.
+    pub fn listen<T>(&mut self, sockets: &mut SocketSet<'_>, endpoint: T) -> Result<(), ListenError>
+    where
+        T: Into<IpListenEndpoint>,
+    {
+        let endpoint = endpoint.into();
+        if endpoint.port == 0 {
+            return Err(ListenError::Unaddressable);
+        }
+        match self.endpoint {
+            Some(listen_endpoint) if listen_endpoint != endpoint => {
+                return Err(ListenError::InvalidState)
+            }
+            _ => (),
+        }
+
+        self.endpoint = Some(endpoint);
+        self.refill(sockets);
+        Ok(())
+    }
+
+    /// Stop listening. The connections established before can still be accepted.
+    pub fn close(&mut self, sockets: &mut SocketSet<'_>) {
+        self.endpoint = None;
+        self.refill(sockets);
+    }
+
+    /// Return the handle of a socket whose connection is established, if any.
+    ///
+    /// This function also recycles the accepted sockets which were closed, and makes the free
+    /// sockets listen. It should be called after every [Interface::poll], so that the pool
+    /// keeps listening.
+    ///
+    /// [Interface::poll]: crate::iface::Interface::poll
+    pub fn accept(&mut self, sockets: &mut SocketSet<'_>) -> Option<SocketHandle> {
+        let mut accepted = None;
+        for slot in self.slots.iter_mut() {
+            let Some(handle) = slot.handle else { continue };
+            let socket = sockets.get::<Socket>(handle);
+            if !slot.accepted && Self::is_established(socket.state()) {
+                slot.accepted = true;
+                accepted = Some(handle);
+                break;
+            }
+        }
+
+        self.refill(sockets);
+        accepted
+    }
+
+    fn is_established(state: State) -> bool {
+        !matches!(state, State::Closed | State::Listen | State::SynReceived)
+    }
+
+    /// Return whether a closed socket may listen again. An aborted socket first sends a reset.
+    fn is_free(socket: &Socket) -> bool {
+        socket.tuple.is_none()
+    }
+
+    /// Recycle the accepted sockets which were closed, and make as many free sockets listen as
+    /// the backlog allows.
+    fn refill(&mut self, sockets: &mut SocketSet<'_>) {
+        let mut pending = 0;
+        for slot in self.slots.iter_mut() {
+            let Some(handle) = slot.handle else { continue };
+            let socket = sockets.get::<Socket>(handle);
+            match socket.state() {
+                State::Closed if Self::is_free(socket) => slot.accepted = false,
+                State::Closed | State::Listen => (),
+                _ if slot.accepted => (),
+                _ => pending += 1,
+            }
+        }
+
+        for slot in self.slots.iter_mut() {
+            let Some(handle) = slot.handle else { continue };
+            let socket = sockets.get_mut::<Socket>(handle);
+            match (socket.state(), self.endpoint) {
+                (State::Listen, Some(endpoint))
+                    if pending < self.backlog && socket.listen_endpoint == endpoint =>
+                {
+                    pending += 1
+                }
+                (State::Listen, _) => socket.close(),
+                (State::Closed, Some(endpoint))
+                    if pending < self.backlog && Self::is_free(socket) =>
+                {
+                    // A closed socket may always listen.
+                    socket.listen(endpoint).unwrap();
+                    pending += 1;
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "medium-ip", feature = "proto-ipv4"))]
+mod test {
+    use super::*;
+    use crate::iface::Interface;
+    use crate::phy::Medium;
+    use crate::socket::tcp::SocketBuffer;
+    use crate::tests::{setup, TestingDevice};
+    use crate::time::Instant;
+    use crate::wire::{IpAddress, IpEndpoint};
+
+    fn socket() -> Socket<'static> {
+        Socket::new(
+            SocketBuffer::new(vec![0; 64]),
+            SocketBuffer::new(vec![0; 64]),
+        )
+    }
+
+    fn poll(iface: &mut Interface, device: &mut TestingDevice, sockets: &mut SocketSet<'_>) {
+        for _ in 0..4 {
+            iface.poll(Instant::ZERO, device, sockets);
+        }
+    }
+
+    fn connect(
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'static>,
+        local_port: u16,
+    ) -> SocketHandle {
+        let mut client = socket();
+        let remote = IpEndpoint::new(IpAddress::v4(127, 0, 0, 1), 80);
+        client.connect(iface.context(), remote, local_port).unwrap();
+        sockets.add(client)
+    }
+
+    #[test]
+    fn test_accept() {
+        let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+        let mut listener = Listener::new(vec![]);
+        for _ in 0..3 {
+            listener.add(sockets.add(socket()));
+        }
+        assert_eq!(listener.accept(&mut sockets), None);
+
+        listener.listen(&mut sockets, 80).unwrap();
+        assert!(listener.is_listening());
+        assert_eq!(listener.accept(&mut sockets), None);
+
+        let client_1 = connect(&mut iface, &mut sockets, 49152);
+        let client_2 = connect(&mut iface, &mut sockets, 49153);
+        poll(&mut iface, &mut device, &mut sockets);
+
+        let server_1 = listener.accept(&mut sockets).unwrap();
+        let server_2 = listener.accept(&mut sockets).unwrap();
+        assert_ne!(server_1, server_2);
+        assert_eq!(listener.accept(&mut sockets), None);
+        assert_eq!(sockets.get::<Socket>(client_1).state(), State::Established);
+        assert_eq!(sockets.get::<Socket>(client_2).state(), State::Established);
+
+        let server = sockets.get_mut::<Socket>(server_1);
+        assert_eq!(server.state(), State::Established);
+        assert_eq!(server.remote_endpoint().unwrap().port, 49152);
+    }
+
+    #[test]
+    fn test_backlog() {
+        let (mut iface, mut sockets, mut device) = setup(Medium::Ip);
+        let mut listener = Listener::new(vec![]);
+        let handles = [(); 3].map(|_| sockets.add(socket()));
+        for handle in handles {
+            listener.add(handle);
+        }
+        listener.set_backlog(2);
+        listener.listen(&mut sockets, 80).unwrap();
+        assert_eq!(sockets.get::<Socket>(handles[2]).state(), State::Closed);
+
+        // The third connection is reset, the backlog is full.
+        let client_1 = connect(&mut iface, &mut sockets, 49152);
+        let client_2 = connect(&mut iface, &mut sockets, 49153);
+        let client_3 = connect(&mut iface, &mut sockets, 49154);
+        poll(&mut iface, &mut device, &mut sockets);
+        assert_eq!(sockets.get::<Socket>(client_1).state(), State::Established);
+        assert_eq!(sockets.get::<Socket>(client_2).state(), State::Established);
+        assert_eq!(sockets.get::<Socket>(client_3).state(), State::Closed);
+
+        // Accepting a connection makes room for another one.
+        let server_1 = listener.accept(&mut sockets).unwrap();
+        assert_eq!(sockets.get::<Socket>(handles[2]).state(), State::Listen);
+
+        // A closed socket returns to the pool, once it reset the connection.
+        sockets.get_mut::<Socket>(server_1).abort();
+        listener.refill(&mut sockets);
+        assert_eq!(sockets.get::<Socket>(server_1).state(), State::Closed);
+        poll(&mut iface, &mut device, &mut sockets);
+        assert_eq!(sockets.get::<Socket>(client_1).state(), State::Closed);
+        assert!(listener.accept(&mut sockets).is_some());
+        assert_eq!(listener.accept(&mut sockets), None);
+        assert_eq!(sockets.get::<Socket>(server_1).state(), State::Listen);
+
+        listener.close(&mut sockets);
+        for handle in handles {
+            assert_ne!(sockets.get::<Socket>(handle).state(), State::Listen);
+        }
+    }
+
+    #[test]
+    fn test_listen_error() {
+        let (_, mut sockets, _) = setup(Medium::Ip);
+        let mut listener = Listener::new(vec![]);
+        assert_eq!(
+            listener.listen(&mut sockets, 0),
+            Err(ListenError::Unaddressable)
+        );
+        listener.listen(&mut sockets, 80).unwrap();
+        listener.listen(&mut sockets, 80).unwrap();
+        assert_eq!(
+            listener.listen(&mut sockets, 81),
+            Err(ListenError::InvalidState)
+        );
+    }
+}
@@ -4,7 +4,7 @@ use core::{cmp, fmt};
 use super::{Error, Result};
 use crate::phy::ChecksumCapabilities;
 use crate::wire::ip::checksum;
-use crate::wire::{Ipv4Packet, Ipv4Repr};
+use crate::wire::{Ipv4Packet, Ipv4Repr, IPV4_HEADER_LEN};
 
 enum_with_unknown! {
     /// Internet protocol control message type.
@@ -179,6 +179,8 @@ mod field {
     pub const ECHO_IDENT: Field = 4..6;
     pub const ECHO_SEQNO: Field = 6..8;
 
+    pub const NEXT_HOP_MTU: Field = 6..8;
+
     pub const HEADER_END: usize = 8;
 }
 
@@ -259,6 +261,17 @@ impl<T: AsRef<[u8]>> Packet<T> {
         NetworkEndian::read_u16(&data[field::ECHO_SEQNO])
     }
 
+    /// Return the next-hop MTU field (for "fragmentation required" packets), see RFC 1191 § 4.
+    /// It is zero if the router doesn't report it.
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a destination unreachable packet.
+    #[inline]
+    pub fn next_hop_mtu(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        NetworkEndian::read_u16(&data[field::NEXT_HOP_MTU])
+    }
+
     /// Return the header length.
     /// The result depends on the value of the message type field.
     pub fn header_len(&self) -> usize {
@@ -335,6 +348,16 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
         NetworkEndian::write_u16(&mut data[field::ECHO_SEQNO], value)
     }
 
+    /// Set the next-hop MTU field (for "fragmentation required" packets).
+    ///
+    /// # Panics
+    /// This function may panic if this packet is not a destination unreachable packet.
+    #[inline]
+    pub fn set_next_hop_mtu(&mut self, value: u16) {
+        let data = self.buffer.as_mut();
+        NetworkEndian::write_u16(&mut data[field::NEXT_HOP_MTU], value)
+    }
+
     /// Compute and fill in the header checksum.
     pub fn fill_checksum(&mut self) {
         self.set_checksum(0);
@@ -401,6 +424,21 @@ impl<'a> Repr<'a> {
     {
         packet.check_len()?;
 
+        fn quoted_packet<'a, T>(packet: &Packet<&'a T>) -> Result<Ipv4Packet<&'a [u8]>>
+        where
+            T: AsRef<[u8]> + ?Sized,
+        {
+            // The quoted packet is usually truncated, so we only check whether its IPv4 header
+            // is present, and not its total length.
+            let ip_packet = Ipv4Packet::new_unchecked(packet.data());
+            if packet.data().len() < IPV4_HEADER_LEN
+                || packet.data().len() < ip_packet.header_len() as usize
+            {
+                return Err(Error);
+            }
+            Ok(ip_packet)
+        }
+
         // Valid checksum is expected.
         if checksum_caps.icmpv4.rx() && !packet.verify_checksum() {
             return Err(Error);
@@ -420,7 +458,7 @@ impl<'a> Repr<'a> {
             }),
 
             (Message::DstUnreachable, code) => {
-                let ip_packet = Ipv4Packet::new_checked(packet.data())?;
+                let ip_packet = quoted_packet(packet)?;
 
                 let payload = &packet.data()[ip_packet.header_len() as usize..];
                 // RFC 792 requires exactly eight bytes to be returned.
@@ -443,7 +481,7 @@ impl<'a> Repr<'a> {
             }
 
             (Message::TimeExceeded, code) => {
-                let ip_packet = Ipv4Packet::new_checked(packet.data())?;
+                let ip_packet = quoted_packet(packet)?;
 
                 let payload = &packet.data()[ip_packet.header_len() as usize..];
                 // RFC 792 requires exactly eight bytes to be returned.
@@ -701,4 +739,14 @@ mod test {
         assert_eq!(Packet::new_checked(&bytes[..4]), Err(Error));
         assert!(Packet::new_checked(&bytes[..]).is_ok());
     }
+
+    #[test]
+    fn test_next_hop_mtu() {
+        let mut bytes = [0x03, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let mut packet = Packet::new_unchecked(&mut bytes[..]);
+        assert_eq!(packet.next_hop_mtu(), 0);
+        packet.set_next_hop_mtu(1400);
+        assert_eq!(packet.next_hop_mtu(), 1400);
+        assert_eq!(&bytes[4..], &[0x00, 0x00, 0x05, 0x78]);
+    }
 }
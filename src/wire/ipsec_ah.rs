@@ -105,6 +105,13 @@ impl<T: AsRef<[u8]>> Packet<T> {
         let field = &self.buffer.as_ref()[field::SEQUENCE_NUMBER];
         NetworkEndian::read_u32(field)
     }
+
+    /// Return the length of the Authentication Header in octets, including the integrity
+    /// check value.
+    pub fn header_len(&self) -> usize {
+        let data = self.buffer.as_ref();
+        field::ICV(data[field::PAYLOAD_LEN]).end
+    }
 }
 
 impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
@@ -114,6 +121,13 @@ impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
         let data = self.buffer.as_ref();
         &data[field::ICV(data[field::PAYLOAD_LEN])]
     }
+
+    /// Return a pointer to the payload following the Authentication Header.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[field::ICV(data[field::PAYLOAD_LEN]).end..]
+    }
 }
 
 impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
@@ -124,31 +138,31 @@ impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
 
 impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
     /// Set next header protocol field
-    fn set_next_header(&mut self, value: IpProtocol) {
+    pub fn set_next_header(&mut self, value: IpProtocol) {
         let data = self.buffer.as_mut();
         data[field::NEXT_HEADER] = value.into()
     }
 
     /// Set payload length field
-    fn set_payload_len(&mut self, value: u8) {
+    pub fn set_payload_len(&mut self, value: u8) {
         let data = self.buffer.as_mut();
         data[field::PAYLOAD_LEN] = value
     }
 
     /// Clear reserved field
-    fn clear_reserved(&mut self) {
+    pub fn clear_reserved(&mut self) {
         let data = self.buffer.as_mut();
         data[field::RESERVED].fill(0)
     }
 
     /// Set security parameters index field
-    fn set_security_parameters_index(&mut self, value: u32) {
+    pub fn set_security_parameters_index(&mut self, value: u32) {
         let data = self.buffer.as_mut();
         NetworkEndian::write_u32(&mut data[field::SPI], value)
     }
 
     /// Set sequence number
-    fn set_sequence_number(&mut self, value: u32) {
+    pub fn set_sequence_number(&mut self, value: u32) {
         let data = self.buffer.as_mut();
         NetworkEndian::write_u32(&mut data[field::SEQUENCE_NUMBER], value)
     }
@@ -165,10 +179,10 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Repr<'a> {
-    next_header: IpProtocol,
-    security_parameters_index: u32,
-    sequence_number: u32,
-    integrity_check_value: &'a [u8],
+    pub next_header: IpProtocol,
+    pub security_parameters_index: u32,
+    pub sequence_number: u32,
+    pub integrity_check_value: &'a [u8],
 }
 
 impl<'a> Repr<'a> {
@@ -223,6 +237,7 @@ mod test {
         let packet = Packet::new_unchecked(&PACKET_BYTES1[..]);
         assert_eq!(packet.next_header(), IpProtocol::IpSecEsp);
         assert_eq!(packet.payload_len(), 4);
+        assert_eq!(packet.header_len(), 24);
         assert_eq!(packet.security_parameters_index(), 0x8179b705);
         assert_eq!(packet.sequence_number(), 1);
         assert_eq!(
@@ -9,12 +9,16 @@ pub struct Packet<T: AsRef<[u8]>> {
 }
 
 mod field {
-    use crate::wire::field::Field;
+    use crate::wire::field::{Field, Rest};
 
     pub const SPI: Field = 0..4;
     pub const SEQUENCE_NUMBER: Field = 4..8;
+    pub const PAYLOAD: Rest = SEQUENCE_NUMBER.end..;
 }
 
+/// Length of the fixed part of the ESP header (SPI and sequence number).
+pub const HEADER_LEN: usize = field::SEQUENCE_NUMBER.end;
+
 impl<T: AsRef<[u8]>> Packet<T> {
     /// Imbue a raw octet buffer with IPsec Encapsulating Security Payload packet structure.
     pub const fn new_unchecked(buffer: T) -> Packet<T> {
@@ -61,6 +65,18 @@ impl<T: AsRef<[u8]>> Packet<T> {
     }
 }
 
+impl<'a, T: AsRef<[u8]> + ?Sized> Packet<&'a T> {
+    /// Return a pointer to the payload, i.e. everything following the sequence number.
+    ///
+    /// This contains the (optional) initialization vector, the encrypted payload and trailer,
+    /// and the integrity check value.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        let data = self.buffer.as_ref();
+        &data[field::PAYLOAD]
+    }
+}
+
 impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
     fn as_ref(&self) -> &[u8] {
         self.buffer.as_ref()
@@ -69,23 +85,30 @@ impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
 
 impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
     /// Set security parameters index field
-    fn set_security_parameters_index(&mut self, value: u32) {
+    pub fn set_security_parameters_index(&mut self, value: u32) {
         let data = self.buffer.as_mut();
         NetworkEndian::write_u32(&mut data[field::SPI], value)
     }
 
     /// Set sequence number
-    fn set_sequence_number(&mut self, value: u32) {
+    pub fn set_sequence_number(&mut self, value: u32) {
         let data = self.buffer.as_mut();
         NetworkEndian::write_u32(&mut data[field::SEQUENCE_NUMBER], value)
     }
+
+    /// Return a mutable pointer to the payload.
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[field::PAYLOAD]
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Repr {
-    security_parameters_index: u32,
-    sequence_number: u32,
+    pub security_parameters_index: u32,
+    pub sequence_number: u32,
 }
 
 impl Repr {
@@ -1,4 +1,4 @@
-use super::{Error, Result};
+use super::{Error, Ipv6Address, Ipv6Repr, Result};
 use core::fmt;
 
 use byteorder::{ByteOrder, NetworkEndian};
@@ -138,6 +138,17 @@ impl<'a, T: AsRef<[u8]> + ?Sized> fmt::Display for Header<&'a T> {
     }
 }
 
+/// The key identifying the fragments of an IPv6 packet, see [RFC 8200 § 4.5].
+///
+/// [RFC 8200 § 4.5]: https://www.rfc-editor.org/rfc/rfc8200#section-4.5
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Key {
+    ident: u32,
+    src_addr: Ipv6Address,
+    dst_addr: Ipv6Address,
+}
+
 /// A high-level representation of an IPv6 Fragment header.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -171,6 +182,15 @@ impl Repr {
         field::IDENT.end
     }
 
+    /// Return the key identifying the fragments of the packet with the IPv6 header `header`.
+    pub fn get_key(&self, header: &Ipv6Repr) -> Key {
+        Key {
+            ident: self.ident,
+            src_addr: header.src_addr,
+            dst_addr: header.dst_addr,
+        }
+    }
+
     /// Emit a high-level representation into an IPv6 Fragment Header.
     pub fn emit<T: AsRef<[u8]> + AsMut<[u8]> + ?Sized>(&self, header: &mut Header<&mut T>) {
         header.clear_reserved();
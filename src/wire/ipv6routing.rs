@@ -435,6 +435,68 @@ impl<'a> Repr<'a> {
     }
 }
 
+impl<'a> Repr<'a> {
+    /// Return the number of addresses `n` in a RPL Source Route Header, or `None` if the
+    /// header is not a RPL Source Route Header or if the addresses do not add up.
+    ///
+    /// See [RFC 6554 § 3] for details.
+    ///
+    /// [RFC 6554 § 3]: https://datatracker.ietf.org/doc/html/rfc6554#section-3
+    pub fn rpl_address_count(&self) -> Option<usize> {
+        let Repr::Rpl {
+            cmpr_i,
+            cmpr_e,
+            pad,
+            addresses,
+            ..
+        } = *self
+        else {
+            return None;
+        };
+
+        if cmpr_i > 15 || cmpr_e > 15 {
+            return None;
+        }
+
+        let addresses_len = addresses
+            .len()
+            .checked_sub(pad as usize + 16 - cmpr_e as usize)?;
+        let address_len = 16 - cmpr_i as usize;
+        if addresses_len % address_len != 0 {
+            return None;
+        }
+
+        Some(addresses_len / address_len + 1)
+    }
+
+    /// Return `addresses[index]` of a RPL Source Route Header, with `index` from 1 to `n`. The
+    /// elided prefix octets are taken from `dst_addr`, the destination address of the IPv6
+    /// header that carries this Routing Header.
+    pub fn rpl_address(&self, index: usize, dst_addr: &Address) -> Option<Address> {
+        let n = self.rpl_address_count()?;
+        let Repr::Rpl {
+            cmpr_i,
+            cmpr_e,
+            addresses,
+            ..
+        } = *self
+        else {
+            return None;
+        };
+
+        if index == 0 || index > n {
+            return None;
+        }
+
+        let cmpr = if index == n { cmpr_e } else { cmpr_i } as usize;
+        let start = (index - 1) * (16 - cmpr_i as usize);
+
+        let mut address = *dst_addr;
+        address.0[cmpr..].copy_from_slice(&addresses[start..][..16 - cmpr]);
+        Some(address)
+    }
+}
+
 impl<'a> fmt::Display for Repr<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -604,4 +666,43 @@ mod test {
         assert_eq!(REPR_SRH_FULL.buffer_len(), 38);
         assert_eq!(REPR_SRH_ELIDED.buffer_len(), 14);
     }
+
+    #[test]
+    fn test_rpl_addresses() {
+        let dst_addr = Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+
+        assert_eq!(REPR_TYPE2.rpl_address_count(), None);
+        assert_eq!(REPR_TYPE2.rpl_address(1, &dst_addr), None);
+
+        assert_eq!(REPR_SRH_FULL.rpl_address_count(), Some(2));
+        assert_eq!(
+            REPR_SRH_FULL.rpl_address(1, &dst_addr),
+            Some(Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 2))
+        );
+        assert_eq!(
+            REPR_SRH_FULL.rpl_address(2, &dst_addr),
+            Some(Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x0301))
+        );
+        assert_eq!(REPR_SRH_FULL.rpl_address(0, &dst_addr), None);
+        assert_eq!(REPR_SRH_FULL.rpl_address(3, &dst_addr), None);
+
+        assert_eq!(REPR_SRH_ELIDED.rpl_address_count(), Some(2));
+        assert_eq!(
+            REPR_SRH_ELIDED.rpl_address(1, &dst_addr),
+            Some(Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 2))
+        );
+        assert_eq!(
+            REPR_SRH_ELIDED.rpl_address(2, &dst_addr),
+            Some(Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x0301))
+        );
+
+        let truncated = Repr::Rpl {
+            segments_left: 1,
+            cmpr_i: 0,
+            cmpr_e: 0,
+            pad: 0,
+            addresses: &[0; 8],
+        };
+        assert_eq!(truncated.rpl_address_count(), None);
+    }
 }
@@ -155,8 +155,9 @@ pub use self::arp::{
 #[cfg(feature = "proto-rpl")]
 pub use self::rpl::{
     data::HopByHopOption as RplHopByHopRepr, data::Packet as RplHopByHopPacket,
-    options::Packet as RplOptionPacket, options::Repr as RplOptionRepr,
-    InstanceId as RplInstanceId, Repr as RplRepr,
+    options::OptionsIterator as RplOptionsIterator, options::Packet as RplOptionPacket,
+    options::Repr as RplOptionRepr, InstanceId as RplInstanceId,
+    ModeOfOperation as RplModeOfOperation, Repr as RplRepr,
 };
 
 #[cfg(all(feature = "proto-sixlowpan", feature = "medium-ieee802154"))]
@@ -201,14 +202,16 @@ pub use self::ipv6::{
 #[cfg(feature = "proto-ipv6")]
 pub use self::ipv6option::{
     FailureType as Ipv6OptionFailureType, Ipv6Option, Ipv6OptionsIterator, Repr as Ipv6OptionRepr,
-    Type as Ipv6OptionType,
+    RouterAlert as Ipv6OptionRouterAlert, Type as Ipv6OptionType,
 };
 
 #[cfg(feature = "proto-ipv6")]
 pub use self::ipv6ext_header::{Header as Ipv6ExtHeader, Repr as Ipv6ExtHeaderRepr};
 
 #[cfg(feature = "proto-ipv6")]
-pub use self::ipv6fragment::{Header as Ipv6FragmentHeader, Repr as Ipv6FragmentRepr};
+pub use self::ipv6fragment::{
+    Header as Ipv6FragmentHeader, Key as Ipv6FragKey, Repr as Ipv6FragmentRepr,
+};
 
 #[cfg(feature = "proto-ipv6")]
 pub use self::ipv6hbh::{Header as Ipv6HopByHopHeader, Repr as Ipv6HopByHopRepr};
@@ -226,7 +229,10 @@ pub use self::icmpv4::{
 };
 
 #[cfg(feature = "proto-igmp")]
-pub use self::igmp::{IgmpVersion, Packet as IgmpPacket, Repr as IgmpRepr};
+pub use self::igmp::{
+    GroupRecord as IgmpGroupRecord, IgmpVersion, Packet as IgmpPacket,
+    RecordType as IgmpRecordType, Repr as IgmpRepr,
+};
 
 #[cfg(feature = "proto-ipv6")]
 pub use self::icmpv6::{
@@ -256,13 +262,15 @@ pub use self::ndiscoption::{
 };
 
 #[cfg(feature = "proto-ipv6")]
-pub use self::mld::{AddressRecord as MldAddressRecord, Repr as MldRepr};
+pub use self::mld::{
+    AddressRecord as MldAddressRecord, RecordType as MldRecordType, Repr as MldRepr,
+};
 
 pub use self::udp::{Packet as UdpPacket, Repr as UdpRepr, HEADER_LEN as UDP_HEADER_LEN};
 
 pub use self::tcp::{
     Control as TcpControl, Packet as TcpPacket, Repr as TcpRepr, SeqNumber as TcpSeqNumber,
-    TcpOption, HEADER_LEN as TCP_HEADER_LEN,
+    TcpOption, TimestampRepr as TcpTimestampRepr, HEADER_LEN as TCP_HEADER_LEN,
 };
 
 #[cfg(feature = "proto-dhcpv4")]
@@ -283,7 +291,9 @@ pub use self::dns::{
 pub use self::ipsec_ah::{Packet as IpSecAuthHeaderPacket, Repr as IpSecAuthHeaderRepr};
 
 #[cfg(feature = "proto-ipsec-esp")]
-pub use self::ipsec_esp::{Packet as IpSecEspPacket, Repr as IpSecEspRepr};
+pub use self::ipsec_esp::{
+    Packet as IpSecEspPacket, Repr as IpSecEspRepr, HEADER_LEN as IPSEC_ESP_HEADER_LEN,
+};
 
 /// Parsing a packet failed.
 ///
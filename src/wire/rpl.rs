@@ -980,11 +980,48 @@ pub mod options {
 
         #[inline]
         pub fn new_checked(buffer: T) -> Result<Self> {
-            if buffer.as_ref().is_empty() {
+            let packet = Self::new_unchecked(buffer);
+            packet.check_len()?;
+            Ok(packet)
+        }
+
+        /// Ensure that no accessor method will panic if called.
+        /// Returns `Err(Error)` if the buffer is too short for the option, or if the option
+        /// length is invalid for its type.
+        #[inline]
+        pub fn check_len(&self) -> Result<()> {
+            let data = self.buffer.as_ref();
+            if data.is_empty() {
                 return Err(Error);
             }
 
-            Ok(Packet { buffer })
+            if self.option_type() == OptionType::Pad1 {
+                return Ok(());
+            }
+
+            if data.len() < 2 || data.len() < 2 + self.option_length() as usize {
+                return Err(Error);
+            }
+
+            let valid = match self.option_type() {
+                OptionType::Pad1 | OptionType::PadN | OptionType::Unknown(_) => true,
+                // The DAG Metric Container is not supported.
+                OptionType::DagMetricContainer => false,
+                OptionType::RouteInformation => self.option_length() >= 6,
+                OptionType::DodagConfiguration => self.option_length() >= 14,
+                // Only full IPv6 addresses are supported as target.
+                OptionType::RplTarget => self.option_length() == 18,
+                OptionType::TransitInformation => matches!(self.option_length(), 4 | 20),
+                OptionType::SolicitedInformation => self.option_length() >= 19,
+                OptionType::PrefixInformation => self.option_length() >= 30,
+                OptionType::RplTargetDescriptor => self.option_length() >= 4,
+            };
+
+            if valid {
+                Ok(())
+            } else {
+                Err(Error)
+            }
         }
 
         /// Return the type field.
@@ -2226,6 +2263,49 @@ pub mod options {
             }
         }
     }
+
+    /// An iterator over the options of a RPL Control Message.
+    #[derive(Debug)]
+    pub struct OptionsIterator<'a> {
+        data: &'a [u8],
+        hit_error: bool,
+    }
+
+    impl<'a> OptionsIterator<'a> {
+        /// Create a new `OptionsIterator`, used to iterate over the options contained in a RPL
+        /// Control Message.
+        pub fn new(data: &'a [u8]) -> OptionsIterator<'a> {
+            OptionsIterator {
+                data,
+                hit_error: false,
+            }
+        }
+    }
+
+    impl<'a> Iterator for OptionsIterator<'a> {
+        type Item = Result<Repr<'a>>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.data.is_empty() || self.hit_error {
+                // If we failed to parse a previous option or hit the end of the buffer, we do
+                // not continue to iterate.
+                return None;
+            }
+
+            let result = Packet::new_checked(self.data).and_then(|packet| {
+                let len = match packet.option_type() {
+                    OptionType::Pad1 => 1,
+                    _ => 2 + packet.option_length() as usize,
+                };
+                let repr = Repr::parse(&Packet::new_unchecked(&self.data[..len]))?;
+                self.data = &self.data[len..];
+                Ok(repr)
+            });
+
+            self.hit_error = result.is_err();
+            Some(result)
+        }
+    }
 }
 
 pub mod data {
@@ -2720,4 +2800,49 @@ mod tests {
 
         assert_eq!(&data[..], &buffer[..]);
     }
+
+    #[test]
+    fn options_iterator() {
+        use super::options::OptionsIterator;
+
+        let options = [
+            // Pad1
+            0x00, //
+            // RPL Target
+            0x05, 0x12, 0x00, 0x80, 0xfd, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x02,
+            0x00, 0x02, 0x00, 0x02, 0x00, 0x02, //
+            // Transit Information
+            0x06, 0x04, 0x00, 0x00, 0x00, 0x1e, //
+            // Truncated DODAG Configuration
+            0x04, 0x0e, 0x00, 0x08,
+        ];
+
+        let mut iterator = OptionsIterator::new(&options);
+        assert_eq!(iterator.next(), Some(Ok(OptionRepr::Pad1)));
+        assert_eq!(
+            iterator.next(),
+            Some(Ok(OptionRepr::RplTarget {
+                prefix_length: 128,
+                prefix: Ipv6Address::new(0xfd00, 0, 0, 0, 0x0202, 0x0002, 0x0002, 0x0002),
+            }))
+        );
+        assert_eq!(
+            iterator.next(),
+            Some(Ok(OptionRepr::TransitInformation {
+                external: false,
+                path_control: 0,
+                path_sequence: 0,
+                path_lifetime: 30,
+                parent_address: None,
+            }))
+        );
+        assert_eq!(iterator.next(), Some(Err(Error)));
+        assert_eq!(iterator.next(), None);
+
+        // A RPL Target option that is too short is an error.
+        let options = [0x05, 0x04, 0x00, 0x40, 0xfd, 0x00];
+        let mut iterator = OptionsIterator::new(&options);
+        assert_eq!(iterator.next(), Some(Err(Error)));
+        assert_eq!(iterator.next(), None);
+    }
 }